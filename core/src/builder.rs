@@ -0,0 +1,291 @@
+//! Creation of DFU files
+//!
+//! References:
+//! - [DFU 1.1 Specification](https://www.usb.org/sites/default/files/DFU_1.1.pdf) for the suffix
+//! - ST UM0391 for the DfuSe file format
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// DFU version stored in the suffix of plain DFU files
+pub const DFU_VERSION_PLAIN: u16 = 0x0100;
+
+/// DFU version stored in the suffix of DfuSe files
+pub const DFU_VERSION_DFUSE: u16 = 0x011A;
+
+/// Length of the DFU suffix in bytes
+pub const SUFFIX_LENGTH: u8 = 16;
+
+/// Length of the target name field in the DfuSe target prefix
+const TARGET_NAME_LENGTH: usize = 255;
+
+/// Values stored in the DFU suffix
+#[derive(Debug, Clone, Copy)]
+pub struct Suffix {
+    /// Release number of the device, 0xFFFF to ignore
+    pub device_version: u16,
+
+    /// Product id of the device, 0xFFFF to ignore
+    pub product_id: u16,
+
+    /// Vendor id of the device, 0xFFFF to ignore
+    pub vendor_id: u16,
+
+    /// DFU specification number
+    pub dfu_version: u16,
+}
+
+impl Suffix {
+    /// Return a suffix accepted by all devices with the given DFU version
+    pub fn wildcard(dfu_version: u16) -> Self {
+        Self {
+            device_version: 0xFFFF,
+            product_id: 0xFFFF,
+            vendor_id: 0xFFFF,
+            dfu_version,
+        }
+    }
+}
+
+/// Single image of a DfuSe file
+#[derive(Debug, Clone)]
+pub struct Image {
+    /// Alternate setting of the target
+    pub alt_setting: u8,
+
+    /// Optional target name
+    pub name: Option<String>,
+
+    /// Elements of the image
+    pub elements: Vec<Element>,
+}
+
+/// Single element of a DfuSe image
+#[derive(Debug, Clone)]
+pub struct Element {
+    /// Start address
+    pub address: u32,
+
+    /// Data to be stored at the address
+    pub data: Vec<u8>,
+}
+
+/// Padding of the data of converted files
+///
+/// Some bootloaders reject a short final block or element sizes that are not
+/// a multiple of a word, so the data can be extended with 0xFF, the erased
+/// state of flash memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Deserialize, serde::Serialize)]
+pub enum Padding {
+    /// Data is stored as it is
+    #[default]
+    None,
+
+    /// Start and end are aligned to 4-byte words
+    Word,
+
+    /// Start and end are aligned to the sectors containing the data
+    Sector,
+}
+
+impl Padding {
+    /// All paddings in the order they are shown
+    pub const ALL: [Self; 3] = [Self::None, Self::Word, Self::Sector];
+
+    /// Return a description of the padding
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::None => "Store the data as it is",
+            Self::Word => "Align start and end of the data to 4-byte words",
+            Self::Sector => {
+                "Align start and end of the data to the sectors containing it, \
+                for plain DFU devices pad the data to a multiple of the transfer size"
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Padding {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "None"),
+            Self::Word => write!(f, "Word"),
+            Self::Sector => write!(f, "Sector"),
+        }
+    }
+}
+
+/// Return the elements extended with 0xFF to the addresses returned by
+/// `align` for the start and end address of each element
+///
+/// Elements overlapping after the extension are merged into one.
+pub fn pad_elements(
+    mut elements: Vec<Element>,
+    align: impl Fn(u32, u32) -> (u32, u32),
+) -> Vec<Element> {
+    elements.sort_by_key(|element| element.address);
+
+    let mut groups: Vec<(u32, u32, Vec<Element>)> = Vec::new();
+    for element in elements {
+        let end = element.address.saturating_add(element.data.len() as u32);
+        let (start, end) = align(element.address, end);
+
+        match groups.last_mut() {
+            Some((group_start, group_end, members)) if start <= *group_end => {
+                *group_start = (*group_start).min(start);
+                *group_end = (*group_end).max(end);
+                members.push(element);
+            }
+            _ => groups.push((start, end, vec![element])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(start, end, members)| {
+            let mut data = vec![0xFF; (end - start) as usize];
+            for member in members {
+                let offset = (member.address - start) as usize;
+                data[offset..offset + member.data.len()].copy_from_slice(&member.data);
+            }
+            Element {
+                address: start,
+                data,
+            }
+        })
+        .collect()
+}
+
+/// Return start and end address aligned to multiples of `alignment`
+pub fn align_range(start: u32, end: u32, alignment: u32) -> (u32, u32) {
+    if alignment == 0 {
+        return (start, end);
+    }
+
+    (
+        start - start % alignment,
+        end.checked_next_multiple_of(alignment).unwrap_or(end),
+    )
+}
+
+/// Return the CRC as used in the DFU suffix
+///
+/// This is a standard CRC32 without the final inversion.
+pub fn crc(data: &[u8]) -> u32 {
+    !crc32fast::hash(data)
+}
+
+/// Append a DFU suffix including the CRC to the data
+pub fn append_suffix(data: &mut Vec<u8>, suffix: &Suffix) {
+    data.extend_from_slice(&suffix.device_version.to_le_bytes());
+    data.extend_from_slice(&suffix.product_id.to_le_bytes());
+    data.extend_from_slice(&suffix.vendor_id.to_le_bytes());
+    data.extend_from_slice(&suffix.dfu_version.to_le_bytes());
+    data.extend_from_slice(b"UFD");
+    data.push(SUFFIX_LENGTH);
+
+    let crc = crc(data);
+    data.extend_from_slice(&crc.to_le_bytes());
+}
+
+/// Return the content of a DfuSe file including the suffix
+pub fn dfuse_file(images: &[Image], suffix: &Suffix) -> Vec<u8> {
+    let mut targets = Vec::new();
+
+    for image in images {
+        let elements_size: usize = image
+            .elements
+            .iter()
+            .map(|element| 8 + element.data.len())
+            .sum();
+
+        // Target prefix
+        targets.extend_from_slice(b"Target");
+        targets.push(image.alt_setting);
+        targets.extend_from_slice(&(image.name.is_some() as u32).to_le_bytes());
+        let mut name = [0; TARGET_NAME_LENGTH];
+        if let Some(image_name) = &image.name {
+            let len = std::cmp::min(image_name.len(), TARGET_NAME_LENGTH - 1);
+            name[..len].copy_from_slice(&image_name.as_bytes()[..len]);
+        }
+        targets.extend_from_slice(&name);
+        targets.extend_from_slice(&(elements_size as u32).to_le_bytes());
+        targets.extend_from_slice(&(image.elements.len() as u32).to_le_bytes());
+
+        // Image elements
+        for element in &image.elements {
+            targets.extend_from_slice(&element.address.to_le_bytes());
+            targets.extend_from_slice(&(element.data.len() as u32).to_le_bytes());
+            targets.extend_from_slice(&element.data);
+        }
+    }
+
+    // DfuSe prefix
+    let mut data = Vec::with_capacity(11 + targets.len() + SUFFIX_LENGTH as usize);
+    data.extend_from_slice(b"DfuSe");
+    data.push(0x01);
+    data.extend_from_slice(&((11 + targets.len()) as u32).to_le_bytes());
+    data.push(images.len() as u8);
+    data.extend_from_slice(&targets);
+
+    append_suffix(&mut data, suffix);
+
+    data
+}
+
+/// Return the content of a plain DFU file including the suffix
+pub fn plain_file(data: &[u8], suffix: &Suffix) -> Vec<u8> {
+    let mut file_data = Vec::with_capacity(data.len() + SUFFIX_LENGTH as usize);
+    file_data.extend_from_slice(data);
+    append_suffix(&mut file_data, suffix);
+
+    file_data
+}
+
+/// Write the content to a new file in the temporary directory and return its path
+///
+/// Each call creates a file with a unique name, so that a changed content is
+/// never mistaken for a previous one with the same path.
+pub fn write_temp_file(name: &str, content: &[u8]) -> std::io::Result<std::path::PathBuf> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let file_name = format!("dfu-buddy-{}-{}-{}.dfu", std::process::id(), count, name);
+    let path = std::env::temp_dir().join(file_name);
+    std::fs::write(&path, content)?;
+
+    Ok(path)
+}
+
+/// Recalculate the CRC of a file with DFU suffix and return if it was wrong
+pub fn fix_crc(data: &mut [u8]) -> Result<bool, SuffixError> {
+    let len = data.len();
+
+    if len < SUFFIX_LENGTH as usize || &data[len - 8..len - 5] != b"UFD" {
+        return Err(SuffixError::NotFound);
+    }
+
+    let crc = crc(&data[..len - 4]).to_le_bytes();
+    let wrong = data[len - 4..] != crc;
+    data[len - 4..].copy_from_slice(&crc);
+
+    Ok(wrong)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Errors when handling a DFU suffix
+#[derive(Debug)]
+pub enum SuffixError {
+    /// File has no DFU suffix
+    NotFound,
+}
+
+impl std::error::Error for SuffixError {}
+
+impl std::fmt::Display for SuffixError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "File has no DFU suffix."),
+        }
+    }
+}
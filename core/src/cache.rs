@@ -0,0 +1,247 @@
+//! Host-side cache of firmware file blocks
+//!
+//! When the same file is flashed to several devices in a row, the data is read
+//! from disk and split into transfer blocks only once. Blocks with identical
+//! content share a single buffer.
+//!
+//! Files with a payload larger than [`MAX_CACHED_SIZE`] are not cached but
+//! streamed from disk using a small pool of reusable buffers.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use anyhow::Result;
+
+/// Max. payload size of a file that is kept in the cache
+pub const MAX_CACHED_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Max. number of buffers kept in a buffer pool
+const MAX_POOL_BUFFERS: usize = 4;
+
+/// Cache for the blocks of a single file
+#[derive(Default)]
+pub struct BlockCache {
+    /// Path of the cached file
+    path: Option<std::path::PathBuf>,
+
+    /// File modification time when the cache was filled
+    modified: Option<std::time::SystemTime>,
+
+    /// File size when the cache was filled
+    len: u64,
+
+    /// Blocks per element, keyed by image number, element number and block size
+    elements: HashMap<(usize, usize, u32), Vec<Block>>,
+
+    /// Unique block buffers, keyed by content hash
+    buffers: HashMap<u64, Vec<Arc<[u8]>>>,
+}
+
+/// Single block of an image element
+#[derive(Debug, Clone)]
+pub struct Block {
+    /// Start address of the block
+    pub address: u32,
+
+    /// Block data, possibly shared with other blocks of the same content
+    pub data: Arc<[u8]>,
+}
+
+impl BlockCache {
+    /// Make sure the cache belongs to the file at `path` and its content is
+    /// unchanged, otherwise clear it
+    pub fn validate(&mut self, path: &std::path::Path) {
+        let metadata = std::fs::metadata(path).ok();
+        let modified = metadata.as_ref().and_then(|m| m.modified().ok());
+        let len = metadata.as_ref().map_or(0, |m| m.len());
+
+        if self.path.as_deref() != Some(path) || self.modified != modified || self.len != len {
+            if self.path.is_some() {
+                log::debug!("Block cache invalidated");
+            }
+            self.clear();
+            self.path = Some(path.to_path_buf());
+            self.modified = modified;
+            self.len = len;
+        }
+    }
+
+    /// Return the number of bytes held by the block buffers
+    pub fn memory_usage(&self) -> usize {
+        self.buffers.values().flatten().map(|b| b.len()).sum()
+    }
+
+    /// Remove all cached data
+    pub fn clear(&mut self) {
+        self.path = None;
+        self.modified = None;
+        self.len = 0;
+        self.elements.clear();
+        self.buffers.clear();
+    }
+
+    /// Return the blocks of an image element, reading them from the file
+    /// only if not already cached
+    pub fn element_blocks(
+        &mut self,
+        file: &mut std::fs::File,
+        image_no: usize,
+        element_no: usize,
+        element: &dfufile::dfuse::ImageElement,
+        block_size: u32,
+    ) -> Result<Vec<Block>> {
+        let key = (image_no, element_no, block_size);
+
+        if let Some(blocks) = self.elements.get(&key) {
+            log::debug!(
+                "Using {} cached blocks for image {}, element {}",
+                blocks.len(),
+                image_no,
+                element_no
+            );
+            return Ok(blocks.clone());
+        }
+
+        let start_address = element.dwElementAddress;
+        let end_address = start_address + element.dwElementSize;
+        let mut address = start_address;
+        let mut blocks = Vec::new();
+
+        while address < end_address {
+            let chunk_size = std::cmp::min(block_size, end_address - address);
+            let mut data = vec![0; chunk_size as usize];
+            element.read_at(file, address - start_address, &mut data)?;
+
+            let mut hasher = DefaultHasher::new();
+            data.hash(&mut hasher);
+            let hash = hasher.finish();
+
+            blocks.push(Block {
+                address,
+                data: self.shared_buffer(hash, data),
+            });
+
+            address += chunk_size;
+        }
+
+        self.elements.insert(key, blocks.clone());
+
+        Ok(blocks)
+    }
+
+    /// Return a buffer with the given content, reusing an existing one if possible
+    fn shared_buffer(&mut self, hash: u64, data: Vec<u8>) -> Arc<[u8]> {
+        let candidates = self.buffers.entry(hash).or_default();
+
+        if let Some(buffer) = candidates.iter().find(|b| b[..] == data[..]) {
+            return buffer.clone();
+        }
+
+        let buffer: Arc<[u8]> = data.into();
+        candidates.push(buffer.clone());
+
+        buffer
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Source of the block data of an image element
+pub enum BlockSource {
+    /// Blocks taken from the cache
+    Cached(Vec<Block>),
+
+    /// Blocks read from the file on demand
+    Streamed,
+}
+
+impl BlockSource {
+    /// Call `f` with block number, address and data for each block of an element
+    pub fn for_each_block(
+        &self,
+        file: &mut std::fs::File,
+        element: &dfufile::dfuse::ImageElement,
+        block_size: u32,
+        pool: &mut BufferPool,
+        mut f: impl FnMut(usize, u32, &[u8]) -> Result<()>,
+    ) -> Result<()> {
+        match self {
+            Self::Cached(blocks) => {
+                for (block_no, block) in blocks.iter().enumerate() {
+                    f(block_no, block.address, &block.data)?;
+                }
+            }
+            Self::Streamed => {
+                let start_address = element.dwElementAddress;
+                let end_address = start_address + element.dwElementSize;
+                let mut address = start_address;
+                let mut block_no = 0;
+
+                while address < end_address {
+                    let chunk_size = std::cmp::min(block_size, end_address - address);
+                    let mut data = pool.take(chunk_size as usize);
+                    let result = element
+                        .read_at(file, address - start_address, &mut data)
+                        .and_then(|_| f(block_no, address, &data));
+                    pool.put(data);
+                    result?;
+
+                    address += chunk_size;
+                    block_no += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Pool of reusable buffers with a bounded number of entries
+#[derive(Default)]
+pub struct BufferPool {
+    /// Buffers currently not in use
+    free: Vec<Vec<u8>>,
+
+    /// Number of bytes allocated by buffers currently in use
+    used_bytes: usize,
+
+    /// Max. number of bytes allocated at the same time
+    peak_bytes: usize,
+}
+
+impl BufferPool {
+    /// Take a zeroed buffer of `size` bytes from the pool
+    pub fn take(&mut self, size: usize) -> Vec<u8> {
+        let mut buffer = self.free.pop().unwrap_or_default();
+        buffer.clear();
+        buffer.resize(size, 0);
+
+        self.used_bytes += buffer.capacity();
+        self.peak_bytes = std::cmp::max(self.peak_bytes, self.memory_usage());
+
+        buffer
+    }
+
+    /// Return a buffer to the pool
+    pub fn put(&mut self, buffer: Vec<u8>) {
+        self.used_bytes = self.used_bytes.saturating_sub(buffer.capacity());
+
+        if self.free.len() < MAX_POOL_BUFFERS {
+            self.free.push(buffer);
+        }
+    }
+
+    /// Return the number of bytes currently allocated by the pool
+    pub fn memory_usage(&self) -> usize {
+        self.used_bytes + self.free.iter().map(|b| b.capacity()).sum::<usize>()
+    }
+
+    /// Return the max. number of bytes allocated at the same time
+    pub fn peak_memory_usage(&self) -> usize {
+        self.peak_bytes
+    }
+}
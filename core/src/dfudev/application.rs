@@ -0,0 +1,124 @@
+//! Device started after leaving DFU mode
+//!
+//! After a reset, the application enumerates on the same port as the
+//! bootloader, usually with other ids. The port path is used to find it,
+//! as the bus address changes on every enumeration.
+
+use anyhow::Result;
+
+use super::{info, settings, Device};
+
+/// Interval between two attempts to find the application
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// Descriptors and strings of the device found on a port
+#[derive(Debug, Clone)]
+pub struct ApplicationInfo {
+    /// USB vendor id
+    pub vendor_id: u16,
+
+    /// USB product id
+    pub product_id: u16,
+
+    /// Release number as binary coded decimal
+    pub bcd_device: u16,
+
+    /// Product string, empty if it can't be read
+    pub product_string: String,
+
+    /// Serial number string, empty if it can't be read
+    pub serial_number_string: String,
+
+    /// Flag if the device still provides a DFU interface in DFU mode
+    pub dfu_mode: bool,
+}
+
+/// Port of a device, identified by the bus and the port path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Port {
+    /// Bus number
+    pub bus_number: u8,
+
+    /// Port numbers from the root hub
+    pub port_numbers: Vec<u8>,
+
+    /// Address of the device when the port was taken, skipped when searching
+    pub address: u8,
+}
+
+impl Port {
+    /// Return the port of a DFU device
+    pub fn of(info: &super::DeviceInfo) -> Self {
+        Self {
+            bus_number: info.bus_number,
+            port_numbers: info.port_numbers.clone(),
+            address: info.address,
+        }
+    }
+}
+
+/// Wait for another device to enumerate on a port and return its info,
+/// `None` on timeout or if `cancelled` returns `true`
+pub fn wait(
+    port: &Port,
+    timeout: std::time::Duration,
+    cancelled: impl Fn() -> bool,
+) -> Result<Option<ApplicationInfo>> {
+    let deadline = std::time::Instant::now() + timeout;
+
+    while std::time::Instant::now() < deadline && !cancelled() {
+        std::thread::sleep(POLL_INTERVAL);
+
+        // Enumeration may fail while the device is still initializing
+        let Ok(devices) = rusb::devices() else {
+            continue;
+        };
+        let device = devices.iter().find(|device| {
+            device.bus_number() == port.bus_number
+                && device.address() != port.address
+                && device
+                    .port_numbers()
+                    .is_ok_and(|ports| ports == port.port_numbers)
+        });
+        if let Some(device) = device {
+            return Ok(Some(application_info(&device)?));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Return the info of a device, strings are left empty if it can't be opened
+fn application_info(device: &Device) -> Result<ApplicationInfo> {
+    let device_desc = device.device_descriptor()?;
+    let timeout = settings::get().timeout();
+
+    // Applications often have no driver usable by libusb or no permissions
+    let handle = super::open_device(device).ok();
+    let language = handle
+        .as_ref()
+        .and_then(|handle| handle.read_languages(timeout).ok())
+        .and_then(|languages| languages.first().copied());
+    let strings = handle.as_ref().zip(language);
+
+    Ok(ApplicationInfo {
+        vendor_id: device_desc.vendor_id(),
+        product_id: device_desc.product_id(),
+        bcd_device: info::version_to_bcd(device_desc.device_version()),
+        product_string: strings
+            .and_then(|(handle, language)| {
+                handle
+                    .read_product_string(language, &device_desc, timeout)
+                    .ok()
+            })
+            .unwrap_or_default(),
+        serial_number_string: strings
+            .and_then(|(handle, language)| {
+                handle
+                    .read_serial_number_string(language, &device_desc, timeout)
+                    .ok()
+            })
+            .unwrap_or_default(),
+        dfu_mode: super::dfu_interface(device, false).is_some(),
+    })
+}
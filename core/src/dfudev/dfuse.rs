@@ -0,0 +1,556 @@
+//! DfuSe extensions module
+//!
+//! References:
+//! - ST UM0290 for string descriptors memory segments coding
+
+use anyhow::{anyhow, Result};
+
+use super::{states, timing, DfuDevice, Error};
+
+/// Command code for "Set Address Pointer"
+const CMD_SET_ADDRESS_PTR: u8 = 0x21;
+
+/// Command code for "Erase Page"
+const CMD_ERASE_PAGE: u8 = 0x41;
+
+/// Command code for "Read Unprotect"
+const CMD_READ_UNPROTECT: u8 = 0x92;
+
+/// Representation of a target memory segment
+#[derive(Debug)]
+pub struct MemorySegment {
+    /// Name of the segment
+    pub name: String,
+
+    /// Vector of regions
+    pub regions: Vec<MemorySegmentRegion>,
+}
+
+/// Properties of a single memory segment region.
+/// Each segment can have several regions but most usually there's only one
+#[derive(Debug)]
+pub struct MemorySegmentRegion {
+    /// First address in this region
+    pub start_address: u32,
+
+    /// Last address in this region
+    pub end_address: u32,
+
+    /// Number of sectors in this region
+    pub sector_count: u32,
+
+    /// Size of a sector in bytes
+    pub sector_size: u32,
+
+    /// Flag to mark region as readable
+    pub readable: bool,
+
+    /// Flag to mark region as writable
+    pub writable: bool,
+
+    /// Flag to mark region as erasable
+    pub erasable: bool,
+}
+
+impl MemorySegment {
+    /// Creates a new segment by parsing the string descriptor
+    ///
+    /// The expected format is e.g. `@Internal Flash  /0x08000000/04*016Kg,01*064Kg`,
+    /// see ST UM0290. Malformed descriptors are reported as
+    /// `Error::InvalidMemoryLayout` with the reason.
+    pub fn from_string_desc<T: AsRef<str>>(string_desc: T) -> Result<Self> {
+        let string_desc = string_desc.as_ref();
+        let invalid =
+            |reason: String| anyhow!(Error::InvalidMemoryLayout(string_desc.to_string(), reason));
+
+        let mut regions = Vec::new();
+
+        let mut parts: Vec<&str> = string_desc.split('/').collect();
+
+        // Strip of the @ at the beginning and remove trailing spaces
+        let name = parts
+            .remove(0)
+            .trim()
+            .strip_prefix('@')
+            .ok_or_else(|| invalid("Name does not start with @.".into()))?
+            .to_string();
+
+        if parts.len() % 2 != 0 {
+            return Err(invalid("Start address without sectors.".into()));
+        }
+
+        let re = regex::Regex::new(r"^\s*(\d+)\*(\d+)\s*(\D)(\w)\s*$").unwrap();
+
+        for pair in parts.chunks(2) {
+            let address_str = pair[0].trim();
+            let mut address = u32::from_str_radix(address_str.trim_start_matches("0x"), 16)
+                .map_err(|_| invalid(format!("Invalid start address \"{address_str}\".")))?;
+
+            for sector_str in pair[1].split(',') {
+                let captures = re
+                    .captures(sector_str)
+                    .ok_or_else(|| invalid(format!("Invalid sectors \"{sector_str}\".")))?;
+
+                let number = |index: usize| {
+                    captures[index]
+                        .parse::<u32>()
+                        .map_err(|_| invalid(format!("Number too large in \"{sector_str}\".")))
+                };
+                let sector_count = number(1)?;
+
+                let multiplier = match &captures[3] {
+                    "K" => 1024,
+                    "M" => 1024 * 1024,
+                    " " | "B" => 1,
+                    other => {
+                        return Err(invalid(format!(
+                            "Invalid multiplier \"{other}\" in \"{sector_str}\"."
+                        )))
+                    }
+                };
+                let sector_size = number(2)?.checked_mul(multiplier).ok_or_else(|| {
+                    invalid(format!("Sector size too large in \"{sector_str}\"."))
+                })?;
+
+                let sector_type = &captures[4];
+                if !matches!(sector_type, "a" | "b" | "c" | "d" | "e" | "f" | "g") {
+                    return Err(invalid(format!(
+                        "Invalid sector type \"{sector_type}\" in \"{sector_str}\"."
+                    )));
+                }
+                let readable = matches!(sector_type, "a" | "c" | "e" | "g");
+                let writable = matches!(sector_type, "d" | "e" | "f" | "g");
+                let erasable = matches!(sector_type, "b" | "c" | "f" | "g");
+
+                let region_size = sector_count
+                    .checked_mul(sector_size)
+                    .filter(|size| address.checked_add(size.saturating_sub(1)).is_some())
+                    .ok_or_else(|| {
+                        invalid(format!(
+                            "Sectors \"{sector_str}\" exceed the address space."
+                        ))
+                    })?;
+
+                let region = MemorySegmentRegion {
+                    start_address: address,
+                    end_address: address + region_size.saturating_sub(1),
+                    sector_count,
+                    sector_size,
+                    readable,
+                    writable,
+                    erasable,
+                };
+
+                regions.push(region);
+
+                address = address.wrapping_add(region_size);
+            }
+        }
+
+        Ok(Self { name, regions })
+    }
+
+    /// Return a description of each problem with the geometry of the regions,
+    /// empty if the geometry is usable
+    pub fn geometry_errors(&self) -> Vec<String> {
+        if self.regions.is_empty() {
+            return vec!["No memory regions found.".into()];
+        }
+
+        self.regions
+            .iter()
+            .filter_map(|region| {
+                if region.sector_count == 0 {
+                    Some(format!(
+                        "Region at 0x{:08X} has no sectors.",
+                        region.start_address
+                    ))
+                } else if region.sector_size == 0 {
+                    Some(format!(
+                        "Region at 0x{:08X} has a sector size of 0.",
+                        region.start_address
+                    ))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Return if the address range is completely covered by writable regions
+    pub fn is_writable(&self, start_address: u32, size: u32) -> bool {
+        let end_address = start_address as u64 + size as u64;
+        let mut address = start_address as u64;
+
+        while address < end_address {
+            let region = self.regions.iter().find(|region| {
+                region.writable
+                    && region.start_address as u64 <= address
+                    && address <= region.end_address as u64
+            });
+            match region {
+                Some(region) => address = region.end_address as u64 + 1,
+                None => return false,
+            }
+        }
+
+        true
+    }
+
+    /// Return the erasable region containing the whole address range
+    ///
+    /// Sectors are erased with the sector size of a single region, so ranges
+    /// crossing a region boundary are not supported.
+    pub fn erase_region(&self, start_address: u32, size: u32) -> Option<&MemorySegmentRegion> {
+        let last_address = start_address as u64 + (size as u64).saturating_sub(1);
+
+        self.regions.iter().find(|region| {
+            region.erasable
+                && region.start_address <= start_address
+                && region.end_address as u64 >= last_address
+        })
+    }
+
+    /// Return if an address is the first one of a sector, addresses outside
+    /// of all regions count as sector start
+    pub fn is_sector_start(&self, address: u32) -> bool {
+        self.regions
+            .iter()
+            .find(|region| region.start_address <= address && address <= region.end_address)
+            .and_then(|region| (address - region.start_address).checked_rem(region.sector_size))
+            .map_or(true, |offset| offset == 0)
+    }
+
+    /// Return start address and size of the sector containing an address,
+    /// `None` if it lies outside of all regions
+    pub fn sector_at(&self, address: u32) -> Option<(u32, u32)> {
+        let region = self
+            .regions
+            .iter()
+            .find(|region| region.start_address <= address && address <= region.end_address)?;
+        let offset = (address - region.start_address).checked_rem(region.sector_size)?;
+
+        Some((address - offset, region.sector_size))
+    }
+
+    /// Return a description of the problem with writing an address range,
+    /// or `None` if it can be written. Erasability is checked if `erase` is set.
+    pub fn range_problem(&self, start_address: u32, size: u32, erase: bool) -> Option<String> {
+        let last_address = start_address as u64 + (size as u64).saturating_sub(1);
+        let segment_end = self
+            .regions
+            .iter()
+            .map(|region| region.end_address as u64)
+            .max()?;
+
+        if !self.regions.iter().any(|region| {
+            region.start_address <= start_address && start_address <= region.end_address
+        }) {
+            Some("starts outside of the memory regions".into())
+        } else if last_address > segment_end {
+            Some(format!(
+                "exceeds the capacity by {} bytes",
+                last_address - segment_end
+            ))
+        } else if !self.is_writable(start_address, size) {
+            Some("covers memory that is not writable".into())
+        } else if erase && self.erase_region(start_address, size).is_none() {
+            let overlapping = self.regions.iter().filter(|region| {
+                region.start_address as u64 <= last_address && start_address <= region.end_address
+            });
+            Some(if overlapping.clone().all(|region| region.erasable) {
+                "crosses a region boundary and can't be erased sector by sector".into()
+            } else {
+                "covers memory that is not erasable".into()
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Return if the segment holds system memory or OTP, which is only read
+    ///
+    /// Writing these areas is either impossible or irreversible, so all
+    /// write operations are blocked for them.
+    pub fn is_protected(&self) -> bool {
+        let name = self.name.to_lowercase();
+
+        name.contains("system") || name.contains("otp")
+    }
+
+    /// Return the total size of all regions in bytes
+    pub fn size(&self) -> u64 {
+        self.regions
+            .iter()
+            .map(|region| region.sector_count as u64 * region.sector_size as u64)
+            .sum()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// High-level function to set the address for subsequent uploads or downloads
+pub fn set_address(device: &DfuDevice, address: u32) -> Result<()> {
+    // Device must be in idle state for this operation
+    device.abort_request()?;
+
+    // Issue the request
+    set_address_request(device, address)?;
+
+    // First status response must have state dfuDNBUSY
+    let status = device.getstatus_request()?;
+    if status.bState != states::DeviceStateCode::dfuDNBUSY {
+        return Err(anyhow!(status.unexpected_state()));
+    }
+
+    device.wait_for_status_response(status.bwPollTimeout as u64)?;
+
+    // Abort to return to idle state, otherwise following requests can fail
+    device.abort_request()?;
+
+    Ok(())
+}
+
+/// High-level function to erase a page
+pub fn erase_page(device: &DfuDevice, address: u32) -> Result<()> {
+    // Device must be in idle state for this operation
+    device.abort_request()?;
+
+    // Issue the request
+    erase_page_request(device, address)?;
+
+    // First status response must have state dfuDNBUSY
+    let status = device.getstatus_request()?;
+    if status.bState != states::DeviceStateCode::dfuDNBUSY {
+        return Err(anyhow!(status.unexpected_state()));
+    }
+
+    let res = device.wait_for_status_response(status.bwPollTimeout as u64);
+
+    match res {
+        Ok(_) => Ok(()),
+        Err(err) if device.info.quirks.erase_busy_state => erase_busy_workaround(device, err),
+        Err(err) => Err(err)
+    }
+}
+
+/// High-level function to erase the complete flash memory
+pub fn mass_erase(device: &DfuDevice) -> Result<()> {
+    // Device must be in idle state for this operation
+    device.abort_request()?;
+
+    // Issue the request
+    mass_erase_request(device)?;
+
+    // First status response must have state dfuDNBUSY
+    let status = device.getstatus_request()?;
+    if status.bState != states::DeviceStateCode::dfuDNBUSY {
+        return Err(anyhow!(status.unexpected_state()));
+    }
+
+    device.wait_for_status_response(status.bwPollTimeout as u64)?;
+
+    Ok(())
+}
+
+/// High-level function to remove the readout protection
+///
+/// The device performs a mass erase of the flash memory and resets itself,
+/// so it usually disconnects before answering. The device must be reopened
+/// after it re-enumerated.
+pub fn read_unprotect(device: &DfuDevice) -> Result<()> {
+    // Device must be in idle state for this operation
+    device.abort_request()?;
+
+    // Issue the request
+    read_unprotect_request(device)?;
+
+    // First status response must have state dfuDNBUSY
+    let status = match device.getstatus_request() {
+        Err(err) if super::is_disconnected(&err) => return Ok(()),
+        result => result?,
+    };
+    if status.bState != states::DeviceStateCode::dfuDNBUSY {
+        return Err(anyhow!(status.unexpected_state()));
+    }
+
+    match device.wait_for_status_response(status.bwPollTimeout as u64) {
+        Err(err) if super::is_disconnected(&err) => Ok(()),
+        result => result.map(|_| ()),
+    }
+}
+
+/// Return if a failed upload is consistent with active readout protection
+///
+/// With readout protection, STM32 bootloaders stall uploads and enter the
+/// error state. The error state is cleared, so the device accepts further
+/// requests, e.g. to unprotect it.
+pub fn is_read_protected(device: &DfuDevice, upload_error: &anyhow::Error) -> bool {
+    if !super::is_stalled(upload_error) {
+        return false;
+    }
+
+    match device.getstatus_request() {
+        Ok(status) if status.bState == states::DeviceStateCode::dfuERROR => {
+            log::debug!("Upload failed with status {:?}", status.bStatus);
+            device.clrstatus_request().ok();
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Return if data read back is consistent with active readout protection
+///
+/// Some devices answer uploads from protected memory with zeros instead of
+/// stalling. Erased flash reads as 0xFF, so zeros where the expected data
+/// differs are taken as a sign of protection. This is only a hint: a failed
+/// download leaves zeros as well, so it must not be used for memory written
+/// by the same job.
+pub fn is_protected_readback(read: &[u8], expected: &[u8]) -> bool {
+    read != expected && read.iter().all(|&byte| byte == 0)
+}
+
+/// High-level function to leave DFU mode and start the application
+///
+/// The address pointer is set to the application start address and a
+/// zero-length download triggers the manifestation, after which the
+/// device jumps to the address.
+pub fn leave(device: &DfuDevice, address: u32) -> Result<()> {
+    set_address(device, address)?;
+
+    device.download_request(2, &[])?;
+
+    // The device may disconnect before answering, which is expected here
+    match device.wait_for_manifestation() {
+        Err(err) if super::is_disconnected(&err) => Ok(()),
+        result => result,
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Send a SET_ADDRESS request
+pub fn set_address_request(device: &DfuDevice, address: u32) -> Result<()> {
+    let addr = address.to_le_bytes();
+    let data = [CMD_SET_ADDRESS_PTR, addr[0], addr[1], addr[2], addr[3]];
+
+    device.write_request(timing::Request::Download, 0, &data)?;
+
+    Ok(())
+}
+
+/// Send a ERASE_PAGE request
+pub fn erase_page_request(device: &DfuDevice, address: u32) -> Result<()> {
+    let addr = address.to_le_bytes();
+    let data = [CMD_ERASE_PAGE, addr[0], addr[1], addr[2], addr[3]];
+
+    device.write_request(timing::Request::Download, 0, &data)?;
+
+    Ok(())
+}
+
+/// Send a READ_UNPROTECT request
+pub fn read_unprotect_request(device: &DfuDevice) -> Result<()> {
+    let data = [CMD_READ_UNPROTECT];
+
+    device.write_request(timing::Request::Download, 0, &data)?;
+
+    Ok(())
+}
+
+/// Send a ERASE_PAGE request without address, which erases the whole memory
+pub fn mass_erase_request(device: &DfuDevice) -> Result<()> {
+    let data = [CMD_ERASE_PAGE];
+
+    device.write_request(timing::Request::Download, 0, &data)?;
+
+    Ok(())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+fn erase_busy_workaround(device: &DfuDevice, erase_err: anyhow::Error) -> Result<()> {
+    // Enabled by `Quirks::erase_busy_state`, e.g. for STM32H7 Rev. V
+    // sector erase beyond 1MB
+
+    if let Some(Error::InvalidDeviceState(state)) = erase_err.downcast_ref::<Error>() {
+        if *state == states::DeviceStateCode::dfuDNBUSY {
+            log::debug!("erase busy state workaround");
+            let _ = device.clrstatus_request();
+            return device.clrstatus_request()
+        }
+    }
+    Err(erase_err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Return the descriptor and the reason of a parse error
+    fn layout_error(string_desc: &str) -> (String, String) {
+        match MemorySegment::from_string_desc(string_desc)
+            .unwrap_err()
+            .downcast::<Error>()
+        {
+            Ok(Error::InvalidMemoryLayout(desc, reason)) => (desc, reason),
+            other => panic!("Unexpected result {other:?}"),
+        }
+    }
+
+    #[test]
+    fn valid_layout() {
+        let segment =
+            MemorySegment::from_string_desc("@Internal Flash  /0x08000000/04*016Kg,01*064Kg")
+                .unwrap();
+
+        assert_eq!(segment.name, "Internal Flash");
+        assert_eq!(segment.regions.len(), 2);
+        assert_eq!(segment.regions[1].start_address, 0x0801_0000);
+        assert_eq!(segment.regions[1].end_address, 0x0801_FFFF);
+        assert_eq!(segment.regions[1].sector_size, 64 * 1024);
+        assert!(segment.geometry_errors().is_empty());
+    }
+
+    #[test]
+    fn empty_region_list() {
+        let segment = MemorySegment::from_string_desc("@Internal Flash").unwrap();
+
+        assert!(segment.regions.is_empty());
+        assert_eq!(segment.geometry_errors(), vec!["No memory regions found."]);
+    }
+
+    #[test]
+    fn zero_sector_size() {
+        let segment =
+            MemorySegment::from_string_desc("@Internal Flash  /0x08000000/04*000Kg").unwrap();
+
+        assert_eq!(
+            segment.geometry_errors(),
+            vec!["Region at 0x08000000 has a sector size of 0."]
+        );
+    }
+
+    #[test]
+    fn bad_multiplier() {
+        assert_eq!(
+            layout_error("@Internal Flash  /0x08000000/04*016Xg"),
+            (
+                "@Internal Flash  /0x08000000/04*016Xg".to_string(),
+                "Invalid multiplier \"X\" in \"04*016Xg\".".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn address_overflow() {
+        assert_eq!(
+            layout_error("@Internal Flash  /0xFFFFF000/02*004Kg"),
+            (
+                "@Internal Flash  /0xFFFFF000/02*004Kg".to_string(),
+                "Sectors \"02*004Kg\" exceed the address space.".to_string()
+            )
+        );
+    }
+}
@@ -0,0 +1,46 @@
+//! Check of the USB driver bound to a device
+//!
+//! On Windows, libusb can only open devices bound to the WinUSB driver.
+//! Devices using another driver or none at all are enumerated, but opening
+//! them fails. Such devices are still listed with the information available
+//! from their descriptors, so the user can be guided to install the driver.
+
+/// URL of the Zadig driver installation tool
+const ZADIG_URL: &str = "https://zadig.akeo.ie";
+
+/// State of the driver bound to a device
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DriverStatus {
+    /// Device can be opened
+    #[default]
+    Ready,
+
+    /// No driver usable by libusb is bound to the device
+    Missing,
+}
+
+/// Return if an error opening a device was caused by a missing driver
+#[cfg(target_os = "windows")]
+pub fn is_missing(error: &rusb::Error) -> bool {
+    matches!(error, rusb::Error::NotSupported | rusb::Error::NotFound)
+}
+
+/// Return if an error opening a device was caused by a missing driver
+#[cfg(not(target_os = "windows"))]
+pub fn is_missing(_error: &rusb::Error) -> bool {
+    // Devices are accessed via the kernel on other platforms
+    false
+}
+
+/// Return step-by-step instructions to install the WinUSB driver for a device
+pub fn install_instructions(vendor_id: u16, product_id: u16) -> String {
+    format!(
+        "The device is not bound to the WinUSB driver, so it can't be accessed.\n\n\
+        1. Download Zadig from {ZADIG_URL} and run it.\n\
+        2. Choose Options > List All Devices.\n\
+        3. Select the device with USB ID {vendor_id:04X} {product_id:04X} from the list.\n\
+        4. Choose WinUSB as target driver and click Install Driver or Replace Driver.\n\
+        5. Reconnect the device and search for devices again.\n\n\
+        Other applications relying on the previous driver may not find the device anymore."
+    )
+}
@@ -0,0 +1,119 @@
+//! Detection of the STM32 family of a device
+//!
+//! The STM32 system bootloader reports the same vendor and product id for all
+//! families, so the family is inferred from the memory layout published in
+//! the alt setting names. The option bytes address is unique for most
+//! families, the sector size of the internal flash is used to tell apart
+//! the remaining ones. Families with identical layouts can't be distinguished,
+//! e.g. STM32F2 devices are reported as STM32F4.
+
+use super::dfuse::MemorySegment;
+use super::DeviceInfo;
+
+/// Vendor id of STMicroelectronics
+const VENDOR_ID_ST: u16 = 0x0483;
+
+/// Product id of the STM32 DfuSe bootloader
+const PRODUCT_ID_DFUSE: u16 = 0xDF11;
+
+/// Address range of the internal flash on all supported families
+const FLASH_RANGE: std::ops::Range<u32> = 0x0800_0000..0x0900_0000;
+
+/// STM32 family
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+pub enum Family {
+    /// STM32F4, also STM32F2 with the same layout
+    F4,
+
+    /// STM32F7
+    F7,
+
+    /// STM32G0
+    G0,
+
+    /// STM32G4
+    G4,
+
+    /// STM32H7
+    H7,
+
+    /// STM32L0
+    L0,
+
+    /// STM32L1
+    L1,
+
+    /// STM32L4
+    L4,
+
+    /// STM32L5
+    L5,
+
+    /// STM32U5
+    U5,
+
+    /// STM32WB
+    WB,
+}
+
+impl std::fmt::Display for Family {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "STM32{self:?}")
+    }
+}
+
+/// Return the family of a device or `None` if it can't be determined
+pub fn detect(info: &DeviceInfo) -> Option<Family> {
+    if info.vendor_id != VENDOR_ID_ST || info.product_id != PRODUCT_ID_DFUSE {
+        return None;
+    }
+
+    let segments: Vec<MemorySegment> = info
+        .alt_settings
+        .iter()
+        .filter(|(_, name)| name.starts_with('@'))
+        .filter_map(|(_, name)| MemorySegment::from_string_desc(name).ok())
+        .collect();
+
+    let option_bytes = segments
+        .iter()
+        .find(|segment| segment.name.starts_with("Option Bytes"))
+        .and_then(|segment| segment.regions.first());
+
+    // Smallest sector size of the internal flash
+    let flash_sector_size = segments
+        .iter()
+        .flat_map(|segment| &segment.regions)
+        .filter(|region| FLASH_RANGE.contains(&region.start_address))
+        .map(|region| region.sector_size)
+        .min();
+
+    let family = match option_bytes.map(|region| (region.start_address, region.sector_size)) {
+        Some((0x1FFF_C000, _)) => Some(Family::F4),
+        Some((0x1FFF_0000, _)) => Some(Family::F7),
+        Some((0x5200_201C, _)) => Some(Family::H7),
+        Some((0x1FFF_8000, _)) => Some(Family::WB),
+        Some((0x1FFF_7800, 40)) => Some(Family::L4),
+        Some((0x1FFF_7800, 48)) => Some(Family::G4),
+        Some((0x1FFF_7800, 128)) => Some(Family::G0),
+        Some((0x1FF8_0000, _)) => match flash_sector_size {
+            Some(128) => Some(Family::L0),
+            Some(256) => Some(Family::L1),
+            _ => None,
+        },
+        Some((0x4002_2040, _)) => match flash_sector_size {
+            Some(2048) => Some(Family::L5),
+            Some(8192) => Some(Family::U5),
+            _ => None,
+        },
+        // Without option bytes, only the uniform 128K sectors of the H7 are unique
+        None if flash_sector_size == Some(128 * 1024) => Some(Family::H7),
+        _ => None,
+    };
+
+    if let Some(family) = family {
+        log::debug!("Detected MCU family {family}");
+    }
+
+    family
+}
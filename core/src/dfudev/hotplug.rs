@@ -0,0 +1,104 @@
+//! Detection of connected and disconnected USB devices
+//!
+//! The hotplug support of libusb is used if available. Otherwise, the list of
+//! USB devices is polled periodically.
+
+use std::sync::mpsc;
+use std::time::Duration;
+
+use rusb::UsbContext;
+
+/// Interval for polling the device list if hotplug is not available
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Time to wait after an event so that the device can finish its enumeration
+/// and multiple events are combined
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+/// Timeout for handling libusb events, limits the reaction time on errors
+const EVENT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Hotplug handler forwarding all events to a channel
+struct Handler {
+    /// Sender for the event notifications
+    sender: mpsc::Sender<()>,
+}
+
+impl<T: UsbContext> rusb::Hotplug<T> for Handler {
+    fn device_arrived(&mut self, _device: rusb::Device<T>) {
+        self.sender.send(()).ok();
+    }
+
+    fn device_left(&mut self, _device: rusb::Device<T>) {
+        self.sender.send(()).ok();
+    }
+}
+
+/// Call `on_change` from a background thread whenever a USB device is
+/// connected or disconnected
+pub fn watch(on_change: impl Fn() + Send + 'static) {
+    let (sender, receiver) = mpsc::channel();
+
+    std::thread::spawn(move || {
+        if rusb::has_hotplug() {
+            match handle_hotplug_events(sender.clone()) {
+                Ok(_) => return,
+                Err(error) => log::warn!("USB hotplug failed, polling instead: {error}"),
+            }
+        }
+        poll_devices(sender);
+    });
+
+    std::thread::spawn(move || {
+        while receiver.recv().is_ok() {
+            std::thread::sleep(SETTLE_TIME);
+            receiver.try_iter().count();
+            on_change();
+        }
+    });
+}
+
+/// Register a hotplug callback and handle the libusb events
+fn handle_hotplug_events(sender: mpsc::Sender<()>) -> rusb::Result<()> {
+    let context = rusb::Context::new()?;
+    let _registration: rusb::Registration<rusb::Context> = rusb::HotplugBuilder::new()
+        .enumerate(false)
+        .register(&context, Box::new(Handler { sender }))?;
+
+    log::debug!("USB hotplug callback registered.");
+
+    loop {
+        context.handle_events(Some(EVENT_TIMEOUT))?;
+    }
+}
+
+/// Poll the device list and notify on changes
+fn poll_devices(sender: mpsc::Sender<()>) {
+    let mut last_devices = connected_devices();
+
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+
+        let devices = connected_devices();
+        if devices != last_devices {
+            last_devices = devices;
+            if sender.send(()).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// Return bus number and address of all connected devices
+fn connected_devices() -> Vec<(u8, u8)> {
+    let mut devices: Vec<(u8, u8)> = rusb::devices()
+        .map(|list| {
+            list.iter()
+                .map(|device| (device.bus_number(), device.address()))
+                .collect()
+        })
+        .unwrap_or_default();
+    devices.sort_unstable();
+
+    devices
+}
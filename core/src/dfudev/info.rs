@@ -0,0 +1,297 @@
+//! Additional device info based on parsing descriptors
+
+use anyhow::{anyhow, Result};
+
+use super::driver::DriverStatus;
+use super::family::{self, Family};
+use super::quirks::{self, Quirks};
+use super::settings;
+use super::{
+    Device, DfuFunctionalDescriptor, Error, ATTR_CAN_DNLOAD, ATTR_CAN_UPLOAD,
+    ATTR_MANIFESTATION_TOLERANT, ATTR_WILL_DETACH,
+};
+
+/// Info read from the descriptors of a device
+#[derive(Debug, Clone)]
+pub struct DeviceInfo {
+    /// USB vendor id
+    pub vendor_id: u16,
+
+    /// USB product id
+    pub product_id: u16,
+
+    /// Device release number formatted for display
+    pub device_version: String,
+
+    /// Device release number as binary coded decimal
+    pub bcd_device: u16,
+
+    /// Manufacturer from the string descriptor
+    pub manufacturer_string: String,
+
+    /// Product name from the string descriptor
+    pub product_string: String,
+
+    /// Serial number from the string descriptor
+    pub serial_number_string: String,
+
+    /// Configuration containing the DFU interface
+    pub dfu_config_number: u8,
+
+    /// Number of the DFU interface
+    pub dfu_interface_number: u8,
+
+    /// Alternate settings of the DFU interface with their names
+    pub alt_settings: Vec<(u8, String)>,
+
+    /// Attributes of the DFU functional descriptor
+    pub dfu_attributes: u8,
+
+    /// Flag if the device accepts downloads
+    pub can_download: bool,
+
+    /// Flag if the device supports uploads
+    pub can_upload: bool,
+
+    /// Flag if the device stays in DFU mode after manifestation
+    pub manifestation_tolerant: bool,
+
+    /// Flag if the device detaches itself on a detach request
+    pub will_detach: bool,
+
+    /// Time in ms the device waits for a reset after a detach request
+    pub dfu_detach_timeout: u16,
+
+    /// Maximum number of bytes per transfer
+    pub dfu_transfer_size: u16,
+
+    /// DFU specification version as binary coded decimal
+    pub dfu_version: u16,
+
+    /// Number of the USB bus
+    pub bus_number: u8,
+
+    /// Address on the USB bus
+    pub address: u8,
+
+    /// Port path from the root hub
+    pub port_numbers: Vec<u8>,
+
+    /// Known device family, if detected
+    pub family: Option<Family>,
+
+    /// Status of the driver bound to the DFU interface
+    pub driver: DriverStatus,
+
+    /// Quirks applied to the device
+    pub quirks: Quirks,
+}
+
+impl std::fmt::Display for DeviceInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} [0x{:04X?}:0x{:04X?}] v{} {}",
+            self.manufacturer_string,
+            self.product_string,
+            self.vendor_id,
+            self.product_id,
+            self.device_version,
+            self.serial_number_string
+        )
+    }
+}
+
+impl DeviceInfo {
+    /// Return the physical location as "bus-port.port..."
+    pub fn bus_path(&self) -> String {
+        let ports = self
+            .port_numbers
+            .iter()
+            .map(|port| port.to_string())
+            .collect::<Vec<_>>()
+            .join(".");
+
+        if ports.is_empty() {
+            format!("{}", self.bus_number)
+        } else {
+            format!("{}-{}", self.bus_number, ports)
+        }
+    }
+
+    /// Return the names of the attribute bits set in the DFU functional descriptor
+    pub fn attribute_names(&self) -> Vec<&'static str> {
+        [
+            (self.can_download, "download"),
+            (self.can_upload, "upload"),
+            (self.manifestation_tolerant, "manifestation tolerant"),
+            (self.will_detach, "will detach"),
+        ]
+        .into_iter()
+        .filter_map(|(set, name)| set.then_some(name))
+        .collect()
+    }
+
+    /// Return a multi-line description with all details
+    pub fn details(&self) -> String {
+        format!(
+            "Manufacturer: {}\nProduct: {}\nVendor ID: 0x{:04X}\nProduct ID: 0x{:04X}\n\
+            Device version: {}\nSerial number: {}\nMCU family: {}\nBus {}, address {}, port path {}",
+            self.manufacturer_string,
+            self.product_string,
+            self.vendor_id,
+            self.product_id,
+            self.device_version,
+            self.serial_number_string,
+            self.family
+                .map_or("unknown".to_string(), |family| family.to_string()),
+            self.bus_number,
+            self.address,
+            self.bus_path()
+        )
+    }
+}
+
+/// Return additional device information depending on configuration
+/// and interface number
+pub fn info(
+    device: &Device,
+    dfu_config_number: u8,
+    dfu_interface_number: u8,
+) -> Result<DeviceInfo> {
+    // Without a driver, only the descriptors are available, but no strings
+    let (handle, driver) = match super::open_device(device) {
+        Ok(handle) => (Some(handle), DriverStatus::Ready),
+        Err(error) if matches!(error.downcast_ref(), Some(Error::DriverMissing)) => {
+            (None, DriverStatus::Missing)
+        }
+        Err(error) => return Err(error),
+    };
+    let timeout = settings::get().timeout();
+    let language = match &handle {
+        Some(handle) => Some(handle.read_languages(timeout)?[0]),
+        None => None,
+    };
+    let strings = handle.as_ref().zip(language);
+    let device_desc = device.device_descriptor()?;
+
+    let manufacturer_string = strings
+        .and_then(|(handle, language)| {
+            handle
+                .read_manufacturer_string(language, &device_desc, timeout)
+                .ok()
+        })
+        .unwrap_or_default();
+    let product_string = strings
+        .and_then(|(handle, language)| {
+            handle
+                .read_product_string(language, &device_desc, timeout)
+                .ok()
+        })
+        .unwrap_or_default();
+    let serial_number_string = strings
+        .and_then(|(handle, language)| {
+            handle
+                .read_serial_number_string(language, &device_desc, timeout)
+                .ok()
+        })
+        .unwrap_or_default();
+
+    let mut alt_settings = Vec::<(u8, String)>::new();
+
+    let mut dfu_attributes = 0;
+    let mut dfu_detach_timeout = 0;
+    let mut dfu_transfer_size = 0;
+    let mut dfu_version = 0;
+
+    for n in 0..device_desc.num_configurations() {
+        let config_desc = match device.config_descriptor(n) {
+            Ok(desc) => desc,
+            Err(_) => continue,
+        };
+
+        if config_desc.number() == dfu_config_number {
+            for interface in config_desc.interfaces() {
+                for interface_desc in interface.descriptors() {
+                    if interface_desc.interface_number() == dfu_interface_number {
+                        let interface_string = strings
+                            .and_then(|(handle, language)| {
+                                handle
+                                    .read_interface_string(language, &interface_desc, timeout)
+                                    .ok()
+                            })
+                            .unwrap_or_else(|| String::from("(unnamed)"));
+                        alt_settings.push((interface_desc.setting_number(), interface_string));
+
+                        // Extra bytes contain the DFU functional descriptor
+                        if !interface_desc.extra().is_empty() {
+                            let extra = interface_desc.extra();
+                            if extra.len() == 9 && extra[0] == 9 && extra[1] == 0x21 {
+                                let func_desc = DfuFunctionalDescriptor::from_bytes(extra);
+                                dfu_attributes = func_desc.bmAttributes;
+                                dfu_detach_timeout = func_desc.wDetachTimeOut;
+                                dfu_transfer_size = func_desc.wTransferSize;
+                                dfu_version = func_desc.bcdDFUVersion;
+                            } else {
+                                return Err(anyhow!(Error::NoDfuFunctionalDescriptor));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let mut info = DeviceInfo {
+        vendor_id: device_desc.vendor_id(),
+        product_id: device_desc.product_id(),
+        device_version: format!("{}", device_desc.device_version()),
+        bcd_device: version_to_bcd(device_desc.device_version()),
+        manufacturer_string,
+        product_string,
+        serial_number_string,
+        dfu_config_number,
+        dfu_interface_number,
+        alt_settings,
+        dfu_attributes,
+        can_download: dfu_attributes & ATTR_CAN_DNLOAD != 0,
+        can_upload: dfu_attributes & ATTR_CAN_UPLOAD != 0,
+        manifestation_tolerant: dfu_attributes & ATTR_MANIFESTATION_TOLERANT != 0,
+        will_detach: dfu_attributes & ATTR_WILL_DETACH != 0,
+        dfu_detach_timeout,
+        dfu_transfer_size,
+        dfu_version,
+        bus_number: device.bus_number(),
+        address: device.address(),
+        port_numbers: device.port_numbers().unwrap_or_default(),
+        family: None,
+        driver,
+        quirks: Quirks::default(),
+    };
+    info.family = family::detect(&info);
+
+    let quirks = quirks::lookup(&info);
+    quirks.apply(&mut info);
+    if !quirks.memory_map.is_empty() {
+        // The family depends on the memory map
+        info.family = family::detect(&info);
+    }
+    info.quirks = quirks;
+
+    Ok(info)
+}
+
+/// Convert a version back into its binary coded decimal (BCD) representation
+pub(super) fn version_to_bcd(version: rusb::Version) -> u16 {
+    let major = version.major() as u16;
+
+    ((major / 10) << 12)
+        | ((major % 10) << 8)
+        | ((version.minor() as u16) << 4)
+        | version.sub_minor() as u16
+}
+
+/// Return a binary coded decimal release number, e.g. "1.02" for 0x0102
+pub fn bcd_version(version: u16) -> String {
+    format!("{:x}.{:02x}", version >> 8, version & 0xFF)
+}
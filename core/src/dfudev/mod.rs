@@ -0,0 +1,987 @@
+//! USB DFU device management
+//!
+//! Reference: [DFU 1.1 Specification](https://www.usb.org/sites/default/files/DFU_1.1.pdf)
+
+#![allow(dead_code)]
+
+pub mod application;
+pub mod dfuse;
+pub mod driver;
+pub mod family;
+pub mod hotplug;
+pub mod info;
+pub mod quirks;
+pub mod settings;
+pub mod simulator;
+pub mod states;
+pub mod timing;
+pub mod transport;
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use anyhow::{anyhow, Result};
+pub use rusb::has_hotplug;
+use rusb::{constants, GlobalContext};
+
+pub use info::DeviceInfo;
+pub use states::{DeviceStateCode, DeviceStatusCode};
+pub use transport::Transport;
+
+/// USB device as enumerated by libusb
+pub type Device = rusb::Device<GlobalContext>;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Device Firmware Upgrade Code
+const INTERFACE_SUBCLASS_DFU: u8 = 0x01;
+
+/// Interval between two attempts to find a reconnected device
+const RECONNECT_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// Number of attempts to get a status response before a device is
+/// considered unresponsive
+const NUM_PROBE_ATTEMPTS: usize = 3;
+
+/// Max. time to wait for the manifestation phase to finish
+const MANIFESTATION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Bit in `bmAttributes` set if the device supports downloads
+const ATTR_CAN_DNLOAD: u8 = 0x01;
+
+/// Bit in `bmAttributes` set if the device supports uploads
+const ATTR_CAN_UPLOAD: u8 = 0x02;
+
+/// Bit in `bmAttributes` set if the device is manifestation tolerant
+const ATTR_MANIFESTATION_TOLERANT: u8 = 0x04;
+
+/// Bit in `bmAttributes` set if the device detaches on its own after DFU_DETACH
+const ATTR_WILL_DETACH: u8 = 0x08;
+
+/// Requests module, each constant is a tuple of (request_type, request)
+mod requests {
+    /// Generate a detach-attach sequence on the bus
+    pub const DFU_DETACH: (u8, u8) = (0b00100001, 0);
+
+    /// Download firmware data from host to device
+    pub const DFU_DNLOAD: (u8, u8) = (0b00100001, 1);
+
+    /// Upload firmware data from device to host
+    pub const DFU_UPLOAD: (u8, u8) = (0b10100001, 2);
+
+    /// Request the status from the device
+    pub const DFU_GETSTATUS: (u8, u8) = (0b10100001, 3);
+
+    /// Clear device error status
+    pub const DFU_CLRSTATUS: (u8, u8) = (0b00100001, 4);
+
+    /// Request a report about the state of the device
+    pub const DFU_GETSTATE: (u8, u8) = (0b10100001, 5);
+
+    /// Abort operations and return to the idle state
+    pub const DFU_ABORT: (u8, u8) = (0b00100001, 6);
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Device in DFU mode with its info and the handle once opened
+pub struct DfuDevice {
+    /// Unique hash based on vendor id, product id, serial and port path,
+    /// so identical boards on different ports are told apart
+    pub id: u64,
+
+    /// Additional info containing strings and alt settings
+    pub info: DeviceInfo,
+
+    /// Way the device is reached when opening it
+    connection: Connection,
+
+    /// Transport of the requests while the device is open
+    handle: Option<Box<dyn Transport>>,
+
+    /// Flag if the DFU interface is claimed
+    claimed: bool,
+
+    /// Alt setting selected on the DFU interface
+    alt_setting: Option<u8>,
+}
+
+/// Way a device is reached
+enum Connection {
+    /// Device on the USB bus
+    Usb(Device),
+
+    /// Device reached via another transport, returned by the function
+    Transport(Box<dyn Fn() -> Result<Box<dyn Transport>> + Send + Sync>),
+
+    /// Simulated device that can't be opened
+    None,
+}
+
+impl Hash for DfuDevice {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.info.vendor_id.hash(state);
+        self.info.product_id.hash(state);
+        self.info.serial_number_string.hash(state);
+        // The bus address changes on every re-enumeration, the port stays the same
+        self.info.bus_number.hash(state);
+        self.info.port_numbers.hash(state);
+    }
+}
+
+impl DfuDevice {
+    /// Return a vector of all devices with DFU capability
+    ///
+    /// - If `include_runtime` is set to `false`, only devices in DFU mode are returned
+    /// - If `include_runtime` is set to `true`, also devices in runtime configuration
+    ///   are returned
+    pub fn find(include_runtime: bool) -> Result<Option<Vec<Self>>> {
+        let mut devices = Vec::new();
+
+        for device in rusb::devices()?.iter() {
+            if let Some((config_number, interface_number)) = dfu_interface(&device, include_runtime)
+            {
+                let info = info::info(&device, config_number, interface_number)?;
+                devices.push(Self::with_info(Connection::Usb(device), info));
+            }
+        }
+
+        // Sort for a stable order independent of the enumeration sequence
+        devices.sort_by(|a, b| {
+            (
+                &a.info.serial_number_string,
+                a.info.bus_number,
+                &a.info.port_numbers,
+            )
+                .cmp(&(
+                    &b.info.serial_number_string,
+                    b.info.bus_number,
+                    &b.info.port_numbers,
+                ))
+        });
+
+        let result = if !devices.is_empty() {
+            Some(devices)
+        } else {
+            None
+        };
+
+        Ok(result)
+    }
+
+    /// Return a simulated device that is never opened, e.g. for the demo mode
+    pub fn simulated(info: DeviceInfo) -> Self {
+        Self::with_info(Connection::None, info)
+    }
+
+    /// Return a device reached via another transport than USB
+    ///
+    /// `connect` is called each time the device is opened and returns a new
+    /// transport, e.g. to a [simulated device](simulator::SimulatedDevice).
+    pub fn with_transport(
+        info: DeviceInfo,
+        connect: impl Fn() -> Result<Box<dyn Transport>> + Send + Sync + 'static,
+    ) -> Self {
+        Self::with_info(Connection::Transport(Box::new(connect)), info)
+    }
+
+    /// Return a device with its id derived from the info
+    fn with_info(connection: Connection, info: DeviceInfo) -> Self {
+        let mut device = Self {
+            id: 0,
+            connection,
+            info,
+            handle: None,
+            claimed: false,
+            alt_setting: None,
+        };
+        let mut hasher = DefaultHasher::new();
+        device.hash(&mut hasher);
+        device.id = hasher.finish();
+
+        device
+    }
+
+    /// Return if the device is still connected at the same bus address
+    ///
+    /// Replugged devices get a new address, so an entry found by an earlier
+    /// scan can't be opened anymore when this returns `false`. Simulated
+    /// devices and other transports are always connected.
+    pub fn is_connected(&self) -> bool {
+        let Connection::Usb(dev) = &self.connection else {
+            return true;
+        };
+
+        rusb::devices().is_ok_and(|devices| {
+            devices.iter().any(|device| {
+                device.bus_number() == dev.bus_number() && device.address() == dev.address()
+            })
+        })
+    }
+
+    /// Return if the device was found on the USB bus, not attached with
+    /// another transport
+    pub fn is_usb(&self) -> bool {
+        matches!(self.connection, Connection::Usb(_))
+    }
+
+    /// Find a device by its id
+    pub fn find_by_id(id: u64) -> Result<Option<Self>> {
+        let devices = Self::find(false)?;
+
+        if let Some(devices) = devices {
+            Ok(devices.into_iter().find(|x| x.id == id))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Open the device and claim the DFU interface
+    pub fn open(&mut self) -> Result<()> {
+        let handle: Box<dyn Transport> = match &self.connection {
+            Connection::Usb(dev) => {
+                let handle = open_device(dev)?;
+
+                // Kernel drivers bound to the interface are detached when claiming it
+                // and reattached when releasing it. Only supported on Linux.
+                match handle.set_auto_detach_kernel_driver(true) {
+                    Ok(_) | Err(rusb::Error::NotSupported) => {}
+                    Err(error) => {
+                        log::debug!("Auto-detaching kernel driver not available: {error}")
+                    }
+                }
+
+                Box::new(handle)
+            }
+            Connection::Transport(connect) => connect()?,
+            Connection::None => return Err(anyhow!(Error::SimulatedDevice)),
+        };
+
+        self.handle = Some(handle);
+        self.claim_interface()?;
+
+        Ok(())
+    }
+
+    /// Perform a USB port reset, which makes the device re-enumerate
+    pub fn usb_reset(&mut self) -> Result<()> {
+        let handle = self.handle.as_mut().ok_or(anyhow!(Error::NoDeviceHandle))?;
+
+        match handle.reset() {
+            // Device is already gone when it re-enumerates as a new one
+            Ok(_) | Err(rusb::Error::NotFound) | Err(rusb::Error::NoDevice) => Ok(()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Claim the DFU interface unless already done
+    pub fn claim_interface(&mut self) -> Result<()> {
+        if self.claimed {
+            return Ok(());
+        }
+
+        let interface_number = self.info.dfu_interface_number;
+        let handle = self.handle.as_mut().ok_or(anyhow!(Error::NoDeviceHandle))?;
+
+        handle.claim_interface(interface_number)?;
+        self.claimed = true;
+
+        Ok(())
+    }
+
+    /// Release the DFU interface if it was claimed
+    pub fn release_interface(&mut self) {
+        if let (true, Some(handle)) = (self.claimed, self.handle.as_mut()) {
+            handle
+                .release_interface(self.info.dfu_interface_number)
+                .map_err(|error| log::debug!("Error releasing interface: {error}"))
+                .ok();
+        }
+        self.claimed = false;
+        self.alt_setting = None;
+    }
+
+    /// Select an alternate setting of the DFU interface
+    ///
+    /// The request is only sent if the setting differs from the current one.
+    pub fn select_alt_setting(&mut self, alt_setting: u8) -> Result<()> {
+        if self.alt_setting == Some(alt_setting) {
+            return Ok(());
+        }
+
+        self.claim_interface()?;
+
+        let interface_number = self.info.dfu_interface_number;
+        let handle = self.handle.as_mut().ok_or(anyhow!(Error::NoDeviceHandle))?;
+        handle.set_alternate_setting(interface_number, alt_setting)?;
+        self.alt_setting = Some(alt_setting);
+        log::debug!("Selected alt setting {alt_setting}");
+
+        Ok(())
+    }
+
+    /// Select the first alternate setting, used for plain DFU and mass erase
+    pub fn select_default_alt_setting(&mut self) -> Result<()> {
+        let alt_setting = self.info.alt_settings.first().map_or(0, |alt| alt.0);
+
+        self.select_alt_setting(alt_setting)
+    }
+
+    /// Wait for the device to re-enumerate after a disconnect and open it again
+    ///
+    /// The device is found by its id, which doesn't depend on the bus address.
+    /// Devices reached via another transport are opened again instead. The
+    /// alt setting selected before is restored.
+    pub fn reconnect(&mut self, timeout: std::time::Duration) -> Result<()> {
+        let deadline = std::time::Instant::now() + timeout;
+        let alt_setting = self.alt_setting;
+
+        if let Connection::Transport(_) = self.connection {
+            self.close();
+            self.open()?;
+            if let Some(alt_setting) = alt_setting {
+                self.select_alt_setting(alt_setting)?;
+            }
+            return Ok(());
+        }
+
+        loop {
+            std::thread::sleep(RECONNECT_POLL_INTERVAL);
+
+            // Enumeration may fail while the device is still initializing
+            if let Some(mut device) = Self::find_by_id(self.id).ok().flatten() {
+                device.open()?;
+                if let Some(alt_setting) = alt_setting {
+                    device.select_alt_setting(alt_setting)?;
+                }
+                *self = device;
+                return Ok(());
+            }
+
+            if std::time::Instant::now() >= deadline {
+                return Err(anyhow!(Error::ReconnectTimeout));
+            }
+        }
+    }
+
+    /// Check if the device answers status requests, reopening it before each attempt
+    ///
+    /// A device that can be opened repeatedly, but stalls every request, usually
+    /// runs a hanging bootloader that only recovers by a hardware reset. Other
+    /// failures like a disconnect are not classified.
+    pub fn probe(&mut self) -> Result<()> {
+        for attempt in 1..=NUM_PROBE_ATTEMPTS {
+            self.close();
+            if self.open().is_err() {
+                return Ok(());
+            }
+
+            match self.getstatus_request() {
+                Ok(_) => return Ok(()),
+                Err(error) if is_stalled(&error) => {
+                    log::debug!("Probe {attempt} of {NUM_PROBE_ATTEMPTS} failed: {error}");
+                    std::thread::sleep(RECONNECT_POLL_INTERVAL);
+                }
+                Err(_) => return Ok(()),
+            }
+        }
+
+        Err(anyhow!(Error::Unresponsive))
+    }
+
+    /// Return if the device is open
+    pub fn is_open(&self) -> bool {
+        self.handle.is_some()
+    }
+
+    /// Release the DFU interface and close the device
+    pub fn close(&mut self) {
+        self.release_interface();
+        self.handle = None;
+    }
+
+    /// Return the transport of the opened device as result
+    pub fn handle(&self) -> Result<&dyn Transport> {
+        self.handle.as_deref().ok_or(anyhow!(Error::NoDeviceHandle))
+    }
+
+    /// Send a control request writing data to the device and record its duration
+    pub(crate) fn write_request(
+        &self,
+        request: timing::Request,
+        value: u16,
+        data: &[u8],
+    ) -> Result<usize> {
+        let handle = self.handle()?;
+        let (request_type, code) = request_setup(request);
+        let timeout = settings::get().timeout();
+        let length = timing::timed(request, value, data.len(), || {
+            handle.write_control(request_type, code, value, 0, data, timeout)
+        })?;
+
+        Ok(length)
+    }
+
+    /// Send a control request reading data from the device and record its duration
+    pub(crate) fn read_request(
+        &self,
+        request: timing::Request,
+        value: u16,
+        data: &mut [u8],
+    ) -> Result<usize> {
+        let handle = self.handle()?;
+        let (request_type, code) = request_setup(request);
+        let timeout = settings::get().timeout();
+        let length = timing::timed(request, value, data.len(), || {
+            handle.read_control(request_type, code, value, 0, data, timeout)
+        })?;
+
+        Ok(length)
+    }
+
+    /// Send a DFU_DETACH request
+    pub fn detach_request(&self) -> Result<()> {
+        self.write_request(timing::Request::Detach, 0, &[])?;
+
+        Ok(())
+    }
+
+    /// Send a DFU_DNLOAD request
+    ///
+    /// A buffer containing data is written to the device and the number
+    /// of transferred bytes is returned
+    pub fn download_request(&self, block_num: u16, data: &[u8]) -> Result<usize> {
+        let transfer_size = self.write_request(timing::Request::Download, block_num, data)?;
+
+        Ok(transfer_size)
+    }
+
+    /// Send a DFU_UPLOAD request
+    ///
+    /// A buffer is filled with data from the device and the number
+    /// of transferred bytes is returned
+    pub fn upload_request(&self, block_num: u16, data: &mut [u8]) -> Result<usize> {
+        let transfer_size = self.read_request(timing::Request::Upload, block_num, data)?;
+
+        Ok(transfer_size)
+    }
+
+    /// Send a DFU_GETSTATUS request
+    ///
+    /// A `DeviceStatus` struct is returned containing the response
+    /// in a convenient format
+    pub fn getstatus_request(&self) -> Result<DeviceStatusResponse> {
+        let mut buffer = [0; 6];
+
+        self.read_request(timing::Request::GetStatus, 0, &mut buffer)?;
+
+        let mut status = DeviceStatusResponse::from_bytes(&buffer);
+        if let Some(poll_timeout) = self.info.quirks.poll_timeout {
+            status.bwPollTimeout = poll_timeout;
+        }
+        crate::session::status(&status);
+
+        Ok(status)
+    }
+
+    /// Send a DFU_CLRSTATUS request
+    pub fn clrstatus_request(&self) -> Result<()> {
+        self.write_request(timing::Request::ClrStatus, 0, &[])?;
+
+        Ok(())
+    }
+
+    /// Send a DFU_GETSTATE request
+    pub fn getstate_request(&self) -> Result<u8> {
+        let mut buffer = [0; 1];
+
+        self.read_request(timing::Request::GetState, 0, &mut buffer)?;
+
+        Ok(buffer[0])
+    }
+
+    /// Send a DFU_ABORT request
+    pub fn abort_request(&self) -> Result<()> {
+        self.write_request(timing::Request::Abort, 0, &[])?;
+
+        Ok(())
+    }
+
+    /// Wait after a DFU_DNLOAD request until the device is ready for the next block
+    pub fn wait_for_download_idle(&self) -> Result<DeviceStatusResponse> {
+        let status = self.getstatus_request()?;
+
+        match status.bState {
+            states::DeviceStateCode::dfuDNLOAD_IDLE => Ok(status),
+            states::DeviceStateCode::dfuDNBUSY | states::DeviceStateCode::dfuDNLOAD_SYNC => {
+                self.wait_for_status_response(status.bwPollTimeout as u64)
+            }
+            _ => Err(anyhow!(status.unexpected_state())),
+        }
+    }
+
+    /// Wait after the final zero-length DFU_DNLOAD request until manifestation is finished
+    ///
+    /// Devices that are not manifestation tolerant may reset without answering
+    /// the status requests, which is not treated as an error.
+    pub fn wait_for_manifestation(&self) -> Result<()> {
+        let tolerant = self.info.manifestation_tolerant;
+        let start_time = std::time::Instant::now();
+
+        loop {
+            let status = match self.getstatus_request() {
+                Ok(status) => status,
+                Err(_) if !tolerant => return Ok(()),
+                Err(error) => return Err(error),
+            };
+
+            match status.bState {
+                states::DeviceStateCode::dfuMANIFEST_SYNC
+                | states::DeviceStateCode::dfuMANIFEST => {
+                    if start_time.elapsed() > MANIFESTATION_TIMEOUT {
+                        return Err(anyhow!(Error::InvalidDeviceState(status.bState)));
+                    }
+                    timing::poll_wait(std::time::Duration::from_millis(
+                        status.bwPollTimeout as u64,
+                    ));
+                }
+                states::DeviceStateCode::dfuIDLE
+                | states::DeviceStateCode::dfuMANIFEST_WAIT_RESET => return Ok(()),
+                _ => return Err(anyhow!(status.unexpected_state())),
+            }
+        }
+    }
+
+    /// Poll the status until the device leaves the busy states
+    pub fn wait_for_status_response(&self, timeout: u64) -> Result<DeviceStatusResponse> {
+        let max_retries = settings::get().polling_retries;
+        let mut retries = 0;
+
+        loop {
+            // Wait the time requested by the device in status response
+            timing::poll_wait(std::time::Duration::from_millis(timeout));
+
+            // Status response must have state dfuDNLOAD_IDLE
+            let status = self.getstatus_request();
+            if let Ok(status) = status {
+                if status.bState != states::DeviceStateCode::dfuDNLOAD_IDLE {
+                    return Err(anyhow!(status.unexpected_state()));
+                }
+                return Ok(status);
+            } else {
+                // This happens if device reports a too short bwPollTimeout
+                // Retry a few times to get around this issue
+                if retries > max_retries {
+                    return Err(anyhow!(Error::TooManyGetStatusRetries));
+                }
+                retries += 1;
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Return request type and request code of a request
+fn request_setup(request: timing::Request) -> (u8, u8) {
+    match request {
+        timing::Request::Detach => requests::DFU_DETACH,
+        timing::Request::Download => requests::DFU_DNLOAD,
+        timing::Request::Upload => requests::DFU_UPLOAD,
+        timing::Request::GetStatus => requests::DFU_GETSTATUS,
+        timing::Request::ClrStatus => requests::DFU_CLRSTATUS,
+        timing::Request::GetState => requests::DFU_GETSTATE,
+        timing::Request::Abort => requests::DFU_ABORT,
+    }
+}
+
+/// Return if an error was caused by the device being disconnected
+pub fn is_disconnected(error: &anyhow::Error) -> bool {
+    matches!(
+        error.downcast_ref::<rusb::Error>(),
+        Some(rusb::Error::NoDevice)
+    )
+}
+
+/// Return if an error was caused by missing permissions to access the device
+pub fn is_access_denied(error: &anyhow::Error) -> bool {
+    matches!(error.downcast_ref::<Error>(), Some(Error::AccessDenied))
+}
+
+/// Return the owner if an error was caused by another process holding the
+/// device, the inner value is `None` if the process is unknown
+pub fn in_use_by(error: &anyhow::Error) -> Option<Option<String>> {
+    match error.downcast_ref::<Error>() {
+        Some(Error::InUse(owner)) => Some(owner.clone()),
+        _ => None,
+    }
+}
+
+/// Return name and process id of another process that has the device open
+///
+/// Only possible on Linux, by looking for the device node among the open
+/// files of all processes. Processes of other users can't be inspected
+/// without root permissions.
+fn device_owner(device: &Device) -> Option<String> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+
+    let node = std::path::PathBuf::from(format!(
+        "/dev/bus/usb/{:03}/{:03}",
+        device.bus_number(),
+        device.address()
+    ));
+    let own_pid = std::process::id().to_string();
+
+    std::fs::read_dir("/proc")
+        .ok()?
+        .flatten()
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|pid| pid.chars().all(|c| c.is_ascii_digit()) && *pid != own_pid)
+        .find_map(|pid| {
+            let holds_node = std::fs::read_dir(format!("/proc/{pid}/fd"))
+                .ok()?
+                .flatten()
+                .any(|fd| std::fs::read_link(fd.path()).is_ok_and(|target| target == node));
+            holds_node.then(|| {
+                let name = std::fs::read_to_string(format!("/proc/{pid}/comm")).unwrap_or_default();
+                format!("{}, PID {pid}", name.trim())
+            })
+        })
+}
+
+/// Return instructions how to grant access to USB devices on this platform
+pub fn access_hint() -> &'static str {
+    if cfg!(target_os = "linux") {
+        "On Linux, access to USB devices requires a udev rule. Create a file \
+        /etc/udev/rules.d/50-dfu.rules with a line like\n\n\
+        SUBSYSTEM==\"usb\", ATTRS{idVendor}==\"0483\", ATTRS{idProduct}==\"df11\", \
+        MODE=\"0660\", TAG+=\"uaccess\"\n\n\
+        using the vendor and product id of your device, then run \
+        \"sudo udevadm control --reload-rules\" and reconnect the device."
+    } else if cfg!(target_os = "windows") {
+        "On Windows, the device must use the WinUSB driver. Install it with a tool \
+        like Zadig, then reconnect the device. Devices bound to another driver, \
+        e.g. the one of STM32CubeProgrammer, can't be accessed."
+    } else {
+        "Make sure the current user is allowed to access USB devices, then \
+        reconnect the device."
+    }
+}
+
+/// Open a USB device, missing permissions are reported as `Error::AccessDenied`,
+/// a device held by another process as `Error::InUse` and a missing driver
+/// as `Error::DriverMissing`
+pub(crate) fn open_device(device: &Device) -> Result<rusb::DeviceHandle<GlobalContext>> {
+    device.open().map_err(|error| match error {
+        rusb::Error::Access => anyhow!(Error::AccessDenied),
+        rusb::Error::Busy => anyhow!(Error::InUse(device_owner(device))),
+        error if driver::is_missing(&error) => anyhow!(Error::DriverMissing),
+        error => error.into(),
+    })
+}
+
+/// Return if an error was caused by a request that stalled or timed out
+pub fn is_stalled(error: &anyhow::Error) -> bool {
+    matches!(
+        error.downcast_ref::<rusb::Error>(),
+        Some(rusb::Error::Pipe | rusb::Error::Timeout | rusb::Error::Io)
+    ) || matches!(
+        error.downcast_ref::<Error>(),
+        Some(Error::TooManyGetStatusRetries)
+    )
+}
+
+/// Return configuration and interface number of the DFU interface of a device
+///
+/// - If `include_runtime` is set to `false`, only devices in DFU mode are considered
+/// - If `include_runtime` is set to `true`, also devices in runtime configuration
+///   are considered
+fn dfu_interface(device: &Device, include_runtime: bool) -> Option<(u8, u8)> {
+    let device_desc = device.device_descriptor().ok()?;
+
+    for n in 0..device_desc.num_configurations() {
+        let config_desc = match device.config_descriptor(n) {
+            Ok(desc) => desc,
+            Err(_) => continue,
+        };
+
+        for interface in config_desc.interfaces() {
+            for interface_desc in interface.descriptors() {
+                if interface_desc.class_code() == constants::LIBUSB_CLASS_APPLICATION
+                    && interface_desc.sub_class_code() == INTERFACE_SUBCLASS_DFU
+                    && (interface_desc.interface_number() == 0 || include_runtime)
+                {
+                    return Some((config_desc.number(), interface_desc.interface_number()));
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// Return vendor and product ids of all USB devices with DFU capability
+///
+/// Unlike `DfuDevice::find`, this does not open the devices, so it works
+/// without access permissions.
+pub fn usb_ids() -> Vec<(u16, u16)> {
+    let mut usb_ids: Vec<(u16, u16)> = rusb::devices()
+        .map(|devices| {
+            devices
+                .iter()
+                .filter(|device| dfu_interface(device, true).is_some())
+                .filter_map(|device| device.device_descriptor().ok())
+                .map(|desc| (desc.vendor_id(), desc.product_id()))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    usb_ids.sort();
+    usb_ids.dedup();
+
+    usb_ids
+}
+
+/// Return a report listing all USB devices with their DFU capabilities and
+/// access permissions, used for troubleshooting
+pub fn diagnostics() -> String {
+    let devices = match rusb::devices() {
+        Ok(devices) => devices,
+        Err(error) => return format!("Enumerating USB devices failed: {error}"),
+    };
+
+    let mut lines = Vec::new();
+
+    for device in devices.iter() {
+        let device_desc = match device.device_descriptor() {
+            Ok(desc) => desc,
+            Err(error) => {
+                lines.push(format!(
+                    "Bus {:03} Address {:03}: descriptor not readable ({error})",
+                    device.bus_number(),
+                    device.address()
+                ));
+                continue;
+            }
+        };
+
+        let mode = match dfu_interface(&device, true) {
+            Some((_, 0)) => "DFU mode",
+            Some(_) => "DFU runtime",
+            None => continue,
+        };
+
+        let access = match device.open() {
+            Ok(_) => String::from("access ok"),
+            Err(error) => format!("open failed: {error}"),
+        };
+
+        lines.push(format!(
+            "Bus {:03} Address {:03}: {:04x}:{:04x} {mode}, {access}",
+            device.bus_number(),
+            device.address(),
+            device_desc.vendor_id(),
+            device_desc.product_id(),
+        ));
+    }
+
+    if lines.is_empty() {
+        format!(
+            "No USB devices with DFU capability found among {} devices.",
+            devices.len()
+        )
+    } else {
+        lines.join("\n")
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// DFU functional descriptor, see DFU 1.1 specification table 4.2
+#[allow(non_snake_case)]
+#[derive(Debug)]
+pub struct DfuFunctionalDescriptor {
+    /// Size of this descriptor, in bytes.
+    bLength: u8,
+
+    /// DFU FUNCTIONAL descriptor type
+    bDescriptorType: u8,
+
+    /// DFU attributes
+    bmAttributes: u8,
+
+    /// Time, in milliseconds, that the device will wait after receipt of the
+    /// DFU_DETACH request.
+    wDetachTimeOut: u16,
+
+    /// Maximum number of bytes that the device can accept per
+    /// control-write transaction.
+    wTransferSize: u16,
+
+    /// Numeric expression identifying the version of the DFU
+    /// specification release.
+    bcdDFUVersion: u16,
+}
+
+impl DfuFunctionalDescriptor {
+    /// Creates a new descriptor from a buffer of u8 values
+    pub fn from_bytes(buffer: &[u8]) -> Self {
+        Self {
+            bLength: u8::from_le(buffer[0]),
+            bDescriptorType: u8::from_le(buffer[1]),
+            bmAttributes: u8::from_le(buffer[2]),
+            wDetachTimeOut: u16::from_le_bytes([buffer[3], buffer[4]]),
+            wTransferSize: u16::from_le_bytes([buffer[5], buffer[6]]),
+            bcdDFUVersion: u16::from_le_bytes([buffer[7], buffer[8]]),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Response received by the DFU_GETSTATUS request
+#[allow(non_snake_case)]
+#[derive(Debug)]
+pub struct DeviceStatusResponse {
+    /// An indication of the status resulting from the execution of the
+    /// most recent request.
+    pub bStatus: DeviceStatusCode,
+
+    /// Minimum time, in milliseconds, that the host should wait before sending
+    /// a subsequent DFU_GETSTATUS request.
+    pub bwPollTimeout: u32,
+
+    /// An indication of the state that the device is going to enter immediately
+    /// following transmission of this response.
+    pub bState: DeviceStateCode,
+
+    /// Index of status description in string table.
+    pub iString: u8,
+}
+
+impl DeviceStatusResponse {
+    /// Creates a new device status
+    pub fn new(
+        status: DeviceStatusCode,
+        poll_timeout: u32,
+        state: DeviceStateCode,
+        string_index: u8,
+    ) -> Self {
+        Self {
+            bStatus: status,
+            bwPollTimeout: poll_timeout,
+            bState: state,
+            iString: string_index,
+        }
+    }
+
+    /// Returns the error for a response with a state not expected by the host
+    ///
+    /// In the error state, the status code tells what went wrong.
+    pub fn unexpected_state(&self) -> Error {
+        if self.bState == DeviceStateCode::dfuERROR {
+            Error::FailedStatus(self.bStatus)
+        } else {
+            Error::InvalidDeviceState(self.bState)
+        }
+    }
+
+    /// Creates a new image element from a buffer of u8 values
+    pub fn from_bytes(buffer: &[u8; 6]) -> Self {
+        Self::new(
+            DeviceStatusCode::from_byte(buffer[0]).unwrap_or(DeviceStatusCode::errUNKNOWN),
+            u32::from_le_bytes([buffer[1], buffer[2], buffer[3], 0]),
+            DeviceStateCode::from_byte(buffer[4]).unwrap_or(DeviceStateCode::dfuERROR),
+            u8::from_le(buffer[5]),
+        )
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Errors accessing a device
+#[derive(Debug)]
+pub enum Error {
+    /// No device handle available, device not opened
+    NoDeviceHandle,
+
+    /// Simulated device can't be opened
+    SimulatedDevice,
+
+    /// DFU functional descriptor not found.
+    NoDfuFunctionalDescriptor,
+
+    /// Status code byte does not have a valid value
+    InvalidStatusCode,
+
+    /// State code byte does not have a valid value
+    InvalidStateCode,
+
+    /// Invalid device state
+    InvalidDeviceState(states::DeviceStateCode),
+
+    /// Device is in the error state with a status code
+    FailedStatus(states::DeviceStatusCode),
+
+    /// Polling failed after retries
+    TooManyGetStatusRetries,
+
+    /// Device did not re-enumerate in time
+    ReconnectTimeout,
+
+    /// Device can be opened, but stalls all requests
+    Unresponsive,
+
+    /// Missing permissions to access the device
+    AccessDenied,
+
+    /// Interface claimed by another process, with its name and id if known
+    InUse(Option<String>),
+
+    /// No driver usable by libusb bound to the device
+    DriverMissing,
+
+    /// Memory layout string descriptor that can't be parsed, with reason
+    InvalidMemoryLayout(String, String),
+
+    /// Device can't be simulated, only DfuSe devices are supported
+    SimulationNotSupported,
+}
+
+impl std::error::Error for Error {}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::NoDeviceHandle => "No device handle.".to_string(),
+                Self::SimulatedDevice => "Simulated device can't be accessed.".to_string(),
+                Self::NoDfuFunctionalDescriptor =>
+                    "DFU functional descriptor not found.".to_string(),
+                Self::InvalidStatusCode => "Invalid status code".to_string(),
+                Self::InvalidStateCode => "Invalid state code".to_string(),
+                Self::InvalidDeviceState(state) =>
+                    format!("Device is in an unexpected state. {state}"),
+                Self::FailedStatus(status) => format!("Device reported an error. {status}"),
+                Self::TooManyGetStatusRetries => "Too many retries when polling status".to_string(),
+                Self::ReconnectTimeout => "Device did not reconnect in time".to_string(),
+                Self::Unresponsive => "Device does not respond to any request. \
+                    The bootloader seems to hang, disconnect the device or power cycle it \
+                    and enter DFU mode again, e.g. by holding the BOOT0 pin high during reset."
+                    .to_string(),
+                Self::AccessDenied => "Access to the USB device denied.".to_string(),
+                Self::InUse(Some(owner)) =>
+                    format!("Device is in use by another application ({owner})."),
+                Self::InUse(None) => "Device is in use by another application.".to_string(),
+                Self::DriverMissing => "No WinUSB driver installed for the USB device.".to_string(),
+                Self::InvalidMemoryLayout(desc, reason) =>
+                    format!("Unparseable memory layout \"{desc}\": {reason}"),
+                Self::SimulationNotSupported => "Only DfuSe devices can be simulated.".to_string(),
+            }
+        )
+    }
+}
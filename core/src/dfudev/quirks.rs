@@ -0,0 +1,274 @@
+//! Workarounds for bootloaders deviating from the specification
+//!
+//! Quirks are looked up in a table by vendor and product id, optionally
+//! narrowed down by the device version and the detected MCU family. All
+//! matching entries are combined, so a generic entry can be extended by a
+//! more specific one.
+//!
+//! In addition to the bundled table, entries are read from the JSON file
+//! `quirks.json` in the configuration directory of the application. Its
+//! entries are applied after the bundled ones, so they can override them.
+//! Memory maps entered manually in the application are applied last, as
+//! well as the transfer size and poll timeout of the transfer settings.
+
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+use anyhow::{anyhow, Result};
+
+use super::family::Family;
+use super::DeviceInfo;
+
+/// Name of the quirks file in the configuration directory
+const FILE_NAME: &str = "quirks.json";
+
+/// Entries read from the quirks file
+static FILE_TABLE: RwLock<Vec<Entry>> = RwLock::new(Vec::new());
+
+/// Memory maps entered manually
+static MEMORY_MAP_OVERRIDES: RwLock<Vec<MemoryMapOverride>> = RwLock::new(Vec::new());
+
+/// Workarounds enabled for a device
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(default)]
+pub struct Quirks {
+    /// Erase may report `dfuDNBUSY` after its poll timeout although it
+    /// succeeded, the state is cleared instead of failing
+    pub erase_busy_state: bool,
+
+    /// Transfer size used instead of the one in the functional descriptor
+    pub transfer_size: Option<u16>,
+
+    /// Poll timeout in milliseconds used instead of the one in status responses
+    pub poll_timeout: Option<u32>,
+
+    /// Alt setting names used instead of the ones reported by the device,
+    /// to fix memory maps with wrong addresses or sector sizes
+    pub memory_map: BTreeMap<u8, String>,
+}
+
+impl Quirks {
+    /// Enable all workarounds of another set in addition, its overrides
+    /// take precedence
+    fn merge(&mut self, other: &Self) {
+        self.erase_busy_state |= other.erase_busy_state;
+        self.transfer_size = other.transfer_size.or(self.transfer_size);
+        self.poll_timeout = other.poll_timeout.or(self.poll_timeout);
+        self.memory_map.extend(other.memory_map.clone());
+    }
+
+    /// Apply the overrides to the info of a device
+    pub fn apply(&self, info: &mut DeviceInfo) {
+        if let Some(transfer_size) = self.transfer_size {
+            info.dfu_transfer_size = transfer_size;
+        }
+
+        for (alt_setting, name) in &mut info.alt_settings {
+            if let Some(override_name) = self.memory_map.get(alt_setting) {
+                name.clone_from(override_name);
+            }
+        }
+    }
+}
+
+/// Entry of the quirks table
+#[derive(Debug, serde::Deserialize)]
+struct Entry {
+    /// USB vendor id
+    vendor_id: u16,
+
+    /// USB product id
+    product_id: u16,
+
+    /// Device version as BCD, any if `None`
+    #[serde(default)]
+    bcd_device: Option<u16>,
+
+    /// MCU family, any if `None`
+    #[serde(default)]
+    family: Option<Family>,
+
+    /// Serial number, any if `None`
+    #[serde(default)]
+    serial_number: Option<String>,
+
+    /// Workarounds enabled for matching devices
+    quirks: Quirks,
+}
+
+impl Entry {
+    /// Return if a device matches the entry
+    fn matches(&self, info: &DeviceInfo) -> bool {
+        self.vendor_id == info.vendor_id
+            && self.product_id == info.product_id
+            && self.bcd_device.map_or(true, |bcd| bcd == info.bcd_device)
+            && self
+                .family
+                .map_or(true, |family| Some(family) == info.family)
+            && self
+                .serial_number
+                .as_ref()
+                .map_or(true, |serial| *serial == info.serial_number_string)
+    }
+}
+
+/// Memory map entered manually for devices reporting malformed descriptors
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct MemoryMapOverride {
+    /// USB vendor id
+    pub vendor_id: u16,
+
+    /// USB product id
+    pub product_id: u16,
+
+    /// Serial number, any if `None`
+    pub serial_number: Option<String>,
+
+    /// Alt setting names used instead of the ones reported by the device
+    pub memory_map: BTreeMap<u8, String>,
+}
+
+impl MemoryMapOverride {
+    /// Return if a device matches the override
+    pub fn matches(&self, info: &DeviceInfo) -> bool {
+        self.vendor_id == info.vendor_id
+            && self.product_id == info.product_id
+            && self
+                .serial_number
+                .as_ref()
+                .map_or(true, |serial| *serial == info.serial_number_string)
+    }
+}
+
+/// Quirks of known devices
+const TABLE: &[Entry] = &[
+    // Sector erase beyond 1MB on STM32H7 Rev. V, see
+    // https://community.st.com/t5/stm32cubeprogrammer-mcu/weird-stm32h743zi-rev-v-usb-dfu-erase-behavior-beyond-1mb-sector/m-p/234209
+    Entry {
+        vendor_id: 0x0483,
+        product_id: 0xDF11,
+        bcd_device: None,
+        family: Some(Family::H7),
+        serial_number: None,
+        quirks: Quirks {
+            erase_busy_state: true,
+            transfer_size: None,
+            poll_timeout: None,
+            memory_map: BTreeMap::new(),
+        },
+    },
+];
+
+/// Return the quirks of a device
+pub fn lookup(info: &DeviceInfo) -> Quirks {
+    let mut quirks = Quirks::default();
+    let file_table = FILE_TABLE.read().unwrap();
+
+    for entry in TABLE
+        .iter()
+        .chain(file_table.iter())
+        .filter(|entry| entry.matches(info))
+    {
+        quirks.merge(&entry.quirks);
+    }
+
+    for memory_map_override in MEMORY_MAP_OVERRIDES
+        .read()
+        .unwrap()
+        .iter()
+        .filter(|memory_map_override| memory_map_override.matches(info))
+    {
+        quirks
+            .memory_map
+            .extend(memory_map_override.memory_map.clone());
+    }
+
+    let settings = super::settings::get();
+    quirks.transfer_size = settings.transfer_size.or(quirks.transfer_size);
+    quirks.poll_timeout = settings.poll_timeout.or(quirks.poll_timeout);
+
+    if quirks != Quirks::default() {
+        log::debug!("Quirks enabled: {quirks:?}");
+    }
+
+    quirks
+}
+
+/// Set the memory maps entered manually, replacing the ones set before
+///
+/// Devices found afterwards use the new memory maps.
+pub fn set_memory_map_overrides(overrides: Vec<MemoryMapOverride>) {
+    *MEMORY_MAP_OVERRIDES.write().unwrap() = overrides;
+}
+
+/// Read the entries of a quirks file, replacing the ones read before
+///
+/// Returns the number of entries.
+pub fn load(path: &std::path::Path) -> Result<usize> {
+    let json = std::fs::read_to_string(path)?;
+    let entries = parse(&json)?;
+
+    let count = entries.len();
+    *FILE_TABLE.write().unwrap() = entries;
+
+    Ok(count)
+}
+
+/// Return the content of the quirks file in the configuration directory,
+/// `None` if there is none
+pub fn read_file() -> Result<Option<serde_json::Value>> {
+    let Some(path) = file_path().filter(|path| path.exists()) else {
+        return Ok(None);
+    };
+
+    Ok(Some(serde_json::from_str(&std::fs::read_to_string(path)?)?))
+}
+
+/// Replace the quirks file in the configuration directory and read its
+/// entries, the file is left unchanged if they are invalid
+///
+/// Returns the number of entries.
+pub fn replace_file(table: &serde_json::Value) -> Result<usize> {
+    let json = serde_json::to_string_pretty(table)?;
+    parse(&json)?;
+
+    let path = file_path().ok_or_else(|| anyhow!("No configuration directory found."))?;
+    if let Some(directory) = path.parent() {
+        std::fs::create_dir_all(directory)?;
+    }
+    std::fs::write(&path, json)?;
+
+    load(&path)
+}
+
+/// Parse and check the entries of a quirks file
+fn parse(json: &str) -> Result<Vec<Entry>> {
+    let entries: Vec<Entry> = serde_json::from_str(json)?;
+
+    if entries
+        .iter()
+        .any(|entry| entry.quirks.transfer_size == Some(0))
+    {
+        return Err(anyhow!("Transfer size must not be 0."));
+    }
+
+    Ok(entries)
+}
+
+/// Read the quirks file in the configuration directory if present
+/// and log errors instead of returning them
+pub fn load_or_log() {
+    let Some(path) = file_path().filter(|path| path.exists()) else {
+        return;
+    };
+
+    match load(&path) {
+        Ok(count) => log::info!("{count} quirks entries read from {}", path.display()),
+        Err(error) => log::error!("Error reading quirks file {}: {}", path.display(), error),
+    }
+}
+
+/// Return the path of the quirks file
+pub fn file_path() -> Option<std::path::PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("dfu-buddy").join(FILE_NAME))
+}
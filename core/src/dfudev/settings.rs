@@ -0,0 +1,76 @@
+//! Transfer settings overriding the defaults for all devices
+//!
+//! Some bootloaders report bogus values in their descriptors or status
+//! responses, or need more time than usual. Unlike quirks, these settings
+//! apply to all devices. Transfer size and poll timeout are applied to the
+//! devices found afterwards, timeout and retries to all requests sent from
+//! then on.
+
+use std::sync::RwLock;
+use std::time::Duration;
+
+/// Settings in effect
+static SETTINGS: RwLock<TransferSettings> = RwLock::new(TransferSettings::DEFAULT);
+
+/// Settings of the transfers to all devices
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct TransferSettings {
+    /// Transfer size used instead of the one in the functional descriptor
+    pub transfer_size: Option<u16>,
+
+    /// Timeout of control transfers in milliseconds
+    pub timeout: u32,
+
+    /// Number of retries when a status request fails after a download
+    pub polling_retries: usize,
+
+    /// Poll timeout in milliseconds used instead of the one in status responses
+    pub poll_timeout: Option<u32>,
+}
+
+impl TransferSettings {
+    /// Settings used unless changed by the user
+    pub const DEFAULT: Self = Self {
+        transfer_size: None,
+        timeout: 5000,
+        polling_retries: 5,
+        poll_timeout: None,
+    };
+
+    /// Return the timeout of control transfers
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout as u64)
+    }
+
+    /// Return a description of the problem with the settings, `None` if valid
+    pub fn problem(&self) -> Option<&'static str> {
+        if self.transfer_size == Some(0) {
+            Some("Transfer size must not be 0.")
+        } else if self.timeout == 0 {
+            Some("Timeout must not be 0.")
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for TransferSettings {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Return the settings in effect
+pub fn get() -> TransferSettings {
+    *SETTINGS.read().unwrap()
+}
+
+/// Set the settings, replacing the ones set before
+pub fn set(settings: TransferSettings) {
+    if settings != TransferSettings::DEFAULT {
+        log::info!("Transfer settings: {settings:?}");
+    }
+
+    *SETTINGS.write().unwrap() = settings;
+}
@@ -0,0 +1,654 @@
+//! Simulated DfuSe device for tests and dry runs without hardware
+//!
+//! Each alt setting is a target with the memory layout of its name, e.g.
+//! `@Internal Flash  /0x08000000/04*016Kg`. The device answers requests like
+//! an STM32 bootloader: DfuSe commands and blocks are executed on the first
+//! status request after them, and programming can only clear bits like on
+//! real flash memory. The memory is allocated when written, so devices with
+//! large external flash can be simulated as well. Faults can be injected to
+//! run the error paths of the update operations.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+
+use super::dfuse::{MemorySegment, MemorySegmentRegion};
+use super::transport::{self, InterfaceClaim};
+use super::{requests, DeviceInfo, DeviceStateCode, DeviceStatusCode, DfuDevice, Error, Transport};
+
+/// Size of the pages the memory is allocated in
+const PAGE_SIZE: u32 = 4096;
+
+/// Vendor id of the simulated device
+const VENDOR_ID: u16 = 0x0483;
+
+/// Product id of the simulated device
+const PRODUCT_ID: u16 = 0xDF11;
+
+/// Attributes of the simulated device: download, upload and detach, but not
+/// manifestation tolerant
+const ATTRIBUTES: u8 = 0x0B;
+
+/// Fault injected into the simulated device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Uploads stall and enter the error state, like with readout protection
+    ReadProtected,
+
+    /// Erasing the sector containing the address fails with `errERASE`
+    EraseFailure(u32),
+
+    /// Programming leaves the byte at the address unchanged
+    StuckByte(u32),
+
+    /// Device disconnects once instead of receiving the block to be programmed
+    /// at the address and returns when it is opened again
+    Disconnect(u32),
+
+    /// Interface is held by another application, on a platform without
+    /// kernel driver support like Windows
+    InterfaceBusy,
+}
+
+/// Simulated DfuSe device
+///
+/// Clones share the same device, so the memory can be inspected after it
+/// was updated through the [`DfuDevice`] returned by [`Self::device`].
+#[derive(Debug, Clone)]
+pub struct SimulatedDevice {
+    /// State shared with the transports
+    state: Arc<Mutex<State>>,
+
+    /// Info reported by the device
+    info: DeviceInfo,
+}
+
+impl SimulatedDevice {
+    /// Create a device with a single target and erased memory for a layout
+    /// like `@Internal Flash  /0x08000000/04*016Kg`
+    pub fn new(layout: &str, transfer_size: u16) -> Result<Self> {
+        MemorySegment::from_string_desc(layout)?;
+
+        Self::from_info(&DeviceInfo {
+            vendor_id: VENDOR_ID,
+            product_id: PRODUCT_ID,
+            device_version: "2.00".to_string(),
+            bcd_device: 0x0200,
+            manufacturer_string: "STMicroelectronics".to_string(),
+            product_string: "STM32 BOOTLOADER (Simulated)".to_string(),
+            serial_number_string: "SIM00000001".to_string(),
+            dfu_config_number: 1,
+            dfu_interface_number: 0,
+            alt_settings: vec![(0, layout.to_string())],
+            dfu_attributes: ATTRIBUTES,
+            can_download: true,
+            can_upload: true,
+            manifestation_tolerant: false,
+            will_detach: true,
+            dfu_detach_timeout: 255,
+            dfu_transfer_size: transfer_size,
+            dfu_version: crate::builder::DFU_VERSION_DFUSE,
+            bus_number: 0,
+            address: 0,
+            port_numbers: Vec::new(),
+            family: None,
+            driver: Default::default(),
+            quirks: Default::default(),
+        })
+    }
+
+    /// Create a copy of a DfuSe device with erased memory, e.g. to try an
+    /// update before running it on the device
+    ///
+    /// Alt settings without a valid memory layout can't be selected.
+    pub fn from_info(info: &DeviceInfo) -> Result<Self> {
+        if info.dfu_version != crate::builder::DFU_VERSION_DFUSE {
+            return Err(anyhow!(Error::SimulationNotSupported));
+        }
+
+        let segments = info
+            .alt_settings
+            .iter()
+            .filter_map(|(alt_setting, name)| {
+                MemorySegment::from_string_desc(name)
+                    .ok()
+                    .map(|segment| (*alt_setting, segment))
+            })
+            .collect::<BTreeMap<_, _>>();
+        let alt_setting = info.alt_settings.first().map_or(0, |alt| alt.0);
+        let pointer = segments
+            .get(&alt_setting)
+            .and_then(|segment| segment.regions.first())
+            .map_or(0, |region| region.start_address);
+
+        Ok(Self {
+            state: Arc::new(Mutex::new(State {
+                segments,
+                memory: BTreeMap::new(),
+                alt_setting,
+                dfu_state: DeviceStateCode::dfuIDLE,
+                status: DeviceStatusCode::OK,
+                pointer,
+                block_size: None,
+                pending: None,
+                connection: 0,
+                connected: true,
+                left: false,
+                faults: Vec::new(),
+                erased_sectors: Vec::new(),
+            })),
+            info: info.clone(),
+        })
+    }
+
+    /// Return the info the device reports when found on the bus
+    pub fn info(&self) -> DeviceInfo {
+        self.info.clone()
+    }
+
+    /// Return a device accessing the simulation, to be passed to the update
+    pub fn device(&self) -> DfuDevice {
+        let simulation = self.clone();
+
+        DfuDevice::with_transport(self.info(), move || simulation.connect())
+    }
+
+    /// Open a new transport, failing if the device left DFU mode
+    fn connect(&self) -> Result<Box<dyn Transport>> {
+        let mut state = self.state.lock().unwrap();
+        if state.left {
+            return Err(rusb::Error::NoDevice.into());
+        }
+
+        if !state.connected {
+            // Returns from the disconnect like after a reset
+            state.connection += 1;
+            state.connected = true;
+            state.dfu_state = DeviceStateCode::dfuIDLE;
+            state.status = DeviceStatusCode::OK;
+            state.pending = None;
+        }
+
+        Ok(Box::new(SimulatedTransport {
+            state: self.state.clone(),
+            connection: state.connection,
+        }))
+    }
+
+    /// Add a fault, see [`Fault`]
+    pub fn inject(&self, fault: Fault) {
+        self.state.lock().unwrap().faults.push(fault);
+    }
+
+    /// Return a copy of the memory content of the first target at an address
+    pub fn read(&self, address: u32, length: usize) -> Vec<u8> {
+        let mut data = vec![0; length];
+        self.state
+            .lock()
+            .unwrap()
+            .read_memory(self.first_target(), address, &mut data);
+
+        data
+    }
+
+    /// Write data to the memory of the first target directly, e.g. to
+    /// simulate a previous update
+    pub fn write(&self, address: u32, data: &[u8]) {
+        self.state.lock().unwrap().update_memory(
+            self.first_target(),
+            address,
+            data.len(),
+            |index, byte| *byte = data[index],
+        );
+    }
+
+    /// Return the start addresses of the sectors erased so far, in order
+    pub fn erased_sectors(&self) -> Vec<u32> {
+        self.state.lock().unwrap().erased_sectors.clone()
+    }
+
+    /// Return if the device left DFU mode to start the application
+    pub fn has_left(&self) -> bool {
+        self.state.lock().unwrap().left
+    }
+
+    /// Return the alt setting of the first target
+    fn first_target(&self) -> u8 {
+        self.info.alt_settings.first().map_or(0, |alt| alt.0)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Command or block waiting for the next status request
+#[derive(Debug)]
+enum Pending {
+    /// Set the address pointer
+    SetAddress(u32),
+
+    /// Erase the sector at the address, or all sectors if `None`
+    Erase(Option<u32>),
+
+    /// Remove the readout protection with a mass erase and reset
+    ReadUnprotect,
+
+    /// Program data at the address
+    Program(u32, Vec<u8>),
+
+    /// Leave DFU mode after the zero-length download
+    Manifest,
+}
+
+/// State of the simulated device
+#[derive(Debug)]
+struct State {
+    /// Memory layout by alt setting
+    segments: BTreeMap<u8, MemorySegment>,
+
+    /// Pages of the memory written so far by alt setting and address,
+    /// all others are erased
+    memory: BTreeMap<(u8, u32), Vec<u8>>,
+
+    /// Selected alt setting
+    alt_setting: u8,
+
+    /// DFU state
+    dfu_state: DeviceStateCode,
+
+    /// Status of the last request
+    status: DeviceStatusCode,
+
+    /// Address pointer
+    pointer: u32,
+
+    /// Size of the blocks since the pointer was set, known after the first one
+    block_size: Option<usize>,
+
+    /// Command or block executed on the next status request
+    pending: Option<Pending>,
+
+    /// Number of the current connection, transports of earlier ones fail
+    connection: usize,
+
+    /// Flag if the device is connected
+    connected: bool,
+
+    /// Flag if the device left DFU mode
+    left: bool,
+
+    /// Injected faults
+    faults: Vec<Fault>,
+
+    /// Start addresses of the erased sectors
+    erased_sectors: Vec<u32>,
+}
+
+impl State {
+    /// Fail a request on a transport of a previous connection
+    fn request(&self, connection: usize) -> rusb::Result<()> {
+        match self.connected && self.connection == connection {
+            true => Ok(()),
+            false => Err(rusb::Error::NoDevice),
+        }
+    }
+
+    /// Enter the error state with a status and stall the request
+    fn stall(&mut self, status: DeviceStatusCode) -> rusb::Error {
+        self.dfu_state = DeviceStateCode::dfuERROR;
+        self.status = status;
+        self.pending = None;
+
+        rusb::Error::Pipe
+    }
+
+    /// Return the memory layout of the selected alt setting
+    fn segment(&self) -> Option<&MemorySegment> {
+        self.segments.get(&self.alt_setting)
+    }
+
+    /// Return if the address range is completely covered by regions matching
+    /// a filter
+    fn is_covered(
+        &self,
+        address: u32,
+        length: usize,
+        filter: impl Fn(&MemorySegmentRegion) -> bool,
+    ) -> bool {
+        let Some(segment) = self.segment() else {
+            return false;
+        };
+        let end_address = address as u64 + length as u64;
+        let mut address = address as u64;
+
+        while address < end_address {
+            let region = segment.regions.iter().find(|region| {
+                filter(region)
+                    && region.start_address as u64 <= address
+                    && address <= region.end_address as u64
+            });
+            match region {
+                Some(region) => address = region.end_address as u64 + 1,
+                None => return false,
+            }
+        }
+
+        true
+    }
+
+    /// Copy the memory content of an alt setting at an address into `data`
+    fn read_memory(&self, alt_setting: u8, address: u32, data: &mut [u8]) {
+        for (index, byte) in data.iter_mut().enumerate() {
+            let address = address.wrapping_add(index as u32);
+            *byte = self
+                .memory
+                .get(&(alt_setting, address / PAGE_SIZE))
+                .map_or(0xFF, |page| page[(address % PAGE_SIZE) as usize]);
+        }
+    }
+
+    /// Change the memory content of an alt setting byte by byte, passing the
+    /// index in the range to the function
+    fn update_memory(
+        &mut self,
+        alt_setting: u8,
+        address: u32,
+        length: usize,
+        mut update: impl FnMut(usize, &mut u8),
+    ) {
+        for index in 0..length {
+            let address = address.wrapping_add(index as u32);
+            let page = self
+                .memory
+                .entry((alt_setting, address / PAGE_SIZE))
+                .or_insert_with(|| vec![0xFF; PAGE_SIZE as usize]);
+            update(index, &mut page[(address % PAGE_SIZE) as usize]);
+        }
+    }
+
+    /// Return the address of a block relative to the address pointer
+    fn block_address(&mut self, block_no: u16, length: usize) -> u32 {
+        let block_size = *self.block_size.get_or_insert(length);
+
+        self.pointer + (block_no as u32 - 2) * block_size as u32
+    }
+
+    /// Handle a DFU_DNLOAD request
+    fn download(&mut self, block_no: u16, data: &[u8]) -> rusb::Result<usize> {
+        if !matches!(
+            self.dfu_state,
+            DeviceStateCode::dfuIDLE | DeviceStateCode::dfuDNLOAD_IDLE
+        ) {
+            return Err(self.stall(DeviceStatusCode::errSTALLEDPKT));
+        }
+
+        let pending = match (block_no, data) {
+            (_, []) => Pending::Manifest,
+            (0, [0x21, address @ ..]) if address.len() == 4 => {
+                Pending::SetAddress(u32::from_le_bytes(address.try_into().unwrap()))
+            }
+            (0, [0x41]) => Pending::Erase(None),
+            (0, [0x41, address @ ..]) if address.len() == 4 => {
+                Pending::Erase(Some(u32::from_le_bytes(address.try_into().unwrap())))
+            }
+            (0, [0x92]) => Pending::ReadUnprotect,
+            (0 | 1, _) => return Err(self.stall(DeviceStatusCode::errSTALLEDPKT)),
+            _ => {
+                let address = self.block_address(block_no, data.len());
+                let block = address..address + data.len() as u32;
+                let faults = self.faults.len();
+                self.faults
+                    .retain(|fault| !matches!(fault, Fault::Disconnect(at) if block.contains(at)));
+                if self.faults.len() != faults {
+                    self.connected = false;
+                    return Err(rusb::Error::NoDevice);
+                }
+                Pending::Program(address, data.to_vec())
+            }
+        };
+
+        self.dfu_state = match pending {
+            Pending::Manifest => DeviceStateCode::dfuMANIFEST_SYNC,
+            _ => DeviceStateCode::dfuDNLOAD_SYNC,
+        };
+        self.pending = Some(pending);
+
+        Ok(data.len())
+    }
+
+    /// Handle a DFU_UPLOAD request
+    fn upload(&mut self, block_no: u16, data: &mut [u8]) -> rusb::Result<usize> {
+        if !matches!(
+            self.dfu_state,
+            DeviceStateCode::dfuIDLE | DeviceStateCode::dfuUPLOAD_IDLE
+        ) || block_no < 2
+            || self.faults.contains(&Fault::ReadProtected)
+        {
+            return Err(self.stall(DeviceStatusCode::errUNKNOWN));
+        }
+
+        let address = self.block_address(block_no, data.len());
+        if !self.is_covered(address, data.len(), |region| region.readable) {
+            return Err(self.stall(DeviceStatusCode::errADDRESS));
+        }
+        self.read_memory(self.alt_setting, address, data);
+        self.dfu_state = DeviceStateCode::dfuUPLOAD_IDLE;
+
+        Ok(data.len())
+    }
+
+    /// Handle a DFU_GETSTATUS request, executing a pending command
+    fn status(&mut self) -> [u8; 6] {
+        let state = match self.pending.take() {
+            Some(Pending::Manifest) => {
+                // Starts the application after answering
+                self.left = true;
+                self.connected = false;
+                DeviceStateCode::dfuMANIFEST
+            }
+            Some(pending) => {
+                let result = self.execute(pending);
+                self.dfu_state = match result {
+                    Ok(()) => DeviceStateCode::dfuDNLOAD_IDLE,
+                    Err(status) => {
+                        self.status = status;
+                        DeviceStateCode::dfuERROR
+                    }
+                };
+                DeviceStateCode::dfuDNBUSY
+            }
+            None => self.dfu_state,
+        };
+
+        // Poll timeout of 0, the simulation answers without delay
+        [self.status as u8, 0, 0, 0, state as u8, 0]
+    }
+
+    /// Execute a command or program a block, returning the failure status
+    fn execute(&mut self, pending: Pending) -> Result<(), DeviceStatusCode> {
+        match pending {
+            Pending::SetAddress(address) => {
+                self.pointer = address;
+                self.block_size = None;
+            }
+            Pending::Erase(Some(address)) => {
+                let segment = self.segment().ok_or(DeviceStatusCode::errTARGET)?;
+                let (start, size) = segment
+                    .sector_at(address)
+                    .filter(|(start, size)| segment.erase_region(*start, *size).is_some())
+                    .ok_or(DeviceStatusCode::errTARGET)?;
+                let sector = start..start + size;
+                if self
+                    .faults
+                    .iter()
+                    .any(|fault| matches!(fault, Fault::EraseFailure(at) if sector.contains(at)))
+                {
+                    return Err(DeviceStatusCode::errERASE);
+                }
+                let alt_setting = self.alt_setting;
+                self.update_memory(alt_setting, start, size as usize, |_, byte| *byte = 0xFF);
+                self.erased_sectors.push(start);
+            }
+            Pending::Erase(None) => {
+                let alt_setting = self.alt_setting;
+                self.memory.retain(|(alt, _), _| *alt != alt_setting);
+            }
+            Pending::ReadUnprotect => {
+                self.memory.clear();
+                self.faults.retain(|fault| *fault != Fault::ReadProtected);
+                self.connected = false;
+            }
+            Pending::Program(address, data) => {
+                if !self.is_covered(address, data.len(), |region| region.writable) {
+                    return Err(DeviceStatusCode::errADDRESS);
+                }
+                let faults = std::mem::take(&mut self.faults);
+                let alt_setting = self.alt_setting;
+                self.update_memory(alt_setting, address, data.len(), |index, byte| {
+                    let stuck = Fault::StuckByte(address + index as u32);
+                    if !faults.contains(&stuck) {
+                        // Flash memory can only clear bits
+                        *byte &= data[index];
+                    }
+                });
+                self.faults = faults;
+            }
+            Pending::Manifest => {}
+        }
+
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Transport to the simulated device while it is open
+struct SimulatedTransport {
+    /// State of the device
+    state: Arc<Mutex<State>>,
+
+    /// Number of the connection the transport was opened on
+    connection: usize,
+}
+
+impl Transport for SimulatedTransport {
+    fn write_control(
+        &self,
+        request_type: u8,
+        request: u8,
+        value: u16,
+        _index: u16,
+        data: &[u8],
+        _timeout: Duration,
+    ) -> rusb::Result<usize> {
+        let mut state = self.state.lock().unwrap();
+        state.request(self.connection)?;
+
+        match (request_type, request) {
+            requests::DFU_DETACH => Ok(0),
+            requests::DFU_DNLOAD => state.download(value, data),
+            requests::DFU_CLRSTATUS => {
+                state.dfu_state = DeviceStateCode::dfuIDLE;
+                state.status = DeviceStatusCode::OK;
+                Ok(0)
+            }
+            requests::DFU_ABORT => {
+                // Only the status can be cleared in the error state
+                if state.dfu_state != DeviceStateCode::dfuERROR {
+                    state.dfu_state = DeviceStateCode::dfuIDLE;
+                    state.pending = None;
+                }
+                Ok(0)
+            }
+            _ => Err(state.stall(DeviceStatusCode::errSTALLEDPKT)),
+        }
+    }
+
+    fn read_control(
+        &self,
+        request_type: u8,
+        request: u8,
+        value: u16,
+        _index: u16,
+        data: &mut [u8],
+        _timeout: Duration,
+    ) -> rusb::Result<usize> {
+        let mut state = self.state.lock().unwrap();
+        state.request(self.connection)?;
+
+        match (request_type, request) {
+            requests::DFU_UPLOAD => state.upload(value, data),
+            requests::DFU_GETSTATUS => {
+                let status = state.status();
+                let length = data.len().min(status.len());
+                data[..length].copy_from_slice(&status[..length]);
+                Ok(length)
+            }
+            requests::DFU_GETSTATE => {
+                data[0] = state.dfu_state as u8;
+                Ok(1)
+            }
+            _ => Err(state.stall(DeviceStatusCode::errSTALLEDPKT)),
+        }
+    }
+
+    fn claim_interface(&mut self, interface_number: u8) -> Result<()> {
+        transport::claim_interface(self, interface_number)
+    }
+
+    fn release_interface(&mut self, _interface_number: u8) -> rusb::Result<()> {
+        self.state.lock().unwrap().request(self.connection)
+    }
+
+    fn set_alternate_setting(
+        &mut self,
+        _interface_number: u8,
+        alt_setting: u8,
+    ) -> rusb::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.request(self.connection)?;
+
+        match state.segments.contains_key(&alt_setting) {
+            true => {
+                state.alt_setting = alt_setting;
+                Ok(())
+            }
+            false => Err(rusb::Error::NotFound),
+        }
+    }
+
+    fn reset(&mut self) -> rusb::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.request(self.connection)?;
+
+        // Re-enumerates in DFU mode, as no application was started
+        state.connected = false;
+
+        Ok(())
+    }
+}
+
+impl InterfaceClaim for SimulatedTransport {
+    fn try_claim(&mut self, _interface_number: u8) -> rusb::Result<()> {
+        let state = self.state.lock().unwrap();
+        state.request(self.connection)?;
+
+        match state.faults.contains(&Fault::InterfaceBusy) {
+            true => Err(rusb::Error::Busy),
+            false => Ok(()),
+        }
+    }
+
+    fn kernel_driver_active(&self, _interface_number: u8) -> rusb::Result<bool> {
+        Err(rusb::Error::NotSupported)
+    }
+
+    fn detach_kernel_driver(&mut self, _interface_number: u8) -> rusb::Result<()> {
+        Err(rusb::Error::NotSupported)
+    }
+
+    fn owner(&self) -> Option<String> {
+        None
+    }
+}
@@ -93,6 +93,65 @@ impl DeviceStatusCode {
     pub fn as_byte(&self) -> u8 {
         *self as u8
     }
+
+    /// Returns a plain-language explanation of the status
+    pub fn explanation(&self) -> &'static str {
+        match self {
+            Self::OK => "No error.",
+            Self::errTARGET => "The file is not intended for this device.",
+            Self::errFILE => "The device rejected the file in its own validation.",
+            Self::errWRITE => "The device could not write to its memory.",
+            Self::errERASE => "Erasing the memory failed.",
+            Self::errCHECK_ERASED => "The memory was not blank after erasing.",
+            Self::errPROG => "Programming the memory failed.",
+            Self::errVERIFY => "The device found different data after programming.",
+            Self::errADDRESS => "The address is outside of the memory of the device.",
+            Self::errNOTDONE => "The device expected more data before the end of the transfer.",
+            Self::errFIRMWARE => "The firmware on the device is corrupt and can't be started.",
+            Self::errVENDOR => "The device reported a vendor-specific error.",
+            Self::errUSBR => "The device was reset over USB unexpectedly.",
+            Self::errPOR => "The device was powered off and on unexpectedly.",
+            Self::errUNKNOWN => "The device reported an unknown error.",
+            Self::errSTALLEDPKT => "The device refused a request it did not expect.",
+        }
+    }
+
+    /// Returns the likely causes of the status, empty if there is no hint
+    pub fn likely_causes(&self) -> &'static str {
+        match self {
+            Self::OK => "",
+            Self::errTARGET | Self::errFILE => {
+                "The file was built for another device or product variant."
+            }
+            Self::errWRITE | Self::errPROG | Self::errERASE | Self::errCHECK_ERASED => {
+                "The memory is write protected or read protection is active. \
+                A low supply voltage can also cause flash operations to fail."
+            }
+            Self::errVERIFY => "The sectors were not erased before programming.",
+            Self::errADDRESS => {
+                "The file contains data for addresses the device does not have, \
+                e.g. it was built for a variant with more flash."
+            }
+            Self::errNOTDONE => "The transfer was interrupted or the file is truncated.",
+            Self::errFIRMWARE => "A previous update was interrupted, flash the device again.",
+            Self::errVENDOR => "See the documentation of the device for details.",
+            Self::errUSBR | Self::errPOR => "Check the cable, USB hub and power supply.",
+            Self::errUNKNOWN => "Power cycle the device and try again.",
+            Self::errSTALLEDPKT => {
+                "The device was in another state than expected, e.g. after an aborted \
+                operation, or does not support the request."
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for DeviceStatusCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.likely_causes() {
+            "" => write!(f, "{}", self.explanation()),
+            causes => write!(f, "{} Likely causes: {}", self.explanation(), causes),
+        }
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -163,4 +222,27 @@ impl DeviceStateCode {
     pub fn as_byte(&self) -> u8 {
         *self as u8
     }
+
+    /// Returns a plain-language explanation of the state
+    pub fn explanation(&self) -> &'static str {
+        match self {
+            Self::appIDLE => "The device is running its application, not the bootloader.",
+            Self::appDETACH => "The device is about to switch to DFU mode and waits for a reset.",
+            Self::dfuIDLE => "The device is ready for a new operation.",
+            Self::dfuDNLOAD_SYNC => "The device received data and waits to report its status.",
+            Self::dfuDNBUSY => "The device is busy writing data to its memory.",
+            Self::dfuDNLOAD_IDLE => "The device waits for more data to write.",
+            Self::dfuMANIFEST_SYNC => "The device received all data and is about to finish.",
+            Self::dfuMANIFEST => "The device is finishing the update.",
+            Self::dfuMANIFEST_WAIT_RESET => "The device finished the update and waits for a reset.",
+            Self::dfuUPLOAD_IDLE => "The device is in the middle of sending its memory.",
+            Self::dfuERROR => "The device stopped because of an error.",
+        }
+    }
+}
+
+impl std::fmt::Display for DeviceStateCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.explanation())
+    }
 }
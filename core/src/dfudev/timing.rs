@@ -0,0 +1,185 @@
+//! Timing of the requests sent to devices
+//!
+//! Every control transfer is timed and logged at trace level with its start
+//! and stop time, and recorded in the [session log](crate::session) if one
+//! is running. The durations are also summed up per request for the
+//! current thread, together with the time spent waiting for the poll timeouts
+//! requested by the device. The remaining time of a job is spent on the host,
+//! e.g. reading the file, so slow updates can be attributed to one of these.
+
+use std::cell::RefCell;
+use std::time::{Duration, Instant};
+
+/// Request sent to a device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Request {
+    /// DFU_DETACH
+    Detach,
+
+    /// DFU_DNLOAD, including DfuSe commands
+    Download,
+
+    /// DFU_UPLOAD
+    Upload,
+
+    /// DFU_GETSTATUS
+    GetStatus,
+
+    /// DFU_CLRSTATUS
+    ClrStatus,
+
+    /// DFU_GETSTATE
+    GetState,
+
+    /// DFU_ABORT
+    Abort,
+}
+
+impl std::fmt::Display for Request {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Detach => "DFU_DETACH",
+                Self::Download => "DFU_DNLOAD",
+                Self::Upload => "DFU_UPLOAD",
+                Self::GetStatus => "DFU_GETSTATUS",
+                Self::ClrStatus => "DFU_CLRSTATUS",
+                Self::GetState => "DFU_GETSTATE",
+                Self::Abort => "DFU_ABORT",
+            }
+        )
+    }
+}
+
+/// Summed up durations of a request
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RequestTime {
+    /// Number of requests sent
+    pub count: u32,
+
+    /// Total duration of all requests
+    pub total: Duration,
+
+    /// Longest duration of a single request
+    pub max: Duration,
+}
+
+impl RequestTime {
+    /// Return the average duration of a request
+    pub fn average(&self) -> Duration {
+        self.total / self.count.max(1)
+    }
+}
+
+/// Durations collected since the last reset
+#[derive(Debug, Default, Clone)]
+pub struct Timings {
+    /// Time since the last reset
+    pub elapsed: Duration,
+
+    /// Durations by request, in the order of [`Request`]
+    pub requests: Vec<(Request, RequestTime)>,
+
+    /// Time spent waiting for poll timeouts requested by the device
+    pub poll_wait: Duration,
+}
+
+impl Timings {
+    /// Return the total duration of all requests
+    pub fn request_time(&self) -> Duration {
+        self.requests.iter().map(|(_, time)| time.total).sum()
+    }
+
+    /// Return the time spent neither in requests nor waiting for the device
+    pub fn host_time(&self) -> Duration {
+        self.elapsed
+            .saturating_sub(self.request_time())
+            .saturating_sub(self.poll_wait)
+    }
+}
+
+/// Durations of the current thread
+#[derive(Default)]
+struct Collector {
+    /// Time of the last reset
+    started: Option<Instant>,
+
+    /// Durations by request
+    requests: std::collections::BTreeMap<Request, RequestTime>,
+
+    /// Time spent waiting for poll timeouts
+    poll_wait: Duration,
+}
+
+thread_local! {
+    static COLLECTOR: RefCell<Collector> = RefCell::new(Collector::default());
+}
+
+/// Clear the durations of the current thread and restart the elapsed time
+pub fn reset() {
+    COLLECTOR.with_borrow_mut(|collector| {
+        *collector = Collector {
+            started: Some(Instant::now()),
+            ..Default::default()
+        }
+    });
+}
+
+/// Return the durations of the current thread since the last reset
+pub fn snapshot() -> Timings {
+    COLLECTOR.with_borrow(|collector| Timings {
+        elapsed: collector
+            .started
+            .map(|started| started.elapsed())
+            .unwrap_or_default(),
+        requests: collector
+            .requests
+            .iter()
+            .map(|(request, time)| (*request, *time))
+            .collect(),
+        poll_wait: collector.poll_wait,
+    })
+}
+
+/// Run a request transferring `length` bytes and record its duration
+pub(crate) fn timed(
+    request: Request,
+    value: u16,
+    length: usize,
+    transfer: impl FnOnce() -> rusb::Result<usize>,
+) -> rusb::Result<usize> {
+    let start = Instant::now();
+    let result = transfer();
+    let duration = start.elapsed();
+
+    COLLECTOR.with_borrow_mut(|collector| {
+        let offset = collector
+            .started
+            .map(|started| start.saturating_duration_since(started))
+            .unwrap_or_default();
+        log::trace!(
+            "{request} started at {:.3} ms, stopped at {:.3} ms, took {:.3} ms",
+            offset.as_secs_f64() * 1000.0,
+            (offset + duration).as_secs_f64() * 1000.0,
+            duration.as_secs_f64() * 1000.0
+        );
+
+        let time = collector.requests.entry(request).or_default();
+        time.count += 1;
+        time.total += duration;
+        time.max = time.max.max(duration);
+    });
+    crate::session::request(request, value, length, duration, &result);
+
+    result
+}
+
+/// Wait for a poll timeout requested by the device and record it
+pub(crate) fn poll_wait(timeout: Duration) {
+    std::thread::sleep(timeout);
+
+    COLLECTOR.with_borrow_mut(|collector| collector.poll_wait += timeout);
+    crate::session::poll_wait(timeout);
+}
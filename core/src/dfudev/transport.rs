@@ -0,0 +1,157 @@
+//! Transport of the requests to a device
+//!
+//! [`DfuDevice`](super::DfuDevice) sends all requests via the [`Transport`]
+//! trait. Devices found on the bus use a libusb handle, other transports like
+//! the [simulated device](super::simulator) can be attached with
+//! [`DfuDevice::with_transport`](super::DfuDevice::with_transport), e.g. to
+//! test the update logic without hardware.
+
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use rusb::GlobalContext;
+
+use super::Error;
+
+/// Claiming interfaces the way libusb does, implemented by transports that
+/// share the handling of busy interfaces in [`claim_interface`]
+pub trait InterfaceClaim {
+    /// Claim an interface without any fallback
+    fn try_claim(&mut self, interface_number: u8) -> rusb::Result<()>;
+
+    /// Return if a kernel driver is bound to the interface, failing with
+    /// `rusb::Error::NotSupported` on platforms without kernel drivers
+    fn kernel_driver_active(&self, interface_number: u8) -> rusb::Result<bool>;
+
+    /// Detach the kernel driver bound to the interface
+    fn detach_kernel_driver(&mut self, interface_number: u8) -> rusb::Result<()>;
+
+    /// Return name and process id of another process holding the device, if known
+    fn owner(&self) -> Option<String>;
+}
+
+/// Claim an interface, detaching a kernel driver that blocks it
+///
+/// Fails with [`Error::AccessDenied`] without permissions and with
+/// [`Error::InUse`] if another application holds the interface.
+pub fn claim_interface(handle: &mut impl InterfaceClaim, interface_number: u8) -> Result<()> {
+    match handle.try_claim(interface_number) {
+        // Auto-detach may not work if the driver was bound after opening.
+        // Backends without kernel driver support fail the query, the
+        // interface is then held by another application.
+        Err(rusb::Error::Busy)
+            if matches!(handle.kernel_driver_active(interface_number), Ok(true)) =>
+        {
+            log::info!("Detaching kernel driver from interface {interface_number}");
+            handle.detach_kernel_driver(interface_number)?;
+            handle.try_claim(interface_number)?;
+        }
+        Err(rusb::Error::Access) => return Err(anyhow!(Error::AccessDenied)),
+        Err(rusb::Error::Busy) => return Err(anyhow!(Error::InUse(handle.owner()))),
+        result => result?,
+    }
+
+    Ok(())
+}
+
+/// Control transfers and interface handling of an opened device
+///
+/// Failed transfers return the libusb error of the equivalent USB failure,
+/// e.g. `rusb::Error::Pipe` for a stall or `rusb::Error::NoDevice` for a
+/// disconnect, so they are classified the same way.
+pub trait Transport: Send + Sync {
+    /// Send a control request writing `data`, returning the number of bytes written
+    fn write_control(
+        &self,
+        request_type: u8,
+        request: u8,
+        value: u16,
+        index: u16,
+        data: &[u8],
+        timeout: Duration,
+    ) -> rusb::Result<usize>;
+
+    /// Send a control request reading into `data`, returning the number of bytes read
+    fn read_control(
+        &self,
+        request_type: u8,
+        request: u8,
+        value: u16,
+        index: u16,
+        data: &mut [u8],
+        timeout: Duration,
+    ) -> rusb::Result<usize>;
+
+    /// Claim an interface, failing with [`Error::AccessDenied`] or
+    /// [`Error::InUse`] if not possible
+    fn claim_interface(&mut self, interface_number: u8) -> Result<()>;
+
+    /// Release a claimed interface
+    fn release_interface(&mut self, interface_number: u8) -> rusb::Result<()>;
+
+    /// Select an alternate setting of an interface
+    fn set_alternate_setting(&mut self, interface_number: u8, alt_setting: u8) -> rusb::Result<()>;
+
+    /// Perform a USB port reset
+    fn reset(&mut self) -> rusb::Result<()>;
+}
+
+impl Transport for rusb::DeviceHandle<GlobalContext> {
+    fn write_control(
+        &self,
+        request_type: u8,
+        request: u8,
+        value: u16,
+        index: u16,
+        data: &[u8],
+        timeout: Duration,
+    ) -> rusb::Result<usize> {
+        rusb::DeviceHandle::write_control(self, request_type, request, value, index, data, timeout)
+    }
+
+    fn read_control(
+        &self,
+        request_type: u8,
+        request: u8,
+        value: u16,
+        index: u16,
+        data: &mut [u8],
+        timeout: Duration,
+    ) -> rusb::Result<usize> {
+        rusb::DeviceHandle::read_control(self, request_type, request, value, index, data, timeout)
+    }
+
+    fn claim_interface(&mut self, interface_number: u8) -> Result<()> {
+        claim_interface(self, interface_number)
+    }
+
+    fn release_interface(&mut self, interface_number: u8) -> rusb::Result<()> {
+        rusb::DeviceHandle::release_interface(self, interface_number)
+    }
+
+    fn set_alternate_setting(&mut self, interface_number: u8, alt_setting: u8) -> rusb::Result<()> {
+        rusb::DeviceHandle::set_alternate_setting(self, interface_number, alt_setting)
+    }
+
+    fn reset(&mut self) -> rusb::Result<()> {
+        rusb::DeviceHandle::reset(self)
+    }
+}
+
+impl InterfaceClaim for rusb::DeviceHandle<GlobalContext> {
+    fn try_claim(&mut self, interface_number: u8) -> rusb::Result<()> {
+        rusb::DeviceHandle::claim_interface(self, interface_number)
+    }
+
+    fn kernel_driver_active(&self, interface_number: u8) -> rusb::Result<bool> {
+        rusb::DeviceHandle::kernel_driver_active(self, interface_number)
+    }
+
+    fn detach_kernel_driver(&mut self, interface_number: u8) -> rusb::Result<()> {
+        rusb::DeviceHandle::detach_kernel_driver(self, interface_number)
+    }
+
+    fn owner(&self) -> Option<String> {
+        super::device_owner(&self.device())
+    }
+}
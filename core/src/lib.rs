@@ -0,0 +1,109 @@
+//! Library driving USB DFU and DfuSe firmware updates
+//!
+//! This crate contains the device access and update logic of DFU Buddy
+//! without any user interface, so other tools can flash devices the same way.
+//!
+//! - [`dfudev`] finds devices in DFU mode and sends the DFU requests.
+//! - [`update`] runs operations like erase, program and verify on a device.
+//! - [`progress`] reports the events of a running job and cancels it.
+//! - [`builder`] creates DFU and DfuSe files from raw data.
+//! - [`cache`] keeps the blocks of a file for flashing several devices in a row.
+//! - [`session`] writes a structured log of each job for diagnosing failures.
+//!
+//! # Example
+//!
+//! Flash a file to the first device found and print the progress:
+//!
+//! ```no_run
+//! use std::sync::{mpsc, Arc, Mutex};
+//!
+//! use dfu_buddy_core::progress::{Event, JobEvent, Reporter};
+//! use dfu_buddy_core::{cache, dfudev, update};
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let devices = dfudev::DfuDevice::find(false)?.unwrap_or_default();
+//! let device = devices
+//!     .first()
+//!     .ok_or(anyhow::anyhow!("No device in DFU mode"))?;
+//!
+//! let (sender, receiver) = mpsc::channel::<JobEvent>();
+//! let reporter = Reporter::new(sender);
+//! let options = update::Options::default();
+//! let operations = update::Phases::default().operations(options.after_update);
+//! let device_id = device.id;
+//!
+//! let job = std::thread::spawn(move || {
+//!     update::run(
+//!         device_id,
+//!         Some("firmware.dfu".into()),
+//!         operations,
+//!         Arc::new(Mutex::new(cache::BlockCache::default())),
+//!         options,
+//!         reporter,
+//!     )
+//! });
+//!
+//! // The loop ends when the job drops the reporter
+//! for JobEvent { event, .. } in receiver {
+//!     match event {
+//!         Event::Progress {
+//!             step, bytes, total, ..
+//!         } => println!("{step:?}: {bytes} of {total} bytes"),
+//!         Event::Finished => println!("Done"),
+//!         _ => {}
+//!     }
+//! }
+//!
+//! job.join().unwrap()
+//! # }
+//! ```
+
+#![warn(missing_docs)]
+
+pub mod builder;
+pub mod cache;
+pub mod dfudev;
+pub mod progress;
+pub mod session;
+pub mod update;
+
+/// Current step of update procedure
+#[derive(PartialEq, Eq, Debug, Clone, Copy, serde::Deserialize, serde::Serialize)]
+pub enum DeviceUpdateStep {
+    /// Erase operation in progress
+    Erase,
+
+    /// Program operation in progress
+    Program,
+
+    /// Verify operation in progress
+    Verify,
+
+    /// Backup operation in progress
+    Backup,
+
+    /// Leaving DFU mode
+    Leave,
+
+    /// Checksum calculation in progress
+    Checksum,
+
+    /// Comparison of the sectors before a differential update in progress
+    Compare,
+}
+
+/// State of a sector during erase
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum SectorState {
+    /// Not erased yet
+    Pending,
+
+    /// Erase in progress
+    Erasing,
+
+    /// Erased successfully
+    Done,
+
+    /// Erase failed
+    Failed,
+}
@@ -0,0 +1,265 @@
+//! Typed progress events of jobs running in worker threads
+//!
+//! Each job gets a unique id and all events it sends carry this id, so the
+//! receiver can tell apart the events of several jobs running at the same
+//! time, e.g. on different devices.
+
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+
+use crate::{dfudev, update, DeviceUpdateStep, SectorState};
+
+/// Unique id of a job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct JobId(u64);
+
+impl JobId {
+    /// Return a new id, unique for this process
+    pub fn next() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+        Self(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl std::fmt::Display for JobId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// Event sent by a job
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// Job started
+    Started,
+
+    /// Steps to run and the number of payload bytes
+    Plan {
+        /// Steps in the order they are run.
+        steps: Vec<DeviceUpdateStep>,
+        /// Steps left out because the device can't perform them, with the reason.
+        skipped: Vec<update::SkippedStep>,
+        /// Number of payload bytes in the file.
+        payload_size: u64,
+    },
+
+    /// Step started
+    Step(DeviceUpdateStep),
+
+    /// Bytes done by a step
+    Progress {
+        /// Step the bytes belong to.
+        step: DeviceUpdateStep,
+        /// Number of bytes done.
+        bytes: u64,
+        /// Total number of bytes of the step.
+        total: u64,
+        /// Address of the last block transferred, if known.
+        address: Option<u32>,
+    },
+
+    /// Step left out while running, with the reason
+    Skipped(DeviceUpdateStep, String),
+
+    /// Sectors of a target about to be erased
+    EraseTarget {
+        /// Name of the target memory segment.
+        name: String,
+        /// Start address and size of the sectors in the order they are erased.
+        sectors: Vec<(u32, u32)>,
+    },
+
+    /// State of a sector changed
+    SectorState(usize, SectorState),
+
+    /// Sector programmed completely, by alt setting
+    SectorProgrammed(u8, update::AddressRange),
+
+    /// CRC-32 of the device memory
+    Checksum(u32),
+
+    /// Sectors differing from the file, sent when verification failed
+    Differences(Vec<update::Difference>),
+
+    /// Backup file written
+    BackupFinished(std::path::PathBuf),
+
+    /// Memory statistics
+    Statistics(update::Statistics),
+
+    /// Durations of the requests sent since the job started
+    Timings(dfudev::timing::Timings),
+
+    /// Job stopped on request of the user
+    Cancelled {
+        /// Behavior chosen by the user.
+        mode: CancelMode,
+        /// Point to continue from, `None` if the job can't be resumed.
+        resume_point: Option<update::ResumePoint>,
+    },
+
+    /// Waiting for the application to start after the job finishes
+    AwaitingFirmware,
+
+    /// Application found after leaving DFU mode, compared with the file
+    FirmwareCheck(update::FirmwareCheck),
+
+    /// Job finished successfully
+    Finished,
+
+    /// Job finished, but the data written couldn't be read back to verify
+    /// it, with the reason. Not a success, but the device was updated.
+    FinishedUnverified(String),
+
+    /// Job failed with an error message
+    Error(String),
+}
+
+impl Event {
+    /// Return a progress event without address
+    pub fn progress(step: DeviceUpdateStep, bytes: u64, total: u64) -> Self {
+        Self::Progress {
+            step,
+            bytes,
+            total,
+            address: None,
+        }
+    }
+}
+
+/// Event with the id of the job that sent it
+#[derive(Debug, Clone)]
+pub struct JobEvent {
+    /// Id of the job
+    pub job: JobId,
+
+    /// Event sent by the job
+    pub event: Event,
+}
+
+/// How a job stops when cancelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum CancelMode {
+    /// Stop before the next block, even in the middle of a sector
+    Abort,
+
+    /// Finish the sector being written first, so the job stops at a
+    /// consistent point it can be resumed from
+    Stop,
+}
+
+impl CancelMode {
+    /// Return the value stored in the cancellation flag
+    fn flag(self) -> u8 {
+        match self {
+            Self::Abort => 1,
+            Self::Stop => 2,
+        }
+    }
+}
+
+/// Function passing an event to the receiver, failing if it's gone
+type Send = dyn Fn(JobEvent) -> Result<(), Disconnected> + std::marker::Send + Sync;
+
+/// Sends the events of a single job and tells it when to stop
+///
+/// Clones share the cancellation flag, so the thread that started the job
+/// can keep a clone to cancel it.
+#[derive(Clone)]
+pub struct Reporter {
+    /// Id of the job
+    job: JobId,
+
+    /// Sends the events to the receiver
+    send: Arc<Send>,
+
+    /// Flag set when the job should stop, holding the [`CancelMode`]
+    cancelled: Arc<AtomicU8>,
+}
+
+impl Reporter {
+    /// Create a reporter for a new job, sending its events to a channel
+    ///
+    /// The channel can carry any type the events convert into, so they can
+    /// be mixed with other messages of the receiver.
+    pub fn new<T: From<JobEvent> + std::marker::Send + 'static>(sender: Sender<T>) -> Self {
+        Self {
+            job: JobId::next(),
+            send: Arc::new(move |event| sender.send(event.into()).map_err(|_| Disconnected)),
+            cancelled: Default::default(),
+        }
+    }
+
+    /// Ask the job to stop, an abort overrides a stop requested before
+    pub fn cancel(&self, mode: CancelMode) {
+        match mode {
+            CancelMode::Abort => self.cancelled.store(mode.flag(), Ordering::Relaxed),
+            CancelMode::Stop => {
+                self.cancelled
+                    .compare_exchange(0, mode.flag(), Ordering::Relaxed, Ordering::Relaxed)
+                    .ok();
+            }
+        }
+    }
+
+    /// Return how the job should stop, `None` if it should continue
+    pub fn cancel_mode(&self) -> Option<CancelMode> {
+        match self.cancelled.load(Ordering::Relaxed) {
+            0 => None,
+            flag if flag == CancelMode::Stop.flag() => Some(CancelMode::Stop),
+            _ => Some(CancelMode::Abort),
+        }
+    }
+
+    /// Return if the job should stop
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel_mode().is_some()
+    }
+
+    /// Return the id of the job
+    pub fn job(&self) -> JobId {
+        self.job
+    }
+
+    /// Send an event of the job
+    pub fn send(&self, event: Event) -> Result<(), Disconnected> {
+        crate::session::event(&event);
+
+        (self.send)(JobEvent {
+            job: self.job,
+            event,
+        })
+    }
+}
+
+impl std::fmt::Debug for Reporter {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Reporter")
+            .field("job", &self.job)
+            .field("cancel_mode", &self.cancel_mode())
+            .finish()
+    }
+}
+
+/// Error sending an event after the receiver is gone
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Disconnected;
+
+impl std::error::Error for Disconnected {}
+
+impl std::fmt::Display for Disconnected {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Receiver of the job events is gone.")
+    }
+}
+
+/// Return the fraction 0..1 of bytes done, a step without bytes is complete
+pub fn fraction(bytes: u64, total: u64) -> f32 {
+    if total == 0 {
+        1.0
+    } else {
+        bytes as f32 / total as f32
+    }
+}
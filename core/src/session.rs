@@ -0,0 +1,237 @@
+//! Structured log of update sessions
+//!
+//! While a job runs with a session log configured in its options, every
+//! request sent to the device, every status returned and every event of the
+//! job is written as a JSON line to a file of its own, with the time since
+//! the session started. The file ends with the result and the timings of the
+//! job, so failed updates can be diagnosed after the fact, e.g. when sent in
+//! by a user. Only the newest files are kept in the directory.
+//!
+//! The session belongs to the thread running the job, requests of other
+//! threads are not recorded.
+
+use std::cell::RefCell;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::Result;
+use serde_json::{json, Value};
+
+use crate::dfudev::{timing, DeviceStatusResponse};
+use crate::progress::Event;
+
+/// Prefix of the session log file names
+const FILE_PREFIX: &str = "session-";
+
+/// Extension of the session log file names
+const FILE_EXTENSION: &str = "jsonl";
+
+/// Where session logs are written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    /// Directory of the files, created if missing
+    pub directory: PathBuf,
+
+    /// Max. number of files kept, the oldest are removed when a session starts
+    pub keep: usize,
+}
+
+impl Config {
+    /// Number of files kept by default
+    pub const DEFAULT_KEEP: usize = 20;
+
+    /// Return the default directory in the local data directory of the
+    /// application, if there is one
+    pub fn default_directory() -> Option<PathBuf> {
+        dirs::data_local_dir().map(|dir| dir.join("dfu-buddy").join("sessions"))
+    }
+}
+
+/// Session log file being written
+struct Session {
+    /// Buffered file
+    file: std::io::BufWriter<std::fs::File>,
+
+    /// Time the session started
+    started: Instant,
+}
+
+thread_local! {
+    static SESSION: RefCell<Option<Session>> = const { RefCell::new(None) };
+}
+
+/// Start a session on the current thread with a header line, removing the
+/// oldest files beyond the number kept and returning the path of the new file
+pub fn start(config: &Config, header: Value) -> Result<PathBuf> {
+    std::fs::create_dir_all(&config.directory)?;
+    rotate(&config.directory, config.keep.saturating_sub(1))?;
+
+    let timestamp = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let path = config
+        .directory
+        .join(format!("{FILE_PREFIX}{timestamp}.{FILE_EXTENSION}"));
+    let file = std::fs::File::create(&path)?;
+
+    SESSION.with_borrow_mut(|session| {
+        *session = Some(Session {
+            file: std::io::BufWriter::new(file),
+            started: Instant::now(),
+        })
+    });
+    record("session", header);
+
+    Ok(path)
+}
+
+/// Write the result and the timings of the job and close the session
+pub fn finish(result: &Result<()>) {
+    let timings = timing::snapshot();
+    record(
+        "finished",
+        json!({
+            "error": result.as_ref().err().map(|error| format!("{error:#}")),
+            "elapsed_ms": millis(timings.elapsed),
+            "request_ms": millis(timings.request_time()),
+            "poll_wait_ms": millis(timings.poll_wait),
+            "host_ms": millis(timings.host_time()),
+            "requests": timings
+                .requests
+                .iter()
+                .map(|(request, time)| json!({
+                    "request": request.to_string(),
+                    "count": time.count,
+                    "total_ms": millis(time.total),
+                    "max_ms": millis(time.max),
+                }))
+                .collect::<Vec<_>>(),
+        }),
+    );
+
+    SESSION.with_borrow_mut(|session| {
+        if let Some(mut session) = session.take() {
+            if let Err(error) = session.file.flush() {
+                log::warn!("Error writing session log: {error}");
+            }
+        }
+    });
+}
+
+/// Return if a session is running on the current thread
+pub fn is_active() -> bool {
+    SESSION.with_borrow(Option::is_some)
+}
+
+/// Write a line of a kind with the fields of an object, if a session is running
+pub fn record(kind: &str, fields: Value) {
+    SESSION.with_borrow_mut(|session| {
+        let Some(session) = session else {
+            return;
+        };
+
+        let mut line = json!({
+            "time_ms": millis(session.started.elapsed()),
+            "kind": kind,
+        });
+        if let (Some(line), Value::Object(fields)) = (line.as_object_mut(), fields) {
+            line.extend(fields);
+        }
+
+        if let Err(error) = writeln!(session.file, "{line}") {
+            log::warn!("Error writing session log: {error}");
+        }
+    });
+}
+
+/// Record a control request with its duration and result
+pub(crate) fn request(
+    request: timing::Request,
+    value: u16,
+    length: usize,
+    duration: Duration,
+    result: &rusb::Result<usize>,
+) {
+    if !is_active() {
+        return;
+    }
+
+    record(
+        "request",
+        json!({
+            "request": request.to_string(),
+            "value": value,
+            "length": length,
+            "duration_ms": millis(duration),
+            "transferred": result.as_ref().ok(),
+            "error": result.as_ref().err().map(ToString::to_string),
+        }),
+    );
+}
+
+/// Record a status returned by DFU_GETSTATUS
+pub(crate) fn status(status: &DeviceStatusResponse) {
+    if !is_active() {
+        return;
+    }
+
+    record(
+        "status",
+        json!({
+            "status": format!("{:?}", status.bStatus),
+            "state": format!("{:?}", status.bState),
+            "poll_timeout_ms": status.bwPollTimeout,
+        }),
+    );
+}
+
+/// Record a wait for a poll timeout
+pub(crate) fn poll_wait(timeout: Duration) {
+    if !is_active() {
+        return;
+    }
+
+    record("poll_wait", json!({ "duration_ms": millis(timeout) }));
+}
+
+/// Record an event of the job, except the progress already visible from the
+/// requests
+pub(crate) fn event(event: &Event) {
+    if !is_active() || matches!(event, Event::Progress { .. }) {
+        return;
+    }
+
+    record("event", json!({ "event": format!("{event:?}") }));
+}
+
+/// Remove the oldest session logs in a directory until at most `keep` are left
+fn rotate(directory: &Path, keep: usize) -> Result<()> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension().is_some_and(|ext| ext == FILE_EXTENSION)
+                && path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with(FILE_PREFIX))
+        })
+        .collect();
+
+    // File names hold the start time with a fixed number of digits
+    files.sort();
+
+    for path in files.iter().take(files.len().saturating_sub(keep)) {
+        if let Err(error) = std::fs::remove_file(path) {
+            log::warn!("Error removing session log {}: {error}", path.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Return a duration in milliseconds with microsecond resolution
+fn millis(duration: Duration) -> f64 {
+    (duration.as_micros() as f64) / 1000.0
+}
@@ -0,0 +1,2984 @@
+//! Device update operations
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+
+use crate::dfudev::info::bcd_version;
+use crate::progress::{self, CancelMode, Event};
+use crate::{builder, cache, dfudev, session, DeviceUpdateStep, SectorState};
+
+/// Max. number of bytes read from a plain DFU device that doesn't end the upload
+const MAX_PLAIN_UPLOAD_SIZE: usize = 64 * 1024 * 1024;
+
+/// Max. time to wait for a disconnected device to re-enumerate
+const RECONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Max. time for a device to return after removing the readout protection,
+/// which includes a mass erase
+const UNPROTECT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Max. time for the application to enumerate after leaving DFU mode
+const FIRMWARE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Max. number of DfuSe blocks sent after setting the address pointer, block
+/// numbers start at 2 and must fit into 16 bits
+const MAX_BLOCK_COUNT: usize = u16::MAX as usize - 2;
+
+/// Max. number of bytes read by [`read_memory`]
+pub const MAX_MEMORY_READ_SIZE: u32 = 16 * 1024 * 1024;
+
+/// Step left out of a run because the device can't perform it, with the reason
+pub type SkippedStep = (DeviceUpdateStep, String);
+
+/// Run a list of operations on the device in the given order.
+///
+/// Backups are written to their own path, all other operations use the DFU
+/// file given by `file_path`. A USB reset selected in the options is performed
+/// after the last operation if the device was programmed.
+///
+/// This function is executed in a separate thread and reports its progress
+/// as events of the job of `reporter`
+pub fn run(
+    device_id: u64,
+    file_path: Option<std::path::PathBuf>,
+    operations: Vec<Operation>,
+    block_cache: Arc<Mutex<cache::BlockCache>>,
+    options: Options,
+    reporter: progress::Reporter,
+) -> Result<()> {
+    run_job(
+        || dfudev::DfuDevice::find_by_id(device_id)?.ok_or(anyhow!(Error::DeviceNotFound)),
+        file_path,
+        operations,
+        block_cache,
+        options,
+        reporter,
+    )
+}
+
+/// Run a list of operations on a device not searched on the bus, e.g. a
+/// [simulated device](dfudev::simulator::SimulatedDevice), see [`run`]
+pub fn run_device(
+    device: dfudev::DfuDevice,
+    file_path: Option<std::path::PathBuf>,
+    operations: Vec<Operation>,
+    block_cache: Arc<Mutex<cache::BlockCache>>,
+    options: Options,
+    reporter: progress::Reporter,
+) -> Result<()> {
+    run_job(
+        || Ok(device),
+        file_path,
+        operations,
+        block_cache,
+        options,
+        reporter,
+    )
+}
+
+/// Run a job on the device returned by `locate_device`, see [`run`]
+fn run_job(
+    locate_device: impl FnOnce() -> Result<dfudev::DfuDevice>,
+    file_path: Option<std::path::PathBuf>,
+    operations: Vec<Operation>,
+    block_cache: Arc<Mutex<cache::BlockCache>>,
+    options: Options,
+    reporter: progress::Reporter,
+) -> Result<()> {
+    dfudev::timing::reset();
+    if let Some(config) = &options.session_log {
+        let header = serde_json::json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "file": file_path.as_ref().map(|path| path.display().to_string()),
+            "operations": format!("{operations:?}"),
+            "options": format!("{options:?}"),
+        });
+        match session::start(config, header) {
+            Ok(path) => log::info!("Writing session log to {}", path.display()),
+            Err(error) => log::warn!("Session log not written: {error}"),
+        }
+    }
+
+    let result = (|| -> Result<()> {
+        reporter.send(Event::Started)?;
+
+        let mut engine = UpdateEngine::new(
+            locate_device,
+            file_path.as_deref(),
+            operations,
+            block_cache,
+            options,
+            reporter,
+        )?;
+        session::record("device", device_record(&engine.device.info));
+        while engine.step()? {}
+
+        engine.finish()
+    })();
+
+    session::finish(&result);
+
+    result
+}
+
+/// Return the fields of the device info recorded in the session log
+fn device_record(info: &dfudev::DeviceInfo) -> serde_json::Value {
+    serde_json::json!({
+        "vendor_id": format!("{:04x}", info.vendor_id),
+        "product_id": format!("{:04x}", info.product_id),
+        "product": info.product_string,
+        "serial_number": info.serial_number_string,
+        "device_version": info.device_version,
+        "dfu_version": format!("{:04x}", info.dfu_version),
+        "transfer_size": info.dfu_transfer_size,
+        "detach_timeout": info.dfu_detach_timeout,
+        "attributes": info.dfu_attributes,
+        "alt_settings": info.alt_settings,
+        "bus": info.bus_number,
+        "ports": info.port_numbers,
+    })
+}
+
+/// Operations of a job as planned before the first one is run
+struct Plan {
+    /// Operations in the order they are run
+    operations: Vec<Operation>,
+
+    /// Targets processed in one pass each by the operations per target,
+    /// see [`target_passes`]
+    passes: Vec<Option<u8>>,
+
+    /// Flag if any operation writes to the device
+    writes: bool,
+
+    /// Port of the device the application enumerates on after the update,
+    /// `None` if the firmware isn't checked
+    port: Option<dfudev::application::Port>,
+}
+
+/// Runs the operations of a job on a device one step at a time
+///
+/// The device is located and opened once and the file is parsed once, both
+/// are shared by all steps. Devices appearing while the job runs can't be
+/// mixed up with it, as the bus is only searched again to reconnect.
+///
+/// Each step runs one operation for one pass of targets, so the job can be
+/// cancelled between the steps as well as inside them via the reporter.
+struct UpdateEngine {
+    /// Device opened for the whole job
+    device: dfudev::DfuDevice,
+
+    /// DFU file used by all operations except backups
+    file: Option<dfufile::DfuFile>,
+
+    /// Planned operations
+    plan: Plan,
+
+    /// Options of the job, adjusted for each pass
+    options: Options,
+
+    /// Blocks of the file shared with other jobs
+    block_cache: Arc<Mutex<cache::BlockCache>>,
+
+    /// Reporter of the progress, also telling if the job was cancelled
+    reporter: progress::Reporter,
+
+    /// Sectors left out by erase and program, see [`Options::unchanged_sectors`]
+    unchanged_sectors: Vec<(u8, AddressRange)>,
+
+    /// Index of the next operation and pass
+    position: (usize, usize),
+
+    /// Reason the data written can't be read back, set when a step reading
+    /// it was skipped
+    unverified: Option<String>,
+}
+
+impl UpdateEngine {
+    /// Open the file, check the operations against it and the device and
+    /// report the plan
+    fn new(
+        locate_device: impl FnOnce() -> Result<dfudev::DfuDevice>,
+        file_path: Option<&std::path::Path>,
+        mut operations: Vec<Operation>,
+        block_cache: Arc<Mutex<cache::BlockCache>>,
+        options: Options,
+        reporter: progress::Reporter,
+    ) -> Result<Self> {
+        let file = match file_path {
+            Some(file_path) => {
+                block_cache.lock().unwrap().validate(file_path);
+                Some(dfufile::DfuFile::open(file_path)?)
+            }
+            None => None,
+        };
+
+        let payload_size = match file.as_ref().map(|file| &file.content) {
+            None => 0,
+            Some(dfufile::Content::Plain) => {
+                // Plain DFU has no separate erase and can't read back after manifestation,
+                // so these operations can't be run on their own
+                if !operations.contains(&Operation::Program) {
+                    if let Some(operation) = operations.iter().find(|op| op.needs_upload_or_erase())
+                    {
+                        return Err(anyhow!(Error::OperationNotSupported(operation.clone())));
+                    }
+                }
+                if options.range.is_some() {
+                    return Err(anyhow!(Error::RangeNotSupported));
+                }
+                plain_payload(&file.as_ref().unwrap().path)?.len() as u64
+            }
+            Some(dfufile::Content::DfuSe(content)) => {
+                if options.range.is_some()
+                    && options.mass_erase
+                    && operations.contains(&Operation::Erase)
+                {
+                    return Err(anyhow!(Error::RangeNotSupported));
+                }
+
+                // Comparing precedes the erase, it only pays off if sectors are erased one by one
+                if options.differential && options.mass_erase {
+                    log::warn!("Differential update is not possible with mass erase.");
+                } else if let (true, Some(index)) = (
+                    options.differential,
+                    operations.iter().position(|op| *op == Operation::Erase),
+                ) {
+                    operations.insert(index, Operation::Compare);
+                }
+
+                payload_size(&selected_images(content, &options), options.range)
+            }
+        };
+
+        if let (Some(range), Some(_), 0) = (options.range, &file, payload_size) {
+            return Err(anyhow!(Error::NothingInRange(range)));
+        }
+
+        // The device is opened once and its handle shared by all steps
+        let mut device = locate_device()?;
+        let (operations, skipped) = plan_operations(&device.info, operations)?;
+        for (step, reason) in &skipped {
+            log::warn!("Skipping {step:?}: {reason}");
+        }
+
+        reporter
+            .send(Event::Plan {
+                steps: operations.iter().map(Operation::step).collect(),
+                skipped,
+                payload_size,
+            })
+            .ok();
+
+        for (image_no, alt_setting) in &options.targets.remapped {
+            log::warn!("Image {image_no} of the file is remapped to alt setting {alt_setting}");
+        }
+
+        // The application enumerates on the port of the bootloader after a reset,
+        // other transports have no port to watch
+        let port = (device.is_usb()
+            && operations.contains(&Operation::Program)
+            && options.after_update != AfterUpdate::Stay)
+            .then(|| dfudev::application::Port::of(&device.info));
+
+        let passes = target_passes(file.as_ref(), &options);
+        let writes = operations.iter().any(Operation::needs_download);
+
+        // Blank blocks only match the device memory if erased before programming
+        let skip_blank_blocks = options.skip_blank_blocks && operations.contains(&Operation::Erase);
+
+        // Sectors completed before are skipped like unchanged ones, a mass erase
+        // would clear them
+        let unchanged_sectors = if options.mass_erase {
+            if !options.completed_sectors.is_empty() {
+                log::warn!("Resuming is not possible with mass erase, all sectors are written.");
+            }
+            Vec::new()
+        } else {
+            options.completed_sectors.clone()
+        };
+
+        device.open()?;
+
+        Ok(Self {
+            device,
+            file,
+            plan: Plan {
+                operations,
+                passes,
+                writes,
+                port,
+            },
+            options: Options {
+                skip_blank_blocks,
+                ..options
+            },
+            block_cache,
+            reporter,
+            unchanged_sectors,
+            position: (0, 0),
+            unverified: None,
+        })
+    }
+
+    /// Run the next step, returning `false` if all steps are done
+    fn step(&mut self) -> Result<bool> {
+        let (index, pass) = self.position;
+        let Some(operation) = self.plan.operations.get(index).cloned() else {
+            return Ok(false);
+        };
+
+        // Compares all targets at once, before the first pass erases anything
+        if operation == Operation::Compare {
+            self.position = (index + 1, 0);
+            self.compare_step(&operation)?;
+            return Ok(true);
+        }
+
+        // Operations for each target are repeated per pass, see `target_passes`
+        let (passes, reboot) = if operation.is_per_target() {
+            (self.plan.passes.len(), self.options.targets.reboot_between)
+        } else {
+            (1, false)
+        };
+        self.position = if pass + 1 < passes {
+            (index, pass + 1)
+        } else {
+            (index + 1, 0)
+        };
+
+        if pass > 0 && reboot {
+            reboot_device(&mut self.device)?;
+        }
+
+        let options = Options {
+            target: if operation.is_per_target() {
+                self.plan.passes[pass]
+            } else {
+                None
+            },
+            // Mass erase would remove the targets programmed in previous passes
+            mass_erase: self.options.mass_erase && pass == 0,
+            unchanged_sectors: self.unchanged_sectors.clone(),
+            ..self.options.clone()
+        };
+
+        // Reading back is optional as long as something is written, see `plan_operations`
+        match self.run_operation(&operation, &options) {
+            Err(error) if is_cancelled(&error) => Err(stop_job(
+                &self.device,
+                &operation,
+                options.target,
+                &self.reporter,
+                error,
+            )),
+            // In strict mode, an update that can't be verified fails
+            Err(error) if self.plan.writes && !self.options.strict && is_read_protected(&error) => {
+                let reason = "Device is read-protected, verification is unavailable.";
+                log::warn!("Skipping {:?}: {reason}", operation.step());
+                self.reporter
+                    .send(Event::Skipped(operation.step(), reason.to_string()))
+                    .ok();
+                self.unverified = Some(reason.to_string());
+                // The remaining passes of the operation would fail the same way
+                self.position = (index + 1, 0);
+                Ok(true)
+            }
+            result => result.map(|_| true),
+        }
+    }
+
+    /// Run the comparison of a differential update and keep the unchanged sectors
+    fn compare_step(&mut self, operation: &Operation) -> Result<()> {
+        check_cancelled(&self.reporter)?;
+        self.reporter.send(Event::Step(operation.step())).ok();
+        let options = self.options.clone();
+        let result = self.compare(&options);
+        self.reporter
+            .send(Event::Timings(dfudev::timing::snapshot()))
+            .ok();
+
+        match result.map_err(|error| classify_error(&mut self.device, error)) {
+            Ok(sectors) => self.unchanged_sectors = sectors,
+            Err(error) if is_cancelled(&error) => {
+                return Err(stop_job(
+                    &self.device,
+                    operation,
+                    None,
+                    &self.reporter,
+                    error,
+                ));
+            }
+            // Without reading back, all sectors are written
+            Err(error) if is_read_protected(&error) => {
+                let reason = "Device is read-protected, all sectors are written.";
+                log::warn!("Skipping {:?}: {reason}", operation.step());
+                self.reporter
+                    .send(Event::Skipped(operation.step(), reason.to_string()))
+                    .ok();
+            }
+            Err(error) => return Err(error),
+        }
+
+        Ok(())
+    }
+
+    /// Perform the action after the update, report the job as finished and
+    /// check the firmware started by it
+    fn finish(mut self) -> Result<()> {
+        if self.plan.operations.contains(&Operation::Program) {
+            self.finish_update()?;
+        }
+        self.device.close();
+        if self.plan.port.is_some() {
+            self.reporter.send(Event::AwaitingFirmware).ok();
+        }
+        match self.unverified.take() {
+            Some(reason) => self.reporter.send(Event::FinishedUnverified(reason))?,
+            None => self.reporter.send(Event::Finished)?,
+        }
+
+        // Reported after the update finished, it doesn't fail the update
+        if let (Some(port), Some(file)) = (self.plan.port.take(), &self.file) {
+            match confirm_firmware(&port, file, &self.reporter) {
+                Ok(Some(check)) => {
+                    log::info!("{}", check.summary());
+                    self.reporter.send(Event::FirmwareCheck(check)).ok();
+                }
+                Ok(None) => {}
+                Err(error) => log::error!("Error checking the firmware version: {}", error),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run a single operation and classify its errors
+    fn run_operation(&mut self, operation: &Operation, options: &Options) -> Result<()> {
+        check_cancelled(&self.reporter)?;
+        self.reporter.send(Event::Step(operation.step())).ok();
+
+        let result = match operation {
+            Operation::Backup(backup_path) => self.backup(backup_path, options),
+            Operation::Erase => self.erase(options),
+            Operation::Program => self.program(options),
+            Operation::Verify => self.verify(options),
+            Operation::Leave => self.leave(),
+            Operation::Checksum => self.checksum(options),
+            // Run by `compare_step`, as it returns the unchanged sectors
+            Operation::Compare => Ok(()),
+        };
+        self.reporter
+            .send(Event::Timings(dfudev::timing::snapshot()))
+            .ok();
+
+        result.map_err(|error| classify_error(&mut self.device, error))
+    }
+}
+
+/// Wait for the application to enumerate and compare its version with the
+/// file, `None` if the job was cancelled while waiting
+fn confirm_firmware(
+    port: &dfudev::application::Port,
+    file: &dfufile::DfuFile,
+    reporter: &progress::Reporter,
+) -> Result<Option<FirmwareCheck>> {
+    let expected_version = Some(file.suffix.bcdDevice).filter(|version| *version != 0xFFFF);
+
+    log::info!("Waiting for the application to start");
+    let device = dfudev::application::wait(port, FIRMWARE_TIMEOUT, || reporter.is_cancelled())?;
+    if reporter.is_cancelled() {
+        return Ok(None);
+    }
+
+    Ok(Some(FirmwareCheck {
+        device,
+        expected_version,
+    }))
+}
+
+/// Check the operations against the capabilities declared in the DFU
+/// functional descriptor of the device
+///
+/// Reading back is skipped with the reason if the device can't upload and
+/// other operations remain. Operations that are the purpose of the run, like
+/// programming or a backup, fail instead.
+fn plan_operations(
+    info: &dfudev::DeviceInfo,
+    operations: Vec<Operation>,
+) -> Result<(Vec<Operation>, Vec<SkippedStep>)> {
+    let mut planned = Vec::new();
+    let mut skipped = Vec::new();
+
+    for operation in operations.iter() {
+        if operation.needs_download() && !info.can_download {
+            return Err(anyhow!(Error::DownloadNotSupported(operation.clone())));
+        }
+
+        if operation.needs_upload() && !info.can_upload {
+            // Verification is optional as long as something is written
+            if operations.iter().any(Operation::needs_download)
+                && matches!(
+                    operation,
+                    Operation::Verify | Operation::Compare | Operation::Checksum
+                )
+            {
+                skipped.push((
+                    operation.step(),
+                    "Device does not support reading its memory.".to_string(),
+                ));
+                continue;
+            }
+            return Err(anyhow!(Error::UploadNotSupported(operation.clone())));
+        }
+
+        planned.push(operation.clone());
+    }
+
+    Ok((planned, skipped))
+}
+
+/// Return the targets processed in one pass each
+///
+/// All targets are processed in a single pass (`None`) unless a reboot between
+/// targets is requested and the file contains images for several alt settings.
+fn target_passes(file: Option<&dfufile::DfuFile>, options: &Options) -> Vec<Option<u8>> {
+    if !options.targets.reboot_between {
+        return vec![None];
+    }
+
+    let Some(dfufile::Content::DfuSe(content)) = file.map(|file| &file.content) else {
+        return vec![None];
+    };
+
+    let mut targets: Vec<Option<u8>> = Vec::new();
+    for (image_no, image) in selected_images(content, options) {
+        let target = Some(options.targets.alt_setting(image_no, image));
+        if !targets.contains(&target) {
+            targets.push(target);
+        }
+    }
+
+    if targets.len() < 2 {
+        return vec![None];
+    }
+
+    targets
+}
+
+/// Reset the device between two targets and wait for it to re-enumerate
+fn reboot_device(device: &mut dfudev::DfuDevice) -> Result<()> {
+    log::info!("Resetting device before the next target");
+    device.usb_reset()?;
+    device.close();
+
+    device.reconnect(RECONNECT_TIMEOUT)
+}
+
+impl UpdateEngine {
+    /// Erase the data in the device.
+    fn erase(&mut self, options: &Options) -> Result<()> {
+        let Self {
+            ref mut device,
+            file: Some(ref file),
+            ref reporter,
+            ..
+        } = *self
+        else {
+            return Err(anyhow!(Error::NoFile(Operation::Erase)));
+        };
+        let mut progress_throttle = ProgressThrottle::new(options.progress_interval());
+
+        reset_state(device)?;
+
+        match &file.content {
+            dfufile::Content::Plain => {
+                log::warn!("Plain DFU does not support separate erase. Skipped.");
+            }
+            dfufile::Content::DfuSe(_) if options.mass_erase => {
+                log::debug!("Mass erasing device");
+                device.select_default_alt_setting()?;
+                reporter
+                    .send(Event::progress(DeviceUpdateStep::Erase, 0, 1))
+                    .ok();
+                dfudev::dfuse::mass_erase(device)?;
+                reporter
+                    .send(Event::progress(DeviceUpdateStep::Erase, 1, 1))
+                    .ok();
+            }
+            dfufile::Content::DfuSe(content) => {
+                let images = selected_images(content, options);
+                let mut progress = ByteProgress::new(payload_size(&images, options.range));
+
+                for (image_no, image) in images {
+                    let alt_setting = options.targets.alt_setting(image_no, image);
+                    let mut plan = erase_plan(image, alt_setting, &device.info, options.range)?;
+
+                    let unchanged_sectors = options.unchanged_sectors(alt_setting);
+                    plan.sectors.retain(|sector| {
+                        let unchanged = unchanged_sectors
+                            .iter()
+                            .any(|unchanged| unchanged.start == sector.address);
+                        if unchanged {
+                            progress.add(sector.payload_bytes);
+                        }
+                        !unchanged
+                    });
+                    if plan.sectors.is_empty() {
+                        log::debug!("All sectors of alt setting {alt_setting} are unchanged");
+                        reporter
+                            .send(progress.event(DeviceUpdateStep::Erase, None))
+                            .ok();
+                        continue;
+                    }
+
+                    device.select_alt_setting(plan.alt_setting)?;
+
+                    reporter
+                        .send(Event::EraseTarget {
+                            name: plan.name,
+                            sectors: plan
+                                .sectors
+                                .iter()
+                                .map(|sector| (sector.address, sector.size))
+                                .collect(),
+                        })
+                        .ok();
+
+                    for (index, sector) in plan.sectors.iter().enumerate() {
+                        // Sectors are erased as a whole, so both modes stop consistently
+                        if reporter.is_cancelled() {
+                            return Err(anyhow!(Error::Stopped(ResumePoint {
+                                step: DeviceUpdateStep::Erase,
+                                alt_setting: Some(plan.alt_setting),
+                                address: Some(sector.address),
+                            })));
+                        }
+                        log::debug!("Erasing sector at 0x{:08X}", sector.address);
+                        reporter
+                            .send(Event::SectorState(index, SectorState::Erasing))
+                            .ok();
+
+                        let result = with_reconnect(device, sector.address, None, |device| {
+                            dfudev::dfuse::erase_page(device, sector.address)
+                        });
+                        let state = if result.is_ok() {
+                            SectorState::Done
+                        } else {
+                            SectorState::Failed
+                        };
+                        reporter.send(Event::SectorState(index, state)).ok();
+                        result?;
+
+                        progress.add(sector.payload_bytes);
+                        if progress_throttle.ready() || index + 1 == plan.sectors.len() {
+                            reporter
+                                .send(progress.event(DeviceUpdateStep::Erase, Some(sector.address)))
+                                .ok();
+                        }
+                    }
+                }
+            }
+        }
+
+        // Leave the device idle for the next step
+        device.abort_request()?;
+
+        Ok(())
+    }
+
+    /// Downloads the data to the device.
+    fn program(&mut self, options: &Options) -> Result<()> {
+        let Self {
+            ref mut device,
+            file: Some(ref mut file),
+            ref block_cache,
+            ref reporter,
+            ..
+        } = *self
+        else {
+            return Err(anyhow!(Error::NoFile(Operation::Program)));
+        };
+        let mut progress_throttle = ProgressThrottle::new(options.progress_interval());
+
+        reset_state(device)?;
+
+        match &file.content {
+            dfufile::Content::Plain => {
+                device.select_default_alt_setting()?;
+                let data = plain_payload(&file.path)?;
+                let transfer_size = device.info.dfu_transfer_size as usize;
+                if transfer_size == 0 {
+                    return Err(anyhow!(Error::InvalidTransferSize));
+                }
+                let num_blocks = data.len().div_ceil(transfer_size);
+                log::debug!(
+                    "Programming {} bytes in {} blocks of {} bytes",
+                    data.len(),
+                    num_blocks,
+                    transfer_size
+                );
+
+                for (block_no, chunk) in data.chunks(transfer_size).enumerate() {
+                    check_cancelled(reporter)?;
+                    log::debug!("Programming block {} with {} bytes", block_no, chunk.len());
+
+                    // Block numbers start at 0 and wrap around after 65535.
+                    // Plain DFU can't resume, the block sequence restarts on reset.
+                    device
+                        .download_request(block_no as u16, chunk)
+                        .and_then(|_| device.wait_for_download_idle())
+                        .map_err(|error| {
+                            disconnected_at((block_no * transfer_size) as u32, error)
+                        })?;
+
+                    log::debug!("Block no {} written", block_no);
+
+                    if progress_throttle.ready() || block_no + 1 == num_blocks {
+                        let bytes = (block_no * transfer_size + chunk.len()) as u64;
+                        reporter
+                            .send(Event::Progress {
+                                step: DeviceUpdateStep::Program,
+                                bytes,
+                                total: data.len() as u64,
+                                address: Some((block_no * transfer_size) as u32),
+                            })
+                            .ok();
+                    }
+                }
+
+                // A zero-length download signals the end of the transfer
+                log::debug!("Starting manifestation");
+                device.download_request(num_blocks as u16, &[])?;
+                device.wait_for_manifestation()?;
+                log::debug!("Manifestation finished");
+
+                // Device may have reset itself, so no further requests are sent
+                device.close();
+
+                return Ok(());
+            }
+            dfufile::Content::DfuSe(content) => {
+                let images = selected_images(content, options);
+                let mut progress = ByteProgress::new(payload_size(&images, options.range));
+                let streamed = progress.total > cache::MAX_CACHED_SIZE;
+                let mut buffer_pool = cache::BufferPool::default();
+
+                for (image_no, image) in images {
+                    let alt_setting = options.targets.alt_setting(image_no, image);
+                    let target = device
+                        .info
+                        .alt_settings
+                        .iter()
+                        .find(|&alt| alt.0 == alt_setting);
+
+                    if let Some(target) = target {
+                        let memory_segment = checked_memory_segment(&target.1)?;
+                        if memory_segment.is_protected() {
+                            return Err(anyhow!(Error::ProtectedTarget(memory_segment.name)));
+                        }
+                        let transfer_size = memory_segment
+                            .regions
+                            .iter()
+                            .map(|region| region.sector_size)
+                            .min()
+                            .ok_or_else(|| {
+                                anyhow!(Error::InvalidGeometry(
+                                    target.1.clone(),
+                                    "No memory regions found.".into()
+                                ))
+                            })?;
+                        let transfer_size =
+                            std::cmp::min(transfer_size, device.info.dfu_transfer_size as u32);
+                        log::debug!(
+                            "Found target \"{}\" for alt setting {}. Transfer size is {} bytes",
+                            memory_segment.name,
+                            target.0,
+                            transfer_size
+                        );
+                        device.select_alt_setting(alt_setting)?;
+                        let unchanged_sectors = options.unchanged_sectors(alt_setting);
+                        let mut current_sector = None;
+
+                        for (element_no, element) in image.image_elements.iter().enumerate() {
+                            if element_range(element, options.range).is_none() {
+                                log::debug!(
+                                    "Element at address 0x{:08X} is outside the address range",
+                                    element.dwElementAddress
+                                );
+                                continue;
+                            }
+                            log::debug!(
+                                "Reading element at address 0x{:08X}, size {}",
+                                element.dwElementAddress,
+                                element.dwElementSize
+                            );
+                            let source = block_source(
+                                block_cache,
+                                &mut file.file,
+                                image_no,
+                                element_no,
+                                element,
+                                transfer_size,
+                                streamed,
+                            )?;
+
+                            let mut sequence = BlockSequence::new(transfer_size);
+                            let mut skipped_blocks = 0;
+
+                            source.for_each_block(
+                                &mut file.file,
+                                element,
+                                transfer_size,
+                                &mut buffer_pool,
+                                |_, address, data| {
+                                    // Stopping waits for the next sector, so the ones
+                                    // started are programmed completely
+                                    match reporter.cancel_mode() {
+                                        Some(CancelMode::Abort) => {
+                                            return Err(anyhow!(Error::Cancelled))
+                                        }
+                                        Some(CancelMode::Stop)
+                                            if memory_segment.is_sector_start(address) =>
+                                        {
+                                            return Err(anyhow!(Error::Stopped(ResumePoint {
+                                                step: DeviceUpdateStep::Program,
+                                                alt_setting: Some(alt_setting),
+                                                address: Some(address),
+                                            })))
+                                        }
+                                        _ => {}
+                                    }
+                                    let Some((address, data)) =
+                                        clip_block(options.range, address, data)
+                                    else {
+                                        return Ok(());
+                                    };
+                                    let size = data.len() as u64;
+
+                                    // The address pointer is set again at the next written block
+                                    let written = changed_part(&unchanged_sectors, address, data)
+                                        .filter(|(_, data)| {
+                                            !(options.skip_blank_blocks && is_blank(data))
+                                        });
+
+                                    if let Some((address, data)) = written {
+                                        let (pointer, block_no) = sequence.next(device, address)?;
+                                        log::debug!(
+                                        "Programming block {} with {} bytes at address 0x{:08X}",
+                                        block_no,
+                                        data.len(),
+                                        address
+                                    );
+
+                                        with_reconnect(device, address, Some(pointer), |device| {
+                                            device.download_request(block_no as u16 + 2, data)?;
+
+                                            // First status response must have state dfuDNBUSY
+                                            let status = device.getstatus_request()?;
+                                            if status.bState
+                                                != dfudev::states::DeviceStateCode::dfuDNBUSY
+                                            {
+                                                return Err(anyhow!(status.unexpected_state()));
+                                            }
+
+                                            device.wait_for_status_response(
+                                                status.bwPollTimeout as u64,
+                                            )?;
+
+                                            Ok(())
+                                        })?;
+
+                                        log::debug!("Block no {} written", block_no);
+                                    } else {
+                                        log::trace!("Skipping block at address 0x{address:08X}");
+                                        skipped_blocks += 1;
+                                    }
+
+                                    progress.add(size);
+                                    if progress_throttle.ready() || progress.is_complete() {
+                                        reporter
+                                            .send(
+                                                progress.event(
+                                                    DeviceUpdateStep::Program,
+                                                    Some(address),
+                                                ),
+                                            )
+                                            .ok();
+                                    }
+
+                                    // A sector is complete when the next one is started
+                                    let sector =
+                                        memory_segment.sector_at(address).map(|(start, size)| {
+                                            AddressRange {
+                                                start,
+                                                end: start.saturating_add(size),
+                                            }
+                                        });
+                                    if sector != current_sector {
+                                        if let Some(completed) =
+                                            std::mem::replace(&mut current_sector, sector)
+                                        {
+                                            reporter
+                                                .send(Event::SectorProgrammed(
+                                                    alt_setting,
+                                                    completed,
+                                                ))
+                                                .ok();
+                                        }
+                                    }
+
+                                    Ok(())
+                                },
+                            )?;
+
+                            if skipped_blocks > 0 {
+                                log::debug!("Skipped {skipped_blocks} blank or unchanged blocks");
+                            }
+                            send_statistics(block_cache, &buffer_pool, streamed, reporter);
+                        }
+
+                        if let Some(completed) = current_sector {
+                            reporter
+                                .send(Event::SectorProgrammed(alt_setting, completed))
+                                .ok();
+                        }
+                    } else {
+                        return Err(anyhow!(Error::TargetNotFound(alt_setting)));
+                    }
+                }
+            }
+        }
+
+        // Leave the device idle for the next step
+        device.abort_request()?;
+
+        Ok(())
+    }
+
+    /// Read back the sectors covered by the file and return the ones already
+    /// holding the data a full update would leave in them, by alt setting
+    ///
+    /// Erasing clears the bytes of a sector not covered by the file, so these
+    /// must be erased already for the sector to count as unchanged.
+    fn compare(&mut self, options: &Options) -> Result<Vec<(u8, AddressRange)>> {
+        let Self {
+            ref mut device,
+            ref mut file,
+            ref reporter,
+            ..
+        } = *self;
+        let mut progress_throttle = ProgressThrottle::new(options.progress_interval());
+
+        let Some(dfufile::DfuFile {
+            content: dfufile::Content::DfuSe(content),
+            file,
+            ..
+        }) = file
+        else {
+            return Ok(Vec::new());
+        };
+
+        reset_state(device)?;
+
+        // The plans check the memory maps of the targets
+        let mut plans = Vec::new();
+        for (image_no, image) in selected_images(content, options) {
+            let alt_setting = options.targets.alt_setting(image_no, image);
+            plans.push((
+                image,
+                erase_plan(image, alt_setting, &device.info, options.range)?,
+            ));
+        }
+
+        let transfer_size = device.info.dfu_transfer_size as u32;
+        if transfer_size == 0 {
+            return Err(anyhow!(Error::InvalidTransferSize));
+        }
+
+        let mut progress = ByteProgress::new(
+            plans
+                .iter()
+                .flat_map(|(_, plan)| &plan.sectors)
+                .map(|sector| sector.size as u64)
+                .sum(),
+        );
+        let mut unchanged_sectors = Vec::new();
+        let mut sector_count = 0;
+
+        for (image, plan) in &plans {
+            device.select_alt_setting(plan.alt_setting)?;
+
+            for sector in &plan.sectors {
+                let sector_range = AddressRange {
+                    start: sector.address,
+                    end: sector.address.saturating_add(sector.size),
+                };
+                let expected = sector_data(file, image, sector_range, options.range)?;
+                let mut data = vec![0; sector.size as usize];
+
+                read_range(device, sector.address, &mut data, transfer_size, |bytes| {
+                    progress.add(bytes as u64);
+                    if progress_throttle.ready() || progress.is_complete() {
+                        reporter
+                            .send(progress.event(DeviceUpdateStep::Compare, Some(sector.address)))
+                            .ok();
+                    }
+                    check_cancelled(reporter)
+                })?;
+
+                sector_count += 1;
+                if data == expected {
+                    log::debug!("Sector at 0x{:08X} is unchanged", sector.address);
+                    unchanged_sectors.push((plan.alt_setting, sector_range));
+                }
+            }
+        }
+
+        device.abort_request()?;
+
+        log::info!(
+            "{} of {sector_count} sectors are unchanged",
+            unchanged_sectors.len()
+        );
+
+        Ok(unchanged_sectors)
+    }
+
+    /// Verifies the data in the device.
+    fn verify(&mut self, options: &Options) -> Result<()> {
+        // Zeros read back from memory the job has written itself are an
+        // error of the download, not a sign of readout protection
+        let downloaded = self.plan.operations.contains(&Operation::Program);
+
+        let Self {
+            ref mut device,
+            file: Some(ref mut file),
+            ref block_cache,
+            ref reporter,
+            ..
+        } = *self
+        else {
+            return Err(anyhow!(Error::NoFile(Operation::Verify)));
+        };
+        let mut progress_throttle = ProgressThrottle::new(options.progress_interval());
+
+        if let dfufile::Content::Plain = &file.content {
+            // Plain DFU devices usually leave DFU mode after manifestation
+            log::warn!("Plain DFU does not support verification after manifestation. Skipped.");
+            return Ok(());
+        }
+
+        reset_state(device)?;
+
+        // Readout protection shows up on the first block read
+        let mut first_block = true;
+
+        // Reading continues after a mismatch to report all differences
+        let mut differences = Vec::new();
+
+        match &file.content {
+            dfufile::Content::Plain => {}
+            dfufile::Content::DfuSe(content) => {
+                let images = selected_images(content, options);
+                let mut progress = ByteProgress::new(payload_size(&images, options.range));
+                let streamed = progress.total > cache::MAX_CACHED_SIZE;
+                let mut buffer_pool = cache::BufferPool::default();
+
+                for (image_no, image) in images {
+                    let alt_setting = options.targets.alt_setting(image_no, image);
+                    let target = device
+                        .info
+                        .alt_settings
+                        .iter()
+                        .find(|&alt| alt.0 == alt_setting);
+
+                    if let Some(target) = target {
+                        let memory_segment = checked_memory_segment(&target.1)?;
+                        // Uploads don't depend on the erase geometry, so the blocks
+                        // can be as large as the device or the transfer settings allow
+                        let transfer_size = device.info.dfu_transfer_size as u32;
+                        if transfer_size == 0 {
+                            return Err(anyhow!(Error::InvalidTransferSize));
+                        }
+                        log::debug!(
+                            "Found target \"{}\" for alt setting {}. Transfer size is {} bytes",
+                            memory_segment.name,
+                            target.0,
+                            transfer_size
+                        );
+                        device.select_alt_setting(alt_setting)?;
+
+                        for (element_no, element) in image.image_elements.iter().enumerate() {
+                            if element_range(element, options.range).is_none() {
+                                log::debug!(
+                                    "Element at address 0x{:08X} is outside the address range",
+                                    element.dwElementAddress
+                                );
+                                continue;
+                            }
+                            log::debug!(
+                                "Reading element at address 0x{:08X}, size {}",
+                                element.dwElementAddress,
+                                element.dwElementSize
+                            );
+                            let source = block_source(
+                                block_cache,
+                                &mut file.file,
+                                image_no,
+                                element_no,
+                                element,
+                                transfer_size,
+                                streamed,
+                            )?;
+
+                            let mut sequence = BlockSequence::new(transfer_size);
+                            let mut device_data = Vec::new();
+
+                            source.for_each_block(
+                                &mut file.file,
+                                element,
+                                transfer_size,
+                                &mut buffer_pool,
+                                |_, address, data| {
+                                    check_cancelled(reporter)?;
+                                    let Some((address, data)) =
+                                        clip_block(options.range, address, data)
+                                    else {
+                                        return Ok(());
+                                    };
+                                    let (pointer, block_no) = sequence.next(device, address)?;
+                                    device_data.clear();
+                                    device_data.resize(data.len(), 0);
+                                    let result =
+                                        with_reconnect(device, address, Some(pointer), |device| {
+                                            device.upload_request(
+                                                block_no as u16 + 2,
+                                                &mut device_data,
+                                            )?;
+                                            Ok(())
+                                        });
+
+                                    if let Err(error) = result {
+                                        if first_block
+                                            && dfudev::dfuse::is_read_protected(device, &error)
+                                        {
+                                            return Err(anyhow!(Error::ReadProtected));
+                                        }
+                                        return Err(error);
+                                    }
+
+                                    if device_data != data {
+                                        if first_block
+                                            && !downloaded
+                                            && dfudev::dfuse::is_protected_readback(
+                                                &device_data,
+                                                data,
+                                            )
+                                        {
+                                            return Err(anyhow!(Error::ReadProtected));
+                                        }
+                                        add_differences(
+                                            &mut differences,
+                                            &memory_segment,
+                                            alt_setting,
+                                            address,
+                                            data,
+                                            &device_data,
+                                        );
+                                    }
+                                    first_block = false;
+
+                                    progress.add(data.len() as u64);
+                                    if progress_throttle.ready() || progress.is_complete() {
+                                        reporter
+                                            .send(
+                                                progress
+                                                    .event(DeviceUpdateStep::Verify, Some(address)),
+                                            )
+                                            .ok();
+                                    }
+
+                                    Ok(())
+                                },
+                            )?;
+
+                            send_statistics(block_cache, &buffer_pool, streamed, reporter);
+                        }
+                    } else {
+                        return Err(anyhow!(Error::TargetNotFound(alt_setting)));
+                    }
+                }
+            }
+        }
+
+        // Leave the device idle for the next step
+        device.abort_request()?;
+
+        if let Some(first) = differences.first() {
+            let bytes: u64 = differences.iter().map(|diff| diff.bytes as u64).sum();
+            log::error!(
+                "{bytes} bytes in {} sectors differ from the file",
+                differences.len()
+            );
+            for diff in &differences {
+                log::debug!("{diff}");
+            }
+            let address = first.first_address;
+            reporter.send(Event::Differences(differences)).ok();
+            return Err(anyhow!(Error::VerificationFailed(address)));
+        }
+
+        Ok(())
+    }
+
+    /// Perform the action selected to be taken after the update
+    ///
+    /// Leaving DFU mode is a separate operation, see [`Phases::operations`].
+    fn finish_update(&mut self) -> Result<()> {
+        if self.options.after_update != AfterUpdate::UsbReset {
+            return Ok(());
+        }
+
+        let Some(device) = self.remaining_device()? else {
+            log::debug!("Device already left DFU mode");
+            return Ok(());
+        };
+
+        log::info!("Resetting device");
+        device.usb_reset()?;
+        device.close();
+
+        Ok(())
+    }
+
+    /// Leave DFU mode and start the application at the start address of the file
+    ///
+    /// Plain DFU devices have no leave request and are reset instead.
+    fn leave(&mut self) -> Result<()> {
+        // Start address of DfuSe files, plain DFU files have none
+        let start_address = match self.file.as_ref().map(|file| &file.content) {
+            None => return Err(anyhow!(Error::NoFile(Operation::Leave))),
+            Some(dfufile::Content::DfuSe(content)) => Some(
+                content
+                    .images
+                    .first()
+                    .and_then(|image| image.image_elements.first())
+                    .map(|element| element.dwElementAddress)
+                    .ok_or(anyhow!(Error::NoStartAddress))?,
+            ),
+            Some(dfufile::Content::Plain) => None,
+        };
+
+        let Some(device) = self.remaining_device()? else {
+            log::debug!("Device already left DFU mode");
+            return Ok(());
+        };
+
+        match start_address {
+            Some(address) => {
+                log::info!("Leaving DFU mode, starting application at 0x{address:08X}");
+                device.abort_request()?;
+                dfudev::dfuse::leave(device, address)?;
+            }
+            None => {
+                log::info!("Resetting device");
+                device.usb_reset()?;
+            }
+        }
+
+        device.close();
+
+        Ok(())
+    }
+
+    /// Return the device for a request after programming, `None` if it left
+    /// DFU mode
+    ///
+    /// Plain DFU devices may leave DFU mode on their own after manifestation,
+    /// so their handle is closed by [`Self::program`] and the device is
+    /// searched again.
+    fn remaining_device(&mut self) -> Result<Option<&mut dfudev::DfuDevice>> {
+        if !self.device.is_open() {
+            let Some(mut device) = dfudev::DfuDevice::find_by_id(self.device.id)? else {
+                return Ok(None);
+            };
+            device.open()?;
+            self.device = device;
+        }
+
+        Ok(Some(&mut self.device))
+    }
+
+    /// Calculate the CRC-32 of the device memory covered by the file
+    /// and send it to the UI
+    fn checksum(&mut self, options: &Options) -> Result<()> {
+        let Self {
+            ref mut device,
+            file: Some(ref file),
+            ref reporter,
+            ..
+        } = *self
+        else {
+            return Err(anyhow!(Error::NoFile(Operation::Checksum)));
+        };
+        let mut progress_throttle = ProgressThrottle::new(options.progress_interval());
+
+        let dfufile::Content::DfuSe(content) = &file.content else {
+            return Err(anyhow!(Error::PlainUploadNotSupported));
+        };
+
+        reset_state(device)?;
+
+        if device.info.dfu_version != builder::DFU_VERSION_DFUSE {
+            return Err(anyhow!(Error::PlainUploadNotSupported));
+        }
+
+        let transfer_size = device.info.dfu_transfer_size as u32;
+        if transfer_size == 0 {
+            return Err(anyhow!(Error::InvalidTransferSize));
+        }
+
+        let images = selected_images(content, options);
+        let total_size = payload_size(&images, options.range);
+        let mut bytes_done: u64 = 0;
+        let mut hasher = crc32fast::Hasher::new();
+
+        for (image_no, image) in images {
+            device.select_alt_setting(options.targets.alt_setting(image_no, image))?;
+
+            for element in &image.image_elements {
+                let Some((address, size)) = element_range(element, options.range) else {
+                    continue;
+                };
+                let mut data = vec![0; size as usize];
+
+                read_range(device, address, &mut data, transfer_size, |bytes| {
+                    bytes_done += bytes as u64;
+                    if progress_throttle.ready() || bytes_done == total_size {
+                        reporter
+                            .send(Event::progress(
+                                DeviceUpdateStep::Checksum,
+                                bytes_done,
+                                total_size,
+                            ))
+                            .ok();
+                    }
+                    check_cancelled(reporter)
+                })?;
+
+                hasher.update(&data);
+            }
+        }
+
+        device.abort_request()?;
+
+        let checksum = hasher.finalize();
+        log::info!("CRC-32 of device memory is 0x{checksum:08X}");
+        reporter.send(Event::Checksum(checksum)).ok();
+
+        Ok(())
+    }
+
+    /// Read the memory of the device and write it to a file.
+    ///
+    /// All readable regions of the first memory segment are read. Depending on the
+    /// file extension, a DfuSe file with one element per contiguous range or a
+    /// binary file with gaps filled by 0xFF is written.
+    ///
+    /// Plain DFU devices are read until they end the upload, see [`backup_plain_device`].
+    fn backup(&mut self, file_path: &std::path::Path, options: &Options) -> Result<()> {
+        let Self {
+            ref mut device,
+            ref reporter,
+            ..
+        } = *self;
+        let mut progress_throttle = ProgressThrottle::new(options.progress_interval());
+
+        reset_state(device)?;
+
+        if device.info.dfu_version != builder::DFU_VERSION_DFUSE {
+            return backup_plain_device(device, file_path, reporter);
+        }
+
+        let target = device
+            .info
+            .alt_settings
+            .first()
+            .ok_or(anyhow!(Error::TargetNotFound(0)))?;
+        let memory_segment = checked_memory_segment(&target.1)?;
+        let alt_setting = target.0;
+        device.select_alt_setting(alt_setting)?;
+
+        let ranges = readable_ranges(&memory_segment);
+
+        if ranges.is_empty() {
+            return Err(anyhow!(Error::NoReadableRegion(alt_setting)));
+        }
+
+        let transfer_size = device.info.dfu_transfer_size as u32;
+        if transfer_size == 0 {
+            return Err(anyhow!(Error::InvalidTransferSize));
+        }
+
+        let total_size: u64 = ranges
+            .iter()
+            .map(|(start, end)| (end - start) as u64 + 1)
+            .sum();
+        let mut bytes_done: u64 = 0;
+        let mut elements = Vec::new();
+
+        log::debug!(
+            "Reading {} bytes from target \"{}\"",
+            total_size,
+            memory_segment.name
+        );
+
+        for (start_address, end_address) in ranges {
+            let size = end_address - start_address + 1;
+            let mut data = vec![0; size as usize];
+
+            read_range(device, start_address, &mut data, transfer_size, |bytes| {
+                bytes_done += bytes as u64;
+                if progress_throttle.ready() || bytes_done == total_size {
+                    reporter
+                        .send(Event::progress(
+                            DeviceUpdateStep::Backup,
+                            bytes_done,
+                            total_size,
+                        ))
+                        .ok();
+                }
+                check_cancelled(reporter)
+            })?;
+
+            elements.push(builder::Element {
+                address: start_address,
+                data,
+            });
+        }
+
+        // Leave the device idle for the next step
+        device.abort_request()?;
+
+        let is_dfu_file = file_path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("dfu"));
+
+        let file_data = if is_dfu_file {
+            let image = builder::Image {
+                alt_setting,
+                name: Some(memory_segment.name.clone()),
+                elements,
+            };
+            let suffix = builder::Suffix {
+                device_version: device.info.bcd_device,
+                product_id: device.info.product_id,
+                vendor_id: device.info.vendor_id,
+                dfu_version: builder::DFU_VERSION_DFUSE,
+            };
+            builder::dfuse_file(&[image], &suffix)
+        } else {
+            let base_address = elements[0].address;
+            let mut data = Vec::new();
+            for element in elements {
+                data.resize((element.address - base_address) as usize, 0xFF);
+                data.extend_from_slice(&element.data);
+            }
+            data
+        };
+
+        std::fs::write(file_path, file_data)?;
+        log::info!("Backup written to {:?}", file_path);
+
+        reporter.send(Event::BackupFinished(file_path.to_path_buf()))?;
+
+        Ok(())
+    }
+}
+
+/// Return the content of a sector after a full update, i.e. the data of the
+/// image inside the address range with all other bytes erased
+fn sector_data(
+    file: &mut std::fs::File,
+    image: &dfufile::dfuse::Image,
+    sector: AddressRange,
+    range: Option<AddressRange>,
+) -> Result<Vec<u8>> {
+    let mut data = vec![0xFF; (sector.end - sector.start) as usize];
+
+    for element in &image.image_elements {
+        let Some((address, size)) = element_range(element, range) else {
+            continue;
+        };
+        let Some((start, size)) = sector.clip(address, size) else {
+            continue;
+        };
+        let offset = (start - sector.start) as usize;
+        element.read_at(
+            file,
+            start - element.dwElementAddress,
+            &mut data[offset..offset + size as usize],
+        )?;
+    }
+
+    Ok(data)
+}
+
+/// Read the firmware of a plain DFU device and write it to a file.
+///
+/// The length of the firmware is unknown in advance. The device signals the
+/// end of the upload with a block shorter than the transfer size, which may
+/// also be empty. Depending on the file extension, a plain DFU file with suffix
+/// or a binary file is written.
+fn backup_plain_device(
+    device: &mut dfudev::DfuDevice,
+    file_path: &std::path::Path,
+    reporter: &progress::Reporter,
+) -> Result<()> {
+    device.select_default_alt_setting()?;
+    reset_state(device)?;
+
+    let transfer_size = device.info.dfu_transfer_size as usize;
+    if transfer_size == 0 {
+        return Err(anyhow!(Error::InvalidTransferSize));
+    }
+
+    let mut data = Vec::new();
+    let mut block = vec![0; transfer_size];
+
+    for block_no in 0.. {
+        check_cancelled(reporter)?;
+        if data.len() > MAX_PLAIN_UPLOAD_SIZE {
+            device.abort_request()?;
+            return Err(anyhow!(Error::UploadTooLarge));
+        }
+
+        // Block numbers wrap around after 65535
+        let length = device.upload_request(block_no as u16, &mut block)?;
+        log::debug!("Block {} read with {} bytes", block_no, length);
+        data.extend_from_slice(&block[..length]);
+
+        if length < transfer_size {
+            break;
+        }
+    }
+
+    reporter
+        .send(Event::progress(
+            DeviceUpdateStep::Backup,
+            data.len() as u64,
+            data.len() as u64,
+        ))
+        .ok();
+    log::debug!("Upload finished after {} bytes", data.len());
+
+    let is_dfu_file = file_path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("dfu"));
+
+    let file_data = if is_dfu_file {
+        let suffix = builder::Suffix {
+            device_version: device.info.bcd_device,
+            product_id: device.info.product_id,
+            vendor_id: device.info.vendor_id,
+            dfu_version: builder::DFU_VERSION_PLAIN,
+        };
+        builder::plain_file(&data, &suffix)
+    } else {
+        data
+    };
+
+    std::fs::write(file_path, file_data)?;
+    log::info!("Backup written to {:?}", file_path);
+
+    reporter.send(Event::BackupFinished(file_path.to_path_buf()))?;
+
+    Ok(())
+}
+
+/// Remove the readout protection of a DfuSe device and wait for it to return
+///
+/// This erases the whole flash memory of the device.
+pub fn unprotect_device(device_id: u64) -> Result<()> {
+    let mut device =
+        dfudev::DfuDevice::find_by_id(device_id)?.ok_or(anyhow!(Error::DeviceNotFound))?;
+    device.open()?;
+
+    if device.info.dfu_version != builder::DFU_VERSION_DFUSE {
+        return Err(anyhow!(Error::UnprotectNotSupported));
+    }
+
+    log::warn!("Removing readout protection, the flash memory is erased");
+    device.select_alt_setting(0)?;
+    dfudev::dfuse::read_unprotect(&device)?;
+    device.close();
+
+    // The option bytes are reloaded by a reset after the mass erase
+    device.reconnect(UNPROTECT_TIMEOUT)?;
+    device.close();
+    log::info!("Device returned after removing the readout protection");
+
+    Ok(())
+}
+
+/// Read all readable regions of a memory segment and return the start address
+/// and the data with gaps filled by 0xFF
+///
+/// Intended for small areas like system memory or OTP, so no progress is reported.
+pub fn read_segment(device_id: u64, alt_setting: u8) -> Result<(u32, Vec<u8>)> {
+    let (mut device, memory_segment, transfer_size) = open_target(device_id, alt_setting)?;
+    let ranges = readable_ranges(&memory_segment);
+
+    let base_address = ranges
+        .first()
+        .ok_or(anyhow!(Error::NoReadableRegion(alt_setting)))?
+        .0;
+    let mut data = Vec::new();
+
+    for (start_address, end_address) in ranges {
+        let offset = (start_address - base_address) as usize;
+        data.resize(offset + (end_address - start_address) as usize + 1, 0xFF);
+        read_range(
+            &device,
+            start_address,
+            &mut data[offset..],
+            transfer_size,
+            |_| Ok(()),
+        )?;
+    }
+
+    device.abort_request()?;
+    device.close();
+
+    log::info!(
+        "Read {} bytes from target \"{}\"",
+        data.len(),
+        memory_segment.name
+    );
+
+    Ok((base_address, data))
+}
+
+/// Read `length` bytes starting at an address of a target, e.g. to inspect
+/// option areas or check single values
+///
+/// The range must lie inside the readable regions of the target. No progress
+/// is reported, so the size is limited.
+pub fn read_memory(device_id: u64, alt_setting: u8, address: u32, length: u32) -> Result<Vec<u8>> {
+    if length > MAX_MEMORY_READ_SIZE {
+        return Err(anyhow!(Error::ReadTooLarge(length)));
+    }
+
+    let (mut device, memory_segment, transfer_size) = open_target(device_id, alt_setting)?;
+    let last_address = address as u64 + (length as u64).saturating_sub(1);
+    if length == 0
+        || !readable_ranges(&memory_segment)
+            .iter()
+            .any(|&(start, end)| start <= address && last_address <= end as u64)
+    {
+        return Err(anyhow!(Error::MemoryRegionNotFound(
+            address,
+            last_address as u32
+        )));
+    }
+
+    let mut data = vec![0; length as usize];
+    read_range(&device, address, &mut data, transfer_size, |_| Ok(()))?;
+
+    device.abort_request()?;
+    device.close();
+
+    log::info!(
+        "Read {length} bytes at address 0x{address:08X} from target \"{}\"",
+        memory_segment.name
+    );
+
+    Ok(data)
+}
+
+/// Open a DfuSe device with an alt setting selected for reading and return
+/// it with the memory layout and the transfer size of the target
+fn open_target(
+    device_id: u64,
+    alt_setting: u8,
+) -> Result<(dfudev::DfuDevice, dfudev::dfuse::MemorySegment, u32)> {
+    let mut device =
+        dfudev::DfuDevice::find_by_id(device_id)?.ok_or(anyhow!(Error::DeviceNotFound))?;
+    device.open()?;
+
+    if device.info.dfu_version != builder::DFU_VERSION_DFUSE {
+        return Err(anyhow!(Error::PlainUploadNotSupported));
+    }
+
+    device.select_alt_setting(alt_setting)?;
+
+    reset_state(&device)?;
+
+    let target = device
+        .info
+        .alt_settings
+        .iter()
+        .find(|alt| alt.0 == alt_setting)
+        .ok_or(anyhow!(Error::TargetNotFound(alt_setting)))?;
+    let memory_segment = checked_memory_segment(&target.1)?;
+
+    let transfer_size = device.info.dfu_transfer_size as u32;
+    if transfer_size == 0 {
+        return Err(anyhow!(Error::InvalidTransferSize));
+    }
+
+    Ok((device, memory_segment, transfer_size))
+}
+
+/// Parse the memory map of a target and check that its geometry is usable
+///
+/// Block sizes and sector numbers are derived from the regions, so a map
+/// without regions or with empty sectors is rejected before any transfer.
+fn checked_memory_segment(descriptor: &str) -> Result<dfudev::dfuse::MemorySegment> {
+    let memory_segment = dfudev::dfuse::MemorySegment::from_string_desc(descriptor)?;
+
+    let geometry_errors = memory_segment.geometry_errors();
+    if !geometry_errors.is_empty() {
+        return Err(anyhow!(Error::InvalidGeometry(
+            descriptor.to_string(),
+            geometry_errors.join(" ")
+        )));
+    }
+
+    Ok(memory_segment)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Make sure the device is in idle state and its status is OK
+fn reset_state(device: &dfudev::DfuDevice) -> Result<()> {
+    device.abort_request()?;
+
+    while let Ok(status) = device.getstatus_request() {
+        if status.bStatus == dfudev::DeviceStatusCode::OK {
+            break;
+        } else {
+            log::warn!(
+                "Clearing device status {:?}: {}",
+                status.bStatus,
+                status.bStatus
+            );
+            device.clrstatus_request()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Run a transfer at an address and retry it once if the device was
+/// disconnected and re-enumerated in time
+///
+/// After reconnecting, the address pointer is set to `pointer` if given, so
+/// the block numbers of the interrupted element remain valid.
+fn with_reconnect(
+    device: &mut dfudev::DfuDevice,
+    address: u32,
+    pointer: Option<u32>,
+    mut transfer: impl FnMut(&dfudev::DfuDevice) -> Result<()>,
+) -> Result<()> {
+    match transfer(device) {
+        Err(error) if dfudev::is_disconnected(&error) => {
+            log::warn!("Device disconnected at address 0x{address:08X}, waiting for it to return");
+            device
+                .reconnect(RECONNECT_TIMEOUT)
+                .map_err(|_| anyhow!(Error::DeviceDisconnected(address)))?;
+            log::info!("Device reconnected, resuming at address 0x{address:08X}");
+
+            device.abort_request()?;
+            if let Some(pointer) = pointer {
+                dfudev::dfuse::set_address(device, pointer)?;
+            }
+
+            transfer(device).map_err(|error| disconnected_at(address, error))
+        }
+        result => result,
+    }
+}
+
+/// Replace an error caused by stalled requests with [`dfudev::Error::Unresponsive`]
+/// if the device doesn't answer any request after reopening it
+fn classify_error(device: &mut dfudev::DfuDevice, error: anyhow::Error) -> anyhow::Error {
+    if !dfudev::is_stalled(&error) {
+        return error;
+    }
+
+    log::warn!("Request failed ({error}), checking if the device responds");
+
+    match device.probe() {
+        Ok(_) => error,
+        Err(probe_error) => probe_error,
+    }
+}
+
+/// Return an error if the job was cancelled, checked between transfers, so
+/// the device is never left in the middle of a request
+fn check_cancelled(reporter: &progress::Reporter) -> Result<()> {
+    if reporter.is_cancelled() {
+        Err(anyhow!(Error::Cancelled))
+    } else {
+        Ok(())
+    }
+}
+
+/// Return if an operation stopped because the job was cancelled
+pub fn is_cancelled(error: &anyhow::Error) -> bool {
+    matches!(
+        error.downcast_ref::<Error>(),
+        Some(Error::Cancelled | Error::Stopped(_))
+    )
+}
+
+/// Report a cancelled operation with the point to resume from and return
+/// the device to idle state
+///
+/// Operations stopped between their blocks report where they stopped.
+/// Otherwise a stop resumes with the start of the operation, while an
+/// abort leaves no resume point.
+fn stop_job(
+    device: &dfudev::DfuDevice,
+    operation: &Operation,
+    target: Option<u8>,
+    reporter: &progress::Reporter,
+    error: anyhow::Error,
+) -> anyhow::Error {
+    let mode = reporter.cancel_mode().unwrap_or(CancelMode::Abort);
+    let resume_point = match error.downcast_ref::<Error>() {
+        Some(Error::Stopped(resume_point)) => Some(*resume_point),
+        _ if mode == CancelMode::Stop => Some(ResumePoint {
+            step: operation.step(),
+            alt_setting: target,
+            address: None,
+        }),
+        _ => None,
+    };
+
+    match &resume_point {
+        Some(resume_point) => log::info!("Stopped, resume point is {resume_point}"),
+        None => log::info!("{:?} aborted", operation.step()),
+    }
+    reporter.send(Event::Cancelled { mode, resume_point }).ok();
+
+    log::info!("Returning device to idle");
+    idle_device(device);
+
+    error
+}
+
+/// Return a device to idle state after a cancelled operation
+///
+/// Failures are only logged, the device may already be gone.
+fn idle_device(device: &dfudev::DfuDevice) {
+    if !device.is_open() {
+        return;
+    }
+
+    if let Err(error) = device.abort_request() {
+        log::warn!("Device not returned to idle state: {error}");
+    }
+}
+
+/// Return if an operation failed because readout protection is active
+fn is_read_protected(error: &anyhow::Error) -> bool {
+    matches!(error.downcast_ref::<Error>(), Some(Error::ReadProtected))
+}
+
+/// Replace an error caused by a disconnect with one containing the address
+fn disconnected_at(address: u32, error: anyhow::Error) -> anyhow::Error {
+    if dfudev::is_disconnected(&error) {
+        anyhow!(Error::DeviceDisconnected(address))
+    } else {
+        error
+    }
+}
+
+/// Return the readable regions of a segment merged into contiguous ranges
+/// of first and last address
+fn readable_ranges(memory_segment: &dfudev::dfuse::MemorySegment) -> Vec<(u32, u32)> {
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+
+    for region in memory_segment.regions.iter().filter(|r| r.readable) {
+        match ranges.last_mut() {
+            Some(range) if range.1 + 1 == region.start_address => range.1 = region.end_address,
+            _ => ranges.push((region.start_address, region.end_address)),
+        }
+    }
+
+    ranges
+}
+
+/// Read memory starting at an address into a buffer, calling `on_block`
+/// with the number of bytes after each block, an error stops reading
+fn read_range(
+    device: &dfudev::DfuDevice,
+    start_address: u32,
+    data: &mut [u8],
+    transfer_size: u32,
+    mut on_block: impl FnMut(usize) -> Result<()>,
+) -> Result<()> {
+    let mut sequence = BlockSequence::new(transfer_size);
+
+    for (index, chunk) in data.chunks_mut(transfer_size as usize).enumerate() {
+        let address = start_address + index as u32 * transfer_size;
+        let (_, block_no) = sequence.next(device, address)?;
+        log::debug!(
+            "Reading block {} with {} bytes at address 0x{:08X}",
+            block_no,
+            chunk.len(),
+            address
+        );
+
+        // Block numbers 0 and 1 are reserved for DfuSe commands
+        device.upload_request(block_no as u16 + 2, chunk)?;
+        on_block(chunk.len())?;
+    }
+
+    Ok(())
+}
+
+/// Add the bytes of a block differing between the file and the device to
+/// the differences by sector, which are collected in address order
+fn add_differences(
+    differences: &mut Vec<Difference>,
+    memory_segment: &dfudev::dfuse::MemorySegment,
+    alt_setting: u8,
+    address: u32,
+    expected: &[u8],
+    actual: &[u8],
+) {
+    let mismatches = expected
+        .iter()
+        .zip(actual)
+        .enumerate()
+        .filter(|(_, (expected, actual))| expected != actual)
+        .map(|(offset, _)| address + offset as u32);
+
+    for mismatch in mismatches {
+        // Blocks outside the memory layout are reported on their own
+        let sector = memory_segment.sector_at(mismatch).map_or(
+            AddressRange {
+                start: address,
+                end: address.saturating_add(expected.len() as u32),
+            },
+            |(start, size)| AddressRange {
+                start,
+                end: start.saturating_add(size),
+            },
+        );
+
+        match differences.last_mut() {
+            Some(diff) if diff.alt_setting == alt_setting && diff.sector == sector => {
+                diff.last_address = mismatch;
+                diff.bytes += 1;
+            }
+            _ => differences.push(Difference {
+                alt_setting,
+                sector,
+                first_address: mismatch,
+                last_address: mismatch,
+                bytes: 1,
+            }),
+        }
+    }
+}
+
+/// Return the firmware data of a plain DFU file without the suffix
+fn plain_payload(file_path: &std::path::Path) -> Result<Vec<u8>> {
+    let mut data = std::fs::read(file_path)?;
+
+    // Suffix length is stored in the 5th byte from the end
+    let suffix_length = data
+        .len()
+        .checked_sub(5)
+        .map_or(0, |index| data[index] as usize);
+    data.truncate(data.len().saturating_sub(suffix_length));
+
+    Ok(data)
+}
+
+/// Return the sectors to erase for the elements of an image
+///
+/// Sectors are listed in the order they are erased. A sector shared by two
+/// consecutive elements is erased only once.
+///
+/// Only the parts of the elements inside the address range are covered. A
+/// sector reaching outside the range is an error, as it would erase data
+/// that must be left untouched.
+fn erase_plan(
+    image: &dfufile::dfuse::Image,
+    alt_setting: u8,
+    info: &dfudev::DeviceInfo,
+    range: Option<AddressRange>,
+) -> Result<ErasePlan> {
+    let target = info
+        .alt_settings
+        .iter()
+        .find(|&alt| alt.0 == alt_setting)
+        .ok_or(anyhow!(Error::TargetNotFound(alt_setting)))?;
+
+    let memory_segment = checked_memory_segment(&target.1)?;
+    if memory_segment.is_protected() {
+        return Err(anyhow!(Error::ProtectedTarget(memory_segment.name)));
+    }
+    log::debug!(
+        "Found target \"{}\" for alt setting {}",
+        memory_segment.name,
+        target.0,
+    );
+
+    let mut sectors: Vec<EraseSector> = Vec::new();
+
+    for element in &image.image_elements {
+        log::debug!(
+            "Reading element at address 0x{:08X}, size {}",
+            element.dwElementAddress,
+            element.dwElementSize
+        );
+        let Some((start_address, size)) = element_range(element, range) else {
+            continue;
+        };
+        let end_address = start_address.saturating_add(size);
+        let region = memory_segment
+            .erase_region(start_address, size)
+            .ok_or(anyhow!(Error::MemoryRegionNotFound(
+                start_address,
+                end_address
+            )))?;
+
+        let sector_size = region.sector_size;
+        log::debug!("Memory region found, sector size is {}", sector_size);
+        let mut address = start_address / sector_size * sector_size;
+
+        while address < end_address {
+            if range.is_some_and(|range| !range.contains(address, sector_size)) {
+                return Err(anyhow!(Error::SectorOutsideRange(address, sector_size)));
+            }
+
+            // Only the bytes of the element within the sector count
+            let payload_bytes = address
+                .saturating_add(sector_size)
+                .min(end_address)
+                .saturating_sub(address.max(start_address)) as u64;
+
+            match sectors.last_mut() {
+                Some(last) if last.address == address => last.payload_bytes += payload_bytes,
+                _ => sectors.push(EraseSector {
+                    address,
+                    size: sector_size,
+                    payload_bytes,
+                }),
+            }
+
+            // Sectors at the end of the address space have no successor
+            match address.checked_add(sector_size) {
+                Some(next_address) => address = next_address,
+                None => break,
+            }
+        }
+    }
+
+    Ok(ErasePlan {
+        alt_setting,
+        name: memory_segment.name,
+        sectors,
+    })
+}
+
+/// Return the images of a DfuSe file in the order they are processed,
+/// together with their index in the file
+///
+/// Images of alt settings listed in `target_order` come first in that order,
+/// all others follow in file order.
+pub fn ordered_images<'a>(
+    content: &'a dfufile::dfuse::Content,
+    target_order: &[u8],
+) -> Vec<(usize, &'a dfufile::dfuse::Image)> {
+    let mut images: Vec<_> = content.images.iter().enumerate().collect();
+    images.sort_by_key(|(_, image)| {
+        target_order
+            .iter()
+            .position(|&alt| alt == image.target_prefix.bAlternateSetting)
+            .unwrap_or(target_order.len())
+    });
+
+    images
+}
+
+/// Return the ordered images processed in the current pass,
+/// leaving out skipped images
+pub fn selected_images<'a>(
+    content: &'a dfufile::dfuse::Content,
+    options: &Options,
+) -> Vec<(usize, &'a dfufile::dfuse::Image)> {
+    ordered_images(content, &options.targets.order)
+        .into_iter()
+        .filter(|&(index, image)| {
+            options.targets.is_selected(index)
+                && options
+                    .target
+                    .map_or(true, |alt| alt == options.targets.alt_setting(index, image))
+        })
+        .collect()
+}
+
+/// Return the total number of payload bytes of images inside the address range
+fn payload_size(images: &[(usize, &dfufile::dfuse::Image)], range: Option<AddressRange>) -> u64 {
+    images
+        .iter()
+        .flat_map(|(_, image)| &image.image_elements)
+        .filter_map(|element| element_range(element, range))
+        .map(|(_, size)| size as u64)
+        .sum()
+}
+
+/// Return address and size of the part of an element inside the address range
+pub fn element_range(
+    element: &dfufile::dfuse::ImageElement,
+    range: Option<AddressRange>,
+) -> Option<(u32, u32)> {
+    match range {
+        Some(range) => range.clip(element.dwElementAddress, element.dwElementSize),
+        None => Some((element.dwElementAddress, element.dwElementSize)),
+    }
+}
+
+/// Return the part of a block inside the address range with its address
+fn clip_block(range: Option<AddressRange>, address: u32, data: &[u8]) -> Option<(u32, &[u8])> {
+    let Some(range) = range else {
+        return Some((address, data));
+    };
+    let (start, size) = range.clip(address, data.len() as u32)?;
+    let offset = (start - address) as usize;
+
+    Some((start, &data[offset..offset + size as usize]))
+}
+
+/// Numbering of consecutive DfuSe blocks relative to the address pointer
+///
+/// The device calculates the address of a block from the address pointer and
+/// the block number. A block not following the previous one, e.g. because
+/// blocks outside the address range were left out, moves the pointer to it.
+/// The pointer is also moved before the 16-bit block number runs out, as
+/// numbers 0 and 1 are DfuSe commands.
+struct BlockSequence {
+    /// Size of all but the last block of an element
+    block_size: u32,
+
+    /// Address pointer and number of blocks sent since it was set,
+    /// `None` before the first block
+    position: Option<(u32, usize)>,
+}
+
+impl BlockSequence {
+    /// Create a sequence for blocks of a given size
+    fn new(block_size: u32) -> Self {
+        Self {
+            block_size,
+            position: None,
+        }
+    }
+
+    /// Return the address pointer and the number of the block at an address,
+    /// setting the pointer first if required
+    fn next(&mut self, device: &dfudev::DfuDevice, address: u32) -> Result<(u32, usize)> {
+        let position = match self.position {
+            Some((pointer, count))
+                if count < MAX_BLOCK_COUNT
+                    && pointer as u64 + count as u64 * self.block_size as u64 == address as u64 =>
+            {
+                (pointer, count)
+            }
+            _ => {
+                dfudev::dfuse::set_address(device, address)?;
+                (address, 0)
+            }
+        };
+        self.position = Some((position.0, position.1 + 1));
+
+        Ok(position)
+    }
+}
+
+/// Return the part of a block outside the unchanged sectors with its address,
+/// `None` if there is none
+///
+/// Blocks are not larger than the smallest sector, so only their start or end
+/// can lie in an unchanged sector.
+fn changed_part<'a>(
+    unchanged_sectors: &[AddressRange],
+    address: u32,
+    data: &'a [u8],
+) -> Option<(u32, &'a [u8])> {
+    let mut start = address;
+    let mut end = address + data.len() as u32;
+
+    for sector in unchanged_sectors {
+        if sector.start <= start && start < sector.end {
+            start = sector.end.min(end);
+        }
+        if sector.start < end && end <= sector.end {
+            end = sector.start.max(start);
+        }
+    }
+
+    (start < end).then(|| {
+        (
+            start,
+            &data[(start - address) as usize..(end - address) as usize],
+        )
+    })
+}
+
+/// Return if a block contains only the erased state of flash memory
+fn is_blank(data: &[u8]) -> bool {
+    data.iter().all(|&byte| byte == 0xFF)
+}
+
+/// Return the block source for an element, either from the cache or streamed
+/// from the file if the payload is too large for caching
+fn block_source(
+    block_cache: &Mutex<cache::BlockCache>,
+    file: &mut std::fs::File,
+    image_no: usize,
+    element_no: usize,
+    element: &dfufile::dfuse::ImageElement,
+    block_size: u32,
+    streamed: bool,
+) -> Result<cache::BlockSource> {
+    if streamed {
+        log::debug!("Streaming element data from file");
+        Ok(cache::BlockSource::Streamed)
+    } else {
+        let blocks = block_cache
+            .lock()
+            .unwrap()
+            .element_blocks(file, image_no, element_no, element, block_size)?;
+        Ok(cache::BlockSource::Cached(blocks))
+    }
+}
+
+/// Send the current statistics to the UI
+fn send_statistics(
+    block_cache: &Mutex<cache::BlockCache>,
+    buffer_pool: &cache::BufferPool,
+    streamed: bool,
+    reporter: &progress::Reporter,
+) {
+    let statistics = Statistics {
+        cache_memory: block_cache.lock().unwrap().memory_usage(),
+        buffer_memory: buffer_pool.peak_memory_usage(),
+        streamed,
+    };
+    reporter.send(Event::Statistics(statistics)).ok();
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Options for the update process
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Max. number of progress messages per second
+    pub progress_rate: u32,
+
+    /// Flag to erase the whole flash instead of the sectors to be written
+    pub mass_erase: bool,
+
+    /// Action after a successful update
+    pub after_update: AfterUpdate,
+
+    /// Order and selection of the targets of a DfuSe file
+    pub targets: Targets,
+
+    /// Alt setting processed in the current pass, `None` for all.
+    /// Set by [`run`] for each pass when rebooting between targets.
+    pub target: Option<u8>,
+
+    /// Address window the operations are restricted to, `None` for the whole file
+    pub range: Option<AddressRange>,
+
+    /// Flag to skip programming blocks of only `0xFF`, the erased state of flash.
+    /// Cleared by [`run`] unless the sectors are erased by the same job.
+    pub skip_blank_blocks: bool,
+
+    /// Flag to read back the sectors covered by the file first and only erase
+    /// and program the ones that differ
+    pub differential: bool,
+
+    /// Sectors already holding the data of the file by alt setting, which are
+    /// neither erased nor programmed. Set by [`run`] in differential mode.
+    pub unchanged_sectors: Vec<(u8, AddressRange)>,
+
+    /// Sectors programmed completely by an interrupted update of the same
+    /// file by alt setting, which are skipped when resuming it
+    pub completed_sectors: Vec<(u8, AddressRange)>,
+
+    /// Where to write the structured log of the job, `None` for no log
+    pub session_log: Option<session::Config>,
+
+    /// Flag to fail an update whose data can't be read back for verification
+    /// instead of finishing it unverified
+    pub strict: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            progress_rate: 10,
+            mass_erase: false,
+            after_update: AfterUpdate::default(),
+            targets: Targets::default(),
+            target: None,
+            range: None,
+            skip_blank_blocks: true,
+            differential: false,
+            unchanged_sectors: Vec::new(),
+            completed_sectors: Vec::new(),
+            session_log: None,
+            strict: false,
+        }
+    }
+}
+
+impl Options {
+    /// Return the min. interval between two progress messages
+    pub fn progress_interval(&self) -> Duration {
+        Duration::from_millis(1000 / self.progress_rate.max(1) as u64)
+    }
+
+    /// Return the unchanged sectors of a target
+    fn unchanged_sectors(&self, alt_setting: u8) -> Vec<AddressRange> {
+        self.unchanged_sectors
+            .iter()
+            .filter(|(alt, _)| *alt == alt_setting)
+            .map(|(_, sector)| *sector)
+            .collect()
+    }
+}
+
+/// Point a stopped job can be continued from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct ResumePoint {
+    /// Step that was stopped
+    pub step: DeviceUpdateStep,
+
+    /// Alt setting of the target, `None` for all targets
+    pub alt_setting: Option<u8>,
+
+    /// Address of the first sector not done yet, `None` to restart the step
+    pub address: Option<u32>,
+}
+
+impl std::fmt::Display for ResumePoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?}", self.step)?;
+        if let Some(alt_setting) = self.alt_setting {
+            write!(f, " of alt setting {alt_setting}")?;
+        }
+        match self.address {
+            Some(address) => write!(f, " at 0x{address:08X}"),
+            None => write!(f, " from the start"),
+        }
+    }
+}
+
+/// Address window an update is restricted to
+///
+/// Data of the file outside the window is neither erased, programmed nor
+/// verified. Sectors to erase must lie completely inside the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct AddressRange {
+    /// First address of the window
+    pub start: u32,
+
+    /// Address following the last one of the window
+    pub end: u32,
+}
+
+impl AddressRange {
+    /// Return address and size of the part of `size` bytes at `address`
+    /// inside the window, `None` if there is none
+    pub fn clip(&self, address: u32, size: u32) -> Option<(u32, u32)> {
+        let start = address.max(self.start);
+        let end = address.saturating_add(size).min(self.end);
+
+        (start < end).then(|| (start, end - start))
+    }
+
+    /// Return if a range of `size` bytes at `address` lies completely inside the window
+    pub fn contains(&self, address: u32, size: u32) -> bool {
+        address >= self.start && address.saturating_add(size) <= self.end
+    }
+}
+
+impl std::fmt::Display for AddressRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "0x{:08X}..0x{:08X}", self.start, self.end)
+    }
+}
+
+/// Bytes of a sector differing between the file and the device memory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Difference {
+    /// Alt setting of the target
+    pub alt_setting: u8,
+
+    /// Sector containing the bytes, the block read if the memory layout has
+    /// no sector at the address
+    pub sector: AddressRange,
+
+    /// Address of the first differing byte
+    pub first_address: u32,
+
+    /// Address of the last differing byte
+    pub last_address: u32,
+
+    /// Number of differing bytes
+    pub bytes: u32,
+}
+
+impl std::fmt::Display for Difference {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Alt setting {}, sector {}: {} bytes differ between 0x{:08X} and 0x{:08X}",
+            self.alt_setting, self.sector, self.bytes, self.first_address, self.last_address
+        )
+    }
+}
+
+/// Order and selection of the targets of a DfuSe file
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Targets {
+    /// Alt settings in the order their images are processed,
+    /// images of other alt settings follow in file order
+    pub order: Vec<u8>,
+
+    /// Indices of the images in the file that are left out,
+    /// stored per file by the caller
+    #[serde(skip)]
+    pub skipped: Vec<usize>,
+
+    /// Device alt settings written instead of the ones given in the file,
+    /// by image index, for files made for a different target layout
+    #[serde(skip)]
+    pub remapped: std::collections::BTreeMap<usize, u8>,
+
+    /// Flag to run all steps for one target at a time and reset
+    /// the device before the next target
+    pub reboot_between: bool,
+}
+
+impl Targets {
+    /// Return if the image with an index in the file is processed
+    pub fn is_selected(&self, index: usize) -> bool {
+        !self.skipped.contains(&index)
+    }
+
+    /// Return the device alt setting an image is written to
+    pub fn alt_setting(&self, index: usize, image: &dfufile::dfuse::Image) -> u8 {
+        self.remapped
+            .get(&index)
+            .copied()
+            .unwrap_or(image.target_prefix.bAlternateSetting)
+    }
+
+    /// Leave out all images of the given alt settings
+    pub fn skip_alt_settings(&mut self, content: &dfufile::dfuse::Content, alt_settings: &[u8]) {
+        for (index, image) in content.images.iter().enumerate() {
+            if alt_settings.contains(&image.target_prefix.bAlternateSetting)
+                && self.is_selected(index)
+            {
+                self.skipped.push(index);
+            }
+        }
+    }
+}
+
+/// Single operation run on a device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+    /// Read the device memory into a file
+    Backup(std::path::PathBuf),
+
+    /// Erase the memory covered by the file
+    Erase,
+
+    /// Download the file to the device
+    Program,
+
+    /// Compare the device memory with the file
+    Verify,
+
+    /// Find the sectors already holding the data of the file, see [`Options::differential`]
+    Compare,
+
+    /// Leave DFU mode and start the application
+    Leave,
+
+    /// Calculate the CRC-32 of the device memory covered by the file
+    Checksum,
+}
+
+impl Operation {
+    /// Return the step shown in the UI and recorded in the journal
+    pub fn step(&self) -> DeviceUpdateStep {
+        match self {
+            Self::Backup(_) => DeviceUpdateStep::Backup,
+            Self::Erase => DeviceUpdateStep::Erase,
+            Self::Program => DeviceUpdateStep::Program,
+            Self::Verify => DeviceUpdateStep::Verify,
+            Self::Compare => DeviceUpdateStep::Compare,
+            Self::Leave => DeviceUpdateStep::Leave,
+            Self::Checksum => DeviceUpdateStep::Checksum,
+        }
+    }
+
+    /// Return if the operation is repeated for each target when
+    /// rebooting between targets
+    fn is_per_target(&self) -> bool {
+        matches!(self, Self::Erase | Self::Program | Self::Verify)
+    }
+
+    /// Return if the operation reads from the device
+    fn needs_upload(&self) -> bool {
+        matches!(
+            self,
+            Self::Backup(_) | Self::Verify | Self::Compare | Self::Checksum
+        )
+    }
+
+    /// Return if the operation writes to the device
+    fn needs_download(&self) -> bool {
+        matches!(self, Self::Erase | Self::Program)
+    }
+
+    /// Return if the operation needs a separate erase or reading back
+    /// from the device, which plain DFU only offers together with programming
+    fn needs_upload_or_erase(&self) -> bool {
+        matches!(
+            self,
+            Self::Erase | Self::Verify | Self::Compare | Self::Checksum
+        )
+    }
+}
+
+impl std::fmt::Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?}", self.step())
+    }
+}
+
+/// Steps of an update that can be enabled individually
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Phases {
+    /// Erase the sectors covered by the file
+    pub erase: bool,
+
+    /// Download the file to the device
+    pub program: bool,
+
+    /// Compare the device memory with the file
+    pub verify: bool,
+}
+
+impl Default for Phases {
+    fn default() -> Self {
+        Self {
+            erase: true,
+            program: true,
+            verify: true,
+        }
+    }
+}
+
+impl Phases {
+    /// Erase the sectors covered by the file only
+    pub const ERASE_ONLY: Self = Self {
+        erase: true,
+        program: false,
+        verify: false,
+    };
+
+    /// Compare the device memory with the file without modifying it
+    pub const VERIFY_ONLY: Self = Self {
+        erase: false,
+        program: false,
+        verify: true,
+    };
+
+    /// Return if the device memory is modified
+    pub fn modifies_device(&self) -> bool {
+        self.erase || self.program
+    }
+
+    /// Return if no phase is enabled
+    pub fn is_empty(&self) -> bool {
+        !self.erase && !self.program && !self.verify
+    }
+
+    /// Return the operations to run, including leaving DFU mode if selected
+    pub fn operations(&self, after_update: AfterUpdate) -> Vec<Operation> {
+        [
+            (self.erase, Operation::Erase),
+            (self.program, Operation::Program),
+            (self.verify, Operation::Verify),
+            (
+                self.program && after_update == AfterUpdate::Leave,
+                Operation::Leave,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, operation)| enabled.then_some(operation))
+        .collect()
+    }
+}
+
+/// Action after a successful update
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum AfterUpdate {
+    /// Stay in DFU mode
+    #[default]
+    Stay,
+
+    /// Reset the USB port, so the device can re-enumerate
+    UsbReset,
+
+    /// Leave DFU mode and start the application (DfuSe only, USB reset otherwise)
+    Leave,
+}
+
+impl AfterUpdate {
+    /// All variants in display order
+    pub const ALL: [Self; 3] = [Self::Stay, Self::UsbReset, Self::Leave];
+}
+
+impl std::fmt::Display for AfterUpdate {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Stay => write!(f, "Stay in DFU mode"),
+            Self::UsbReset => write!(f, "USB reset"),
+            Self::Leave => write!(f, "Leave DFU mode"),
+        }
+    }
+}
+
+/// Progress of a step counted in bytes across all images and elements
+struct ByteProgress {
+    /// Number of bytes of the whole step
+    total: u64,
+
+    /// Number of bytes done
+    done: u64,
+}
+
+impl ByteProgress {
+    /// Create a new progress for a number of bytes
+    fn new(total: u64) -> Self {
+        Self { total, done: 0 }
+    }
+
+    /// Add a number of bytes done
+    fn add(&mut self, bytes: u64) {
+        self.done = (self.done + bytes).min(self.total);
+    }
+
+    /// Return the progress event of a step at an address
+    fn event(&self, step: DeviceUpdateStep, address: Option<u32>) -> Event {
+        Event::Progress {
+            step,
+            bytes: self.done,
+            total: self.total,
+            address,
+        }
+    }
+
+    /// Return `true` if all bytes are done
+    fn is_complete(&self) -> bool {
+        self.done == self.total
+    }
+}
+
+/// Limits the rate of progress messages sent to the UI
+struct ProgressThrottle {
+    /// Min. interval between two messages
+    interval: Duration,
+
+    /// Time of the last message
+    last: Option<Instant>,
+}
+
+impl ProgressThrottle {
+    /// Create a new throttle with a min. interval between messages
+    fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last: None,
+        }
+    }
+
+    /// Return `true` if the next message is due
+    fn ready(&mut self) -> bool {
+        let now = Instant::now();
+
+        match self.last {
+            Some(last) if now.duration_since(last) < self.interval => false,
+            _ => {
+                self.last = Some(now);
+                true
+            }
+        }
+    }
+}
+
+/// Sectors to erase for one image
+struct ErasePlan {
+    /// Alt setting of the target
+    alt_setting: u8,
+
+    /// Name of the target memory segment
+    name: String,
+
+    /// Sectors in the order they are erased
+    sectors: Vec<EraseSector>,
+}
+
+/// Single sector to erase
+struct EraseSector {
+    /// Start address
+    address: u32,
+
+    /// Size in bytes
+    size: u32,
+
+    /// Number of payload bytes within the sector, used for the progress
+    payload_bytes: u64,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Device found after leaving DFU mode, compared with the file
+#[derive(Debug, Clone)]
+pub struct FirmwareCheck {
+    /// Device enumerated on the port of the bootloader, `None` if none did in time
+    pub device: Option<dfudev::application::ApplicationInfo>,
+
+    /// Release number stored in the file, `None` if it's a wildcard
+    pub expected_version: Option<u16>,
+}
+
+impl FirmwareCheck {
+    /// Return if the firmware was confirmed, `None` if the file names no version
+    pub fn passed(&self) -> Option<bool> {
+        match (&self.device, self.expected_version) {
+            (Some(device), _) if device.dfu_mode => Some(false),
+            (Some(device), Some(version)) => Some(device.bcd_device == version),
+            (Some(_), None) => None,
+            (None, _) => Some(false),
+        }
+    }
+
+    /// Return a description of the result
+    pub fn summary(&self) -> String {
+        let Some(device) = &self.device else {
+            return format!(
+                "No application started within {} s.",
+                FIRMWARE_TIMEOUT.as_secs()
+            );
+        };
+
+        let name = if device.product_string.is_empty() {
+            format!("{:04x}:{:04x}", device.vendor_id, device.product_id)
+        } else {
+            format!(
+                "{} ({:04x}:{:04x})",
+                device.product_string, device.vendor_id, device.product_id
+            )
+        };
+
+        match self.expected_version {
+            _ if device.dfu_mode => format!("{name} is still in DFU mode."),
+            Some(version) if version == device.bcd_device => {
+                format!("{name} runs firmware version {}.", bcd_version(version))
+            }
+            Some(version) => format!(
+                "{name} runs firmware version {}, the file has version {}.",
+                bcd_version(device.bcd_device),
+                bcd_version(version)
+            ),
+            None => format!(
+                "{name} started with version {}, the file names no version.",
+                bcd_version(device.bcd_device)
+            ),
+        }
+    }
+}
+
+/// Statistics collected during an update
+#[derive(Debug, Default, Clone)]
+pub struct Statistics {
+    /// Number of bytes held by the block cache
+    pub cache_memory: usize,
+
+    /// Max. number of bytes used by transfer buffers
+    pub buffer_memory: usize,
+
+    /// Flag if the file data is streamed instead of cached
+    pub streamed: bool,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Errors of the update operations
+#[derive(Debug)]
+pub enum Error {
+    /// Target not found for an alternate setting
+    TargetNotFound(u8),
+
+    /// Memory region not found for an address range
+    MemoryRegionNotFound(u32, u32),
+
+    /// Verification error
+    VerificationFailed(u32),
+
+    /// Device reports a transfer size of 0
+    InvalidTransferSize,
+
+    /// No readable memory region found for an alternate setting
+    NoReadableRegion(u8),
+
+    /// Reading from plain DFU devices is not supported yet
+    PlainUploadNotSupported,
+
+    /// Plain DFU device did not end the upload
+    UploadTooLarge,
+
+    /// Target is system memory or OTP, which is never written
+    ProtectedTarget(String),
+
+    /// Memory map of a target has an unusable geometry, with the raw
+    /// descriptor and the problems found
+    InvalidGeometry(String, String),
+
+    /// Device not found, e.g. disconnected between two steps
+    DeviceNotFound,
+
+    /// Device disconnected during a transfer and did not return
+    DeviceDisconnected(u32),
+
+    /// File contains no element to take the application start address from
+    NoStartAddress,
+
+    /// Operation can't be used with plain DFU files
+    OperationNotSupported(Operation),
+
+    /// Operation requires a DFU file, but none is given
+    NoFile(Operation),
+
+    /// Operation reads from a device without upload support
+    UploadNotSupported(Operation),
+
+    /// Operation writes to a device without download support
+    DownloadNotSupported(Operation),
+
+    /// Memory can't be read back because readout protection is active
+    ReadProtected,
+
+    /// Readout protection can only be removed from DfuSe devices
+    UnprotectNotSupported,
+
+    /// Operation stopped because the job was cancelled
+    Cancelled,
+
+    /// Operation stopped at a consistent point on request
+    Stopped(ResumePoint),
+
+    /// Sector to erase, with address and size, reaches outside the address range
+    SectorOutsideRange(u32, u32),
+
+    /// Address range can't be used with plain DFU files or a full chip erase
+    RangeNotSupported,
+
+    /// No data of the file lies inside the address range
+    NothingInRange(AddressRange),
+
+    /// Number of bytes to read exceeds [`MAX_MEMORY_READ_SIZE`]
+    ReadTooLarge(u32),
+}
+
+impl std::error::Error for Error {}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::TargetNotFound(alt_setting) =>
+                    format!("No target found for alt setting {alt_setting}."),
+                Self::MemoryRegionNotFound(start_address, end_address) => format!(
+                    "No memory region found with address 0x{start_address:08X}..0x{end_address:08X}"
+                ),
+                Self::VerificationFailed(address) =>
+                    format!("Verification failed at address 0x{address:08X}."),
+                Self::InvalidTransferSize => "Device reports an invalid transfer size.".to_string(),
+                Self::NoReadableRegion(alt_setting) =>
+                    format!("No readable memory region found for alt setting {alt_setting}."),
+                Self::PlainUploadNotSupported =>
+                    "Reading from plain DFU devices is not supported yet.".to_string(),
+                Self::UploadTooLarge => format!(
+                    "Device did not end the upload after {} bytes.",
+                    MAX_PLAIN_UPLOAD_SIZE
+                ),
+                Self::DeviceNotFound => "Device not found.".to_string(),
+                Self::NoStartAddress => "No application start address found.".to_string(),
+                Self::OperationNotSupported(operation) =>
+                    format!("Operation \"{operation}\" is not supported for plain DFU files."),
+                Self::NoFile(operation) => format!("Operation \"{operation}\" requires a file."),
+                Self::UploadNotSupported(operation) => format!(
+                    "Operation \"{operation}\" requires reading from the device, \
+                    which it does not support."
+                ),
+                Self::DownloadNotSupported(operation) => format!(
+                    "Operation \"{operation}\" requires writing to the device, \
+                    which it does not support."
+                ),
+                Self::ReadProtected => "Device is read-protected, its memory can't be read \
+                    back and verification is unavailable. Removing the protection erases \
+                    the device."
+                    .to_string(),
+                Self::UnprotectNotSupported =>
+                    "Read protection can only be removed from DfuSe devices.".to_string(),
+                Self::Cancelled => "Operation cancelled.".to_string(),
+                Self::Stopped(resume_point) =>
+                    format!("Operation stopped, can be resumed at {resume_point}."),
+                Self::SectorOutsideRange(address, size) => format!(
+                    "Sector at 0x{address:08X} with {size} bytes reaches outside the address \
+                    range. The range must start and end on sector boundaries."
+                ),
+                Self::RangeNotSupported => "An address range can't be used with plain DFU \
+                    files or a full chip erase."
+                    .to_string(),
+                Self::NothingInRange(range) =>
+                    format!("The file contains no data in the address range {range}."),
+                Self::ReadTooLarge(length) => format!(
+                    "Reading {length} bytes exceeds the limit of {MAX_MEMORY_READ_SIZE} bytes."
+                ),
+                Self::DeviceDisconnected(address) =>
+                    format!("Device disconnected at address 0x{address:08X}."),
+                Self::ProtectedTarget(name) =>
+                    format!("Writing to target \"{name}\" is blocked, it is read-only."),
+                Self::InvalidGeometry(descriptor, problems) =>
+                    format!("Invalid memory map \"{descriptor}\": {problems}"),
+            }
+        )
+    }
+}
@@ -0,0 +1,427 @@
+//! Update operations run on a simulated DfuSe device
+
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+
+use dfu_buddy_core::dfudev::simulator::{Fault, SimulatedDevice};
+use dfu_buddy_core::dfudev::{self, DeviceStatusCode};
+use dfu_buddy_core::progress::{CancelMode, Event, JobEvent, Reporter};
+use dfu_buddy_core::update::{self, Operation, Options};
+use dfu_buddy_core::{builder, cache, DeviceUpdateStep};
+
+/// Memory layout with four sectors of 1 KiB and one of 4 KiB
+const LAYOUT: &str = "@Internal Flash  /0x08000000/04*001Kg,01*004Kg";
+
+/// Start address of the flash memory
+const FLASH_START: u32 = 0x0800_0000;
+
+/// Transfer size of the device
+const TRANSFER_SIZE: u16 = 256;
+
+/// Return firmware data covering the first three sectors partly
+fn firmware() -> Vec<u8> {
+    (0..2500u32).map(|offset| (offset * 7 + 3) as u8).collect()
+}
+
+/// Write a DfuSe file with the data at the start of the flash memory
+fn file(name: &str, data: &[u8]) -> std::path::PathBuf {
+    let image = builder::Image {
+        alt_setting: 0,
+        name: None,
+        elements: vec![builder::Element {
+            address: FLASH_START,
+            data: data.to_vec(),
+        }],
+    };
+    let content = builder::dfuse_file(
+        &[image],
+        &builder::Suffix::wildcard(builder::DFU_VERSION_DFUSE),
+    );
+
+    builder::write_temp_file(name, &content).unwrap()
+}
+
+/// Run operations on the device and return the result with the events
+fn run(
+    device: &SimulatedDevice,
+    file_path: &std::path::Path,
+    operations: &[Operation],
+    options: Options,
+) -> (anyhow::Result<()>, Vec<Event>) {
+    let (sender, receiver) = mpsc::channel::<JobEvent>();
+    let result = update::run_device(
+        device.device(),
+        Some(file_path.to_path_buf()),
+        operations.to_vec(),
+        Arc::new(Mutex::new(cache::BlockCache::default())),
+        options,
+        Reporter::new(sender),
+    );
+    std::fs::remove_file(file_path).ok();
+
+    (
+        result,
+        receiver.try_iter().map(|event| event.event).collect(),
+    )
+}
+
+#[test]
+fn erase_program_verify() {
+    let device = SimulatedDevice::new(LAYOUT, TRANSFER_SIZE).unwrap();
+    device.write(FLASH_START, &[0x00; 5 * 1024]);
+    let data = firmware();
+
+    let (result, events) = run(
+        &device,
+        &file("update", &data),
+        &[Operation::Erase, Operation::Program, Operation::Verify],
+        Options::default(),
+    );
+
+    result.unwrap();
+    assert_eq!(device.read(FLASH_START, data.len()), data);
+    assert_eq!(
+        device.erased_sectors(),
+        vec![FLASH_START, FLASH_START + 1024, FLASH_START + 2048]
+    );
+    assert!(matches!(events.last(), Some(Event::Finished)));
+}
+
+#[test]
+fn program_without_erase_fails_verification() {
+    let device = SimulatedDevice::new(LAYOUT, TRANSFER_SIZE).unwrap();
+    device.write(FLASH_START + 1024, &[0x55; 1024]);
+
+    let (result, _) = run(
+        &device,
+        &file("no-erase", &firmware()),
+        &[Operation::Program, Operation::Verify],
+        Options::default(),
+    );
+
+    let error = result.unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<update::Error>(),
+        Some(update::Error::VerificationFailed(address)) if *address >= FLASH_START + 1024
+    ));
+}
+
+#[test]
+fn verification_reports_all_differences() {
+    let device = SimulatedDevice::new(LAYOUT, TRANSFER_SIZE).unwrap();
+    device.write(FLASH_START + 1024, &[0x55; 1024]);
+    device.inject(Fault::StuckByte(FLASH_START + 2100));
+
+    let (result, events) = run(
+        &device,
+        &file("differences", &firmware()),
+        &[Operation::Program, Operation::Verify],
+        Options::default(),
+    );
+
+    assert!(result.is_err());
+    let differences = events
+        .iter()
+        .find_map(|event| match event {
+            Event::Differences(differences) => Some(differences.clone()),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(differences.len(), 2);
+    assert_eq!(differences[0].sector.start, FLASH_START + 1024);
+    assert_eq!(differences[1].sector.start, FLASH_START + 2048);
+    assert_eq!(differences[1].first_address, FLASH_START + 2100);
+    assert_eq!(differences[1].bytes, 1);
+}
+
+#[test]
+fn stuck_byte_fails_verification() {
+    let device = SimulatedDevice::new(LAYOUT, TRANSFER_SIZE).unwrap();
+    device.inject(Fault::StuckByte(FLASH_START + 1500));
+
+    let (result, events) = run(
+        &device,
+        &file("stuck-byte", &firmware()),
+        &[Operation::Erase, Operation::Program, Operation::Verify],
+        Options::default(),
+    );
+
+    let error = result.unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<update::Error>(),
+        Some(update::Error::VerificationFailed(_))
+    ));
+    assert!(!events.iter().any(|event| matches!(event, Event::Finished)));
+}
+
+#[test]
+fn empty_sectors_fail_program() {
+    let device =
+        SimulatedDevice::new("@Internal Flash  /0x08000000/04*000Kg", TRANSFER_SIZE).unwrap();
+
+    let (result, _) = run(
+        &device,
+        &file("empty-sectors", &firmware()),
+        &[Operation::Program, Operation::Verify],
+        Options::default(),
+    );
+
+    let error = result.unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<update::Error>(),
+        Some(update::Error::InvalidGeometry(..))
+    ));
+    assert_eq!(device.read(FLASH_START, 16), vec![0xFF; 16]);
+}
+
+#[test]
+fn erase_failure_reports_status() {
+    let device = SimulatedDevice::new(LAYOUT, TRANSFER_SIZE).unwrap();
+    device.inject(Fault::EraseFailure(FLASH_START + 1024));
+
+    let (result, _) = run(
+        &device,
+        &file("erase-failure", &firmware()),
+        &[Operation::Erase, Operation::Program],
+        Options::default(),
+    );
+
+    let error = result.unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<dfudev::Error>(),
+        Some(dfudev::Error::FailedStatus(DeviceStatusCode::errERASE))
+    ));
+    assert_eq!(device.erased_sectors(), vec![FLASH_START]);
+}
+
+#[test]
+fn read_protection_skips_verification() {
+    let device = SimulatedDevice::new(LAYOUT, TRANSFER_SIZE).unwrap();
+    device.inject(Fault::ReadProtected);
+    let data = firmware();
+
+    let (result, events) = run(
+        &device,
+        &file("read-protected", &data),
+        &[Operation::Erase, Operation::Program, Operation::Verify],
+        Options::default(),
+    );
+
+    result.unwrap();
+    assert_eq!(device.read(FLASH_START, data.len()), data);
+    assert!(events
+        .iter()
+        .any(|event| matches!(event, Event::Skipped(DeviceUpdateStep::Verify, _))));
+    assert!(matches!(events.last(), Some(Event::FinishedUnverified(_))));
+    assert!(!events.iter().any(|event| matches!(event, Event::Finished)));
+}
+
+#[test]
+fn read_protection_fails_strict_update() {
+    let device = SimulatedDevice::new(LAYOUT, TRANSFER_SIZE).unwrap();
+    device.inject(Fault::ReadProtected);
+
+    let (result, _) = run(
+        &device,
+        &file("read-protected-strict", &firmware()),
+        &[Operation::Erase, Operation::Program, Operation::Verify],
+        Options {
+            strict: true,
+            ..Options::default()
+        },
+    );
+
+    let error = result.unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<update::Error>(),
+        Some(update::Error::ReadProtected)
+    ));
+}
+
+#[test]
+fn zeros_after_program_fail_verification() {
+    let device = SimulatedDevice::new(LAYOUT, TRANSFER_SIZE).unwrap();
+    device.write(FLASH_START, &[0x00; 1024]);
+
+    let (result, events) = run(
+        &device,
+        &file("zeros", &firmware()),
+        &[Operation::Program, Operation::Verify],
+        Options::default(),
+    );
+
+    let error = result.unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<update::Error>(),
+        Some(update::Error::VerificationFailed(FLASH_START))
+    ));
+    assert!(!events
+        .iter()
+        .any(|event| matches!(event, Event::Skipped(..) | Event::FinishedUnverified(_))));
+}
+
+#[test]
+fn read_protection_fails_backup() {
+    let device = SimulatedDevice::new(LAYOUT, TRANSFER_SIZE).unwrap();
+    device.inject(Fault::ReadProtected);
+    let backup_path = std::env::temp_dir().join(format!(
+        "dfu-buddy-{}-backup-protected.dfu",
+        std::process::id()
+    ));
+
+    let (result, _) = run(
+        &device,
+        &file("backup-protected", &firmware()),
+        &[Operation::Backup(backup_path.clone())],
+        Options::default(),
+    );
+
+    assert!(result.is_err());
+    assert!(!backup_path.exists());
+}
+
+#[test]
+fn busy_interface_reports_device_in_use() {
+    let device = SimulatedDevice::new(LAYOUT, TRANSFER_SIZE).unwrap();
+    device.inject(Fault::InterfaceBusy);
+
+    let (result, _) = run(
+        &device,
+        &file("busy", &firmware()),
+        &[Operation::Erase, Operation::Program],
+        Options::default(),
+    );
+
+    let error = result.unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<dfudev::Error>(),
+        Some(dfudev::Error::InUse(_))
+    ));
+    assert!(device.erased_sectors().is_empty());
+}
+
+#[test]
+fn program_resumes_after_disconnect() {
+    let device = SimulatedDevice::new(LAYOUT, TRANSFER_SIZE).unwrap();
+    device.inject(Fault::Disconnect(FLASH_START + 1300));
+    let data = firmware();
+
+    let (result, _) = run(
+        &device,
+        &file("disconnect", &data),
+        &[Operation::Erase, Operation::Program, Operation::Verify],
+        Options::default(),
+    );
+
+    result.unwrap();
+    assert_eq!(device.read(FLASH_START, data.len()), data);
+}
+
+#[test]
+fn differential_update_erases_changed_sectors() {
+    let device = SimulatedDevice::new(LAYOUT, TRANSFER_SIZE).unwrap();
+    let mut data = firmware();
+    device.write(FLASH_START, &data);
+    data[1100] ^= 0xFF;
+
+    let (result, events) = run(
+        &device,
+        &file("differential", &data),
+        &[Operation::Erase, Operation::Program, Operation::Verify],
+        Options {
+            differential: true,
+            ..Options::default()
+        },
+    );
+
+    result.unwrap();
+    assert_eq!(device.read(FLASH_START, data.len()), data);
+    assert_eq!(device.erased_sectors(), vec![FLASH_START + 1024]);
+    assert!(events
+        .iter()
+        .any(|event| matches!(event, Event::Step(DeviceUpdateStep::Compare))));
+}
+
+#[test]
+fn block_numbers_restart_after_address_pointer() {
+    // Blocks of 1 byte, so the 16-bit block numbers run out within the element
+    let device = SimulatedDevice::new("@Internal Flash  /0x08000000/128*001Kg", 1).unwrap();
+    let data: Vec<u8> = (0..70_000u32).map(|offset| (offset % 251) as u8).collect();
+
+    let (result, events) = run(
+        &device,
+        &file("block-numbers", &data),
+        &[
+            Operation::Erase,
+            Operation::Program,
+            Operation::Verify,
+            Operation::Checksum,
+        ],
+        Options::default(),
+    );
+
+    result.unwrap();
+    assert_eq!(device.read(FLASH_START, data.len()), data);
+    assert!(events
+        .iter()
+        .any(|event| matches!(event, Event::Checksum(crc) if *crc == crc32fast::hash(&data))));
+}
+
+#[test]
+fn checksum_matches_file() {
+    let device = SimulatedDevice::new(LAYOUT, TRANSFER_SIZE).unwrap();
+    let data = firmware();
+    device.write(FLASH_START, &data);
+
+    let (result, events) = run(
+        &device,
+        &file("checksum", &data),
+        &[Operation::Checksum],
+        Options::default(),
+    );
+
+    result.unwrap();
+    assert!(events
+        .iter()
+        .any(|event| matches!(event, Event::Checksum(crc) if *crc == crc32fast::hash(&data))));
+}
+
+#[test]
+fn leave_starts_application() {
+    let device = SimulatedDevice::new(LAYOUT, TRANSFER_SIZE).unwrap();
+
+    let (result, _) = run(
+        &device,
+        &file("leave", &firmware()),
+        &[Operation::Erase, Operation::Program, Operation::Leave],
+        Options::default(),
+    );
+
+    result.unwrap();
+    assert!(device.has_left());
+}
+
+#[test]
+fn cancelled_job_stops() {
+    let device = SimulatedDevice::new(LAYOUT, TRANSFER_SIZE).unwrap();
+    let (sender, receiver) = mpsc::channel::<JobEvent>();
+    let reporter = Reporter::new(sender);
+    reporter.cancel(CancelMode::Abort);
+    let file_path = file("cancelled", &firmware());
+
+    let result = update::run_device(
+        device.device(),
+        Some(file_path.clone()),
+        vec![Operation::Erase, Operation::Program],
+        Arc::new(Mutex::new(cache::BlockCache::default())),
+        Options::default(),
+        reporter,
+    );
+    std::fs::remove_file(file_path).ok();
+
+    assert!(result.is_err());
+    assert!(device.erased_sectors().is_empty());
+    assert!(receiver
+        .try_iter()
+        .any(|event| matches!(event.event, Event::Cancelled { .. })));
+}
@@ -0,0 +1,85 @@
+//! Firmware delivered in zip archives
+//!
+//! Vendors often ship a DFU file zipped together with release notes. The DFU
+//! files of an archive are listed by name, the one chosen is extracted into a
+//! directory of its own and then opened like any other file. Sidecar files
+//! next to it in the archive, e.g. `firmware.dfu.sha256`, are extracted as
+//! well, so the integrity checks find them.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::{anyhow, Result};
+
+/// Magic bytes at the start of a zip archive
+const MAGIC: [u8; 4] = *b"PK\x03\x04";
+
+/// Extension of the DFU files looked for in an archive
+const DFU_EXTENSION: &str = ".dfu";
+
+/// Return if a file is a zip archive, recognized by its content
+pub fn is_archive(path: &Path) -> bool {
+    let mut header = [0; MAGIC.len()];
+
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok_and(|_| header == MAGIC)
+}
+
+/// Return the names of the DFU files in an archive
+pub fn dfu_entries(path: &Path) -> Result<Vec<String>> {
+    let archive = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+
+    Ok(archive
+        .file_names()
+        .filter(|name| name.to_lowercase().ends_with(DFU_EXTENSION))
+        .map(String::from)
+        .collect())
+}
+
+/// Extract a DFU file of an archive together with its sidecar files and
+/// return the path of the extracted DFU file
+///
+/// Each call uses a new directory, so that a changed content is never
+/// mistaken for a previous one with the same path.
+pub fn extract(path: &Path, entry: &str) -> Result<PathBuf> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let directory =
+        std::env::temp_dir().join(format!("dfu-buddy-{}-{}-zip", std::process::id(), count));
+    std::fs::create_dir_all(&directory)?;
+
+    let mut dfu_path = None;
+
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index)?;
+        let belongs = file
+            .name()
+            .strip_prefix(entry)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'));
+        if !file.is_file() || !belongs {
+            continue;
+        }
+
+        // Only the file name is used, paths in the archive are untrusted
+        let Some(file_name) = file
+            .enclosed_name()
+            .and_then(|name| name.file_name().map(|file_name| directory.join(file_name)))
+        else {
+            continue;
+        };
+
+        let mut content = Vec::new();
+        file.read_to_end(&mut content)?;
+        std::fs::write(&file_name, content)?;
+
+        if file.name() == entry {
+            dfu_path = Some(file_name);
+        }
+    }
+
+    dfu_path.ok_or_else(|| anyhow!("{entry} not found in the archive."))
+}
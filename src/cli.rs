@@ -0,0 +1,667 @@
+//! Headless command line interface
+//!
+//! Invoked with `--cli` as first argument, the application performs the
+//! requested operation without opening a window. Progress is written to
+//! stderr, the exit code is non-zero on failure.
+
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Result};
+
+use crate::progress::{self, Event};
+use crate::{
+    cache, dfudev, format, history, integrity, journal, selfupdate, session, update,
+    DeviceUpdateStep, DfuFileChecks, Message, INSPECTOR_FLAG,
+};
+
+/// Usage text printed on invalid arguments
+const USAGE: &str = "\
+Usage: dfu-buddy --cli [--inspector] <command> [options]
+       dfu-buddy --check-update | --self-update
+
+Commands:
+  list                    List all devices in DFU mode
+  flash <file>            Erase, program and verify a device with a DFU file
+  erase <file>            Erase the memory areas covered by a DFU file
+  verify <file>           Compare the device memory with a DFU file
+  checksum <file>         Print the CRC-32 of the device memory covered by a DFU file
+  upload <file>           Read the device memory into a .bin or .dfu file
+
+Options:
+  --serial <serial>       Select the device by its serial number
+  --force                 Flash even if the file checks fail
+  --strict                Refuse all overrides like --force and downgrades, fail unverified
+                          updates and require a signature with --public-key, for release flashing
+  --mass-erase            Erase the whole flash before programming
+  --program-blank         Also program blocks of only 0xFF, skipped after erasing by default
+  --differential          Read back first and only erase and program the sectors that differ
+  --no-verify             Skip verification after flashing
+  --timing                Print the time spent in USB requests, poll timeouts and on the host
+  --session-log <dir>     Write all requests, statuses and timings to a new file in <dir>
+  --after <action>        Action after flashing: stay (default), reset or leave
+  --order <alt,...>       Process the images of these alt settings first, in this order
+  --skip <alt,...>        Leave the images of these alt settings untouched
+  --skip-image <index,...>
+                          Leave these images untouched, counted from 0 in file order
+  --reset-between         Reset the device between the targets of a DfuSe file
+  --range <start>..<end>  Only write the data inside this hex address range, end exclusive
+  --integrity <name,...>  Also check the file with sha256 (<file>.sha256), crc32 (<file>.crc32),
+                          crc32-trailer (CRC-32 in the last 4 bytes of each image)
+                          or signature (<file>.minisig or <file>.sig)
+  --public-key <key>      Base64 minisign public key to verify signatures with
+  --transfer-size <bytes> Use instead of the transfer size reported by the device
+  --timeout <ms>          Timeout of USB control transfers, 5000 by default
+  --poll-retries <count>  Status requests repeated after a download, 5 by default
+  --poll-timeout <ms>     Use instead of the poll timeout reported by the device
+  --inspector             Refuse all commands that modify the device
+
+Updates:
+  --check-update          Print if a newer release is available
+  --self-update           Replace the application with the latest release
+";
+
+/// Run the command line interface and return the process exit code
+pub fn run(args: &[String]) -> i32 {
+    let inspector_mode = args.iter().any(|arg| arg == INSPECTOR_FLAG);
+    let args: Vec<String> = args
+        .iter()
+        .filter(|arg| *arg != INSPECTOR_FLAG)
+        .cloned()
+        .collect();
+
+    let result = match args.first().map(String::as_str) {
+        Some("list") => list(),
+        Some("flash") | Some("erase") if inspector_mode => Err(anyhow!(
+            "Modifying the device is disabled in inspector mode."
+        )),
+        Some("flash") => parse_options(&args[1..]).and_then(|options| {
+            let phases = update::Phases {
+                verify: !options.no_verify,
+                ..Default::default()
+            };
+            flash(&options, phases)
+        }),
+        Some("erase") => parse_options(&args[1..])
+            .and_then(|options| flash(&options, update::Phases::ERASE_ONLY)),
+        Some("verify") => parse_options(&args[1..])
+            .and_then(|options| flash(&options, update::Phases::VERIFY_ONLY)),
+        Some("checksum") => parse_options(&args[1..]).and_then(|options| checksum(&options)),
+        Some("upload") => parse_options(&args[1..]).and_then(|options| upload(&options)),
+        Some("help") | Some("--help") | Some("-h") => {
+            print!("{USAGE}");
+            Ok(())
+        }
+        _ => Err(anyhow!("Invalid command.\n\n{USAGE}")),
+    };
+
+    match result {
+        Ok(_) => 0,
+        Err(error) => {
+            eprintln!("Error: {error}");
+            if dfudev::is_access_denied(&error) {
+                eprintln!("\n{}", dfudev::access_hint());
+            }
+            1
+        }
+    }
+}
+
+/// Check for a newer release and install it if `install` is set,
+/// return the process exit code
+///
+/// When only checking, the exit code is 0 if the application is up to date
+/// and 2 if a newer release is available.
+pub fn self_update(install: bool) -> i32 {
+    let result = selfupdate::latest_release().and_then(|release| {
+        if !release.is_newer() {
+            eprintln!(
+                "Version {} is up to date, latest release is {}.",
+                env!("CARGO_PKG_VERSION"),
+                release.version()
+            );
+            return Ok(0);
+        }
+
+        eprintln!(
+            "Version {} is available, running {}.",
+            release.version(),
+            env!("CARGO_PKG_VERSION")
+        );
+
+        if install {
+            selfupdate::install(&release)?;
+            eprintln!("Updated to version {}.", release.version());
+            Ok(0)
+        } else {
+            Ok(2)
+        }
+    });
+
+    match result {
+        Ok(code) => code,
+        Err(error) => {
+            eprintln!("Error: {error}");
+            1
+        }
+    }
+}
+
+/// Options of the flash and upload commands
+#[derive(Debug, Default)]
+struct Options {
+    /// Path of the file to read or write
+    file_path: std::path::PathBuf,
+
+    /// Serial number of the device to use
+    serial: Option<String>,
+
+    /// Flag to skip the file checks
+    force: bool,
+
+    /// Flag to refuse all overrides
+    strict: bool,
+
+    /// Flag to erase the whole flash before programming
+    mass_erase: bool,
+
+    /// Flag to program blocks of only 0xFF too
+    program_blank: bool,
+
+    /// Flag to only erase and program the sectors differing from the file
+    differential: bool,
+
+    /// Flag to skip verification after programming
+    no_verify: bool,
+
+    /// Flag to print the durations of the requests at the end
+    timing: bool,
+
+    /// Directory the session log is written to, `None` for no log
+    session_log: Option<std::path::PathBuf>,
+
+    /// Action after flashing
+    after_update: update::AfterUpdate,
+
+    /// Order and selection of the targets
+    targets: update::Targets,
+
+    /// Alt settings whose images are left out
+    skipped_alt_settings: Vec<u8>,
+
+    /// Address window the update is restricted to
+    range: Option<update::AddressRange>,
+
+    /// Integrity checks required in addition to the suffix CRC
+    integrity: Vec<integrity::Algorithm>,
+
+    /// Base64 minisign public key signatures are verified with
+    public_key: String,
+
+    /// Transfer size, timeouts and polling behavior
+    transfer: dfudev::settings::TransferSettings,
+}
+
+impl Options {
+    /// Return where the session log is written, if requested
+    fn session_log_config(&self) -> Option<session::Config> {
+        self.session_log.clone().map(|directory| session::Config {
+            directory,
+            keep: session::Config::DEFAULT_KEEP,
+        })
+    }
+}
+
+/// Parse the arguments following the command
+fn parse_options(args: &[String]) -> Result<Options> {
+    let mut options = Options::default();
+    let mut file_path = None;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--serial" => {
+                let serial = args
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing value for --serial."))?;
+                options.serial = Some(serial.clone());
+            }
+            "--force" => options.force = true,
+            "--strict" => options.strict = true,
+            "--mass-erase" => options.mass_erase = true,
+            "--program-blank" => options.program_blank = true,
+            "--differential" => options.differential = true,
+            "--no-verify" => options.no_verify = true,
+            "--timing" => options.timing = true,
+            "--session-log" => {
+                let directory = args
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing value for --session-log."))?;
+                options.session_log = Some(directory.into());
+            }
+            "--after" => {
+                options.after_update = match args.next().map(String::as_str) {
+                    Some("stay") => update::AfterUpdate::Stay,
+                    Some("reset") => update::AfterUpdate::UsbReset,
+                    Some("leave") => update::AfterUpdate::Leave,
+                    _ => return Err(anyhow!("Invalid value for --after.")),
+                };
+            }
+            "--order" => options.targets.order = parse_alt_settings(arg, args.next())?,
+            "--skip" => options.skipped_alt_settings = parse_alt_settings(arg, args.next())?,
+            "--skip-image" => options.targets.skipped = parse_image_indices(arg, args.next())?,
+            "--reset-between" => options.targets.reboot_between = true,
+            "--range" => options.range = Some(parse_range(arg, args.next())?),
+            "--integrity" => options.integrity = parse_algorithms(arg, args.next())?,
+            "--public-key" => {
+                options.public_key = args
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing value for --public-key."))?
+                    .clone();
+            }
+            "--transfer-size" => {
+                options.transfer.transfer_size = Some(parse_number(arg, args.next())?);
+            }
+            "--timeout" => options.transfer.timeout = parse_number(arg, args.next())?,
+            "--poll-retries" => options.transfer.polling_retries = parse_number(arg, args.next())?,
+            "--poll-timeout" => {
+                options.transfer.poll_timeout = Some(parse_number(arg, args.next())?);
+            }
+            _ if arg.starts_with("--") => return Err(anyhow!("Unknown option {arg}.")),
+            _ if file_path.is_none() => file_path = Some(std::path::PathBuf::from(arg)),
+            _ => return Err(anyhow!("Unexpected argument {arg}.")),
+        }
+    }
+
+    options.file_path = file_path.ok_or_else(|| anyhow!("Missing file argument.\n\n{USAGE}"))?;
+
+    if options.strict && options.force {
+        return Err(anyhow!("--force can't be used with --strict."));
+    }
+    if let Some(problem) = options.transfer.problem() {
+        return Err(anyhow!(problem));
+    }
+
+    Ok(options)
+}
+
+/// Parse a decimal number given as value of an option
+fn parse_number<T: std::str::FromStr>(option: &str, value: Option<&String>) -> Result<T> {
+    value
+        .ok_or_else(|| anyhow!("Missing value for {option}."))?
+        .trim()
+        .parse()
+        .map_err(|_| anyhow!("Invalid value for {option}."))
+}
+
+/// Parse a comma-separated list of alt settings given as value of an option
+fn parse_alt_settings(option: &str, value: Option<&String>) -> Result<Vec<u8>> {
+    value
+        .ok_or_else(|| anyhow!("Missing value for {option}."))?
+        .split(',')
+        .map(|alt| alt.trim().parse::<u8>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| anyhow!("Invalid value for {option}."))
+}
+
+/// Parse an address range given as two hex addresses, e.g. `08000000..08004000`
+fn parse_range(option: &str, value: Option<&String>) -> Result<update::AddressRange> {
+    let value = value.ok_or_else(|| anyhow!("Missing value for {option}."))?;
+    let parse = |text: &str| u32::from_str_radix(text.trim().trim_start_matches("0x"), 16).ok();
+
+    value
+        .split_once("..")
+        .and_then(|(start, end)| {
+            Some(update::AddressRange {
+                start: parse(start)?,
+                end: parse(end)?,
+            })
+        })
+        .filter(|range| range.start < range.end)
+        .ok_or_else(|| anyhow!("Invalid value for {option}."))
+}
+
+/// Parse a comma-separated list of image indices
+fn parse_image_indices(option: &str, value: Option<&String>) -> Result<Vec<usize>> {
+    value
+        .ok_or_else(|| anyhow!("Missing value for {option}."))?
+        .split(',')
+        .map(|index| index.trim().parse::<usize>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| anyhow!("Invalid value for {option}."))
+}
+
+/// Parse a comma separated list of integrity check algorithms
+fn parse_algorithms(option: &str, value: Option<&String>) -> Result<Vec<integrity::Algorithm>> {
+    value
+        .ok_or_else(|| anyhow!("Missing value for {option}."))?
+        .split(',')
+        .map(|name| {
+            integrity::Algorithm::from_arg(name.trim())
+                .ok_or_else(|| anyhow!("Invalid value for {option}."))
+        })
+        .collect()
+}
+
+/// Print all devices in DFU mode
+fn list() -> Result<()> {
+    let devices = dfudev::DfuDevice::find(false)?.unwrap_or_default();
+
+    if devices.is_empty() {
+        println!("No DFU devices found.");
+    }
+
+    for device in devices {
+        let info = &device.info;
+        println!(
+            "{:04x}:{:04x}  {} | {} | S/N {} | {}",
+            info.vendor_id,
+            info.product_id,
+            info.manufacturer_string,
+            info.product_string,
+            info.serial_number_string,
+            info.bus_path()
+        );
+    }
+
+    Ok(())
+}
+
+/// Perform the enabled steps of an update on the selected device
+fn flash(options: &Options, phases: update::Phases) -> Result<()> {
+    let device = select_device(options)?;
+
+    let mut dfu_file = dfufile::DfuFile::open(&options.file_path)?;
+    let mut targets = options.targets.clone();
+    if let dfufile::Content::DfuSe(content) = &dfu_file.content {
+        targets.skip_alt_settings(content, &options.skipped_alt_settings);
+    }
+
+    let mut checks = DfuFileChecks::default();
+    checks.check_crc(&mut dfu_file);
+    checks.integrity = integrity::check(
+        &integrity::required(&options.integrity, options.strict, &options.public_key),
+        &options.public_key,
+        &options.file_path,
+        &mut dfu_file,
+    );
+    checks.match_device(&dfu_file, &device, &targets);
+
+    for problem in checks.memory_problems.iter().chain(&checks.erase_problems) {
+        eprintln!("{problem}");
+    }
+    for outcome in checks.integrity.iter().filter(|outcome| !outcome.passed) {
+        eprintln!("{}: {}", outcome.algorithm, outcome.details);
+    }
+    if let (true, Some(report)) = (phases.modifies_device(), &checks.geometry_report) {
+        return Err(anyhow!(
+            "Invalid memory map reported by the device, erase and program are blocked.\n{report}"
+        ));
+    }
+    if phases.erase && !options.mass_erase && !checks.erase_problems.is_empty() {
+        return Err(anyhow!(
+            "File can't be erased sector by sector, use --mass-erase instead."
+        ));
+    }
+
+    if let (true, Some(message)) = (phases.program, checks.downgrade_message()) {
+        if options.strict {
+            return Err(anyhow!("{message} Downgrading is refused with --strict."));
+        }
+        eprintln!("Warning: {message}");
+    }
+
+    if !checks.passed() {
+        if options.force {
+            eprintln!("Warning: file checks failed, continuing anyway.");
+        } else {
+            return Err(anyhow!(
+                "File does not match the device, use --force to continue anyway."
+            ));
+        }
+    }
+
+    let device_id = device.id;
+    let file_path = options.file_path.clone();
+    let block_cache = Arc::new(Mutex::new(cache::BlockCache::default()));
+    let update_options = update::Options {
+        mass_erase: options.mass_erase,
+        skip_blank_blocks: !options.program_blank,
+        differential: options.differential,
+        after_update: options.after_update,
+        targets,
+        range: options.range,
+        session_log: options.session_log_config(),
+        strict: options.strict,
+        ..Default::default()
+    };
+
+    let operation = journal::Operation::from_phases(&phases);
+    let mut record = journal::Record::new(&device.info, operation, &file_path, None);
+    record.set_file_hash(&history::file_hash(&file_path)?);
+
+    run_operation(record, options.timing, move |reporter| {
+        update::run(
+            device_id,
+            Some(file_path),
+            phases.operations(update_options.after_update),
+            block_cache,
+            update_options,
+            reporter,
+        )
+    })
+}
+
+/// Read the memory of the selected device into a file
+fn upload(options: &Options) -> Result<()> {
+    let device = select_device(options)?;
+    let device_id = device.id;
+    let file_path = options.file_path.clone();
+    let record = journal::Record::new(&device.info, journal::Operation::Backup, &file_path, None);
+    let update_options = update::Options {
+        session_log: options.session_log_config(),
+        ..Default::default()
+    };
+
+    run_operation(record, options.timing, move |reporter| {
+        update::run(
+            device_id,
+            None,
+            vec![update::Operation::Backup(file_path)],
+            Arc::new(Mutex::new(cache::BlockCache::default())),
+            update_options,
+            reporter,
+        )
+    })
+}
+
+/// Print the CRC-32 of the memory of the selected device covered by a file
+fn checksum(options: &Options) -> Result<()> {
+    let device = select_device(options)?;
+    let device_id = device.id;
+    let file_path = options.file_path.clone();
+    let mut record =
+        journal::Record::new(&device.info, journal::Operation::Verify, &file_path, None);
+    record.set_file_hash(&history::file_hash(&file_path)?);
+    let update_options = update::Options {
+        session_log: options.session_log_config(),
+        ..Default::default()
+    };
+
+    run_operation(record, options.timing, move |reporter| {
+        update::run(
+            device_id,
+            Some(file_path),
+            vec![update::Operation::Checksum],
+            Arc::new(Mutex::new(cache::BlockCache::default())),
+            update_options,
+            reporter,
+        )
+    })
+}
+
+/// Print the time spent in requests, poll timeouts and on the host
+fn print_timings(timings: &dfudev::timing::Timings) {
+    eprintln!("\nTotal:          {}", format::millis(timings.elapsed));
+    eprintln!("USB requests:   {}", format::millis(timings.request_time()));
+    eprintln!("Poll timeouts:  {}", format::millis(timings.poll_wait));
+    eprintln!("Host:           {}", format::millis(timings.host_time()));
+    eprintln!();
+
+    for (request, time) in &timings.requests {
+        eprintln!(
+            "{:<15} {:>7} requests, avg. {}, max. {}",
+            request.to_string(),
+            time.count,
+            format::millis(time.average()),
+            format::millis(time.max)
+        );
+    }
+}
+
+/// Return the device with the given serial number or the only one connected
+fn select_device(options: &Options) -> Result<dfudev::DfuDevice> {
+    // Transfer size and poll timeout are applied when the device is found
+    dfudev::settings::set(options.transfer);
+    let devices = dfudev::DfuDevice::find(false)?.unwrap_or_default();
+
+    match options.serial.as_deref() {
+        Some(serial) => devices
+            .into_iter()
+            .find(|device| device.info.serial_number_string == serial)
+            .ok_or_else(|| anyhow!("No DFU device with serial number {serial} found.")),
+        None => match devices.len() {
+            0 => Err(anyhow!("No DFU devices found.")),
+            1 => Ok(devices.into_iter().next().unwrap()),
+            _ => Err(anyhow!(
+                "Multiple DFU devices found, select one with --serial."
+            )),
+        },
+    }
+}
+
+/// Run an operation in a separate thread, print its progress and
+/// write the result to the journal
+fn run_operation(
+    mut record: journal::Record,
+    timing: bool,
+    operation: impl FnOnce(progress::Reporter) -> Result<()> + Send + 'static,
+) -> Result<()> {
+    let (message_sender, message_receiver) = std::sync::mpsc::channel();
+    let reporter = progress::Reporter::new(message_sender);
+    let job = reporter.job();
+    let worker = std::thread::spawn(move || operation(reporter));
+
+    let mut step = "";
+    let mut timings = None;
+    let mut unverified = None;
+
+    // The loop ends when the worker drops the sender
+    for message in message_receiver {
+        let Message::Job(job_event) = message else {
+            continue;
+        };
+        if job_event.job != job {
+            continue;
+        }
+
+        let progress = match job_event.event {
+            Event::Progress {
+                step, bytes, total, ..
+            } => Some((
+                match step {
+                    DeviceUpdateStep::Erase => "Erasing",
+                    DeviceUpdateStep::Program => "Programming",
+                    DeviceUpdateStep::Verify => "Verifying",
+                    DeviceUpdateStep::Backup => "Reading",
+                    DeviceUpdateStep::Leave => "Leaving",
+                    DeviceUpdateStep::Checksum => "Calculating checksum",
+                    DeviceUpdateStep::Compare => "Comparing",
+                },
+                progress::fraction(bytes, total),
+            )),
+            Event::Checksum(checksum) => {
+                println!("0x{checksum:08X}");
+                None
+            }
+            Event::Finished => {
+                eprintln!("\n{} finished successfully.", record.operation);
+                None
+            }
+            Event::FinishedUnverified(reason) => {
+                eprintln!(
+                    "\n{} finished, but not verified: {reason}",
+                    record.operation
+                );
+                unverified = Some(reason);
+                None
+            }
+            Event::BackupFinished(file_path) => {
+                eprintln!("\nBackup written to {}.", file_path.display());
+                if let Ok(file_hash) = history::file_hash(&file_path) {
+                    record.set_file_hash(&file_hash);
+                }
+                None
+            }
+            Event::Step(step) => {
+                record.start_step(step);
+                None
+            }
+            Event::Plan { skipped, .. } => {
+                for (step, reason) in skipped {
+                    eprintln!("Skipping {step:?}: {reason}");
+                }
+                None
+            }
+            Event::Timings(job_timings) => {
+                timings = Some(job_timings);
+                None
+            }
+            Event::Skipped(step, reason) => {
+                record.skip_step(step);
+                eprintln!("\nSkipped {step:?}: {reason}");
+                None
+            }
+            Event::Differences(differences) => {
+                eprintln!("\nDifferences from the file:");
+                for diff in differences {
+                    eprintln!("  {diff}");
+                }
+                None
+            }
+            _ => None,
+        };
+
+        if let Some((name, value)) = progress {
+            if name != step && !step.is_empty() {
+                eprintln!();
+            }
+            step = name;
+            eprint!("\r{name}: {:3.0}%", value * 100.0);
+            std::io::stderr().flush().ok();
+        }
+    }
+
+    let result = worker
+        .join()
+        .map_err(|_| anyhow!("Operation thread panicked."))?;
+
+    // An update that couldn't be verified is not a success
+    let result = result.and_then(|_| match unverified {
+        Some(reason) => Err(anyhow!("Not verified: {reason}")),
+        None => Ok(()),
+    });
+
+    if result.is_err() && !step.is_empty() {
+        eprintln!();
+    }
+
+    if let (true, Some(timings)) = (timing, timings) {
+        print_timings(&timings);
+    }
+
+    record.finish(result.as_ref().err().map(|error| format!("{error}")));
+    journal::append_or_log(&record);
+
+    result
+}
@@ -0,0 +1,196 @@
+//! Export and import of the application configuration
+//!
+//! All settings that are not specific to a single machine are written to a
+//! JSON file, so that several stations can be provisioned identically.
+
+use anyhow::{anyhow, Result};
+
+use crate::operator::Operator;
+use crate::profile::Profile;
+
+/// Version of the file format, increased on incompatible changes
+const FORMAT_VERSION: u32 = 1;
+
+/// Portable application configuration
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Config {
+    /// Version of the file format
+    pub format_version: u32,
+
+    /// Max. number of progress updates per second
+    pub progress_rate: u32,
+
+    /// Flag to erase the whole flash before programming
+    pub mass_erase: bool,
+
+    /// Flag to skip programming blocks of only 0xFF after erasing
+    pub skip_blank_blocks: bool,
+
+    /// Flag to only erase and program the sectors differing from the file
+    pub differential_update: bool,
+
+    /// Padding of the data of converted files
+    pub padding: crate::builder::Padding,
+
+    /// Transfer size, timeouts and polling behavior for all devices
+    pub transfer_settings: crate::dfudev::settings::TransferSettings,
+
+    /// Action after a successful update
+    pub after_update: crate::update::AfterUpdate,
+
+    /// Name of the board preset restricting the devices shown
+    pub preset: Option<String>,
+
+    /// Steps enabled for updates
+    pub phases: crate::update::Phases,
+
+    /// Memory maps entered manually for devices with malformed descriptors
+    pub memory_maps: Vec<crate::dfudev::quirks::MemoryMapOverride>,
+
+    /// Flag if an operator login is required before flashing
+    pub production_mode: bool,
+
+    /// Operators allowed to log in
+    pub operators: Vec<Operator>,
+
+    /// Flag if all actions modifying the device are disabled
+    pub inspector_mode: bool,
+
+    /// Flag if failed checks can't be overridden
+    pub strict_mode: bool,
+
+    /// Integrity checks required in addition to the suffix CRC
+    pub integrity_checks: Vec<crate::integrity::Algorithm>,
+
+    /// Base64 minisign public key signatures of files are verified with
+    pub public_key: String,
+
+    /// Entries of the quirks file, `None` to keep the file of the station
+    pub quirks: Option<serde_json::Value>,
+
+    /// Project profiles pairing devices with their firmware
+    pub profiles: Vec<Profile>,
+
+    /// Name of the selected profile
+    pub profile: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            format_version: FORMAT_VERSION,
+            progress_rate: crate::update::Options::default().progress_rate,
+            mass_erase: false,
+            skip_blank_blocks: true,
+            differential_update: false,
+            padding: Default::default(),
+            transfer_settings: Default::default(),
+            after_update: Default::default(),
+            preset: None,
+            phases: Default::default(),
+            memory_maps: Vec::new(),
+            production_mode: false,
+            operators: Vec::new(),
+            inspector_mode: false,
+            strict_mode: false,
+            integrity_checks: Vec::new(),
+            public_key: String::new(),
+            quirks: None,
+            profiles: Vec::new(),
+            profile: None,
+        }
+    }
+}
+
+impl Config {
+    /// Write the configuration to a file
+    pub fn export(&self, path: &std::path::Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+
+        Ok(())
+    }
+
+    /// Read a configuration from a file
+    pub fn import(path: &std::path::Path) -> Result<Self> {
+        let json = std::fs::read_to_string(path)?;
+        let config: Self = serde_json::from_str(&json)?;
+
+        if config.format_version > FORMAT_VERSION {
+            return Err(anyhow!(
+                "Configuration was created by a newer version of the application."
+            ));
+        }
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_import_round_trip() {
+        // All fields are listed, so a new one can't be missed by the test
+        let config = Config {
+            format_version: FORMAT_VERSION,
+            progress_rate: 3,
+            mass_erase: true,
+            skip_blank_blocks: false,
+            differential_update: true,
+            padding: crate::builder::Padding::Sector,
+            transfer_settings: crate::dfudev::settings::TransferSettings {
+                transfer_size: Some(1024),
+                timeout: 1000,
+                polling_retries: 2,
+                poll_timeout: Some(50),
+            },
+            after_update: crate::update::AfterUpdate::Leave,
+            preset: Some(String::from("Board")),
+            phases: crate::update::Phases {
+                erase: false,
+                program: true,
+                verify: false,
+            },
+            memory_maps: vec![crate::dfudev::quirks::MemoryMapOverride {
+                vendor_id: 0x0483,
+                product_id: 0xDF11,
+                serial_number: Some(String::from("ABC")),
+                memory_map: [(0, String::from("@Internal Flash /0x08000000/64*002Kg"))].into(),
+            }],
+            production_mode: true,
+            operators: vec![Operator::new("Operator", "1234")],
+            inspector_mode: true,
+            strict_mode: true,
+            integrity_checks: crate::integrity::Algorithm::ALL.to_vec(),
+            public_key: String::from("RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3"),
+            quirks: Some(serde_json::json!([{
+                "vendor_id": 0x0483,
+                "product_id": 0xDF11,
+                "quirks": { "transfer_size": 1024 }
+            }])),
+            profiles: vec![Profile {
+                name: String::from("Project"),
+                vendor_id: Some(0x0483),
+                product_id: None,
+                serial_number: String::from("ABC*"),
+                firmware: String::from("https://example.com/firmware.dfu"),
+                after_update: crate::update::AfterUpdate::UsbReset,
+            }],
+            profile: Some(String::from("Project")),
+        };
+
+        let path =
+            std::env::temp_dir().join(format!("dfu-buddy-{}-config-test.json", std::process::id()));
+        config.export(&path).unwrap();
+        let imported = Config::import(&path);
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(
+            serde_json::to_value(imported.unwrap()).unwrap(),
+            serde_json::to_value(config).unwrap()
+        );
+    }
+}
@@ -0,0 +1,501 @@
+//! Conversion of other firmware formats into DFU files
+//!
+//! Raw binaries and ELF files are converted into temporary DFU files, which
+//! are then handled like any other DFU file.
+
+use std::collections::BTreeMap;
+use std::io::{Read, Seek};
+
+use anyhow::{anyhow, Result};
+
+use crate::dfudev::dfuse::MemorySegment;
+use crate::{builder, dfudev, elf};
+
+/// Max. gap between ELF segments that is filled for plain DFU devices
+const MAX_PLAIN_GAP: u32 = 1024 * 1024;
+
+/// Size of a word for [`builder::Padding::Word`]
+const WORD_SIZE: u32 = 4;
+
+/// Filters of the open file dialog as name and extensions
+pub const FILE_FILTERS: [(&str, &[&str]); 4] = [
+    ("DFU files", &["dfu"]),
+    ("Binary files", &["bin"]),
+    ("ELF files", &["elf", "axf", "out"]),
+    ("Zip archives", &["zip"]),
+];
+
+/// Return the name of the file filter matching the extension of a file
+pub fn file_filter(path: &std::path::Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+
+    FILE_FILTERS
+        .iter()
+        .find(|(_, extensions)| extensions.contains(&extension.as_str()))
+        .map(|(name, _)| *name)
+}
+
+/// Return if a file can be opened, recognized by its content or extension
+pub fn is_supported(path: &std::path::Path) -> bool {
+    path.is_file() && (matches!(sniff(path), Ok(Some(_))) || file_filter(path).is_some())
+}
+
+/// Formats that can be converted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceFormat {
+    /// Raw binary placed at a user-defined address
+    Binary,
+
+    /// ELF file with the addresses taken from its loadable segments
+    Elf,
+}
+
+impl SourceFormat {
+    /// Return the format of a file or `None` for DFU files
+    ///
+    /// The content is checked first, so files with a DFU suffix or the ELF
+    /// magic bytes are recognized regardless of their name. Otherwise the
+    /// extension decides, unknown extensions are opened as DFU files.
+    pub fn detect(path: &std::path::Path) -> Option<Self> {
+        match sniff(path) {
+            Ok(Some(Sniffed::Dfu)) => return None,
+            Ok(Some(Sniffed::Elf)) => return Some(Self::Elf),
+            Ok(None) => {}
+            Err(error) => log::debug!("Error reading file header: {error}"),
+        }
+
+        let extension = path.extension()?.to_string_lossy().to_lowercase();
+
+        match extension.as_str() {
+            "bin" => Some(Self::Binary),
+            "elf" | "axf" | "out" => Some(Self::Elf),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for SourceFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Binary => write!(f, "Binary"),
+            Self::Elf => write!(f, "ELF"),
+        }
+    }
+}
+
+/// File type recognized by its content
+enum Sniffed {
+    /// File ends with a DFU suffix
+    Dfu,
+
+    /// File starts with the ELF magic bytes
+    Elf,
+}
+
+/// Return the file type recognized by the content or `None` if unknown
+fn sniff(path: &std::path::Path) -> std::io::Result<Option<Sniffed>> {
+    let mut file = std::fs::File::open(path)?;
+    let length = file.metadata()?.len();
+
+    let mut header = [0; 4];
+    if length >= header.len() as u64 {
+        file.read_exact(&mut header)?;
+        if elf::is_elf(&header) {
+            return Ok(Some(Sniffed::Elf));
+        }
+    }
+
+    // Signature is stored at the same offset from the end in all suffix versions
+    let mut suffix = [0; builder::SUFFIX_LENGTH as usize];
+    if length >= suffix.len() as u64 {
+        file.seek(std::io::SeekFrom::End(-(suffix.len() as i64)))?;
+        file.read_exact(&mut suffix)?;
+        if &suffix[8..11] == b"UFD" {
+            return Ok(Some(Sniffed::Dfu));
+        }
+    }
+
+    Ok(None)
+}
+
+/// File with the settings to convert it into a DFU file
+pub struct ConvertedFile {
+    /// Path of the source file
+    pub path: std::path::PathBuf,
+
+    /// Format of the source file
+    pub format: SourceFormat,
+
+    /// Alternate setting of the target, for ELF files only used if no
+    /// memory segment of the device contains the data
+    pub alt_setting: u8,
+
+    /// Start address of a binary in the target memory
+    pub address: u32,
+
+    /// Text of the address input field
+    pub address_text: String,
+
+    /// Path of the temporary DFU file
+    dfu_path: Option<std::path::PathBuf>,
+}
+
+impl ConvertedFile {
+    /// Default start address if the device provides no memory layout
+    const DEFAULT_ADDRESS: u32 = 0x0800_0000;
+
+    /// Return a new instance, targeting the first writable region
+    /// of the device if known
+    pub fn new(
+        path: &std::path::Path,
+        format: SourceFormat,
+        device: Option<&dfudev::DfuDevice>,
+    ) -> Self {
+        let (alt_setting, address) = device
+            .and_then(|device| {
+                memory_segments(device).find_map(|(alt, segment)| {
+                    segment
+                        .regions
+                        .iter()
+                        .find(|region| region.writable)
+                        .map(|region| (alt, region.start_address))
+                })
+            })
+            .unwrap_or((0, Self::DEFAULT_ADDRESS));
+
+        Self {
+            path: path.to_path_buf(),
+            format,
+            alt_setting,
+            address,
+            address_text: format!("{address:08X}"),
+            dfu_path: None,
+        }
+    }
+
+    /// Convert the file into a temporary DFU file matching the device and
+    /// return its path
+    ///
+    /// A DfuSe file is created for DfuSe devices or if no device is given,
+    /// a plain DFU file otherwise. The data is padded as given by `padding`.
+    pub fn convert(
+        &mut self,
+        device: Option<&dfudev::DfuDevice>,
+        padding: builder::Padding,
+    ) -> Result<std::path::PathBuf> {
+        let dfu_version =
+            device.map_or(builder::DFU_VERSION_DFUSE, |device| device.info.dfu_version);
+        let data = std::fs::read(&self.path)?;
+
+        let elements = match self.format {
+            SourceFormat::Binary => vec![builder::Element {
+                address: self.address,
+                data,
+            }],
+            SourceFormat::Elf => elf::load_segments(&data)?
+                .into_iter()
+                .map(|segment| builder::Element {
+                    address: segment.address,
+                    data: segment.data,
+                })
+                .collect(),
+        };
+
+        let suffix = builder::Suffix::wildcard(dfu_version);
+        let content = if dfu_version == builder::DFU_VERSION_DFUSE {
+            let images: Vec<_> = self
+                .images(elements, device)
+                .into_iter()
+                .map(|image| pad_image(image, padding, device))
+                .collect();
+            builder::dfuse_file(&images, &suffix)
+        } else {
+            let mut data = flatten(elements)?;
+            pad_plain(&mut data, padding, device);
+            builder::plain_file(&data, &suffix)
+        };
+
+        let name = self
+            .path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let dfu_path = builder::write_temp_file(&name, &content)?;
+
+        self.remove_dfu_file();
+        self.dfu_path = Some(dfu_path.clone());
+
+        Ok(dfu_path)
+    }
+
+    /// Group the elements into images by the memory segment containing them
+    fn images(
+        &self,
+        elements: Vec<builder::Element>,
+        device: Option<&dfudev::DfuDevice>,
+    ) -> Vec<builder::Image> {
+        let segments: Vec<_> = device.map(memory_segments).into_iter().flatten().collect();
+        let mut images: BTreeMap<u8, Vec<builder::Element>> = BTreeMap::new();
+
+        for element in elements {
+            let alt_setting = match self.format {
+                SourceFormat::Binary => self.alt_setting,
+                SourceFormat::Elf => segments
+                    .iter()
+                    .find(|(_, segment)| {
+                        segment.is_writable(element.address, element.data.len() as u32)
+                    })
+                    .map_or(self.alt_setting, |(alt, _)| *alt),
+            };
+            images.entry(alt_setting).or_default().push(element);
+        }
+
+        images
+            .into_iter()
+            .map(|(alt_setting, elements)| builder::Image {
+                alt_setting,
+                name: None,
+                elements,
+            })
+            .collect()
+    }
+
+    /// Remove the temporary DFU file
+    fn remove_dfu_file(&mut self) {
+        if let Some(dfu_path) = self.dfu_path.take() {
+            std::fs::remove_file(dfu_path).ok();
+        }
+    }
+}
+
+impl Drop for ConvertedFile {
+    fn drop(&mut self) {
+        self.remove_dfu_file();
+    }
+}
+
+/// Return the alt settings of a device with a DfuSe memory layout,
+/// excluding read-only areas
+fn memory_segments(device: &dfudev::DfuDevice) -> impl Iterator<Item = (u8, MemorySegment)> + '_ {
+    device
+        .info
+        .alt_settings
+        .iter()
+        .filter(|(_, name)| name.starts_with('@'))
+        .filter_map(|(alt, name)| Some((*alt, MemorySegment::from_string_desc(name).ok()?)))
+        .filter(|(_, segment)| !segment.is_protected())
+}
+
+/// Return an image with its elements padded
+///
+/// Sector padding requires the memory layout of the target, without it the
+/// elements are left as they are.
+fn pad_image(
+    image: builder::Image,
+    padding: builder::Padding,
+    device: Option<&dfudev::DfuDevice>,
+) -> builder::Image {
+    let elements = match padding {
+        builder::Padding::None => image.elements,
+        builder::Padding::Word => builder::pad_elements(image.elements, |start, end| {
+            builder::align_range(start, end, WORD_SIZE)
+        }),
+        builder::Padding::Sector => {
+            let segment = device
+                .into_iter()
+                .flat_map(memory_segments)
+                .find(|(alt, _)| *alt == image.alt_setting)
+                .map(|(_, segment)| segment);
+
+            match segment {
+                Some(segment) => builder::pad_elements(image.elements, |start, end| {
+                    let start = segment.sector_at(start).map_or(start, |(sector, _)| sector);
+                    let end = segment
+                        .sector_at(end.saturating_sub(1))
+                        .map_or(end, |(sector, size)| sector.saturating_add(size));
+                    (start, end)
+                }),
+                None => {
+                    log::warn!(
+                        "No memory layout for alt setting {}, data is not padded to sectors",
+                        image.alt_setting
+                    );
+                    image.elements
+                }
+            }
+        }
+    };
+
+    builder::Image { elements, ..image }
+}
+
+/// Pad the data of a plain DFU file, which has no sectors, so sector padding
+/// uses the transfer size of the device
+fn pad_plain(data: &mut Vec<u8>, padding: builder::Padding, device: Option<&dfudev::DfuDevice>) {
+    let alignment = match padding {
+        builder::Padding::None => return,
+        builder::Padding::Word => WORD_SIZE,
+        builder::Padding::Sector => device.map_or(0, |device| device.info.dfu_transfer_size as u32),
+    };
+
+    let (_, end) = builder::align_range(0, data.len() as u32, alignment);
+    data.resize(end as usize, 0xFF);
+}
+
+/// Return the data of all elements as one block with gaps filled by 0xFF
+fn flatten(mut elements: Vec<builder::Element>) -> Result<Vec<u8>> {
+    elements.sort_by_key(|element| element.address);
+
+    let base_address = elements.first().map_or(0, |element| element.address);
+    let mut data = Vec::new();
+
+    for element in elements {
+        let offset = (element.address - base_address) as usize;
+        if offset > data.len() + MAX_PLAIN_GAP as usize {
+            return Err(anyhow!(Error::GapTooLarge(element.address)));
+        }
+        if offset < data.len() {
+            return Err(anyhow!(Error::Overlap(element.address)));
+        }
+        data.resize(offset, 0xFF);
+        data.extend_from_slice(&element.data);
+    }
+
+    Ok(data)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Errors when converting files
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    /// Gap before an element is too large to be filled
+    GapTooLarge(u32),
+
+    /// Element overlaps the previous one
+    Overlap(u32),
+}
+
+impl std::error::Error for Error {}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::GapTooLarge(address) => write!(
+                f,
+                "Gap before address 0x{address:08X} is too large for a plain DFU file."
+            ),
+            Self::Overlap(address) => {
+                write!(f, "Data at address 0x{address:08X} overlaps previous data.")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Return an element with the data at the address
+    fn element(address: u32, data: &[u8]) -> builder::Element {
+        builder::Element {
+            address,
+            data: data.to_vec(),
+        }
+    }
+
+    /// Return an image of alt setting 0 with the elements
+    fn image(elements: Vec<builder::Element>) -> builder::Image {
+        builder::Image {
+            alt_setting: 0,
+            name: None,
+            elements,
+        }
+    }
+
+    #[test]
+    fn flatten_fills_gaps() {
+        let data = flatten(vec![element(0x104, &[3, 4]), element(0x100, &[1, 2])]).unwrap();
+
+        assert_eq!(data, [1, 2, 0xFF, 0xFF, 3, 4]);
+    }
+
+    #[test]
+    fn flatten_overlapping_elements() {
+        let error = flatten(vec![element(0x100, &[0; 8]), element(0x104, &[0; 4])]).unwrap_err();
+
+        assert_eq!(error.downcast_ref::<Error>(), Some(&Error::Overlap(0x104)));
+    }
+
+    #[test]
+    fn flatten_gap_too_large() {
+        let address = MAX_PLAIN_GAP + 2;
+        let error = flatten(vec![element(0, &[1]), element(address, &[2])]).unwrap_err();
+
+        assert_eq!(
+            error.downcast_ref::<Error>(),
+            Some(&Error::GapTooLarge(address))
+        );
+    }
+
+    #[test]
+    fn plain_word_padding() {
+        let mut data = vec![1, 2, 3, 4, 5];
+        pad_plain(&mut data, builder::Padding::Word, None);
+
+        assert_eq!(data, [1, 2, 3, 4, 5, 0xFF, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn word_padding() {
+        let padded = pad_image(
+            image(vec![element(0x0800_0002, &[1, 2, 3])]),
+            builder::Padding::Word,
+            None,
+        );
+
+        assert_eq!(padded.elements.len(), 1);
+        assert_eq!(padded.elements[0].address, 0x0800_0000);
+        assert_eq!(
+            padded.elements[0].data,
+            [0xFF, 0xFF, 1, 2, 3, 0xFF, 0xFF, 0xFF]
+        );
+    }
+
+    #[test]
+    fn sector_padding_merges_elements() {
+        let simulation =
+            dfudev::simulator::SimulatedDevice::new("@Internal Flash  /0x08000000/04*001Kg", 256)
+                .unwrap();
+        let device = simulation.device();
+
+        let padded = pad_image(
+            image(vec![
+                element(0x0800_0010, &[1; 4]),
+                element(0x0800_0100, &[2; 4]),
+                element(0x0800_0404, &[3; 4]),
+            ]),
+            builder::Padding::Sector,
+            Some(&device),
+        );
+
+        assert_eq!(padded.elements.len(), 1);
+        assert_eq!(padded.elements[0].address, 0x0800_0000);
+        assert_eq!(padded.elements[0].data.len(), 2048);
+        assert_eq!(padded.elements[0].data[0x0F..0x11], [0xFF, 1]);
+        assert_eq!(padded.elements[0].data[0x100], 2);
+        assert_eq!(padded.elements[0].data[0x404], 3);
+    }
+
+    #[test]
+    fn sector_padding_without_layout() {
+        let padded = pad_image(
+            image(vec![element(0x0800_0010, &[1; 4])]),
+            builder::Padding::Sector,
+            None,
+        );
+
+        assert_eq!(padded.elements[0].address, 0x0800_0010);
+        assert_eq!(padded.elements[0].data, [1; 4]);
+    }
+}
@@ -0,0 +1,253 @@
+//! Simulated device and file for demonstrations
+//!
+//! Started with `--demo`, the application shows a simulated STM32 device
+//! instead of the connected ones and opens a generated DFU file for it. All
+//! operations run with plausible timing and progress, but without any USB
+//! transfers, so the workflow can be shown and documented without hardware.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+
+use crate::dfudev::{self, dfuse::MemorySegment};
+use crate::progress::{self, Event};
+use crate::{builder, cache, update, DeviceUpdateStep, SectorState};
+
+/// Memory layout of the internal flash of the simulated device
+const FLASH_LAYOUT: &str = "@Internal Flash  /0x08000000/04*016Kg,01*064Kg,07*128Kg";
+
+/// Memory layout of the option bytes of the simulated device
+const OPTION_BYTES_LAYOUT: &str = "@Option Bytes  /0x1FFFC000/01*016 e";
+
+/// Size of the firmware in the demo file
+const FIRMWARE_SIZE: usize = 200 * 1024;
+
+/// Simulated transfer rates in bytes per second, by step
+const ERASE_RATE: f32 = 64.0 * 1024.0;
+const PROGRAM_RATE: f32 = 32.0 * 1024.0;
+const READ_RATE: f32 = 128.0 * 1024.0;
+
+/// Return the simulated device
+pub fn device() -> dfudev::DfuDevice {
+    let mut info = dfudev::DeviceInfo {
+        vendor_id: 0x0483,
+        product_id: 0xDF11,
+        device_version: "2.00".to_string(),
+        bcd_device: 0x0200,
+        manufacturer_string: "STMicroelectronics".to_string(),
+        product_string: "STM32 BOOTLOADER (Demo)".to_string(),
+        serial_number_string: "DEMO00000001".to_string(),
+        dfu_config_number: 1,
+        dfu_interface_number: 0,
+        alt_settings: vec![
+            (0, FLASH_LAYOUT.to_string()),
+            (1, OPTION_BYTES_LAYOUT.to_string()),
+        ],
+        dfu_attributes: 0x0B,
+        can_download: true,
+        can_upload: true,
+        manifestation_tolerant: false,
+        will_detach: true,
+        dfu_detach_timeout: 255,
+        dfu_transfer_size: 2048,
+        dfu_version: builder::DFU_VERSION_DFUSE,
+        bus_number: 1,
+        address: 1,
+        port_numbers: vec![1],
+        family: None,
+        driver: Default::default(),
+        quirks: Default::default(),
+    };
+    info.family = dfudev::family::detect(&info);
+
+    dfudev::DfuDevice::simulated(info)
+}
+
+/// Write a DFU file matching the simulated device and return its path
+pub fn file() -> std::io::Result<std::path::PathBuf> {
+    // Some structure, so the hex dump and diff views show more than a fill pattern
+    let data = (0..FIRMWARE_SIZE)
+        .map(|offset| (offset as u32).wrapping_mul(2_654_435_761).to_le_bytes()[3])
+        .collect();
+
+    let image = builder::Image {
+        alt_setting: 0,
+        name: Some("Demo Firmware".to_string()),
+        elements: vec![builder::Element {
+            address: 0x0800_0000,
+            data,
+        }],
+    };
+    let suffix = builder::Suffix {
+        device_version: 0x0200,
+        product_id: 0xDF11,
+        vendor_id: 0x0483,
+        dfu_version: builder::DFU_VERSION_DFUSE,
+    };
+
+    builder::write_temp_file("demo", &builder::dfuse_file(&[image], &suffix))
+}
+
+/// Simulate the operations on the simulated device
+///
+/// Takes the same arguments as [`update::run`], so both can be used
+/// interchangeably.
+pub fn run(
+    _device_id: u64,
+    file_path: Option<std::path::PathBuf>,
+    operations: Vec<update::Operation>,
+    _block_cache: Arc<Mutex<cache::BlockCache>>,
+    options: update::Options,
+    reporter: progress::Reporter,
+) -> Result<()> {
+    reporter.send(Event::Started)?;
+
+    let elements = match &file_path {
+        Some(file_path) => elements(file_path, &options)?,
+        None => Vec::new(),
+    };
+    let payload_size: u64 = elements.iter().map(|(_, size)| *size as u64).sum();
+
+    reporter
+        .send(Event::Plan {
+            steps: operations.iter().map(update::Operation::step).collect(),
+            skipped: Vec::new(),
+            payload_size,
+        })
+        .ok();
+
+    let segment = MemorySegment::from_string_desc(FLASH_LAYOUT)?;
+    let interval = options.progress_interval();
+
+    for operation in &operations {
+        if reporter.is_cancelled() {
+            return Err(anyhow!(update::Error::Cancelled));
+        }
+
+        let step = operation.step();
+        reporter.send(Event::Step(step))?;
+
+        match operation {
+            update::Operation::Erase => erase(&segment, &elements, interval, &reporter),
+            update::Operation::Program => {
+                transfer(step, payload_size, PROGRAM_RATE, interval, &reporter)
+            }
+            update::Operation::Verify
+            | update::Operation::Compare
+            | update::Operation::Checksum => {
+                transfer(step, payload_size, READ_RATE, interval, &reporter);
+                if *operation == update::Operation::Checksum {
+                    reporter.send(Event::Checksum(0x1234_ABCD)).ok();
+                }
+            }
+            update::Operation::Backup(backup_path) => {
+                let size = segment.size();
+                transfer(step, size, READ_RATE, interval, &reporter);
+                std::fs::write(backup_path, vec![0xFF; size as usize])?;
+                reporter
+                    .send(Event::BackupFinished(backup_path.clone()))
+                    .ok();
+            }
+            update::Operation::Leave => transfer(step, 1, 1.0, interval, &reporter),
+        }
+    }
+
+    reporter.send(Event::Finished)?;
+
+    Ok(())
+}
+
+/// Return start address and size of the elements of a file written to the flash
+fn elements(file_path: &std::path::Path, options: &update::Options) -> Result<Vec<(u32, u32)>> {
+    let file = dfufile::DfuFile::open(file_path)?;
+
+    let elements = match &file.content {
+        dfufile::Content::Plain => {
+            let size = std::fs::metadata(file_path)?.len() - builder::SUFFIX_LENGTH as u64;
+            vec![(0x0800_0000, size as u32)]
+        }
+        dfufile::Content::DfuSe(content) => update::selected_images(content, options)
+            .into_iter()
+            .flat_map(|(_, image)| &image.image_elements)
+            .filter_map(|element| update::element_range(element, options.range))
+            .collect(),
+    };
+
+    Ok(elements)
+}
+
+/// Simulate erasing the sectors covered by the elements
+fn erase(
+    segment: &MemorySegment,
+    elements: &[(u32, u32)],
+    interval: Duration,
+    reporter: &progress::Reporter,
+) {
+    let mut sectors = Vec::new();
+    for region in &segment.regions {
+        let mut address = region.start_address;
+        while address < region.end_address {
+            let end_address = address + region.sector_size;
+            let covered = elements
+                .iter()
+                .any(|&(start, size)| start < end_address && start + size > address);
+            if covered {
+                sectors.push((address, region.sector_size));
+            }
+            address = end_address;
+        }
+    }
+
+    reporter
+        .send(Event::EraseTarget {
+            name: segment.name.clone(),
+            sectors: sectors.clone(),
+        })
+        .ok();
+
+    let total: u32 = sectors.iter().map(|(_, size)| size).sum();
+    let mut erased = 0;
+
+    for (index, (address, size)) in sectors.iter().enumerate() {
+        if reporter.is_cancelled() {
+            return;
+        }
+
+        reporter
+            .send(Event::SectorState(index, SectorState::Erasing))
+            .ok();
+        std::thread::sleep(Duration::from_secs_f32(*size as f32 / ERASE_RATE).max(interval));
+        reporter
+            .send(Event::SectorState(index, SectorState::Done))
+            .ok();
+
+        erased += size;
+        reporter
+            .send(Event::Progress {
+                step: DeviceUpdateStep::Erase,
+                bytes: erased as u64,
+                total: total as u64,
+                address: Some(*address),
+            })
+            .ok();
+    }
+}
+
+/// Simulate a transfer of a number of bytes, reporting the progress until
+/// the job is cancelled
+fn transfer(
+    step: DeviceUpdateStep,
+    size: u64,
+    rate: f32,
+    interval: Duration,
+    reporter: &progress::Reporter,
+) {
+    let mut bytes = 0;
+
+    while bytes < size && !reporter.is_cancelled() {
+        std::thread::sleep(interval);
+        bytes = size.min(bytes + (rate * interval.as_secs_f32()).max(1.0) as u64);
+        reporter.send(Event::progress(step, bytes, size)).ok();
+    }
+}
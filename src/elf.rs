@@ -0,0 +1,299 @@
+//! Extraction of loadable segments from ELF files
+//!
+//! Only the program headers are evaluated. The data of each `PT_LOAD` segment
+//! is placed at its physical address, which is the load address in flash for
+//! typical embedded firmware.
+
+use anyhow::{anyhow, Result};
+
+/// Magic bytes at the start of each ELF file
+pub const MAGIC: &[u8; 4] = b"\x7fELF";
+
+/// Program header type of loadable segments
+const PT_LOAD: u32 = 1;
+
+/// Loadable segment with address and data
+#[derive(Debug, Clone)]
+pub struct Segment {
+    /// Physical start address
+    pub address: u32,
+
+    /// Data stored in the file, without the zero-initialized part
+    pub data: Vec<u8>,
+}
+
+/// Reader for fields with the endianness and word size of the file
+struct Reader<'a> {
+    /// File content
+    data: &'a [u8],
+
+    /// Flag for 64-bit files
+    is_64: bool,
+
+    /// Flag for big-endian files
+    big_endian: bool,
+}
+
+impl Reader<'_> {
+    /// Return the bytes at `offset`
+    fn bytes<const N: usize>(&self, offset: usize) -> Result<[u8; N]> {
+        self.data
+            .get(offset..offset + N)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| anyhow!(Error::Truncated))
+    }
+
+    /// Return a 16-bit value at `offset`
+    fn u16(&self, offset: usize) -> Result<u16> {
+        let bytes = self.bytes(offset)?;
+        Ok(if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    /// Return a 32-bit value at `offset`
+    fn u32(&self, offset: usize) -> Result<u32> {
+        let bytes = self.bytes(offset)?;
+        Ok(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    /// Return an address or offset at `offset`, 32 or 64 bits wide
+    fn word(&self, offset: usize) -> Result<u64> {
+        if self.is_64 {
+            let bytes = self.bytes(offset)?;
+            Ok(if self.big_endian {
+                u64::from_be_bytes(bytes)
+            } else {
+                u64::from_le_bytes(bytes)
+            })
+        } else {
+            self.u32(offset).map(u64::from)
+        }
+    }
+}
+
+/// Return if the data starts with the ELF magic bytes
+pub fn is_elf(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Return all loadable segments with data, sorted by address
+pub fn load_segments(data: &[u8]) -> Result<Vec<Segment>> {
+    if !is_elf(data) {
+        return Err(anyhow!(Error::InvalidMagic));
+    }
+
+    let reader = Reader {
+        data,
+        is_64: match data.get(4) {
+            Some(1) => false,
+            Some(2) => true,
+            _ => return Err(anyhow!(Error::Unsupported)),
+        },
+        big_endian: match data.get(5) {
+            Some(1) => false,
+            Some(2) => true,
+            _ => return Err(anyhow!(Error::Unsupported)),
+        },
+    };
+
+    // Offsets in the file header and program headers differ for 32 and 64 bit
+    let (phoff_offset, phentsize_offset) = if reader.is_64 {
+        (0x20, 0x36)
+    } else {
+        (0x1C, 0x2A)
+    };
+    let phoff = reader.word(phoff_offset)? as usize;
+    let phentsize = reader.u16(phentsize_offset)? as usize;
+    let phnum = reader.u16(phentsize_offset + 2)? as usize;
+
+    let mut segments = Vec::new();
+
+    for index in 0..phnum {
+        let header = phoff + index * phentsize;
+
+        if reader.u32(header)? != PT_LOAD {
+            continue;
+        }
+
+        let (offset, paddr, filesz) = if reader.is_64 {
+            (
+                reader.word(header + 0x08)?,
+                reader.word(header + 0x18)?,
+                reader.word(header + 0x20)?,
+            )
+        } else {
+            (
+                reader.word(header + 0x04)?,
+                reader.word(header + 0x0C)?,
+                reader.word(header + 0x10)?,
+            )
+        };
+
+        if filesz == 0 {
+            continue;
+        }
+
+        let address = u32::try_from(paddr)
+            .ok()
+            .filter(|address| *address as u64 + filesz <= u32::MAX as u64 + 1)
+            .ok_or_else(|| anyhow!(Error::AddressOutOfRange(paddr)))?;
+
+        let segment_data = offset
+            .checked_add(filesz)
+            .and_then(|end| data.get(offset as usize..end as usize))
+            .ok_or_else(|| anyhow!(Error::Truncated))?;
+
+        segments.push(Segment {
+            address,
+            data: segment_data.to_vec(),
+        });
+    }
+
+    if segments.is_empty() {
+        return Err(anyhow!(Error::NoLoadableSegments));
+    }
+
+    segments.sort_by_key(|segment| segment.address);
+
+    // Overlapping data would be written twice, one part silently lost
+    for pair in segments.windows(2) {
+        if (pair[0].address as u64 + pair[0].data.len() as u64) > pair[1].address as u64 {
+            return Err(anyhow!(Error::OverlappingSegments(pair[1].address)));
+        }
+    }
+
+    Ok(segments)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Errors when reading ELF files
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    /// File does not start with the ELF magic bytes
+    InvalidMagic,
+
+    /// Class or data encoding not supported
+    Unsupported,
+
+    /// File ends before a header or segment
+    Truncated,
+
+    /// Segment address not in 32-bit address space
+    AddressOutOfRange(u64),
+
+    /// File contains no loadable segments with data
+    NoLoadableSegments,
+
+    /// Segment at the address overlaps the previous one
+    OverlappingSegments(u32),
+}
+
+impl std::error::Error for Error {}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::InvalidMagic => "File is not an ELF file.".to_string(),
+                Self::Unsupported => "ELF class or data encoding not supported.".to_string(),
+                Self::Truncated => "ELF file is truncated.".to_string(),
+                Self::AddressOutOfRange(address) =>
+                    format!("Segment address 0x{address:X} exceeds the 32-bit address space."),
+                Self::NoLoadableSegments => "ELF file contains no loadable segments.".to_string(),
+                Self::OverlappingSegments(address) =>
+                    format!("Segment at address 0x{address:08X} overlaps the previous one."),
+            }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Return a little-endian 32-bit ELF file with a `PT_LOAD` segment for
+    /// each physical address and data
+    fn elf32(segments: &[(u32, &[u8])]) -> Vec<u8> {
+        let phoff = 52;
+        let mut data = vec![0; phoff + segments.len() * 32];
+        data[..4].copy_from_slice(MAGIC);
+        data[4] = 1;
+        data[5] = 1;
+        data[0x1C..0x20].copy_from_slice(&(phoff as u32).to_le_bytes());
+        data[0x2A..0x2C].copy_from_slice(&32u16.to_le_bytes());
+        data[0x2C..0x2E].copy_from_slice(&(segments.len() as u16).to_le_bytes());
+
+        for (index, (paddr, content)) in segments.iter().enumerate() {
+            let header = phoff + index * 32;
+            let offset = data.len() as u32;
+            data[header..header + 4].copy_from_slice(&PT_LOAD.to_le_bytes());
+            data[header + 0x04..header + 0x08].copy_from_slice(&offset.to_le_bytes());
+            data[header + 0x0C..header + 0x10].copy_from_slice(&paddr.to_le_bytes());
+            data[header + 0x10..header + 0x14]
+                .copy_from_slice(&(content.len() as u32).to_le_bytes());
+            data.extend_from_slice(content);
+        }
+
+        data
+    }
+
+    /// Return the error of loading the segments
+    fn load_error(data: &[u8]) -> Error {
+        load_segments(data).unwrap_err().downcast().unwrap()
+    }
+
+    #[test]
+    fn segments_sorted_by_address() {
+        let data = elf32(&[(0x0800_1000, &[3, 4]), (0x0800_0000, &[1, 2])]);
+
+        let segments = load_segments(&data).unwrap();
+
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].address, 0x0800_0000);
+        assert_eq!(segments[0].data, [1, 2]);
+        assert_eq!(segments[1].address, 0x0800_1000);
+        assert_eq!(segments[1].data, [3, 4]);
+    }
+
+    #[test]
+    fn overlapping_segments() {
+        let data = elf32(&[(0x0800_0000, &[0; 16]), (0x0800_0008, &[0; 16])]);
+
+        assert_eq!(load_error(&data), Error::OverlappingSegments(0x0800_0008));
+    }
+
+    #[test]
+    fn address_out_of_range() {
+        let data = elf32(&[(0xFFFF_FFF0, &[0; 32])]);
+
+        assert_eq!(load_error(&data), Error::AddressOutOfRange(0xFFFF_FFF0));
+    }
+
+    #[test]
+    fn truncated_segment() {
+        let mut data = elf32(&[(0x0800_0000, &[0; 16])]);
+        data.truncate(data.len() - 1);
+
+        assert_eq!(load_error(&data), Error::Truncated);
+    }
+
+    #[test]
+    fn no_loadable_segments() {
+        assert_eq!(load_error(&elf32(&[])), Error::NoLoadableSegments);
+    }
+
+    #[test]
+    fn invalid_magic() {
+        assert_eq!(load_error(b"\x7fELG\x01\x01"), Error::InvalidMagic);
+    }
+}
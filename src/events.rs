@@ -0,0 +1,125 @@
+//! Log of device lifecycle events of the current session
+//!
+//! Events are kept in memory only. They help to diagnose flaky cables and
+//! enumeration issues, e.g. a device that disappears and reappears repeatedly.
+
+use std::collections::VecDeque;
+use std::time::SystemTime;
+
+/// Max. number of events kept, older ones are dropped
+const MAX_EVENTS: usize = 1000;
+
+/// Kind of event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    /// Device found by a scan
+    Appeared,
+
+    /// Device no longer connected
+    Disappeared,
+
+    /// Device selected by the user or automatically
+    Selected,
+
+    /// Device opened for an operation
+    Opened,
+
+    /// Error accessing the device
+    Error,
+}
+
+impl std::fmt::Display for EventKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Appeared => write!(f, "Appeared"),
+            Self::Disappeared => write!(f, "Disappeared"),
+            Self::Selected => write!(f, "Selected"),
+            Self::Opened => write!(f, "Opened"),
+            Self::Error => write!(f, "Error"),
+        }
+    }
+}
+
+/// Single event
+#[derive(Debug, Clone)]
+pub struct Event {
+    /// Time of the event
+    pub time: SystemTime,
+
+    /// Kind of event
+    pub kind: EventKind,
+
+    /// Short description of the device
+    pub device: String,
+
+    /// Additional information, e.g. the error message
+    pub details: String,
+}
+
+impl Event {
+    /// Return the time of day in UTC with milliseconds
+    pub fn time_of_day(&self) -> String {
+        crate::format::time_of_day(self.time)
+    }
+}
+
+/// Events of the current session, oldest first
+#[derive(Debug, Default)]
+pub struct EventLog {
+    /// Recorded events
+    events: VecDeque<Event>,
+}
+
+impl EventLog {
+    /// Record an event that happened now
+    pub fn push(&mut self, kind: EventKind, device: &crate::dfudev::DeviceInfo, details: &str) {
+        self.push_text(kind, describe(device), details);
+    }
+
+    /// Record an event for a device given as text
+    pub fn push_text(&mut self, kind: EventKind, device: String, details: &str) {
+        log::debug!("Device event: {kind} {device} {details}");
+
+        if self.events.len() == MAX_EVENTS {
+            self.events.pop_front();
+        }
+
+        self.events.push_back(Event {
+            time: SystemTime::now(),
+            kind,
+            device,
+            details: details.to_string(),
+        });
+    }
+
+    /// Return all events, oldest first
+    pub fn events(&self) -> impl DoubleEndedIterator<Item = &Event> {
+        self.events.iter()
+    }
+
+    /// Return the number of events
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Return if no event was recorded
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Remove all events
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+}
+
+/// Return a short description of a device
+pub fn describe(device: &crate::dfudev::DeviceInfo) -> String {
+    format!(
+        "{:04x}:{:04x} S/N {} at {}",
+        device.vendor_id,
+        device.product_id,
+        device.serial_number_string,
+        device.bus_path()
+    )
+}
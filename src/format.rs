@@ -0,0 +1,148 @@
+//! Formatting of numbers and sizes for display
+//!
+//! The decimal and thousands separators are derived from the locale
+//! environment variables (`LC_ALL`, `LC_NUMERIC`, `LANG`). If none of them
+//! is set, English formatting is used.
+
+use std::sync::OnceLock;
+
+pub use dfu_buddy_core::dfudev::info::bcd_version;
+
+/// Units for binary prefixed sizes
+const SIZE_UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+/// Separators used for number formatting
+#[derive(Debug, Clone, Copy)]
+struct Separators {
+    /// Separator between integer and fractional part
+    decimal: char,
+
+    /// Separator between groups of 3 digits
+    thousands: char,
+}
+
+/// Return the separators for the current locale
+fn separators() -> Separators {
+    static SEPARATORS: OnceLock<Separators> = OnceLock::new();
+
+    *SEPARATORS.get_or_init(|| {
+        let locale = ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default();
+        let language = locale.split(['_', '-', '.']).next().unwrap_or_default();
+
+        match language {
+            "de" | "es" | "it" | "nl" | "pt" | "da" | "id" | "tr" | "el" => Separators {
+                decimal: ',',
+                thousands: '.',
+            },
+            "fr" | "ru" | "pl" | "sv" | "fi" | "nb" | "nn" | "cs" | "sk" | "uk" | "hu" => {
+                Separators {
+                    decimal: ',',
+                    thousands: '\u{202F}',
+                }
+            }
+            _ => Separators {
+                decimal: '.',
+                thousands: ',',
+            },
+        }
+    })
+}
+
+/// Return an integer with thousands separators, e.g. "1,048,576"
+pub fn number(value: u64) -> String {
+    let digits = value.to_string();
+    let thousands = separators().thousands;
+    let mut result = String::with_capacity(digits.len() * 4 / 3);
+
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index) % 3 == 0 {
+            result.push(thousands);
+        }
+        result.push(digit);
+    }
+
+    result
+}
+
+/// Return a human-readable size with binary prefix, e.g. "128 KiB" or "1.5 MiB"
+///
+/// Sizes below 1 KiB are shown in bytes. The fractional part is limited to
+/// one digit and omitted if zero.
+pub fn size(bytes: u64) -> String {
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = SIZE_UNITS[0];
+
+    for next_unit in &SIZE_UNITS[1..] {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = next_unit;
+    }
+
+    let tenths = (value * 10.0).round() as u64;
+    let integer = number(tenths / 10);
+
+    match tenths % 10 {
+        0 => format!("{integer} {unit}"),
+        fraction => format!("{integer}{}{fraction} {unit}", separators().decimal),
+    }
+}
+
+/// Return the exact size in bytes, e.g. "1,048,576 bytes"
+pub fn bytes(bytes: u64) -> String {
+    format!("{} bytes", number(bytes))
+}
+
+/// Return a duration as minutes and seconds, e.g. "1:05"
+///
+/// Durations of an hour or more are prefixed with the hours, e.g. "1:02:05".
+pub fn duration(duration: std::time::Duration) -> String {
+    let seconds = duration.as_secs();
+
+    match seconds / 3600 {
+        0 => format!("{}:{:02}", seconds / 60, seconds % 60),
+        hours => format!("{hours}:{:02}:{:02}", seconds / 60 % 60, seconds % 60),
+    }
+}
+
+/// Return the time of day in UTC with milliseconds, e.g. "14:03:27.512"
+pub fn time_of_day(time: std::time::SystemTime) -> String {
+    let millis = time
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() % 86_400_000);
+    let seconds = millis / 1000;
+
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        millis % 1000
+    )
+}
+
+/// Return a short duration in milliseconds with 3 decimals, e.g. "12.345 ms"
+pub fn millis(duration: std::time::Duration) -> String {
+    let micros = duration.as_micros() as u64;
+
+    format!(
+        "{}{}{:03} ms",
+        number(micros / 1000),
+        separators().decimal,
+        micros % 1000
+    )
+}
+
+/// Return a transfer speed, e.g. "42.5 KiB/s"
+pub fn speed(bytes_per_second: f32) -> String {
+    format!("{}/s", size(bytes_per_second as u64))
+}
@@ -0,0 +1,112 @@
+//! History of the update operations in the current session
+
+use std::time::{Duration, Instant};
+
+/// Single update operation
+#[derive(Debug, Clone)]
+pub struct Entry {
+    /// Id of the updated device
+    pub device_id: u64,
+
+    /// Serial number of the updated device
+    pub serial_number: String,
+
+    /// Path of the file used for the update
+    pub file_path: std::path::PathBuf,
+
+    /// SHA-256 hash of the file content
+    pub file_hash: [u8; 32],
+
+    /// Name of the operator logged in when the operation was started
+    pub operator: Option<String>,
+
+    /// Time when the operation was started
+    pub started: Instant,
+
+    /// Duration of the operation, set when finished
+    pub duration: Option<Duration>,
+
+    /// Error message if the operation failed
+    pub error: Option<String>,
+}
+
+impl Entry {
+    /// Return a new entry for an operation started now
+    pub fn new(
+        device_id: u64,
+        serial_number: String,
+        file_path: std::path::PathBuf,
+        file_hash: [u8; 32],
+        operator: Option<String>,
+    ) -> Self {
+        Self {
+            device_id,
+            serial_number,
+            file_path,
+            file_hash,
+            operator,
+            started: Instant::now(),
+            duration: None,
+            error: None,
+        }
+    }
+
+    /// Return if the operation finished without error
+    pub fn succeeded(&self) -> bool {
+        self.duration.is_some() && self.error.is_none()
+    }
+}
+
+/// List of all operations in the order they were started
+#[derive(Debug, Default)]
+pub struct History {
+    /// Finished operations
+    entries: Vec<Entry>,
+}
+
+impl History {
+    /// Add an operation with its result
+    pub fn add(&mut self, mut entry: Entry, error: Option<String>) {
+        entry.duration = Some(entry.started.elapsed());
+        entry.error = error;
+        log::info!(
+            "Update of device {} with {:?} by operator {} {}",
+            entry.serial_number,
+            entry.file_path,
+            entry.operator.as_deref().unwrap_or("-"),
+            if entry.succeeded() {
+                "succeeded"
+            } else {
+                "failed"
+            }
+        );
+        self.entries.push(entry);
+    }
+
+    /// Return the last successful update of a device with a file of the given hash
+    pub fn last_success(&self, device_id: u64, file_hash: &[u8; 32]) -> Option<&Entry> {
+        self.entries.iter().rev().find(|entry| {
+            entry.succeeded() && entry.device_id == device_id && entry.file_hash == *file_hash
+        })
+    }
+}
+
+/// Return the SHA-256 hash of a file
+pub fn file_hash(path: &std::path::Path) -> std::io::Result<[u8; 32]> {
+    use sha2::Digest;
+
+    let data = std::fs::read(path)?;
+
+    Ok(sha2::Sha256::digest(data).into())
+}
+
+/// Return a short description of the elapsed time, e.g. "5 min ago"
+pub fn time_ago(instant: Instant) -> String {
+    let seconds = instant.elapsed().as_secs();
+
+    match seconds {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{} min ago", seconds / 60),
+        _ => format!("{} h ago", seconds / 3600),
+    }
+}
@@ -0,0 +1,419 @@
+//! Integrity checks of files beyond the CRC in the DFU suffix
+//!
+//! The suffix CRC only detects files damaged after they were built. The
+//! algorithms here compare the file or its payload against a value delivered
+//! separately, e.g. a checksum file published next to the firmware or a CRC
+//! the vendor appends to each image. Which algorithms are required is part of
+//! the configuration, so each station can be provisioned with its own set.
+//!
+//! A minisign signature proves in addition that the file was released by the
+//! holder of the secret key, guarding against tampered files. The public key
+//! is part of the configuration as well.
+
+use anyhow::Result;
+
+/// Length of the CRC appended to an image by [`Algorithm::PayloadCrc32Trailer`]
+const TRAILER_LENGTH: usize = 4;
+
+/// Extensions of the signature file, in the order they are looked for
+const SIGNATURE_EXTENSIONS: [&str; 2] = ["minisig", "sig"];
+
+/// Algorithm to check the integrity of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum Algorithm {
+    /// SHA-256 of the whole file, compared with the sidecar file `<file>.sha256`
+    Sha256Sidecar,
+
+    /// CRC-32 of the payload of all images, compared with the sidecar file `<file>.crc32`
+    PayloadCrc32Sidecar,
+
+    /// CRC-32 of the payload of each image, compared with its last 4 bytes
+    PayloadCrc32Trailer,
+
+    /// Ed25519 signature of the whole file in the minisign format, read from
+    /// `<file>.minisig` or `<file>.sig` and verified with the public key
+    Signature,
+}
+
+impl Algorithm {
+    /// All algorithms in the order they are shown
+    pub const ALL: [Self; 4] = [
+        Self::Sha256Sidecar,
+        Self::PayloadCrc32Sidecar,
+        Self::PayloadCrc32Trailer,
+        Self::Signature,
+    ];
+
+    /// Return the short name shown in the checklist
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Sha256Sidecar => "SHA-256 file",
+            Self::PayloadCrc32Sidecar => "Payload CRC file",
+            Self::PayloadCrc32Trailer => "Payload CRC trailer",
+            Self::Signature => "Signature",
+        }
+    }
+
+    /// Return a description of what is compared
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Sha256Sidecar => {
+                "SHA-256 of the file, compared with the hex digest in <file>.sha256"
+            }
+            Self::PayloadCrc32Sidecar => {
+                "CRC-32 of the payload of all images, compared with the hex value in <file>.crc32"
+            }
+            Self::PayloadCrc32Trailer => {
+                "CRC-32 of each image, compared with the little-endian value in its last 4 bytes"
+            }
+            Self::Signature => {
+                "Minisign signature in <file>.minisig or <file>.sig, verified with the public key"
+            }
+        }
+    }
+
+    /// Return the algorithm by its command line name
+    pub fn from_arg(arg: &str) -> Option<Self> {
+        match arg {
+            "sha256" => Some(Self::Sha256Sidecar),
+            "crc32" => Some(Self::PayloadCrc32Sidecar),
+            "crc32-trailer" => Some(Self::PayloadCrc32Trailer),
+            "signature" => Some(Self::Signature),
+            _ => None,
+        }
+    }
+
+    /// Check a file, `source_path` is the file opened by the user,
+    /// which differs from the DFU file for converted files
+    ///
+    /// Returns a description of the matching value.
+    fn check(
+        &self,
+        public_key: &str,
+        source_path: &std::path::Path,
+        dfu_file: &mut dfufile::DfuFile,
+    ) -> Result<String> {
+        match self {
+            Self::Sha256Sidecar => {
+                let expected = read_sidecar(source_path, "sha256")?;
+                let hash = crate::history::file_hash(source_path)?;
+                let actual: String = hash.iter().map(|byte| format!("{byte:02x}")).collect();
+
+                if !expected.eq_ignore_ascii_case(&actual) {
+                    return Err(Error::Mismatch(expected, actual).into());
+                }
+
+                Ok(format!("SHA-256 {actual}"))
+            }
+            Self::PayloadCrc32Sidecar => {
+                let text = read_sidecar(source_path, "crc32")?;
+                let expected = u32::from_str_radix(text.trim_start_matches("0x"), 16)
+                    .map_err(|_| Error::MalformedSidecar(sidecar_path(source_path, "crc32")))?;
+
+                let mut hasher = crc32fast::Hasher::new();
+                for payload in payloads(dfu_file)? {
+                    hasher.update(&payload);
+                }
+                let actual = hasher.finalize();
+
+                if actual != expected {
+                    return Err(Error::Mismatch(
+                        format!("0x{expected:08X}"),
+                        format!("0x{actual:08X}"),
+                    )
+                    .into());
+                }
+
+                Ok(format!("CRC-32 0x{actual:08X}"))
+            }
+            Self::PayloadCrc32Trailer => {
+                let payloads = payloads(dfu_file)?;
+
+                for (index, payload) in payloads.iter().enumerate() {
+                    if payload.len() < TRAILER_LENGTH {
+                        return Err(Error::ImageTooShort(index).into());
+                    }
+                    let (data, trailer) = payload.split_at(payload.len() - TRAILER_LENGTH);
+                    let expected = u32::from_le_bytes(trailer.try_into()?);
+                    let actual = crc32fast::hash(data);
+
+                    if actual != expected {
+                        return Err(Error::ImageMismatch(index, expected, actual).into());
+                    }
+                }
+
+                Ok(format!("CRC-32 of {} image(s) matches", payloads.len()))
+            }
+            Self::Signature => {
+                if public_key.trim().is_empty() {
+                    return Err(Error::MissingPublicKey.into());
+                }
+                let public_key = minisign_verify::PublicKey::from_base64(public_key.trim())
+                    .map_err(|_| Error::InvalidPublicKey)?;
+
+                let sidecar = SIGNATURE_EXTENSIONS
+                    .iter()
+                    .map(|extension| sidecar_path(source_path, extension))
+                    .find(|path| path.exists())
+                    .ok_or_else(|| {
+                        Error::MissingSidecar(sidecar_path(source_path, SIGNATURE_EXTENSIONS[0]))
+                    })?;
+                let signature = minisign_verify::Signature::from_file(&sidecar)
+                    .map_err(|_| Error::MalformedSidecar(sidecar))?;
+
+                let data = std::fs::read(source_path)?;
+                public_key
+                    .verify(&data, &signature, false)
+                    .map_err(|_| Error::BadSignature)?;
+
+                Ok(format!(
+                    "Signature verified: {}",
+                    signature.trusted_comment()
+                ))
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Result of a single integrity check
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    /// Algorithm used
+    pub algorithm: Algorithm,
+
+    /// Flag if the check passed
+    pub passed: bool,
+
+    /// Matching value or the reason of the failure
+    pub details: String,
+}
+
+/// Return the algorithms a file is checked with
+///
+/// In strict mode a configured public key always requires a valid
+/// signature, so unsigned files can't be flashed by leaving the check off.
+pub fn required(algorithms: &[Algorithm], strict: bool, public_key: &str) -> Vec<Algorithm> {
+    let mut required = algorithms.to_vec();
+    if strict && !public_key.trim().is_empty() && !required.contains(&Algorithm::Signature) {
+        required.push(Algorithm::Signature);
+    }
+
+    required
+}
+
+/// Check a file with each of the algorithms, `public_key` is the base64
+/// minisign key signatures are verified with
+pub fn check(
+    algorithms: &[Algorithm],
+    public_key: &str,
+    source_path: &std::path::Path,
+    dfu_file: &mut dfufile::DfuFile,
+) -> Vec<Outcome> {
+    algorithms
+        .iter()
+        .map(|algorithm| {
+            let result = algorithm.check(public_key, source_path, dfu_file);
+            match result {
+                Ok(details) => {
+                    log::info!("{algorithm}: {details}");
+                    Outcome {
+                        algorithm: *algorithm,
+                        passed: true,
+                        details,
+                    }
+                }
+                Err(error) => {
+                    log::error!("{algorithm}: {error}");
+                    Outcome {
+                        algorithm: *algorithm,
+                        passed: false,
+                        details: error.to_string(),
+                    }
+                }
+            }
+        })
+        .collect()
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// SHA-256 and CRC-32 of a block of data
+#[derive(Debug, Clone)]
+pub struct Digest {
+    /// SHA-256 as lowercase hex, as printed by `sha256sum`
+    pub sha256: String,
+
+    /// CRC-32 as used in the DFU suffix and by `crc32`
+    pub crc32: u32,
+}
+
+impl Digest {
+    /// Calculate the digest of data
+    pub fn of(data: &[u8]) -> Self {
+        use sha2::Digest as _;
+
+        Self {
+            sha256: sha2::Sha256::digest(data)
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect(),
+            crc32: crc32fast::hash(data),
+        }
+    }
+}
+
+/// Hashes of a file to cross-check it against values published with a release
+#[derive(Debug, Clone)]
+pub struct FileHashes {
+    /// Digest of the whole file
+    pub file: Digest,
+
+    /// Description and digest of the payload of each image element,
+    /// or of the data before the suffix of a plain file
+    pub elements: Vec<(String, Digest)>,
+}
+
+/// Calculate the hashes of a file, `source_path` is the file opened by the
+/// user, which differs from the DFU file for converted files
+pub fn hashes(
+    source_path: &std::path::Path,
+    dfu_file: &mut dfufile::DfuFile,
+) -> Result<FileHashes> {
+    let file = Digest::of(&std::fs::read(source_path)?);
+
+    let elements = match &dfu_file.content {
+        dfufile::Content::Plain => payloads(dfu_file)?
+            .iter()
+            .map(|payload| ("Payload".to_string(), Digest::of(payload)))
+            .collect(),
+        dfufile::Content::DfuSe(content) => {
+            let mut elements = Vec::new();
+            for image in &content.images {
+                for element in &image.image_elements {
+                    let mut data = vec![0; element.dwElementSize as usize];
+                    element.read_at(&mut dfu_file.file, 0, &mut data)?;
+                    elements.push((
+                        format!(
+                            "Alt {} at 0x{:08X}",
+                            image.target_prefix.bAlternateSetting, element.dwElementAddress
+                        ),
+                        Digest::of(&data),
+                    ));
+                }
+            }
+            elements
+        }
+    };
+
+    Ok(FileHashes { file, elements })
+}
+
+/// Return the path of a sidecar file, e.g. `firmware.dfu.sha256`
+fn sidecar_path(path: &std::path::Path, extension: &str) -> std::path::PathBuf {
+    let mut sidecar = path.as_os_str().to_owned();
+    sidecar.push(".");
+    sidecar.push(extension);
+
+    sidecar.into()
+}
+
+/// Return the first word of a sidecar file, which allows the output
+/// of tools like `sha256sum` with the file name following the value
+fn read_sidecar(path: &std::path::Path, extension: &str) -> Result<String> {
+    let sidecar = sidecar_path(path, extension);
+    let text =
+        std::fs::read_to_string(&sidecar).map_err(|_| Error::MissingSidecar(sidecar.clone()))?;
+
+    text.split_whitespace()
+        .next()
+        .map(str::to_string)
+        .ok_or_else(|| Error::MalformedSidecar(sidecar).into())
+}
+
+/// Return the payload of each image, or the data before the suffix of a plain file
+fn payloads(dfu_file: &mut dfufile::DfuFile) -> Result<Vec<Vec<u8>>> {
+    match &dfu_file.content {
+        dfufile::Content::Plain => {
+            let mut data = std::fs::read(&dfu_file.path)?;
+            data.truncate(data.len().saturating_sub(dfu_file.suffix.bLength as usize));
+            Ok(vec![data])
+        }
+        dfufile::Content::DfuSe(content) => content
+            .images
+            .iter()
+            .map(|image| {
+                let mut payload = Vec::new();
+                for element in &image.image_elements {
+                    let mut data = vec![0; element.dwElementSize as usize];
+                    element.read_at(&mut dfu_file.file, 0, &mut data)?;
+                    payload.extend(data);
+                }
+                Ok(payload)
+            })
+            .collect(),
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Error of an integrity check
+#[derive(Debug)]
+pub enum Error {
+    /// Sidecar file not found or not readable
+    MissingSidecar(std::path::PathBuf),
+
+    /// Sidecar file contains no valid value
+    MalformedSidecar(std::path::PathBuf),
+
+    /// Expected and actual value differ
+    Mismatch(String, String),
+
+    /// Image too short to contain a CRC
+    ImageTooShort(usize),
+
+    /// Expected and actual CRC of an image differ
+    ImageMismatch(usize, u32, u32),
+
+    /// No public key configured to verify signatures
+    MissingPublicKey,
+
+    /// Configured public key is not a valid minisign key
+    InvalidPublicKey,
+
+    /// Signature doesn't match the file and public key
+    BadSignature,
+}
+
+impl std::error::Error for Error {}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::MissingSidecar(path) => write!(f, "Checksum file {path:?} not found."),
+            Self::MalformedSidecar(path) => {
+                write!(f, "Checksum file {path:?} contains no valid value.")
+            }
+            Self::Mismatch(expected, actual) => {
+                write!(f, "Expected {expected}, calculated {actual}.")
+            }
+            Self::ImageTooShort(index) => {
+                write!(f, "Image #{index} is too short to contain a CRC.")
+            }
+            Self::ImageMismatch(index, expected, actual) => write!(
+                f,
+                "Image #{index}: expected 0x{expected:08X}, calculated 0x{actual:08X}."
+            ),
+            Self::MissingPublicKey => write!(f, "No public key configured to verify signatures."),
+            Self::InvalidPublicKey => write!(f, "The public key is not a valid minisign key."),
+            Self::BadSignature => write!(f, "The signature does not match the file."),
+        }
+    }
+}
@@ -0,0 +1,397 @@
+//! Persistent journal of all device operations
+//!
+//! Each finished operation is appended as a single JSON line to a file in the
+//! local data directory, so the flashing history of a board can be looked up
+//! by its serial number across sessions.
+
+use std::io::{BufRead, Write};
+use std::time::{Instant, SystemTime};
+
+use anyhow::{anyhow, Result};
+
+use crate::progress::CancelMode;
+use crate::DeviceUpdateStep;
+
+/// Name of the journal file
+const FILE_NAME: &str = "journal.jsonl";
+
+/// Kind of operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum Operation {
+    /// Erase and program, optionally verify
+    Update,
+
+    /// Erase only
+    Erase,
+
+    /// Compare the device memory with a file
+    Verify,
+
+    /// Read the device memory into a file
+    Backup,
+}
+
+impl Operation {
+    /// Return the kind of operation recorded for the phases of an update
+    pub fn from_phases(phases: &crate::update::Phases) -> Self {
+        if phases.program {
+            Self::Update
+        } else if phases.erase {
+            Self::Erase
+        } else {
+            Self::Verify
+        }
+    }
+}
+
+impl std::fmt::Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Update => write!(f, "Update"),
+            Self::Erase => write!(f, "Erase"),
+            Self::Verify => write!(f, "Verify"),
+            Self::Backup => write!(f, "Backup"),
+        }
+    }
+}
+
+/// Result of a single step of an operation
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct StepResult {
+    /// Name of the step
+    pub step: String,
+
+    /// Flag if the step was completed without error
+    pub passed: bool,
+}
+
+/// Cancellation of an operation by the user
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Cancellation {
+    /// Behavior chosen by the user
+    pub mode: CancelMode,
+
+    /// Point to continue from, `None` if the operation can't be resumed
+    pub resume_point: Option<crate::update::ResumePoint>,
+}
+
+/// Journal record of one operation
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Record {
+    /// Start time in seconds since the Unix epoch
+    pub timestamp: u64,
+
+    /// Serial number of the device
+    pub serial_number: String,
+
+    /// USB vendor id of the device
+    pub vendor_id: u16,
+
+    /// USB product id of the device
+    pub product_id: u16,
+
+    /// Kind of operation
+    pub operation: Operation,
+
+    /// Path of the file written to or read from the device
+    pub file_path: std::path::PathBuf,
+
+    /// SHA-256 hash of the file as hex string, if known
+    pub file_hash: Option<String>,
+
+    /// Name of the operator logged in
+    pub operator: Option<String>,
+
+    /// Steps in the order they were started
+    pub steps: Vec<StepResult>,
+
+    /// Duration of the operation in seconds
+    pub duration: f32,
+
+    /// Error message if the operation failed
+    pub error: Option<String>,
+
+    /// How the operation was cancelled by the user, if it was
+    pub cancellation: Option<Cancellation>,
+
+    /// Time when the operation was started, only valid while running
+    #[serde(skip)]
+    started: Option<Instant>,
+}
+
+impl Record {
+    /// Return a new record for an operation started now
+    pub fn new(
+        device_info: &crate::dfudev::DeviceInfo,
+        operation: Operation,
+        file_path: &std::path::Path,
+        operator: Option<String>,
+    ) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+
+        Self {
+            timestamp,
+            serial_number: device_info.serial_number_string.clone(),
+            vendor_id: device_info.vendor_id,
+            product_id: device_info.product_id,
+            operation,
+            file_path: file_path.to_path_buf(),
+            file_hash: None,
+            operator,
+            steps: Vec::new(),
+            duration: 0.0,
+            error: None,
+            cancellation: None,
+            started: Some(Instant::now()),
+        }
+    }
+
+    /// Set the file hash
+    pub fn set_file_hash(&mut self, file_hash: &[u8; 32]) {
+        self.file_hash = Some(file_hash.iter().map(|byte| format!("{byte:02x}")).collect());
+    }
+
+    /// Mark the previous step as passed and start a new one
+    pub fn start_step(&mut self, step: DeviceUpdateStep) {
+        self.pass_last_step();
+        self.steps.push(StepResult {
+            step: format!("{step:?}"),
+            passed: false,
+        });
+    }
+
+    /// Remove a running step that was left out
+    pub fn skip_step(&mut self, step: DeviceUpdateStep) {
+        if self
+            .steps
+            .last()
+            .is_some_and(|last| last.step == format!("{step:?}"))
+        {
+            self.steps.pop();
+        }
+    }
+
+    /// Record that the operation was cancelled
+    pub fn set_cancellation(
+        &mut self,
+        mode: CancelMode,
+        resume_point: Option<crate::update::ResumePoint>,
+    ) {
+        self.cancellation = Some(Cancellation { mode, resume_point });
+    }
+
+    /// Set the result of the operation, a running step fails with an error
+    pub fn finish(&mut self, error: Option<String>) {
+        if error.is_none() {
+            self.pass_last_step();
+        }
+        self.error = error;
+        self.duration = self
+            .started
+            .take()
+            .map_or(0.0, |started| started.elapsed().as_secs_f32());
+    }
+
+    /// Return if the operation finished without error
+    pub fn succeeded(&self) -> bool {
+        self.started.is_none() && self.error.is_none()
+    }
+
+    /// Return the result as short text
+    pub fn result(&self) -> &'static str {
+        match (&self.error, &self.cancellation) {
+            (None, _) => "OK",
+            (Some(_), Some(cancellation)) => match cancellation.mode {
+                CancelMode::Abort => "Aborted",
+                CancelMode::Stop => "Stopped",
+            },
+            (Some(_), None) => "Failed",
+        }
+    }
+
+    /// Return the start time as UTC date and time
+    pub fn time(&self) -> String {
+        let seconds = self.timestamp % 86400;
+        let (year, month, day) = civil_from_days((self.timestamp / 86400) as i64);
+
+        format!(
+            "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    }
+
+    /// Mark the last step as passed
+    fn pass_last_step(&mut self) {
+        if let Some(step) = self.steps.last_mut() {
+            step.passed = true;
+        }
+    }
+}
+
+/// Append a record to the journal file
+pub fn append(record: &Record) -> Result<()> {
+    let path = file_path().ok_or_else(|| anyhow!("No data directory available."))?;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", serde_json::to_string(record)?)?;
+
+    Ok(())
+}
+
+/// Append a record and log errors instead of returning them
+pub fn append_or_log(record: &Record) {
+    log::info!(
+        "{} of device {} {}",
+        record.operation,
+        record.serial_number,
+        if record.succeeded() {
+            "succeeded"
+        } else {
+            "failed"
+        }
+    );
+
+    if let Err(error) = append(record) {
+        log::error!("Error writing journal: {}", error);
+    }
+}
+
+/// Return all records of a device, oldest first
+///
+/// Lines that can't be parsed are skipped.
+pub fn records(serial_number: &str) -> Result<Vec<Record>> {
+    read_records(|record| record.serial_number == serial_number)
+}
+
+/// Return the records of all devices, oldest first
+///
+/// Lines that can't be parsed are skipped.
+pub fn all_records() -> Result<Vec<Record>> {
+    read_records(|_| true)
+}
+
+/// Return the records accepted by a filter, oldest first
+fn read_records(filter: impl Fn(&Record) -> bool) -> Result<Vec<Record>> {
+    let Some(path) = file_path().filter(|path| path.exists()) else {
+        return Ok(Vec::new());
+    };
+
+    let file = std::io::BufReader::new(std::fs::File::open(path)?);
+    let mut records = Vec::new();
+
+    for line in file.lines() {
+        match serde_json::from_str::<Record>(&line?) {
+            Ok(record) if filter(&record) => records.push(record),
+            Ok(_) => {}
+            Err(error) => log::warn!("Invalid journal entry: {}", error),
+        }
+    }
+
+    Ok(records)
+}
+
+/// Format of exported records
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Comma-separated values, one line per record
+    Csv,
+
+    /// JSON array of records
+    Json,
+}
+
+impl ExportFormat {
+    /// Return the file name extension
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+        }
+    }
+}
+
+impl std::fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Csv => write!(f, "CSV"),
+            Self::Json => write!(f, "JSON"),
+        }
+    }
+}
+
+/// Write records to a file
+pub fn export(records: &[Record], path: &std::path::Path, format: ExportFormat) -> Result<()> {
+    let content = match format {
+        ExportFormat::Csv => {
+            let mut content = String::from(
+                "time,operation,vendor_id,product_id,serial_number,file_path,file_hash,\
+                duration,result,error,operator\n",
+            );
+            for record in records {
+                let fields = [
+                    record.time(),
+                    record.operation.to_string(),
+                    format!("{:04x}", record.vendor_id),
+                    format!("{:04x}", record.product_id),
+                    record.serial_number.clone(),
+                    record.file_path.display().to_string(),
+                    record.file_hash.clone().unwrap_or_default(),
+                    format!("{:.1}", record.duration),
+                    record.result().to_string(),
+                    record.error.clone().unwrap_or_default(),
+                    record.operator.clone().unwrap_or_default(),
+                ];
+                let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+                content.push_str(&fields.join(","));
+                content.push('\n');
+            }
+            content
+        }
+        ExportFormat::Json => serde_json::to_string_pretty(records)?,
+    };
+
+    std::fs::write(path, content)?;
+
+    Ok(())
+}
+
+/// Return a field quoted for CSV if it contains separators, quotes or line breaks
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Return the path of the journal file
+pub fn file_path() -> Option<std::path::PathBuf> {
+    dirs::data_local_dir().map(|dir| dir.join(env!("CARGO_PKG_NAME")).join(FILE_NAME))
+}
+
+/// Return year, month and day for a number of days since the Unix epoch
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // Algorithm from Howard Hinnant's date library
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
@@ -0,0 +1,139 @@
+//! Kiosk mode flashing every matching device that appears
+//!
+//! Once started with a file opened, each device found by a scan is queued
+//! and updated in turn without user interaction. A device is only flashed
+//! once while it stays connected, reconnecting it queues it again. The
+//! result of each device is appended to the session list.
+
+use std::collections::{HashSet, VecDeque};
+use std::time::SystemTime;
+
+/// Result of a device flashed in kiosk mode
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    /// Time the update ended
+    pub time: SystemTime,
+
+    /// Serial number of the device
+    pub serial_number: String,
+
+    /// Short description of the device
+    pub device: String,
+
+    /// Error message if the update failed
+    pub error: Option<String>,
+}
+
+impl Outcome {
+    /// Return the time of day in UTC
+    pub fn time_of_day(&self) -> String {
+        crate::format::time_of_day(self.time)
+    }
+}
+
+/// State of a kiosk session
+#[derive(Debug, Default)]
+pub struct Session {
+    /// Flag if new devices are flashed automatically
+    active: bool,
+
+    /// Connected devices already queued or flashed
+    handled: HashSet<u64>,
+
+    /// Devices waiting to be flashed, in the order they appeared
+    queue: VecDeque<u64>,
+
+    /// Device being flashed
+    current: Option<u64>,
+
+    /// Results of all devices flashed in this session
+    outcomes: Vec<Outcome>,
+}
+
+impl Session {
+    /// Start flashing the devices, including the ones already connected
+    pub fn start(&mut self) {
+        log::info!("Kiosk mode started.");
+        self.active = true;
+        self.handled.clear();
+        self.queue.clear();
+    }
+
+    /// Stop flashing new devices, an update in progress is completed
+    pub fn stop(&mut self) {
+        log::info!("Kiosk mode stopped.");
+        self.active = false;
+        self.queue.clear();
+    }
+
+    /// Return if new devices are flashed automatically
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Queue the devices found by a scan that are not handled yet
+    /// and forget the ones no longer connected
+    pub fn devices_found(&mut self, device_ids: &[u64]) {
+        if !self.active {
+            return;
+        }
+
+        self.handled.retain(|id| device_ids.contains(id));
+        self.queue.retain(|id| device_ids.contains(id));
+
+        for id in device_ids {
+            if self.handled.insert(*id) {
+                self.queue.push_back(*id);
+            }
+        }
+    }
+
+    /// Return the next device to flash and mark it as current,
+    /// `None` if a device is being flashed or none is waiting
+    pub fn next(&mut self) -> Option<u64> {
+        if !self.active || self.current.is_some() {
+            return None;
+        }
+
+        self.current = self.queue.pop_front();
+        self.current
+    }
+
+    /// Return the device being flashed
+    pub fn current(&self) -> Option<u64> {
+        self.current
+    }
+
+    /// Record the result of the device being flashed
+    pub fn finish(&mut self, serial_number: String, device: String, error: Option<String>) {
+        if self.current.take().is_none() {
+            return;
+        }
+
+        match &error {
+            Some(error) => log::warn!("Kiosk: {device} failed: {error}"),
+            None => log::info!("Kiosk: {device} passed"),
+        }
+        self.outcomes.push(Outcome {
+            time: SystemTime::now(),
+            serial_number,
+            device,
+            error,
+        });
+    }
+
+    /// Return the results of the session, oldest first
+    pub fn outcomes(&self) -> &[Outcome] {
+        &self.outcomes
+    }
+
+    /// Return the number of devices waiting
+    pub fn waiting(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Remove all results
+    pub fn clear(&mut self) {
+        self.outcomes.clear();
+    }
+}
@@ -0,0 +1,47 @@
+//! Level of the log messages shown on the console
+//!
+//! The logger is initialized with the most verbose level compiled in, the
+//! level selected in the preferences limits it at runtime.
+
+/// Most verbose level of the log messages shown
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum LogLevel {
+    /// Errors only
+    Error,
+
+    /// Errors and warnings
+    Warn,
+
+    /// Errors, warnings and informational messages
+    Info,
+
+    /// All messages, including the ones for troubleshooting
+    #[default]
+    Debug,
+}
+
+impl LogLevel {
+    /// All levels in the order they are shown
+    pub const ALL: [Self; 4] = [Self::Error, Self::Warn, Self::Info, Self::Debug];
+
+    /// Limit the messages logged from now on to the level
+    pub fn apply(self) {
+        log::set_max_level(match self {
+            Self::Error => log::LevelFilter::Error,
+            Self::Warn => log::LevelFilter::Warn,
+            Self::Info => log::LevelFilter::Info,
+            Self::Debug => log::LevelFilter::Debug,
+        });
+    }
+}
+
+impl std::fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Error => write!(f, "Error"),
+            Self::Warn => write!(f, "Warning"),
+            Self::Info => write!(f, "Info"),
+            Self::Debug => write!(f, "Debug"),
+        }
+    }
+}
@@ -2,16 +2,39 @@
 #![windows_subsystem = "windows"]
 #![warn(missing_docs)]
 
-mod dfudev;
+mod archive;
+mod cli;
+mod config;
+mod convert;
+mod demo;
+mod elf;
+mod events;
+mod format;
+mod history;
+mod integrity;
+mod journal;
+mod kiosk;
+mod logging;
+mod operator;
+mod presets;
+mod profile;
+mod resume;
+mod selfupdate;
 mod theme;
+mod udev;
 mod ui;
-mod update;
+mod warnings;
 
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use dfu_buddy_core::{builder, cache, dfudev, progress, session, update};
+use dfu_buddy_core::{DeviceUpdateStep, SectorState};
+use dfudev::dfuse::MemorySegment;
+use dfudev::simulator::SimulatedDevice;
 use eframe::egui;
 use simple_logger::SimpleLogger;
 use ui::modal::Modal;
+use warnings::{Severity, Warning};
 
 use ui::{device, file};
 
@@ -21,14 +44,52 @@ const WINDOW_SIZE: egui::Vec2 = egui::vec2(850.0, 605.0);
 /// Max number of frames per second
 const FPS_LIMIT: u32 = 25;
 
+/// Selectable max. number of progress updates per second
+const PROGRESS_RATES: [u32; 4] = [2, 5, 10, 25];
+
+/// Command line flag to start in inspector mode
+const INSPECTOR_FLAG: &str = "--inspector";
+
+/// Command line flag to show a simulated device instead of the connected ones
+const DEMO_FLAG: &str = "--demo";
+
+/// Max. time to wait on exit for a running operation to return the device to idle
+const SHUTDOWN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+
+/// Hint shown with errors caused by the readout protection
+const UNPROTECT_HINT: &str = "The protection can be removed via File → Remove read protection..., \
+    which erases the device.";
+
 ////////////////////////////////////////////////////////////////////////////////
 
 /// Starts the application
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    if args.first().map(String::as_str) == Some("--cli") {
+        SimpleLogger::new()
+            .with_level(log::LevelFilter::Warn)
+            .env()
+            .init()
+            .unwrap();
+        dfudev::quirks::load_or_log();
+        std::process::exit(cli::run(&args[1..]));
+    }
+
+    if let Some(flag @ ("--check-update" | "--self-update")) = args.first().map(String::as_str) {
+        SimpleLogger::new()
+            .with_level(log::LevelFilter::Info)
+            .init()
+            .unwrap();
+        std::process::exit(cli::self_update(flag == "--self-update"));
+    }
+
     SimpleLogger::new()
         .with_level(log::LevelFilter::Debug)
+        .env()
         .init()
         .unwrap();
+    dfudev::quirks::load_or_log();
 
     let native_options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
@@ -65,6 +126,14 @@ pub struct App {
     #[serde(skip)]
     device_id: Option<u64>,
 
+    /// Ids of devices found by the last scan that are no longer connected
+    #[serde(skip)]
+    stale_devices: std::collections::HashSet<u64>,
+
+    /// Flag if the last scan failed due to missing permissions
+    #[serde(skip)]
+    access_denied: bool,
+
     /// Instance of currently opened DFU file
     #[serde(skip)]
     dfu_file: Option<dfufile::DfuFile>,
@@ -76,6 +145,13 @@ pub struct App {
     /// Last path shown in the open file dialog
     file_dialog_path: Option<std::path::PathBuf>,
 
+    /// Name of the filter matching the last opened file, preselected in the dialog
+    file_filter: Option<String>,
+
+    /// Thread showing the open file dialog, kept to not open a second one
+    #[serde(skip)]
+    file_dialog: Option<std::thread::JoinHandle<()>>,
+
     /// Message channel
     #[serde(skip)]
     message_channel: (
@@ -87,8 +163,257 @@ pub struct App {
     #[serde(skip)]
     device_update_state: DeviceUpdateState,
 
+    /// Flag if the statistics window is open
+    #[serde(skip)]
+    show_statistics: bool,
+
+    /// Device events of the current session
+    #[serde(skip)]
+    event_log: events::EventLog,
+
+    /// Flag if the event window is open
+    #[serde(skip)]
+    show_events: bool,
+
+    /// Flag if the window with the differences found by verification is open
+    #[serde(skip)]
+    show_differences: bool,
+
+    /// Session of the kiosk mode
+    #[serde(skip)]
+    kiosk: kiosk::Session,
+
+    /// Flag if the kiosk window is open
+    #[serde(skip)]
+    show_kiosk: bool,
+
+    /// Flag if the devices must be rescanned when no update is running
+    #[serde(skip)]
+    rescan_pending: bool,
+
+    /// Opened file in another format, converted into `dfu_file`
+    #[serde(skip)]
+    converted_file: Option<convert::ConvertedFile>,
+
+    /// Path of the archive the DFU file was extracted from followed by its
+    /// name in the archive, shown instead of the path of the extracted file
+    #[serde(skip)]
+    archive_path: Option<std::path::PathBuf>,
+
+    /// Archive with several DFU files waiting for the user to choose one
+    #[serde(skip)]
+    archive_choice: Option<ui::archive::ArchiveChoice>,
+
+    /// SHA-256 hash of the opened file
+    #[serde(skip)]
+    file_hash: Option<[u8; 32]>,
+
+    /// Operations performed in this session
+    #[serde(skip)]
+    history: history::History,
+
+    /// Id of the last job started, events of other jobs are ignored
+    #[serde(skip)]
+    current_job: Option<progress::JobId>,
+
+    /// Reporter and thread of the last job started, to cancel it on exit
+    #[serde(skip)]
+    worker: Option<(progress::Reporter, std::thread::JoinHandle<()>)>,
+
+    /// History entry of the update in progress
+    #[serde(skip)]
+    current_operation: Option<history::Entry>,
+
+    /// Journal record of the operation in progress
+    #[serde(skip)]
+    journal_record: Option<journal::Record>,
+
+    /// Warnings collected for the file, device and update
+    #[serde(skip)]
+    warnings: warnings::Warnings,
+
+    /// Progress of an interrupted update of the selected device with the opened file
+    #[serde(skip)]
+    resume_progress: Option<resume::Progress>,
+
+    /// Device id and file hash `resume_progress` was loaded for
+    #[serde(skip)]
+    resume_key: Option<(u64, [u8; 32])>,
+
+    /// State of the journal window
+    #[serde(skip)]
+    journal_view: ui::journal::JournalView,
+
+    /// State of the flash history window
+    #[serde(skip)]
+    flash_history_view: ui::flash_history::FlashHistoryView,
+
+    /// State of the system memory window
+    #[serde(skip)]
+    memory_view: ui::memory::MemoryView,
+
+    /// State of the read memory window
+    #[serde(skip)]
+    read_memory_view: ui::read_memory::ReadMemoryView,
+
+    /// Cached file blocks, shared with the update thread
+    #[serde(skip)]
+    block_cache: std::sync::Arc<std::sync::Mutex<cache::BlockCache>>,
+
     /// Zoom factor.
     zoom_factor: f32,
+
+    /// Max. number of progress updates per second during an update
+    progress_rate: u32,
+
+    /// Flag to erase the whole flash before programming
+    mass_erase: bool,
+
+    /// Flag to skip programming blocks of only 0xFF after erasing
+    skip_blank_blocks: bool,
+
+    /// Flag to only erase and program the sectors differing from the file
+    differential_update: bool,
+
+    /// Padding of the data of converted files
+    padding: builder::Padding,
+
+    /// Transfer size, timeouts and polling behavior for all devices
+    transfer_settings: dfudev::settings::TransferSettings,
+
+    /// Action after a successful update
+    after_update: update::AfterUpdate,
+
+    /// Name of the board preset restricting the devices shown
+    preset: Option<String>,
+
+    /// Project profiles pairing devices with their firmware
+    profiles: Vec<profile::Profile>,
+
+    /// Name of the selected profile restricting the devices shown
+    profile: Option<String>,
+
+    /// Color theme
+    theme: theme::Theme,
+
+    /// Most verbose level of the log messages shown
+    log_level: logging::LogLevel,
+
+    /// Flag to remember the device selected last
+    remember_device: bool,
+
+    /// Flag to write a session log for each job
+    session_log: bool,
+
+    /// Directory of the session logs, empty for the default one
+    session_log_directory: String,
+
+    /// Serial number of the device selected last
+    last_device: Option<String>,
+
+    /// Steps performed when starting an update
+    phases: update::Phases,
+
+    /// Order of the targets of a file, skipped ones are stored separately
+    targets: update::Targets,
+
+    /// Indices of the images skipped, by file path
+    skipped_images: std::collections::BTreeMap<std::path::PathBuf, Vec<usize>>,
+
+    /// Memory maps entered manually for devices with malformed descriptors
+    memory_maps: Vec<dfudev::quirks::MemoryMapOverride>,
+
+    /// Flag if an operator login is required before flashing
+    production_mode: bool,
+
+    /// Operators allowed to log in
+    operators: Vec<operator::Operator>,
+
+    /// Flag if all actions modifying the device are disabled
+    inspector_mode: bool,
+
+    /// Flag if failed checks can't be overridden, for release flashing
+    strict_mode: bool,
+
+    /// Integrity checks required in addition to the suffix CRC
+    integrity_checks: Vec<integrity::Algorithm>,
+
+    /// Base64 minisign public key signatures of files are verified with
+    public_key: String,
+
+    /// Flag if inspector mode was forced by the command line and can't be left
+    #[serde(skip)]
+    inspector_locked: bool,
+
+    /// Address window updates are restricted to, not persisted to never
+    /// leave parts of a later file unwritten by accident
+    #[serde(skip)]
+    address_range: Option<update::AddressRange>,
+
+    /// Flag if a simulated device is shown instead of the connected ones
+    #[serde(skip)]
+    demo_mode: bool,
+
+    /// Flag if updates run on a simulated copy of the selected device,
+    /// not persisted to never leave a device unchanged by accident
+    #[serde(skip)]
+    simulate_update: bool,
+
+    /// Name of the logged in operator
+    #[serde(skip)]
+    operator: Option<String>,
+
+    /// State of the login window
+    #[serde(skip)]
+    login_form: ui::operator::LoginForm,
+
+    /// State of the operator management window
+    #[serde(skip)]
+    operators_form: ui::operator::OperatorsForm,
+
+    /// State of the profile management window
+    #[serde(skip)]
+    profiles_form: ui::profiles::ProfilesForm,
+
+    /// Flag if the firmware of the selected profile is being downloaded
+    #[serde(skip)]
+    profile_download: bool,
+
+    /// Firmware files downloaded for profiles, removed at exit
+    #[serde(skip)]
+    profile_downloads: Vec<std::path::PathBuf>,
+
+    /// State of the DFU file creation tool
+    #[serde(skip)]
+    suffix_tool_form: ui::suffix_tool::SuffixToolForm,
+
+    /// State of the device permissions window
+    #[serde(skip)]
+    permissions_form: ui::permissions::PermissionsForm,
+
+    /// State of the memory map override window
+    #[serde(skip)]
+    memory_map_form: ui::memory_map::MemoryMapForm,
+
+    /// State of the window to remove the readout protection
+    #[serde(skip)]
+    unprotect_form: ui::unprotect::UnprotectForm,
+
+    /// State of the address range window
+    #[serde(skip)]
+    address_range_form: ui::address_range::AddressRangeForm,
+
+    /// State of the window shown when another application holds the device
+    #[serde(skip)]
+    device_in_use_form: ui::device_in_use::DeviceInUseForm,
+
+    /// State of the transfer settings window
+    #[serde(skip)]
+    transfer_settings_form: ui::transfer_settings::TransferSettingsForm,
+
+    /// State of the preferences window
+    #[serde(skip)]
+    settings_form: ui::settings::SettingsForm,
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -102,6 +427,94 @@ pub enum Message {
     /// Force rescanning of devices
     RescanDevices,
 
+    /// Open the journal of the selected device
+    OpenJournal,
+
+    /// Open the device event window
+    OpenEvents,
+
+    /// Open the window with the differences found by verification
+    OpenDifferences,
+
+    /// Read a system memory or OTP area with an alt setting
+    ReadMemory(u8),
+
+    /// Start address and data of a memory area or an error message
+    MemoryRead(Result<(u32, Vec<u8>), String>),
+
+    /// Read a number of bytes at an address with an alt setting
+    ReadMemoryRange(u8, u32, u32),
+
+    /// Data of a memory range read with an alt setting and start address,
+    /// or an error message
+    MemoryRangeRead(Result<(u8, u32, Vec<u8>), String>),
+
+    /// Save the memory range read last as binary file
+    SaveMemoryRange,
+
+    /// Send from the hotplug watcher when a USB device was connected or disconnected
+    UsbDevicesChanged,
+
+    /// Show a diagnostics report of all USB devices
+    RunDiagnostics,
+
+    /// Show how to grant access to USB devices after access was denied
+    DeviceAccessDenied,
+
+    /// Offer to retry an action that failed because another application holds the device
+    DeviceInUse {
+        /// Name and process id of the application, if known.
+        owner: Option<String>,
+        /// Message sent again when retrying.
+        retry: Box<Message>,
+    },
+
+    /// Send from the memory map window when overrides were changed
+    MemoryMapsChanged,
+
+    /// Replace the transfer settings for all devices
+    SetTransferSettings(dfudev::settings::TransferSettings),
+
+    /// Open the transfer settings window
+    OpenTransferSettings,
+
+    /// Load the journal records of all devices and show them
+    OpenFlashHistory,
+
+    /// Export the records shown in the flash history window
+    ExportFlashHistory(journal::ExportFormat),
+
+    /// Flash every device appearing from now on with the opened file
+    StartKiosk,
+
+    /// Stop flashing new devices automatically
+    StopKiosk,
+
+    /// Replace the preferences
+    SetPreferences(ui::settings::Preferences),
+
+    /// Clear the device remembered as selected last
+    ForgetLastDevice,
+
+    /// Select a board preset by name, `None` to show all devices
+    SelectPreset(Option<String>),
+
+    /// Select a profile by name, `None` to show all devices
+    SelectProfile(Option<String>),
+
+    /// Firmware of a profile downloaded, with the name of the profile and
+    /// the path of the file
+    ProfileFirmwareDownloaded(String, Result<std::path::PathBuf, String>),
+
+    /// Remove the readout protection of the selected device in a separate thread
+    UnprotectDevice,
+
+    /// Restrict updates to an address window, `None` for the whole file
+    SetAddressRange(Option<update::AddressRange>),
+
+    /// Send when removing the readout protection is finished, with an error message on failure
+    DeviceUnprotected(Result<(), String>),
+
     /// Select a device with a specific id
     DeviceSelected(u64),
 
@@ -114,6 +527,38 @@ pub enum Message {
     /// Open a file
     OpenFile(std::path::PathBuf),
 
+    /// Open a DFU file of an archive by its name
+    OpenArchiveEntry(std::path::PathBuf, String),
+
+    /// Write an image of the file to another alt setting of the device,
+    /// or to the one given in the file again
+    RemapTarget {
+        /// Index of the image in the file
+        image: usize,
+        /// Alt setting of the device, `None` to remove the mapping
+        alt_setting: Option<u8>,
+    },
+
+    /// Set target alt setting and start address of an opened raw binary
+    SetRawBinaryTarget {
+        /// Alternate setting of the target
+        alt_setting: u8,
+        /// Start address
+        address: u32,
+    },
+
+    /// Choose a file and write the configuration to it
+    ExportConfig,
+
+    /// Choose a file and apply the configuration read from it
+    ImportConfig,
+
+    /// Open the operator login window
+    OpenLoginWindow,
+
+    /// Send from the login window after a successful login
+    OperatorLoggedIn(String),
+
     /// Open a message dialog.
     OpenMessageDialog {
         /// Title.
@@ -125,26 +570,27 @@ pub enum Message {
     /// Start the update process in a separate thread
     StartUpdate,
 
-    /// Send from update task when operation starts
-    DeviceUpdateStarted,
-
-    /// Send from update task when everything is finished
-    DeviceUpdateFinished,
+    /// Start the update process, skipping the sectors completed by an
+    /// interrupted update of the same file
+    ResumeUpdate,
 
-    /// Send from update task when an error has occurred
-    DeviceUpdateError(String),
+    /// Open the file dialog to choose a backup file
+    OpenBackupDialog,
 
-    /// Set a new update step
-    DeviceUpdateStep(DeviceUpdateStep),
+    /// Start reading the device memory into a file in a separate thread
+    StartBackup(std::path::PathBuf),
 
-    /// Set progress for device erase operation
-    DeviceEraseProgress(f32),
+    /// Cancel the running operation
+    CancelJob(progress::CancelMode),
 
-    /// Set progress for device program operation
-    DeviceProgramProgress(f32),
+    /// Event of a job running in a separate thread
+    Job(progress::JobEvent),
+}
 
-    /// Set progress for device verify operation
-    DeviceVerifyProgress(f32),
+impl From<progress::JobEvent> for Message {
+    fn from(event: progress::JobEvent) -> Self {
+        Self::Job(event)
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -169,6 +615,219 @@ pub struct DfuFileChecks {
 
     /// Flag if all targets are valid
     targets_valid: bool,
+
+    /// Flag if all image elements fit into writable memory of their targets
+    fits_in_memory: bool,
+
+    /// Flag if the device supports uploads, required to verify
+    upload_supported: bool,
+
+    /// Image elements that can't be written, with the reason
+    memory_problems: Vec<String>,
+
+    /// Image elements that can't be erased sector by sector, with the reason
+    erase_problems: Vec<String>,
+
+    /// Raw descriptors and problems of targets with unusable memory geometry
+    geometry_report: Option<String>,
+
+    /// Results of the configured integrity checks
+    integrity: Vec<integrity::Outcome>,
+
+    /// Hashes of the file and its image elements
+    hashes: Option<integrity::FileHashes>,
+
+    /// Name of the board preset checked against, `None` if none is selected
+    preset: Option<&'static str>,
+
+    /// Differences between the device or file and the board preset
+    preset_problems: Vec<String>,
+
+    /// Flag if the device and file match the board preset
+    preset_accepted: bool,
+
+    /// Firmware versions of the device and the file if the file is older
+    downgrade: Option<(u16, u16)>,
+}
+
+impl DfuFileChecks {
+    /// Calculate the CRC of the file and compare it to the suffix
+    fn check_crc(&mut self, dfu_file: &mut dfufile::DfuFile) {
+        match dfu_file.calc_crc() {
+            Ok(crc) => {
+                self.crc_checked = true;
+                self.crc_valid = crc == dfu_file.suffix.dwCRC;
+            }
+            Err(error) => {
+                log::error!("{}", error);
+            }
+        }
+    }
+
+    /// Match the file against a device and set the flags accordingly
+    ///
+    /// Skipped images are left out, remapped images are checked against
+    /// the alt setting they are written to.
+    fn match_device(
+        &mut self,
+        dfu_file: &dfufile::DfuFile,
+        device: &dfudev::DfuDevice,
+        targets: &update::Targets,
+    ) {
+        let file_vendor_id = dfu_file.suffix.idVendor;
+        let file_product_id = dfu_file.suffix.idProduct;
+
+        self.dfu_version_valid = dfu_file.suffix.bcdDFU == device.info.dfu_version;
+        self.upload_supported = device.info.can_upload;
+        self.vendor_id_accepted =
+            (file_vendor_id == 0xFFFF) || (file_vendor_id == device.info.vendor_id);
+        self.product_id_accepted =
+            (file_product_id == 0xFFFF) || (file_product_id == device.info.product_id);
+
+        // Devices reporting version 0 don't track their firmware version
+        let file_version = dfu_file.suffix.bcdDevice;
+        let device_version = device.info.bcd_device;
+        self.downgrade =
+            (file_version != 0xFFFF && device_version != 0 && file_version < device_version)
+                .then_some((device_version, file_version));
+        if let Some(message) = self.downgrade_message() {
+            log::warn!("{}", message);
+        }
+
+        self.targets_valid = match &dfu_file.content {
+            dfufile::Content::Plain => true,
+            dfufile::Content::DfuSe(content) => {
+                selected_images(content, targets).all(|(alt_setting, _)| {
+                    device
+                        .info
+                        .alt_settings
+                        .iter()
+                        .any(|alt| alt.0 == alt_setting)
+                })
+            }
+        };
+
+        self.memory_problems.clear();
+        self.erase_problems.clear();
+
+        if let dfufile::Content::DfuSe(content) = &dfu_file.content {
+            for (alt_setting, image) in selected_images(content, targets) {
+                let target = device
+                    .info
+                    .alt_settings
+                    .iter()
+                    .find(|alt| alt.0 == alt_setting);
+
+                // Only targets with a DfuSe memory layout can be checked,
+                // unparseable layouts are reported by the geometry check
+                let Some(segment) = target
+                    .filter(|(_, name)| name.starts_with('@'))
+                    .and_then(|(_, name)| MemorySegment::from_string_desc(name).ok())
+                else {
+                    continue;
+                };
+
+                for element in &image.image_elements {
+                    let address = element.dwElementAddress;
+                    let size = element.dwElementSize;
+                    let describe = |problem: String| {
+                        format!(
+                            "Alt setting {alt_setting}, element at 0x{address:08X} ({}): {problem}",
+                            format::size(size as u64)
+                        )
+                    };
+
+                    if let Some(problem) = segment.range_problem(address, size, false) {
+                        self.memory_problems.push(describe(problem));
+                    } else if let Some(problem) = segment.range_problem(address, size, true) {
+                        self.erase_problems.push(describe(problem));
+                    }
+                }
+            }
+        }
+        self.fits_in_memory = self.memory_problems.is_empty();
+        for problem in self.memory_problems.iter().chain(&self.erase_problems) {
+            log::warn!("{}", problem);
+        }
+
+        self.geometry_report = match &dfu_file.content {
+            dfufile::Content::Plain => None,
+            dfufile::Content::DfuSe(content) => {
+                let report: Vec<String> = device
+                    .info
+                    .alt_settings
+                    .iter()
+                    .filter(|(alt, name)| {
+                        name.starts_with('@')
+                            && selected_images(content, targets)
+                                .any(|(alt_setting, _)| alt_setting == *alt)
+                    })
+                    .filter_map(|(alt, name)| {
+                        let errors = match MemorySegment::from_string_desc(name) {
+                            Ok(segment) => segment.geometry_errors(),
+                            Err(error) => vec![error.to_string()],
+                        };
+                        (!errors.is_empty()).then(|| {
+                            format!("Alt setting {alt}: {name}\n- {}", errors.join("\n- "))
+                        })
+                    })
+                    .collect();
+                (!report.is_empty()).then(|| report.join("\n\n"))
+            }
+        };
+        if let Some(report) = &self.geometry_report {
+            log::warn!("Invalid memory geometry:\n{}", report);
+        }
+    }
+
+    /// Check the device and the selected images against a board preset
+    fn check_preset(
+        &mut self,
+        preset: Option<&'static presets::Preset>,
+        dfu_file: &dfufile::DfuFile,
+        device: &dfudev::DfuDevice,
+        targets: &update::Targets,
+    ) {
+        self.preset = preset.map(|preset| preset.name);
+        self.preset_problems = match (preset, &dfu_file.content) {
+            (None, _) => Vec::new(),
+            (Some(preset), dfufile::Content::Plain) => preset.problems(&device.info, &[]),
+            (Some(preset), dfufile::Content::DfuSe(content)) => {
+                let elements: Vec<(u32, u32)> = selected_images(content, targets)
+                    .flat_map(|(_, image)| &image.image_elements)
+                    .map(|element| (element.dwElementAddress, element.dwElementSize))
+                    .collect();
+                preset.problems(&device.info, &elements)
+            }
+        };
+        self.preset_accepted = self.preset_problems.is_empty();
+        for problem in &self.preset_problems {
+            log::warn!("{}", problem);
+        }
+    }
+
+    /// Return the message shown if the file would downgrade the firmware
+    fn downgrade_message(&self) -> Option<String> {
+        self.downgrade.map(|(device_version, file_version)| {
+            format!(
+                "This would downgrade the firmware from version {} to {}.",
+                format::bcd_version(device_version),
+                format::bcd_version(file_version)
+            )
+        })
+    }
+
+    /// Return if all checks are passed
+    fn passed(&self) -> bool {
+        self.crc_valid
+            && self.dfu_version_valid
+            && self.vendor_id_accepted
+            && self.product_id_accepted
+            && self.targets_valid
+            && self.fits_in_memory
+            && (self.preset.is_none() || self.preset_accepted)
+            && self.integrity.iter().all(|outcome| outcome.passed)
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -179,94 +838,373 @@ pub struct DeviceUpdateState {
     /// Device ready flag
     device_ready: bool,
 
+    /// Steps left out by the update, with the reason
+    skipped_steps: Vec<update::SkippedStep>,
+
+    /// Address of the last block transferred
+    address: Option<u32>,
+
     /// File ready flag
     file_ready: bool,
 
     /// Flag if everything is ready to start
     preflight_checks_passed: bool,
 
+    /// Flag if an operator must log in before the update can be started
+    login_required: bool,
+
+    /// Flag if updates are disabled by inspector mode
+    read_only: bool,
+
+    /// Flag if failed checks can't be approved by the user
+    strict: bool,
+
+    /// Flag if updates run on a simulated device
+    simulate: bool,
+
     /// Confirmation flag set by user checkbox
     confirmed: bool,
 
     /// Update in progress flag
     running: bool,
 
+    /// How the running operation was asked to stop, if it was
+    cancelling: Option<progress::CancelMode>,
+
     /// Flag set after finishing without errors
     finished: bool,
 
+    /// Reason the data written couldn't be verified, set when finished
+    /// without verification
+    unverified: Option<String>,
+
     /// Current step
     step: Option<DeviceUpdateStep>,
 
     /// Last error
     error: Option<String>,
 
-    /// Erase operation progress 0..1 for 0..100%
-    erase_progress: f32,
+    /// Steps of the current or last operation with their progress
+    phases: Vec<PhaseState>,
 
-    /// Program operation progress 0..1 for 0..100%
-    program_progress: f32,
+    /// Warning shown when the device already received the same file
+    duplicate_warning: Option<String>,
 
-    /// Verify operation progress 0..1 for 0..100%
-    verify_progress: f32,
-}
+    /// Flag if the user confirmed to flash the same file again
+    duplicate_confirmed: bool,
 
-/// Current step of update procedure
-#[derive(PartialEq, Eq, Debug, Clone, Copy)]
-pub enum DeviceUpdateStep {
-    /// Erase operation in progress
-    Erase,
+    /// Warning shown when the file is older than the firmware of the device
+    downgrade_warning: Option<String>,
 
-    /// Program operation in progress
-    Program,
+    /// Flag if the user confirmed to downgrade the firmware
+    downgrade_confirmed: bool,
 
-    /// Verify operation in progress
-    Verify,
-}
+    /// Path of the file written by the last backup
+    backup_path: Option<std::path::PathBuf>,
 
-////////////////////////////////////////////////////////////////////////////////
+    /// Statistics of the current or last update
+    statistics: update::Statistics,
 
-impl Default for App {
-    fn default() -> Self {
-        Self {
-            devices: None,
-            device_id: None,
-            dfu_file: None,
-            file_dialog_path: None,
-            dfu_file_checks: DfuFileChecks::default(),
-            message_channel: std::sync::mpsc::channel(),
-            device_update_state: DeviceUpdateState::default(),
-            zoom_factor: 1.0,
-        }
-    }
-}
+    /// Durations of the requests of the current or last update
+    timings: dfudev::timing::Timings,
 
-impl eframe::App for App {
-    /// Called by the frame work to save state before shutdown
-    fn save(&mut self, storage: &mut dyn eframe::Storage) {
-        eframe::set_value(storage, eframe::APP_KEY, self);
-    }
+    /// Number of payload bytes transferred by the program and verify steps
+    payload_size: u64,
 
-    /// Called each time the UI needs repainting, which may be many times per second.
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        let mut message_dialog = Modal::new(ctx, "message_dialog");
-        message_dialog.show_dialog();
+    /// Time when the operation was started
+    started: Option<Instant>,
 
-        let zoom_factor = ctx.zoom_factor();
-        if self.zoom_factor != zoom_factor {
-            ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(WINDOW_SIZE * zoom_factor));
-            self.zoom_factor = zoom_factor;
-        }
+    /// Time when the current step was started
+    step_started: Option<Instant>,
 
-        // Continuous updates are required for message processing, but keep frame rate limited.
-        ctx.request_repaint_after(Duration::from_millis(1000 / FPS_LIMIT as u64));
+    /// Time when the operation was finished or failed
+    stopped: Option<Instant>,
 
-        while let Ok(message) = self.message_channel.1.try_recv() {
-            self.process_message(&message, ctx, &mut message_dialog);
+    /// CRC-32 of the device memory calculated by the last checksum step
+    checksum: Option<u32>,
+
+    /// Sectors differing from the file found by the last verification
+    differences: Vec<update::Difference>,
+
+    /// Flag if the application is expected to start after the update
+    awaiting_firmware: bool,
+
+    /// Application found after the update, compared with the file
+    firmware_check: Option<update::FirmwareCheck>,
+
+    /// Name of the target erased last
+    erase_target: String,
+
+    /// Sectors of the target erased last
+    sectors: Vec<SectorStatus>,
+
+    /// Sectors programmed completely by the current or last update, by alt setting
+    completed_sectors: Vec<(u8, update::AddressRange)>,
+
+    /// Description of the part completed by an interrupted update that
+    /// can be resumed
+    resumable: Option<String>,
+
+    /// Warnings of all stages, the most severe first
+    warnings: Vec<warnings::Warning>,
+}
+
+impl DeviceUpdateState {
+    /// Return the progress of all steps combined, 0..1 for 0..100%
+    ///
+    /// All steps are weighted equally.
+    pub fn overall_progress(&self) -> f32 {
+        if self.phases.is_empty() {
+            return 0.0;
+        }
+
+        let sum: f32 = self.phases.iter().map(|phase| phase.progress).sum();
+
+        sum / self.phases.len() as f32
+    }
+
+    /// Return the progress of a single step, 0 if it is not part of the operation
+    pub fn step_progress(&self, step: DeviceUpdateStep) -> f32 {
+        self.phases
+            .iter()
+            .find(|phase| phase.step == step)
+            .map_or(0.0, |phase| phase.progress)
+    }
+
+    /// Return the time since the operation was started
+    pub fn elapsed(&self) -> Option<Duration> {
+        let started = self.started?;
+
+        Some(self.stopped.unwrap_or_else(Instant::now) - started)
+    }
+
+    /// Return the estimated time until the operation is finished
+    ///
+    /// The estimate is extrapolated from the overall progress, so it is
+    /// only available after a small part of the work is done.
+    pub fn remaining(&self) -> Option<Duration> {
+        const MIN_PROGRESS: f32 = 0.01;
+
+        if !self.running {
+            return None;
+        }
+
+        let progress = self.overall_progress();
+        if progress < MIN_PROGRESS {
+            return None;
+        }
+
+        self.elapsed()
+            .map(|elapsed| elapsed.mul_f32((1.0 - progress) / progress))
+    }
+
+    /// Return the transfer speed of the current step in bytes per second
+    pub fn transfer_speed(&self) -> Option<f32> {
+        let step = self
+            .step
+            .filter(|step| matches!(step, DeviceUpdateStep::Program | DeviceUpdateStep::Verify))?;
+        let seconds = self.step_started?.elapsed().as_secs_f32();
+
+        if !self.running || seconds <= 0.0 {
+            return None;
+        }
+
+        Some(self.step_progress(step) * self.payload_size as f32 / seconds)
+    }
+}
+
+/// Progress of a single step
+#[derive(Debug, Clone)]
+pub struct PhaseState {
+    /// Step
+    step: DeviceUpdateStep,
+
+    /// Progress 0..1 for 0..100%
+    progress: f32,
+}
+
+/// Sector of the target being erased
+#[derive(Debug, Clone)]
+pub struct SectorStatus {
+    /// Start address
+    address: u32,
+
+    /// Size in bytes
+    size: u32,
+
+    /// Current state
+    state: SectorState,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+impl Default for App {
+    fn default() -> Self {
+        Self {
+            devices: None,
+            device_id: None,
+            stale_devices: Default::default(),
+            access_denied: false,
+            dfu_file: None,
+            file_dialog_path: None,
+            file_dialog: None,
+            file_filter: None,
+            dfu_file_checks: DfuFileChecks::default(),
+            message_channel: std::sync::mpsc::channel(),
+            device_update_state: DeviceUpdateState::default(),
+            show_statistics: false,
+            event_log: Default::default(),
+            show_events: false,
+            show_differences: false,
+            kiosk: Default::default(),
+            show_kiosk: false,
+            rescan_pending: false,
+            converted_file: None,
+            archive_path: None,
+            archive_choice: None,
+            file_hash: None,
+            history: history::History::default(),
+            current_job: None,
+            worker: None,
+            current_operation: None,
+            journal_record: None,
+            warnings: Default::default(),
+            resume_progress: None,
+            resume_key: None,
+            journal_view: Default::default(),
+            flash_history_view: Default::default(),
+            memory_view: Default::default(),
+            read_memory_view: Default::default(),
+            block_cache: Default::default(),
+            zoom_factor: 1.0,
+            progress_rate: update::Options::default().progress_rate,
+            mass_erase: false,
+            skip_blank_blocks: true,
+            differential_update: false,
+            padding: Default::default(),
+            transfer_settings: Default::default(),
+            after_update: update::AfterUpdate::default(),
+            preset: None,
+            profiles: Vec::new(),
+            profile: None,
+            theme: Default::default(),
+            log_level: Default::default(),
+            remember_device: true,
+            session_log: false,
+            session_log_directory: String::new(),
+            last_device: None,
+            phases: update::Phases::default(),
+            targets: update::Targets::default(),
+            skipped_images: Default::default(),
+            memory_maps: Vec::new(),
+            production_mode: false,
+            operators: Vec::new(),
+            inspector_mode: false,
+            strict_mode: false,
+            integrity_checks: Vec::new(),
+            public_key: String::new(),
+            inspector_locked: false,
+            address_range: None,
+            demo_mode: false,
+            simulate_update: false,
+            operator: None,
+            login_form: Default::default(),
+            operators_form: Default::default(),
+            profiles_form: Default::default(),
+            profile_download: false,
+            profile_downloads: Vec::new(),
+            suffix_tool_form: Default::default(),
+            permissions_form: Default::default(),
+            memory_map_form: Default::default(),
+            unprotect_form: Default::default(),
+            address_range_form: Default::default(),
+            transfer_settings_form: Default::default(),
+            device_in_use_form: Default::default(),
+            settings_form: Default::default(),
+        }
+    }
+}
+
+impl eframe::App for App {
+    /// Called by the frame work to save state before shutdown
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, eframe::APP_KEY, self);
+    }
+
+    /// Called by the frame work before shutdown, after saving the state
+    ///
+    /// A running operation is cancelled and given some time to return the
+    /// device to idle, instead of ending the process in a control transfer.
+    /// Firmware files downloaded for profiles are removed.
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        for path in self.profile_downloads.drain(..) {
+            std::fs::remove_file(path).ok();
+        }
+
+        let Some((reporter, thread)) = self.worker.take() else {
+            return;
+        };
+        if thread.is_finished() {
+            return;
+        }
+
+        log::info!("Cancelling job {} before exit", reporter.job());
+        reporter.cancel(progress::CancelMode::Abort);
+
+        let start = std::time::Instant::now();
+        while !thread.is_finished() {
+            if start.elapsed() > SHUTDOWN_TIMEOUT {
+                log::warn!("Job {} did not stop in time", reporter.job());
+                return;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        log::info!("Job {} stopped", reporter.job());
+    }
+
+    /// Called each time the UI needs repainting, which may be many times per second.
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let mut message_dialog = Modal::new(ctx, "message_dialog");
+        message_dialog.show_dialog();
+
+        let zoom_factor = ctx.zoom_factor();
+        if self.zoom_factor != zoom_factor {
+            ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(WINDOW_SIZE * zoom_factor));
+            self.zoom_factor = zoom_factor;
         }
 
-        self.device_update_state.device_ready = self.device_id.is_some();
+        self.process_messages(ctx, &mut message_dialog);
+
+        self.device_update_state.device_ready = self
+            .device_id
+            .is_some_and(|id| !self.stale_devices.contains(&id));
         self.device_update_state.file_ready = self.dfu_file.is_some();
         self.device_update_state.preflight_checks_passed = self.preflight_checks();
+        self.device_update_state.login_required = self.production_mode && self.operator.is_none();
+        self.device_update_state.read_only = self.inspector_mode;
+        self.device_update_state.strict = self.strict_mode;
+        self.device_update_state.simulate = self.simulate_update;
+
+        let resume_key = self.device_id.zip(self.file_hash);
+        if resume_key != self.resume_key {
+            self.resume_key = resume_key;
+            self.load_resume_progress();
+        }
+        self.device_update_state.resumable =
+            self.resume_progress.as_ref().map(resume::Progress::summary);
+        if self.device_update_state.preflight_checks_passed && !self.device_update_state.running {
+            self.collect_preflight_warnings();
+        }
+        self.device_update_state.warnings = self.warnings.list();
+
+        if !self.device_update_state.running && !self.rescan_pending {
+            if let Some(device_id) = self.kiosk.next() {
+                self.start_kiosk_update(device_id);
+            }
+        }
+        if self.kiosk.waiting() > 0 {
+            ctx.request_repaint();
+        }
 
         // Top panel with menu
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
@@ -277,10 +1215,304 @@ impl eframe::App for App {
                         self.message_channel.0.send(Message::OpenFileDialog).ok();
                         ui.close_menu();
                     }
+                    if ui
+                        .add_enabled(
+                            self.get_selected_device()
+                                .is_some_and(|device| device.info.can_upload),
+                            egui::Button::new("Backup device..."),
+                        )
+                        .clicked()
+                    {
+                        self.message_channel.0.send(Message::OpenBackupDialog).ok();
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    ui.add_enabled_ui(
+                        self.operator.is_none() && !self.device_update_state.running,
+                        |ui| {
+                            if ui.button("Import configuration...").clicked() {
+                                self.message_channel.0.send(Message::ImportConfig).ok();
+                                ui.close_menu();
+                            }
+                        },
+                    );
+                    if ui.button("Export configuration...").clicked() {
+                        self.message_channel.0.send(Message::ExportConfig).ok();
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    if ui.button("Profiles...").clicked() {
+                        self.profiles_form.open = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Preferences...").clicked() {
+                        self.settings_form.show(self.preferences());
+                        ui.close_menu();
+                    }
+                    ui.separator();
                     if ui.button("Quit").clicked() {
                         ctx.send_viewport_cmd(egui::ViewportCommand::Close);
                     }
                 });
+                egui::menu::menu_button(ui, "View", |ui| {
+                    if ui.button("Statistics").clicked() {
+                        self.show_statistics = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Flash history...").clicked() {
+                        self.message_channel.0.send(Message::OpenFlashHistory).ok();
+                        ui.close_menu();
+                    }
+                    ui.menu_button("Progress updates", |ui| {
+                        for rate in PROGRESS_RATES {
+                            ui.radio_value(
+                                &mut self.progress_rate,
+                                rate,
+                                format!("{rate} per second"),
+                            );
+                        }
+                    });
+                });
+                egui::menu::menu_button(ui, "Options", |ui| {
+                    ui.add_enabled_ui(!self.device_update_state.running, |ui| {
+                        ui.menu_button("After update", |ui| {
+                            for action in update::AfterUpdate::ALL {
+                                ui.radio_value(&mut self.after_update, action, action.to_string());
+                            }
+                        });
+                        ui.checkbox(&mut self.skip_blank_blocks, "Skip blank blocks")
+                            .on_hover_text(
+                                "Don't program blocks of only 0xFF, the state of erased flash. \
+                                Only applies if the device is erased by the same update.",
+                            );
+                        ui.checkbox(&mut self.differential_update, "Differential update")
+                            .on_hover_text(
+                                "Read back the sectors covered by the file first and only \
+                                erase and program the ones that differ",
+                            );
+                        ui.menu_button("Board preset", |ui| {
+                            let mut preset = self.preset.clone();
+                            ui.radio_value(&mut preset, None, "None")
+                                .on_hover_text("Show all DFU devices");
+                            for entry in presets::PRESETS {
+                                ui.radio_value(
+                                    &mut preset,
+                                    Some(entry.name.to_string()),
+                                    entry.name,
+                                )
+                                .on_hover_text(entry.notes);
+                            }
+                            if preset != self.preset {
+                                self.message_channel
+                                    .0
+                                    .send(Message::SelectPreset(preset))
+                                    .ok();
+                                ui.close_menu();
+                            }
+                        });
+                        ui.menu_button("Padding", |ui| {
+                            for padding in builder::Padding::ALL {
+                                if ui
+                                    .radio_value(&mut self.padding, padding, padding.to_string())
+                                    .on_hover_text(padding.description())
+                                    .changed()
+                                {
+                                    log::info!("Padding of converted files: {padding}");
+                                    self.convert_file();
+                                    self.match_file_against_device();
+                                    self.device_update_state = DeviceUpdateState::default();
+                                }
+                            }
+                        });
+                        if ui
+                            .button("Address range...")
+                            .on_hover_text("Restrict updates to a part of the file")
+                            .clicked()
+                        {
+                            self.address_range_form.show(self.address_range);
+                            ui.close_menu();
+                        }
+                        if ui
+                            .button("Transfer settings...")
+                            .on_hover_text("Override transfer size, timeouts and polling")
+                            .clicked()
+                        {
+                            self.transfer_settings_form.show(self.transfer_settings);
+                            ui.close_menu();
+                        }
+                    });
+                });
+                egui::menu::menu_button(ui, "Tools", |ui| {
+                    if ui.button("Create DFU file...").clicked() {
+                        self.suffix_tool_form.open = true;
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(
+                            self.device_id.is_some(),
+                            egui::Button::new("System memory..."),
+                        )
+                        .clicked()
+                    {
+                        self.memory_view.open = true;
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(
+                            self.device_id.is_some(),
+                            egui::Button::new("Read memory..."),
+                        )
+                        .on_hover_text("Show an address range of the device as hex dump")
+                        .clicked()
+                    {
+                        self.read_memory_view.open = true;
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(
+                            self.device_id.is_some() && !self.device_update_state.running,
+                            egui::Button::new("Memory map override..."),
+                        )
+                        .on_hover_text("Replace a malformed memory layout reported by the device")
+                        .clicked()
+                    {
+                        let device = self
+                            .devices
+                            .iter()
+                            .flatten()
+                            .find(|device| Some(device.id) == self.device_id);
+                        if let Some(device) = device {
+                            self.memory_map_form.show(&device.info, &self.memory_maps);
+                        }
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(
+                            self.device_id.is_some()
+                                && !self.device_update_state.running
+                                && !self.inspector_mode,
+                            egui::Button::new("Remove read protection..."),
+                        )
+                        .on_hover_text("Unprotect a DfuSe device, this erases its flash memory")
+                        .clicked()
+                    {
+                        let device = self
+                            .devices
+                            .iter()
+                            .flatten()
+                            .find(|device| Some(device.id) == self.device_id);
+                        if let Some(device) = device {
+                            self.unprotect_form.show(&device.info);
+                        }
+                        ui.close_menu();
+                    }
+                    if cfg!(target_os = "linux")
+                        && ui.button("Setup device permissions...").clicked()
+                    {
+                        self.permissions_form.show(&dfudev::usb_ids());
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    ui.add_enabled_ui(
+                        !self.inspector_locked && !self.device_update_state.running,
+                        |ui| {
+                            if ui
+                                .checkbox(&mut self.inspector_mode, "Inspector mode (read-only)")
+                                .changed()
+                            {
+                                log::info!("Inspector mode: {}", self.inspector_mode);
+                            }
+                        },
+                    );
+                    ui.add_enabled_ui(!self.device_update_state.running, |ui| {
+                        if ui
+                            .checkbox(&mut self.simulate_update, "Simulate update")
+                            .on_hover_text(
+                                "Run updates on a simulated copy of the device \
+                                to check the file and memory layout without writing it",
+                            )
+                            .changed()
+                        {
+                            log::info!("Simulate update: {}", self.simulate_update);
+                        }
+                    });
+                });
+                egui::menu::menu_button(ui, "Production", |ui| {
+                    // Settings can only be changed without an operator logged in
+                    ui.add_enabled_ui(self.operator.is_none(), |ui| {
+                        ui.checkbox(&mut self.production_mode, "Production mode");
+                        if ui
+                            .checkbox(&mut self.strict_mode, "Strict mode")
+                            .on_hover_text("Only files passing all checks can be flashed")
+                            .changed()
+                        {
+                            log::info!("Strict mode: {}", self.strict_mode);
+                            if self.strict_mode {
+                                self.targets.remapped.clear();
+                            }
+                            // A configured key requires a signature in strict mode
+                            self.check_file_integrity();
+                            // Drop approvals given before
+                            self.match_file_against_device();
+                        }
+                        ui.menu_button("Integrity checks", |ui| {
+                            for algorithm in integrity::Algorithm::ALL {
+                                let mut enabled = self.integrity_checks.contains(&algorithm);
+                                if ui
+                                    .checkbox(&mut enabled, algorithm.name())
+                                    .on_hover_text(algorithm.description())
+                                    .changed()
+                                {
+                                    log::info!("Integrity check \"{algorithm}\": {enabled}");
+                                    self.integrity_checks.retain(|other| *other != algorithm);
+                                    if enabled {
+                                        self.integrity_checks.push(algorithm);
+                                    }
+                                    self.check_file_integrity();
+                                }
+                            }
+
+                            ui.separator();
+                            ui.label("Public key for signatures:");
+                            // The key is what a signature is trusted by, so it
+                            // can't be replaced while overrides are disabled
+                            if ui
+                                .add_enabled(
+                                    !self.strict_mode,
+                                    egui::TextEdit::singleline(&mut self.public_key)
+                                        .hint_text("Base64 key from minisign.pub"),
+                                )
+                                .on_disabled_hover_text("Switch off strict mode to change the key")
+                                .changed()
+                            {
+                                self.check_file_integrity();
+                            }
+                        });
+                        if ui.button("Operators...").clicked() {
+                            self.operators_form.open = true;
+                            ui.close_menu();
+                        }
+                    });
+                    if ui
+                        .add_enabled(!self.inspector_mode, egui::Button::new("Kiosk mode..."))
+                        .on_hover_text("Flash every device appearing automatically")
+                        .clicked()
+                    {
+                        self.show_kiosk = true;
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    if let Some(operator) = &self.operator {
+                        if ui.button(format!("Log out {operator}")).clicked() {
+                            log::info!("Operator \"{operator}\" logged out.");
+                            self.operator = None;
+                            ui.close_menu();
+                        }
+                    } else if ui.button("Log in...").clicked() {
+                        self.login_form.open = true;
+                        ui.close_menu();
+                    }
+                });
             });
             ui.add_space(0.1);
         });
@@ -291,6 +1523,39 @@ impl eframe::App for App {
             ui.horizontal(|ui| {
                 ui.label(format!("v{}", &env!("CARGO_PKG_VERSION")));
                 egui::warn_if_debug_build(ui);
+                if self.inspector_mode {
+                    ui.separator();
+                    ui.label(egui::RichText::new("Inspector mode").color(egui::Color32::YELLOW));
+                }
+                if self.strict_mode {
+                    ui.separator();
+                    ui.label("Strict mode");
+                }
+                if let Some(range) = self.address_range {
+                    ui.separator();
+                    ui.label(
+                        egui::RichText::new(format!("Range {range}")).color(egui::Color32::YELLOW),
+                    )
+                    .on_hover_text("Only the data of the file inside this range is written");
+                }
+                if self.demo_mode {
+                    ui.separator();
+                    ui.label(egui::RichText::new("Demo mode").color(egui::Color32::YELLOW));
+                }
+                if self.simulate_update {
+                    ui.separator();
+                    ui.label(egui::RichText::new("Simulation").color(egui::Color32::YELLOW))
+                        .on_hover_text(
+                            "Updates run on a simulated device, the device is not changed",
+                        );
+                }
+                if self.production_mode {
+                    ui.separator();
+                    ui.label(format!(
+                        "Operator: {}",
+                        self.operator.as_deref().unwrap_or("(not logged in)")
+                    ));
+                }
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                     ui.hyperlink_to("Project homepage", env!("CARGO_PKG_HOMEPAGE"));
                 });
@@ -300,16 +1565,29 @@ impl eframe::App for App {
 
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.scope(|ui| {
-                if self.device_update_state.running {
+                if self.device_update_state.running || self.unprotect_form.running {
                     ui.disable();
                 }
 
                 ui.add_space(5.0);
 
+                if !self.profiles.is_empty() {
+                    ui::profiles::selection(
+                        ui,
+                        &self.profiles,
+                        self.profile.as_deref(),
+                        self.profile_download,
+                        &self.message_channel.0,
+                    );
+                    ui.add_space(5.0);
+                }
+
                 ui::device::selection(
                     ui,
                     &self.devices,
                     &self.get_selected_device(),
+                    &self.stale_devices,
+                    self.preset.as_deref(),
                     &self.message_channel.0,
                 );
 
@@ -318,15 +1596,19 @@ impl eframe::App for App {
                 ui.horizontal(|ui| {
                     ui.set_height(160.0);
 
-                    let device_info = self.get_selected_device().map(|device| &device.info);
+                    if self.devices.is_none() {
+                        device::troubleshooting(ui, &self.message_channel.0);
+                    } else {
+                        let device_info = self.get_selected_device().map(|device| &device.info);
 
-                    device::common_info(ui, device_info);
-                    device::memory_info(ui, device_info);
+                        device::common_info(ui, device_info, &self.message_channel.0);
+                        device::memory_info(ui, device_info, self.dfu_file.as_ref());
+                    }
                 });
 
                 ui.add_space(5.0);
 
-                ui::file::selection(ui, &self.dfu_file, &self.message_channel.0);
+                ui::file::selection(ui, self.selected_file_path(), &self.message_channel.0);
 
                 ui.add_space(5.0);
 
@@ -336,81 +1618,269 @@ impl eframe::App for App {
                     file::common_info(
                         ui,
                         &self.dfu_file,
-                        &mut self.dfu_file_checks,
+                        &self.dfu_file_checks,
                         self.device_id.is_some(),
                     );
 
-                    let device_info = self.get_selected_device().map(|device| &device.info);
+                    let file_key = self.selected_file_path().map(std::path::Path::to_path_buf);
+                    self.targets.skipped = self.skipped_targets();
 
-                    file::content_info(ui, &self.dfu_file, device_info);
+                    // Borrow only the fields required, the raw binary is borrowed mutably
+                    let device_info = self
+                        .devices
+                        .iter()
+                        .flatten()
+                        .find(|device| Some(device.id) == self.device_id)
+                        .map(|device| &device.info);
+
+                    file::content_info(
+                        ui,
+                        &self.dfu_file,
+                        self.converted_file.as_mut(),
+                        device_info,
+                        &self.dfu_file_checks,
+                        &mut self.targets,
+                        &self.message_channel.0,
+                    );
+
+                    let selection_changed = self.targets.skipped != self.skipped_targets();
+
+                    if let Some(file_key) = file_key {
+                        if self.targets.skipped.is_empty() {
+                            self.skipped_images.remove(&file_key);
+                        } else {
+                            self.skipped_images
+                                .insert(file_key, self.targets.skipped.clone());
+                        }
+                    }
+
+                    // Checks of deselected images no longer apply
+                    if selection_changed {
+                        self.match_file_against_device();
+                    }
                 });
             });
 
             ui.add_space(5.0);
 
             ui.horizontal(|ui| {
-                ui.set_height(100.0);
-                device::update_controls(ui, &mut self.device_update_state, &self.message_channel.0);
+                ui.set_height(130.0);
+                device::update_controls(
+                    ui,
+                    &mut self.device_update_state,
+                    &mut self.dfu_file_checks,
+                    &mut self.mass_erase,
+                    &mut self.phases,
+                    &mut self.after_update,
+                    &self.message_channel.0,
+                );
                 ui.add_space(10.0);
                 device::update_progress(ui, &self.device_update_state);
             });
         });
 
+        ui::statistics::window(
+            ctx,
+            &mut self.show_statistics,
+            &self.device_update_state.statistics,
+            &self.device_update_state.timings,
+        );
+        ui::events::window(ctx, &mut self.show_events, &mut self.event_log);
+        ui::archive::window(ctx, &mut self.archive_choice, &self.message_channel.0);
+        ui::differences::window(
+            ctx,
+            &mut self.show_differences,
+            &self.device_update_state.differences,
+        );
+        ui::kiosk::window(
+            ctx,
+            &mut self.show_kiosk,
+            &mut self.kiosk,
+            self.dfu_file.is_some(),
+            &self.message_channel.0,
+        );
+
+        ui::operator::login_window(
+            ctx,
+            &mut self.login_form,
+            &self.operators,
+            &self.message_channel.0,
+        );
+        ui::operator::operators_window(ctx, &mut self.operators_form, &mut self.operators);
+        ui::journal::window(ctx, &mut self.journal_view);
+        ui::flash_history::window(ctx, &mut self.flash_history_view, &self.message_channel.0);
+        let file_path = self.selected_file_path().map(std::path::Path::to_path_buf);
+        let device_info = self
+            .devices
+            .iter()
+            .flatten()
+            .find(|device| Some(device.id) == self.device_id)
+            .map(|device| &device.info);
+        ui::memory::window(
+            ctx,
+            &mut self.memory_view,
+            device_info,
+            &self.message_channel.0,
+        );
+        ui::read_memory::window(
+            ctx,
+            &mut self.read_memory_view,
+            device_info,
+            &self.message_channel.0,
+        );
+        ui::profiles::window(
+            ctx,
+            &mut self.profiles_form,
+            &mut self.profiles,
+            device_info,
+            file_path.as_deref(),
+        );
+        ui::suffix_tool::window(ctx, &mut self.suffix_tool_form, &self.message_channel.0);
+        ui::permissions::window(ctx, &mut self.permissions_form, &self.message_channel.0);
+        ui::memory_map::window(
+            ctx,
+            &mut self.memory_map_form,
+            &mut self.memory_maps,
+            &self.message_channel.0,
+        );
+        ui::unprotect::window(ctx, &mut self.unprotect_form, &self.message_channel.0);
+        ui::address_range::window(
+            ctx,
+            &mut self.address_range_form,
+            self.address_range,
+            &self.message_channel.0,
+        );
+        ui::device_in_use::window(ctx, &mut self.device_in_use_form, &self.message_channel.0);
+        ui::transfer_settings::window(
+            ctx,
+            &mut self.transfer_settings_form,
+            &self.message_channel.0,
+        );
+        ui::settings::window(
+            ctx,
+            &mut self.settings_form,
+            self.last_device.as_deref(),
+            &self.message_channel.0,
+        );
+
         // File drag-and-drop
         if !self.device_update_state.running {
-            if !ctx.input(|i| i.raw.hovered_files.is_empty()) {
+            let hovered_files = ctx.input(|i| i.raw.hovered_files.clone());
+            if !hovered_files.is_empty() {
                 let painter = ctx.layer_painter(egui::LayerId::new(
                     egui::Order::Foreground,
                     egui::Id::new("file_drop_target"),
                 ));
 
+                // Some platforms don't provide the path before the file is dropped
+                let (text, color) = match hovered_files.iter().find_map(|file| file.path.as_ref()) {
+                    Some(path) => match drop_error(path) {
+                        Some(error) => (error, egui::Color32::LIGHT_RED),
+                        None => (
+                            format!(
+                                "Drop to open {}",
+                                path.file_name().unwrap_or_default().to_string_lossy()
+                            ),
+                            egui::Color32::YELLOW,
+                        ),
+                    },
+                    None => ("Drop file to open".to_string(), egui::Color32::YELLOW),
+                };
+
                 let screen_rect = ctx.input(|i| i.screen_rect());
                 painter.rect_filled(screen_rect, 0.0, egui::Color32::from_black_alpha(192));
                 painter.text(
                     screen_rect.center(),
                     egui::Align2::CENTER_CENTER,
-                    "Drop DFU file top open.",
+                    text,
                     egui::FontId::new(16.0, egui::FontFamily::Proportional),
-                    egui::Color32::YELLOW,
+                    color,
                 );
             }
 
-            if !ctx.input(|i| i.raw.dropped_files.is_empty()) {
-                let dropped_files = ctx.input(|i| i.raw.dropped_files.clone());
-                for file in &dropped_files {
-                    if let Some(path) = &file.path {
+            let dropped_files = ctx.input(|i| i.raw.dropped_files.clone());
+            if let Some(path) = dropped_files.iter().find_map(|file| file.path.as_ref()) {
+                match drop_error(path) {
+                    Some(error) => self.show_error("Error opening file", error),
+                    None => {
                         self.message_channel
                             .0
                             .send(Message::OpenFile(path.clone()))
                             .ok();
-                        break;
                     }
                 }
             }
         }
+
+        // Messages sent by UI elements in this frame are processed immediately,
+        // followed by another frame to show the results.
+        if self.process_messages(ctx, &mut message_dialog) {
+            ctx.request_repaint();
+        }
+
+        // Continuous updates are only required while the update thread sends
+        // messages or a dialog is shown, otherwise repaint on demand.
+        if self.device_update_state.running
+            || self.device_update_state.awaiting_firmware
+            || self.unprotect_form.running
+            || message_dialog.is_open()
+        {
+            ctx.request_repaint_after(Duration::from_millis(1000 / FPS_LIMIT as u64));
+        }
     }
 }
 
 impl App {
     /// Create the application
     pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        let app = if let Some(storage) = cc.storage {
+        let mut app = if let Some(storage) = cc.storage {
             eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default()
         } else {
             Self::default()
         };
 
-        cc.egui_ctx.set_visuals(egui::Visuals::dark());
+        app.theme.apply(&cc.egui_ctx);
+        app.log_level.apply();
+
+        dfudev::quirks::set_memory_map_overrides(app.memory_maps.clone());
+        dfudev::settings::set(app.transfer_settings);
 
         log::info!("USB hotplug: {}", dfudev::has_hotplug());
 
+        let ctx = cc.egui_ctx.clone();
+        let message_sender = app.message_channel.0.clone();
+        dfudev::hotplug::watch(move || {
+            message_sender.send(Message::UsbDevicesChanged).ok();
+            ctx.request_repaint();
+        });
+
         app.message_channel.0.send(Message::Init).ok();
 
-        let mut args = std::env::args();
+        let args: Vec<String> = std::env::args().skip(1).collect();
+
+        if args.iter().any(|arg| arg == INSPECTOR_FLAG) {
+            log::info!("Inspector mode forced by command line.");
+            app.inspector_mode = true;
+            app.inspector_locked = true;
+        }
+
+        if args.iter().any(|arg| arg == DEMO_FLAG) {
+            log::info!("Demo mode, showing a simulated device.");
+            app.demo_mode = true;
+            match demo::file() {
+                Ok(file_path) => {
+                    app.message_channel
+                        .0
+                        .send(Message::OpenFile(file_path))
+                        .ok();
+                }
+                Err(error) => log::error!("Error writing demo file: {}", error),
+            }
+        }
 
-        if args.len() > 1 {
-            // First CLI argument is used as file path
-            let file_path = std::path::PathBuf::from(args.nth(1).unwrap().trim());
+        // First CLI argument that is not a flag is used as file path
+        if let Some(arg) = args.iter().find(|arg| !arg.starts_with("--")) {
+            let file_path = std::path::PathBuf::from(arg.trim());
             if file_path.exists() && file_path.is_file() {
                 app.message_channel
                     .0
@@ -424,6 +1894,25 @@ impl App {
         app
     }
 
+    /// Process all pending messages and return `true` if there were any
+    fn process_messages(&mut self, ctx: &egui::Context, message_dialog: &mut Modal) -> bool {
+        let mut processed = false;
+
+        while let Ok(message) = self.message_channel.1.try_recv() {
+            self.process_message(&message, ctx, message_dialog);
+            processed = true;
+        }
+
+        // Rescanning is deferred while the device is in use
+        if self.rescan_pending && !self.device_update_state.running {
+            self.rescan_pending = false;
+            self.scan_devices();
+            processed = true;
+        }
+
+        processed
+    }
+
     /// Process a message
     fn process_message(
         &mut self,
@@ -439,106 +1928,938 @@ impl App {
             Message::RescanDevices => {
                 self.scan_devices();
             }
-            Message::DeviceSelected(device_id) => {
-                self.device_id = Some(*device_id);
-                self.match_file_against_device();
-                let device = self.get_selected_device().unwrap();
-                log::debug!("Selected device {}", device.info);
-                self.device_update_state = DeviceUpdateState::default();
+            Message::OpenJournal => {
+                self.open_journal();
             }
-            Message::OpenFileDialog => {
-                self.open_file_dialog();
+            Message::OpenEvents => {
+                self.show_events = true;
             }
-            Message::ClearFile => {
-                self.dfu_file = None;
-                self.dfu_file_checks = DfuFileChecks::default();
-                self.device_update_state = DeviceUpdateState::default();
+            Message::OpenDifferences => {
+                self.show_differences = true;
             }
-            Message::OpenFile(file_path) => {
-                log::debug!("Opening file {:?}", file_path);
-                self.open_file(file_path);
-                self.match_file_against_device();
-                if let Some(parent_path) = file_path.parent() {
-                    self.file_dialog_path = Some(std::path::PathBuf::from(parent_path));
+            Message::ReadMemory(alt_setting) => {
+                if let (false, Some(device_id)) = (self.device_update_state.running, self.device_id)
+                {
+                    let alt_setting = *alt_setting;
+                    self.push_device_event(
+                        events::EventKind::Opened,
+                        &format!("Read alt setting {alt_setting}"),
+                    );
+                    let message_sender = self.message_channel.0.clone();
+                    std::thread::spawn(move || {
+                        let result =
+                            update::read_segment(device_id, alt_setting).map_err(|error| {
+                                send_access_hint(
+                                    &message_sender,
+                                    &error,
+                                    Message::ReadMemory(alt_setting),
+                                );
+                                format!("{error}")
+                            });
+                        message_sender.send(Message::MemoryRead(result)).ok();
+                    });
+                } else {
+                    self.memory_view.reading = false;
+                    log::error!("Reading memory not possible.");
                 }
-                self.device_update_state = DeviceUpdateState::default();
-            }
-            Message::OpenMessageDialog { title, body } => {
-                message_dialog
-                    .dialog()
-                    .with_title(title)
-                    .with_body(body)
-                    .open();
-            }
-            Message::DeviceUpdateStarted => {
-                log::debug!("Device update started.");
-                self.device_update_state = DeviceUpdateState::default();
-                self.device_update_state.running = true;
-                self.device_update_state.finished = false;
-            }
-            Message::DeviceUpdateFinished => {
-                log::debug!("Device update finished.");
-                self.device_update_state.running = false;
-                self.device_update_state.step = None;
-                self.device_update_state.finished = true;
-            }
-            Message::DeviceUpdateError(error) => {
-                log::error!("Device update error: {}", error);
-                self.device_update_state.running = false;
-                self.device_update_state.error = Some(error.to_string());
-            }
-            Message::DeviceUpdateStep(step) => {
-                log::debug!("Device update step {:?}", step);
-                self.device_update_state.step = Some(*step)
-            }
-            Message::DeviceEraseProgress(value) => self.device_update_state.erase_progress = *value,
-            Message::DeviceProgramProgress(value) => {
-                self.device_update_state.program_progress = *value
             }
-            Message::DeviceVerifyProgress(value) => {
-                self.device_update_state.verify_progress = *value
-            }
-            Message::StartUpdate => {
-                if !self.device_update_state.running {
-                    let device_id = self.device_id.unwrap();
-                    let file_path = self.dfu_file.as_ref().unwrap().path.clone();
+            Message::UnprotectDevice => {
+                if let (false, false, Some(device_id)) = (
+                    self.device_update_state.running,
+                    self.inspector_mode,
+                    self.device_id,
+                ) {
+                    self.push_device_event(events::EventKind::Opened, "Remove read protection");
                     let message_sender = self.message_channel.0.clone();
-                    let message_sender_result = self.message_channel.0.clone();
                     std::thread::spawn(move || {
-                        let result = update::full_update(device_id, file_path, message_sender);
-                        match result {
-                            Ok(_) => {}
-                            Err(error) => {
-                                message_sender_result
-                                    .send(Message::DeviceUpdateError(format!("{error}")))
-                                    .ok();
+                        let result = update::unprotect_device(device_id).map_err(|error| {
+                            if dfudev::is_access_denied(&error) {
+                                message_sender.send(Message::DeviceAccessDenied).ok();
                             }
-                        }
+                            format!("{error}")
+                        });
+                        message_sender.send(Message::DeviceUnprotected(result)).ok();
                     });
                 } else {
-                    log::error!("Update already in progress.");
+                    self.unprotect_form.running = false;
+                    log::error!("Removing read protection not possible.");
                 }
             }
-        }
-    }
-
-    /// Find all DFU devices
-    fn scan_devices(&mut self) {
-        log::debug!("Scanning USB devices...");
-        let devices = dfudev::DfuDevice::find(false);
-
-        match devices {
-            Ok(devices) => {
-                if devices.is_some() {
-                    for device in devices.as_ref().unwrap().iter() {
+            Message::DeviceUnprotected(result) => {
+                self.unprotect_form.running = false;
+                self.unprotect_form.open = false;
+                match result {
+                    Ok(_) => {
+                        message_dialog
+                            .dialog()
+                            .with_title("Read protection removed")
+                            .with_body(
+                                "The read protection was removed and the flash memory erased. \
+                                The device can be programmed again.",
+                            )
+                            .open();
+                    }
+                    Err(error) => {
+                        self.push_device_event(events::EventKind::Error, error);
+                        self.show_error("Error removing read protection", error);
+                    }
+                }
+                self.scan_devices();
+            }
+            Message::ReadMemoryRange(alt_setting, address, length) => {
+                if let (false, Some(device_id)) = (self.device_update_state.running, self.device_id)
+                {
+                    let (alt_setting, address, length) = (*alt_setting, *address, *length);
+                    self.push_device_event(
+                        events::EventKind::Opened,
+                        &format!(
+                            "Read {length} bytes at 0x{address:08X} with alt setting {alt_setting}"
+                        ),
+                    );
+                    let message_sender = self.message_channel.0.clone();
+                    std::thread::spawn(move || {
+                        let result = update::read_memory(device_id, alt_setting, address, length)
+                            .map(|data| (alt_setting, address, data))
+                            .map_err(|error| {
+                                send_access_hint(
+                                    &message_sender,
+                                    &error,
+                                    Message::ReadMemoryRange(alt_setting, address, length),
+                                );
+                                format!("{error}")
+                            });
+                        message_sender.send(Message::MemoryRangeRead(result)).ok();
+                    });
+                } else {
+                    self.read_memory_view.reading = false;
+                    log::error!("Reading memory not possible.");
+                }
+            }
+            Message::MemoryRangeRead(result) => {
+                self.read_memory_view.reading = false;
+                match result {
+                    Ok(content) => self.read_memory_view.content = Some(content.clone()),
+                    Err(error) => {
+                        self.push_device_event(events::EventKind::Error, error);
+                        self.show_error("Error reading memory", error);
+                    }
+                }
+            }
+            Message::SaveMemoryRange => {
+                self.save_memory_range();
+            }
+            Message::MemoryRead(result) => {
+                self.memory_view.reading = false;
+                match result {
+                    Ok(content) => self.memory_view.content = Some(content.clone()),
+                    Err(error) => {
+                        self.push_device_event(events::EventKind::Error, error);
+                        self.show_error("Error reading memory", error);
+                    }
+                }
+            }
+            Message::UsbDevicesChanged => {
+                log::debug!("USB devices changed.");
+                self.check_devices();
+                self.rescan_pending = true;
+            }
+            Message::RunDiagnostics => {
+                let report = dfudev::diagnostics();
+                log::info!("USB diagnostics:\n{}", report);
+                message_dialog
+                    .dialog()
+                    .with_title("USB diagnostics")
+                    .with_body(report)
+                    .open();
+            }
+            Message::SetTransferSettings(settings) => {
+                self.transfer_settings = *settings;
+                dfudev::settings::set(self.transfer_settings);
+                // Transfer size and poll timeout apply to devices found afterwards
+                self.scan_devices();
+            }
+            Message::OpenTransferSettings => {
+                self.transfer_settings_form.show(self.transfer_settings);
+            }
+            Message::OpenFlashHistory => match journal::all_records() {
+                Ok(records) => {
+                    self.flash_history_view.records = records;
+                    self.flash_history_view.open = true;
+                }
+                Err(error) => self.show_error("Error reading journal", error),
+            },
+            Message::ExportFlashHistory(format) => {
+                self.export_flash_history(*format);
+            }
+            Message::StartKiosk => {
+                if self.inspector_mode {
+                    log::error!("Kiosk mode is not available in inspector mode.");
+                } else if self.dfu_file.is_some() {
+                    self.kiosk.start();
+                    self.rescan_pending = true;
+                }
+            }
+            Message::StopKiosk => {
+                self.kiosk.stop();
+            }
+            Message::SetPreferences(preferences) => {
+                self.set_preferences(ctx, preferences.clone());
+            }
+            Message::ForgetLastDevice => {
+                self.last_device = None;
+            }
+            Message::SelectPreset(name) => {
+                self.preset = name.clone();
+                if let Some(preset) = self.selected_preset() {
+                    log::info!("Board preset: {}", preset.name);
+                    self.after_update = preset.after_update;
+                    message_dialog
+                        .dialog()
+                        .with_title(preset.name)
+                        .with_body(preset.notes)
+                        .open();
+                }
+                self.device_update_state = DeviceUpdateState::default();
+                self.scan_devices();
+            }
+            Message::SelectProfile(name) => {
+                self.profile = name.clone();
+                // Downloads still running for another profile are dropped
+                self.profile_download = false;
+                if let Some(profile) = self.selected_profile().cloned() {
+                    log::info!("Profile: {}", profile.name);
+                    self.after_update = profile.after_update;
+                    if let Some(url) = profile.firmware_url() {
+                        self.profile_download = true;
+                        let url = url.to_string();
+                        let message_sender = self.message_channel.0.clone();
+                        std::thread::spawn(move || {
+                            let result = profile::download(&url)
+                                .map_err(|error| format!("Error downloading {url}: {error}"));
+                            message_sender
+                                .send(Message::ProfileFirmwareDownloaded(profile.name, result))
+                                .ok();
+                        });
+                    } else if !profile.firmware.is_empty() {
+                        self.message_channel
+                            .0
+                            .send(Message::OpenFile(profile.firmware.into()))
+                            .ok();
+                    }
+                }
+                self.device_update_state = DeviceUpdateState::default();
+                self.scan_devices();
+            }
+            Message::ProfileFirmwareDownloaded(name, result) => {
+                if self.profile.as_ref() != Some(name) {
+                    log::info!("Firmware of profile \"{name}\" dropped, another one was selected");
+                    if let Ok(file_path) = result {
+                        std::fs::remove_file(file_path).ok();
+                    }
+                    return;
+                }
+                self.profile_download = false;
+                match result {
+                    Ok(file_path) => {
+                        self.profile_downloads.push(file_path.clone());
+                        self.message_channel
+                            .0
+                            .send(Message::OpenFile(file_path.clone()))
+                            .ok();
+                    }
+                    Err(error) => self.show_error("Error downloading firmware", error),
+                }
+            }
+            Message::MemoryMapsChanged => {
+                dfudev::quirks::set_memory_map_overrides(self.memory_maps.clone());
+                self.scan_devices();
+            }
+            Message::DeviceAccessDenied => {
+                message_dialog
+                    .dialog()
+                    .with_title("Access denied")
+                    .with_body(format!(
+                        "The device can't be accessed due to missing permissions.\n\n{}{}",
+                        dfudev::access_hint(),
+                        if cfg!(target_os = "linux") {
+                            "\n\nUse Tools > Setup device permissions to create the rule."
+                        } else {
+                            ""
+                        }
+                    ))
+                    .open();
+            }
+            Message::DeviceInUse { owner, retry } => {
+                self.device_in_use_form
+                    .show(owner.clone(), retry.as_ref().clone());
+            }
+            Message::DeviceSelected(device_id) => {
+                self.device_id = Some(*device_id);
+                self.check_devices();
+                if self.stale_devices.contains(device_id) {
+                    log::warn!("Selected device is no longer connected, rescanning.");
+                    self.rescan_pending = true;
+                }
+                // The content of the converted file depends on the device
+                if self.converted_file.is_some() {
+                    self.convert_file();
+                }
+                self.match_file_against_device();
+                let device = self.get_selected_device().unwrap();
+                log::debug!("Selected device {}", device.info);
+                if self.remember_device && !device.info.serial_number_string.is_empty() {
+                    self.last_device = Some(device.info.serial_number_string.clone());
+                }
+                self.push_device_event(events::EventKind::Selected, "By user");
+                self.collect_device_warnings();
+                self.device_update_state = DeviceUpdateState::default();
+            }
+            Message::OpenFileDialog => {
+                self.open_file_dialog(ctx);
+            }
+            Message::ClearFile => {
+                self.dfu_file = None;
+                self.targets.remapped.clear();
+                self.converted_file = None;
+                self.archive_path = None;
+                self.file_hash = None;
+                self.dfu_file_checks = DfuFileChecks::default();
+                self.collect_file_warnings();
+                self.device_update_state = DeviceUpdateState::default();
+                self.block_cache.lock().unwrap().clear();
+            }
+            Message::OpenFile(file_path) => {
+                log::debug!("Opening file {:?}", file_path);
+                self.open_file(file_path);
+                self.match_file_against_device();
+                if let Some(parent_path) = file_path.parent() {
+                    self.file_dialog_path = Some(std::path::PathBuf::from(parent_path));
+                }
+                self.file_filter = convert::file_filter(file_path).map(String::from);
+                self.device_update_state = DeviceUpdateState::default();
+            }
+            Message::OpenArchiveEntry(archive_path, entry) => {
+                self.open_archive_entry(archive_path, entry);
+                self.match_file_against_device();
+                self.device_update_state = DeviceUpdateState::default();
+            }
+            Message::SetAddressRange(range) => {
+                match range {
+                    Some(range) => log::info!("Updates restricted to {range}"),
+                    None => log::info!("Updates cover the whole file"),
+                }
+                self.address_range = *range;
+                self.device_update_state = DeviceUpdateState::default();
+            }
+            Message::RemapTarget { image, alt_setting } => {
+                if self.strict_mode {
+                    self.message_channel
+                        .0
+                        .send(Message::OpenMessageDialog {
+                            title: "Strict mode".into(),
+                            body: "Targets can't be remapped in strict mode.".into(),
+                        })
+                        .ok();
+                } else {
+                    match alt_setting {
+                        Some(alt_setting) => {
+                            log::info!("Image {image} remapped to alt setting {alt_setting}");
+                            self.targets.remapped.insert(*image, *alt_setting);
+                        }
+                        None => {
+                            self.targets.remapped.remove(image);
+                        }
+                    }
+                    self.match_file_against_device();
+                    self.device_update_state = DeviceUpdateState::default();
+                }
+            }
+            Message::SetRawBinaryTarget {
+                alt_setting,
+                address,
+            } => {
+                if let Some(converted_file) = &mut self.converted_file {
+                    converted_file.alt_setting = *alt_setting;
+                    converted_file.address = *address;
+                    converted_file.address_text = format!("{address:08X}");
+                    self.convert_file();
+                    self.match_file_against_device();
+                    self.device_update_state = DeviceUpdateState::default();
+                }
+            }
+            Message::ExportConfig => {
+                self.export_config();
+            }
+            Message::ImportConfig => {
+                self.import_config();
+            }
+            Message::OpenLoginWindow => {
+                self.login_form.open = true;
+            }
+            Message::OperatorLoggedIn(name) => {
+                log::info!("Operator \"{name}\" logged in.");
+                self.operator = Some(name.clone());
+            }
+            Message::OpenMessageDialog { title, body } => {
+                message_dialog
+                    .dialog()
+                    .with_title(title)
+                    .with_body(body)
+                    .open();
+            }
+            Message::Job(progress::JobEvent { job, event }) => {
+                // Events of jobs started before the current one are outdated
+                if Some(*job) == self.current_job {
+                    self.process_job_event(event);
+                } else {
+                    log::debug!("Ignoring event of job {job}: {event:?}");
+                }
+            }
+            Message::StartUpdate => self.start_update(false),
+            Message::ResumeUpdate => self.start_update(true),
+            Message::OpenBackupDialog => {
+                self.open_backup_dialog();
+            }
+            Message::CancelJob(mode) => {
+                if let (true, Some((reporter, _))) =
+                    (self.device_update_state.running, &self.worker)
+                {
+                    log::info!("Cancelling job {} ({mode:?})", reporter.job());
+                    reporter.cancel(*mode);
+                    // An abort overrides a stop requested before
+                    if self.device_update_state.cancelling != Some(progress::CancelMode::Abort) {
+                        self.device_update_state.cancelling = Some(*mode);
+                    }
+                }
+            }
+            Message::StartBackup(file_path) => {
+                if let (false, Some(device_id)) = (self.device_update_state.running, self.device_id)
+                {
+                    self.device_update_state.running = true;
+                    self.start_journal_record(journal::Operation::Backup, file_path);
+                    let retry = Message::StartBackup(file_path.clone());
+                    let file_path = file_path.clone();
+                    let options = update::Options {
+                        progress_rate: self.progress_rate,
+                        session_log: self.session_log_config(),
+                        ..Default::default()
+                    };
+                    let block_cache = self.block_cache.clone();
+                    let reporter = progress::Reporter::new(self.message_channel.0.clone());
+                    self.current_job = Some(reporter.job());
+                    let message_sender = self.message_channel.0.clone();
+                    let run = if self.demo_mode {
+                        demo::run
+                    } else {
+                        update::run
+                    };
+                    let job_reporter = reporter.clone();
+                    let thread = std::thread::spawn(move || {
+                        let result = run(
+                            device_id,
+                            None,
+                            vec![update::Operation::Backup(file_path)],
+                            block_cache,
+                            options,
+                            reporter.clone(),
+                        );
+                        if let Err(error) = result {
+                            send_update_error(&reporter, &message_sender, &error, retry);
+                        }
+                    });
+                    self.worker = Some((job_reporter, thread));
+                } else {
+                    log::error!("Backup not possible.");
+                }
+            }
+        }
+    }
+
+    /// Select a device appeared in kiosk mode and start its update
+    ///
+    /// Devices not passing the checks are recorded as failed without
+    /// being touched.
+    fn start_kiosk_update(&mut self, device_id: u64) {
+        if self.dfu_file.is_none() {
+            self.kiosk.stop();
+            self.finish_kiosk_update(Some("No file opened.".into()));
+            return;
+        }
+
+        self.device_id = Some(device_id);
+        self.push_device_event(events::EventKind::Selected, "Kiosk mode");
+        if self.converted_file.is_some() {
+            self.convert_file();
+        }
+        self.match_file_against_device();
+        self.collect_device_warnings();
+        self.device_update_state = DeviceUpdateState::default();
+
+        if !self.preflight_checks() {
+            self.finish_kiosk_update(Some("File checks not passed.".into()));
+            return;
+        }
+        if self.phases.program {
+            if let Some(message) = self.dfu_file_checks.downgrade_message() {
+                self.finish_kiosk_update(Some(message));
+                return;
+            }
+        }
+
+        // Each device appearing is flashed, even if it received the file before
+        self.device_update_state.duplicate_confirmed = true;
+        self.device_update_state.login_required = self.production_mode && self.operator.is_none();
+        self.start_update(false);
+        if !self.device_update_state.running {
+            self.finish_kiosk_update(Some("Update could not be started.".into()));
+        }
+    }
+
+    /// Record the result of the device flashed in kiosk mode, if any
+    fn finish_kiosk_update(&mut self, error: Option<String>) {
+        let Some(device_id) = self.kiosk.current() else {
+            return;
+        };
+
+        let (serial_number, device) = self
+            .devices
+            .iter()
+            .flatten()
+            .find(|device| device.id == device_id)
+            .map_or_else(
+                || (String::new(), "(disconnected)".to_string()),
+                |device| {
+                    (
+                        device.info.serial_number_string.clone(),
+                        events::describe(&device.info),
+                    )
+                },
+            );
+        self.kiosk.finish(serial_number, device, error);
+    }
+
+    /// Start the update process in a separate thread, skipping the sectors
+    /// completed by an interrupted update if `resume` is set
+    fn start_update(&mut self, resume: bool) {
+        let phases = if self.inspector_mode {
+            update::Phases::VERIFY_ONLY
+        } else {
+            self.phases
+        };
+
+        if phases.is_empty() {
+            log::error!("No update step enabled.");
+        } else if phases.modifies_device() && self.dfu_file_checks.geometry_report.is_some() {
+            log::error!("Invalid memory geometry, modifying the device is blocked.");
+        } else if phases.erase
+            && !self.mass_erase
+            && !self.dfu_file_checks.erase_problems.is_empty()
+        {
+            log::error!("File can't be erased sector by sector, erase is blocked.");
+        } else if self.device_update_state.login_required {
+            log::error!("Operator login required.");
+        } else if phases.program
+            && self.strict_mode
+            && !self.simulate_update
+            && self.dfu_file_checks.downgrade.is_some()
+        {
+            log::error!("Downgrading the firmware is refused in strict mode.");
+        } else if !self.device_update_state.running {
+            let device_id = self.device_id.unwrap();
+            let file_path = self.dfu_file.as_ref().unwrap().path.clone();
+
+            // The simulation starts with erased memory and takes the layout
+            // of the selected device
+            let simulation = if self.simulate_update {
+                let simulation = self
+                    .get_selected_device()
+                    .ok_or(anyhow::anyhow!("No device selected."))
+                    .and_then(|device| SimulatedDevice::from_info(&device.info));
+                match simulation {
+                    Ok(simulation) => Some(simulation),
+                    Err(error) => {
+                        log::error!("Simulation not possible: {error}");
+                        return;
+                    }
+                }
+            } else {
+                None
+            };
+
+            if phases.program
+                && simulation.is_none()
+                && !self.device_update_state.downgrade_confirmed
+            {
+                if let Some(message) = self.dfu_file_checks.downgrade_message() {
+                    self.device_update_state.downgrade_warning = Some(message);
+                    return;
+                }
+            }
+
+            // Only programming counts as update of the device
+            if let (Some(file_hash), true, None) = (self.file_hash, phases.program, &simulation) {
+                if !self.device_update_state.duplicate_confirmed {
+                    if let Some(entry) = self.history.last_success(device_id, &file_hash) {
+                        log::warn!("Device was already updated with the same file.");
+                        self.device_update_state.duplicate_warning = Some(format!(
+                            "This device already received this file {}.",
+                            history::time_ago(entry.started)
+                        ));
+                        return;
+                    }
+                }
+
+                let serial_number = self
+                    .get_selected_device()
+                    .map(|device| device.info.serial_number_string.clone())
+                    .unwrap_or_default();
+                self.current_operation = Some(history::Entry::new(
+                    device_id,
+                    serial_number,
+                    file_path.clone(),
+                    file_hash,
+                    self.operator.clone(),
+                ));
+            }
+
+            if simulation.is_none() {
+                self.start_journal_record(journal::Operation::from_phases(&phases), &file_path);
+                if let (Some(record), Some(file_hash)) = (&mut self.journal_record, &self.file_hash)
+                {
+                    record.set_file_hash(file_hash);
+                }
+            }
+
+            let completed_sectors = if resume {
+                self.resume_progress
+                    .as_ref()
+                    .map(|progress| progress.completed_sectors.clone())
+                    .unwrap_or_default()
+            } else {
+                if phases.modifies_device() && simulation.is_none() {
+                    self.discard_resume_progress();
+                }
+                Vec::new()
+            };
+
+            // Set early to keep repainting until the thread reports back
+            self.device_update_state.running = true;
+            let block_cache = self.block_cache.clone();
+            let options = update::Options {
+                progress_rate: self.progress_rate,
+                mass_erase: self.mass_erase,
+                skip_blank_blocks: self.skip_blank_blocks,
+                differential: self.differential_update,
+                after_update: self.after_update,
+                targets: update::Targets {
+                    skipped: self.skipped_targets(),
+                    ..self.targets.clone()
+                },
+                range: self.address_range,
+                completed_sectors,
+                session_log: self.session_log_config(),
+                strict: self.strict_mode,
+                ..Default::default()
+            };
+            let retry = if resume {
+                Message::ResumeUpdate
+            } else {
+                Message::StartUpdate
+            };
+            let reporter = progress::Reporter::new(self.message_channel.0.clone());
+            self.current_job = Some(reporter.job());
+            let message_sender = self.message_channel.0.clone();
+            let run = if self.demo_mode {
+                demo::run
+            } else {
+                update::run
+            };
+            let job_reporter = reporter.clone();
+            let thread = std::thread::spawn(move || {
+                let operations = phases.operations(options.after_update);
+                let result = match simulation {
+                    Some(simulation) => update::run_device(
+                        simulation.device(),
+                        Some(file_path),
+                        operations,
+                        block_cache,
+                        options,
+                        reporter.clone(),
+                    ),
+                    None => run(
+                        device_id,
+                        Some(file_path),
+                        operations,
+                        block_cache,
+                        options,
+                        reporter.clone(),
+                    ),
+                };
+                if let Err(error) = result {
+                    send_update_error(&reporter, &message_sender, &error, retry);
+                }
+            });
+            self.worker = Some((job_reporter, thread));
+        } else {
+            log::error!("Update already in progress.");
+        }
+    }
+
+    /// Process an event of the current job
+    fn process_job_event(&mut self, event: &progress::Event) {
+        match event {
+            progress::Event::Started => {
+                log::debug!("Device update started.");
+                self.push_device_event(events::EventKind::Opened, "Operation started");
+                self.device_update_state = DeviceUpdateState::default();
+                self.device_update_state.running = true;
+                self.device_update_state.finished = false;
+                self.device_update_state.started = Some(Instant::now());
+            }
+            progress::Event::Finished => {
+                log::debug!("Device update finished.");
+                if let Some(entry) = self.current_operation.take() {
+                    self.store_resume_progress(&entry, true);
+                    self.history.add(entry, None);
+                }
+                self.finish_journal_record(None);
+                self.finish_kiosk_update(None);
+                self.device_update_state.running = false;
+                self.device_update_state.step = None;
+                self.device_update_state.finished = true;
+                self.device_update_state.stopped = Some(Instant::now());
+            }
+            progress::Event::FinishedUnverified(reason) => {
+                log::warn!("Device update finished without verification: {reason}");
+                // Recorded like a failure, the written data was never checked
+                let error = format!("Not verified: {reason}");
+                self.push_device_event(events::EventKind::Error, &error);
+                if let Some(entry) = self.current_operation.take() {
+                    self.store_resume_progress(&entry, true);
+                    self.history.add(entry, Some(error.clone()));
+                }
+                self.finish_journal_record(Some(error.clone()));
+                self.finish_kiosk_update(Some(error));
+                self.device_update_state.running = false;
+                self.device_update_state.step = None;
+                self.device_update_state.finished = true;
+                self.device_update_state.unverified = Some(reason.clone());
+                self.device_update_state.stopped = Some(Instant::now());
+            }
+            progress::Event::Error(error) => {
+                log::error!("Device update error: {}", error);
+                self.push_device_event(events::EventKind::Error, error);
+                if let Some(entry) = self.current_operation.take() {
+                    self.store_resume_progress(&entry, false);
+                    self.history.add(entry, Some(error.clone()));
+                }
+                self.finish_journal_record(Some(error.clone()));
+                self.finish_kiosk_update(Some(error.clone()));
+                self.device_update_state.running = false;
+                self.device_update_state.error = Some(error.to_string());
+                self.device_update_state.stopped = Some(Instant::now());
+            }
+            progress::Event::Step(step) => {
+                log::debug!("Device update step {:?}", step);
+                if let Some(record) = &mut self.journal_record {
+                    record.start_step(*step);
+                }
+                self.device_update_state.step = Some(*step);
+                self.device_update_state.step_started = Some(Instant::now());
+            }
+            progress::Event::Plan {
+                steps,
+                skipped,
+                payload_size,
+            } => {
+                self.device_update_state.skipped_steps = skipped.clone();
+                self.device_update_state.phases = steps
+                    .iter()
+                    .map(|step| PhaseState {
+                        step: *step,
+                        progress: 0.0,
+                    })
+                    .collect();
+                self.device_update_state.payload_size = *payload_size;
+            }
+            progress::Event::Progress {
+                step,
+                bytes,
+                total,
+                address,
+            } => {
+                if let Some(phase) = self
+                    .device_update_state
+                    .phases
+                    .iter_mut()
+                    .find(|phase| phase.step == *step)
+                {
+                    phase.progress = progress::fraction(*bytes, *total);
+                }
+                if address.is_some() {
+                    self.device_update_state.address = *address;
+                }
+            }
+            progress::Event::Skipped(step, reason) => {
+                if let Some(record) = &mut self.journal_record {
+                    record.skip_step(*step);
+                }
+                self.device_update_state
+                    .phases
+                    .retain(|phase| phase.step != *step);
+                self.device_update_state
+                    .skipped_steps
+                    .push((*step, reason.clone()));
+            }
+            progress::Event::BackupFinished(file_path) => {
+                log::debug!("Device backup written.");
+                self.device_update_state.backup_path = Some(file_path.clone());
+
+                // The hash of the file written to the device takes precedence
+                if let (Some(record), Ok(file_hash)) =
+                    (&mut self.journal_record, history::file_hash(file_path))
+                {
+                    if record.file_hash.is_none() {
+                        record.set_file_hash(&file_hash);
+                    }
+                }
+            }
+            progress::Event::Checksum(checksum) => {
+                self.device_update_state.checksum = Some(*checksum);
+            }
+            progress::Event::Differences(differences) => {
+                self.device_update_state.differences = differences.clone();
+            }
+            progress::Event::AwaitingFirmware => {
+                self.device_update_state.awaiting_firmware = true;
+            }
+            progress::Event::FirmwareCheck(check) => {
+                self.device_update_state.awaiting_firmware = false;
+                self.device_update_state.firmware_check = Some(check.clone());
+            }
+            progress::Event::EraseTarget { name, sectors } => {
+                self.device_update_state.erase_target = name.clone();
+                self.device_update_state.sectors = sectors
+                    .iter()
+                    .map(|(address, size)| SectorStatus {
+                        address: *address,
+                        size: *size,
+                        state: SectorState::Pending,
+                    })
+                    .collect();
+            }
+            progress::Event::SectorProgrammed(alt_setting, sector) => {
+                self.device_update_state
+                    .completed_sectors
+                    .push((*alt_setting, *sector));
+            }
+            progress::Event::SectorState(index, state) => {
+                if let Some(sector) = self.device_update_state.sectors.get_mut(*index) {
+                    sector.state = *state;
+                }
+            }
+            progress::Event::Statistics(statistics) => {
+                self.device_update_state.statistics = statistics.clone();
+            }
+            progress::Event::Timings(timings) => {
+                self.device_update_state.timings = timings.clone();
+            }
+            progress::Event::Cancelled { mode, resume_point } => {
+                if let Some(record) = &mut self.journal_record {
+                    record.set_cancellation(*mode, *resume_point);
+                }
+            }
+        }
+    }
+
+    /// Find all DFU devices
+    fn scan_devices(&mut self) {
+        log::debug!("Scanning USB devices...");
+        // Disconnects of these devices are already recorded
+        let stale_devices = std::mem::take(&mut self.stale_devices);
+        let devices = if self.demo_mode {
+            Ok(Some(vec![demo::device()]))
+        } else {
+            dfudev::DfuDevice::find(false)
+        };
+
+        // Only devices of the selected board are shown
+        let devices = match (devices, self.selected_preset()) {
+            (Ok(Some(mut devices)), Some(preset)) => {
+                devices.retain(|device| preset.matches(&device.info));
+                Ok((!devices.is_empty()).then_some(devices))
+            }
+            (devices, _) => devices,
+        };
+
+        // Only devices of the selected profile are shown
+        let devices = match (devices, self.selected_profile()) {
+            (Ok(Some(mut devices)), Some(profile)) => {
+                devices.retain(|device| profile.matches(&device.info));
+                Ok((!devices.is_empty()).then_some(devices))
+            }
+            (devices, _) => devices,
+        };
+
+        let found: &[dfudev::DfuDevice] = match &devices {
+            Ok(Some(devices)) => devices,
+            _ => &[],
+        };
+        self.kiosk
+            .devices_found(&found.iter().map(|device| device.id).collect::<Vec<_>>());
+        for device in self.devices.iter().flatten() {
+            if !stale_devices.contains(&device.id) && !found.iter().any(|d| d.id == device.id) {
+                self.event_log
+                    .push(events::EventKind::Disappeared, &device.info, "");
+            }
+        }
+        for device in found {
+            if !self.devices.iter().flatten().any(|d| d.id == device.id) {
+                self.event_log
+                    .push(events::EventKind::Appeared, &device.info, "");
+            }
+        }
+
+        match devices {
+            Ok(devices) => {
+                self.access_denied = false;
+                if let Some(devices) = devices {
+                    for device in devices.iter() {
                         log::debug!("Found DFU device {}", &device.info);
                     }
-                    self.devices = devices;
-                    if self.device_id.is_none() {
-                        // Select the first device found
-                        self.device_id = Some(self.devices.as_ref().unwrap()[0].id);
-                        self.match_file_against_device();
+
+                    // Keep the selection if the device is still present,
+                    // otherwise select the device used last or the first one found
+                    let selected_present = self
+                        .device_id
+                        .is_some_and(|id| devices.iter().any(|device| device.id == id));
+                    if !selected_present {
+                        if self.device_id.is_some() {
+                            log::debug!("Selected device is no longer present");
+                        }
+                        let last_device = self.last_device.as_ref().and_then(|serial_number| {
+                            devices
+                                .iter()
+                                .find(|device| &device.info.serial_number_string == serial_number)
+                        });
+                        let (device, reason) = match last_device {
+                            Some(device) => (device, "Automatically, used last"),
+                            None => (&devices[0], "Automatically"),
+                        };
+                        self.device_id = Some(device.id);
+                        self.event_log
+                            .push(events::EventKind::Selected, &device.info, reason);
+
+                        // Keep the result of the last operation visible, devices
+                        // may disconnect on their own after an update
+                        let update_state = &self.device_update_state;
+                        if !update_state.finished && update_state.error.is_none() {
+                            self.device_update_state = DeviceUpdateState::default();
+                        }
                     }
+
+                    self.devices = Some(devices);
+                    self.match_file_against_device();
                 } else {
                     log::debug!("No DFU devices found");
                     self.devices = None;
@@ -547,19 +2868,47 @@ impl App {
             }
             Err(error) => {
                 log::error!("{}", error);
+                self.event_log.push_text(
+                    events::EventKind::Error,
+                    "(scan)".into(),
+                    &error.to_string(),
+                );
+                // Scans are repeated on every hotplug event, show the hint only once
+                let access_denied = dfudev::is_access_denied(&error);
+                if access_denied && !self.access_denied {
+                    self.message_channel
+                        .0
+                        .send(Message::DeviceAccessDenied)
+                        .ok();
+                }
+                self.access_denied = access_denied;
                 self.devices = None;
                 self.device_id = None;
             }
         }
+
+        self.collect_device_warnings();
+    }
+
+    /// Mark all devices of the last scan that are no longer connected
+    fn check_devices(&mut self) {
+        // Devices in use by the update thread are reported as connected
+        if self.device_update_state.running {
+            return;
+        }
+
+        for device in self.devices.iter().flatten() {
+            if !device.is_connected() && self.stale_devices.insert(device.id) {
+                log::debug!("Device {} is no longer connected", device.info);
+                self.event_log
+                    .push(events::EventKind::Disappeared, &device.info, "");
+            }
+        }
     }
 
     /// Return reference to device with a certain id
     fn get_device(&self, id: u64) -> Option<&dfudev::DfuDevice> {
-        if self.devices.is_some() {
-            self.devices.as_ref().unwrap().iter().find(|&x| x.id == id)
-        } else {
-            None
-        }
+        self.devices.as_ref()?.iter().find(|&x| x.id == id)
     }
 
     /// Return reference to currently selected device
@@ -570,8 +2919,148 @@ impl App {
         }
     }
 
-    /// Open the file dialog
-    fn open_file_dialog(&mut self) {
+    /// Return the path of the file selected by the user, the source file in
+    /// case of a converted one or the entry of an archive
+    fn selected_file_path(&self) -> Option<&std::path::Path> {
+        if let Some(archive_path) = &self.archive_path {
+            return Some(archive_path);
+        }
+
+        match &self.converted_file {
+            Some(converted_file) => Some(converted_file.path.as_path()),
+            None => self.dfu_file.as_ref().map(|file| file.path.as_path()),
+        }
+    }
+
+    /// Return the indices of the images skipped for the selected file
+    fn skipped_targets(&self) -> Vec<usize> {
+        self.selected_file_path()
+            .and_then(|path| self.skipped_images.get(path))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Record an event of the selected device
+    fn push_device_event(&mut self, kind: events::EventKind, details: &str) {
+        let device = self
+            .devices
+            .iter()
+            .flatten()
+            .find(|device| Some(device.id) == self.device_id);
+        match device {
+            Some(device) => self.event_log.push(kind, &device.info, details),
+            None => self.event_log.push_text(kind, "-".into(), details),
+        }
+    }
+
+    /// Start a journal record for an operation on the selected device
+    fn start_journal_record(&mut self, operation: journal::Operation, file_path: &std::path::Path) {
+        self.journal_record = self.get_selected_device().map(|device| {
+            journal::Record::new(&device.info, operation, file_path, self.operator.clone())
+        });
+    }
+
+    /// Finish the journal record of the current operation and write it
+    fn finish_journal_record(&mut self, error: Option<String>) {
+        if let Some(mut record) = self.journal_record.take() {
+            // Simulated operations are not recorded
+            if self.demo_mode {
+                return;
+            }
+            record.finish(error);
+            journal::append_or_log(&record);
+        }
+    }
+
+    /// Load the progress of an interrupted update of the selected device
+    /// with the opened file
+    fn load_resume_progress(&mut self) {
+        self.resume_progress = match (self.get_selected_device(), self.file_hash) {
+            (Some(device), Some(file_hash))
+                if !self.demo_mode && !device.info.serial_number_string.is_empty() =>
+            {
+                resume::load(&device.info.serial_number_string, &file_hash)
+            }
+            _ => None,
+        };
+    }
+
+    /// Remove the progress of an interrupted update, a full update writes
+    /// all sectors again
+    fn discard_resume_progress(&mut self) {
+        if let (Some(progress), Some(file_hash)) = (self.resume_progress.take(), self.file_hash) {
+            if let Err(error) = resume::remove(&progress.serial_number, &file_hash) {
+                log::error!("Error removing resume progress: {}", error);
+            }
+        }
+    }
+
+    /// Store the sectors completed by a failed update to resume it later,
+    /// or remove them after a successful one
+    ///
+    /// Devices without serial number can't be told apart, so their
+    /// progress is not stored.
+    fn store_resume_progress(&mut self, entry: &history::Entry, succeeded: bool) {
+        if self.demo_mode || entry.serial_number.is_empty() {
+            return;
+        }
+
+        let result = if succeeded {
+            self.resume_progress = None;
+            resume::remove(&entry.serial_number, &entry.file_hash)
+        } else {
+            // Sectors skipped when resuming are still complete
+            let mut progress = self.resume_progress.take().unwrap_or_else(|| {
+                resume::Progress::new(&entry.serial_number, &entry.file_hash, Vec::new())
+            });
+            progress.merge(&self.device_update_state.completed_sectors);
+
+            if progress.completed_sectors.is_empty() {
+                Ok(())
+            } else {
+                log::info!("Update can be resumed, {}", progress.summary());
+                let result = resume::save(&progress);
+                self.resume_progress = Some(progress);
+                result
+            }
+        };
+
+        if let Err(error) = result {
+            log::error!("Error storing resume progress: {}", error);
+        }
+    }
+
+    /// Load the journal of the selected device and open its window
+    fn open_journal(&mut self) {
+        let Some(device) = self.get_selected_device() else {
+            return;
+        };
+        let serial_number = device.info.serial_number_string.clone();
+
+        match journal::records(&serial_number) {
+            Ok(records) => {
+                self.journal_view = ui::journal::JournalView {
+                    open: true,
+                    serial_number,
+                    records,
+                };
+            }
+            Err(error) => self.show_error("Error reading journal", error),
+        }
+    }
+
+    /// Open the file dialog in a separate thread, so the UI keeps running
+    /// while it is shown. The chosen file is sent as [`Message::OpenFile`].
+    fn open_file_dialog(&mut self, ctx: &egui::Context) {
+        if self
+            .file_dialog
+            .as_ref()
+            .is_some_and(|dialog| !dialog.is_finished())
+        {
+            log::debug!("File dialog is already open");
+            return;
+        }
+
         let mut start_dir = dirs::home_dir().unwrap_or_default();
 
         start_dir = self
@@ -580,37 +3069,341 @@ impl App {
             .unwrap_or(&start_dir)
             .to_path_buf();
 
+        // The first filter is preselected, so the last used one is moved to the front
+        let all_extensions = convert::FILE_FILTERS
+            .iter()
+            .flat_map(|(_, extensions)| extensions.iter().copied())
+            .collect();
+        let mut filters = vec![("Firmware files", all_extensions)];
+        filters.extend(
+            convert::FILE_FILTERS
+                .iter()
+                .map(|(name, extensions)| (*name, extensions.to_vec())),
+        );
+        if let Some(index) = filters
+            .iter()
+            .position(|(name, _)| Some(*name) == self.file_filter.as_deref())
+        {
+            let filter = filters.remove(index);
+            filters.insert(0, filter);
+        }
+        filters.push(("All files", vec!["*"]));
+
+        let message_sender = self.message_channel.0.clone();
+        let ctx = ctx.clone();
+        self.file_dialog = Some(std::thread::spawn(move || {
+            let dialog = filters
+                .iter()
+                .fold(rfd::AsyncFileDialog::new(), |dialog, (name, extensions)| {
+                    dialog.add_filter(*name, extensions)
+                })
+                .set_directory(start_dir);
+
+            if let Some(file) = futures::executor::block_on(dialog.pick_file()) {
+                message_sender
+                    .send(Message::OpenFile(file.path().to_path_buf()))
+                    .ok();
+                ctx.request_repaint();
+            }
+        }));
+    }
+
+    /// Open the file dialog to choose the backup file
+    fn open_backup_dialog(&mut self) {
+        let start_dir = self
+            .file_dialog_path
+            .clone()
+            .unwrap_or(dirs::home_dir().unwrap_or_default());
+
         let result = rfd::FileDialog::new()
+            .add_filter("Binary files", &["bin"])
             .add_filter("DFU files", &["dfu"])
             .set_directory(start_dir)
-            .pick_file();
+            .set_file_name("backup.bin")
+            .save_file();
 
         if let Some(file_path) = result {
             self.message_channel
                 .0
-                .send(Message::OpenFile(file_path))
+                .send(Message::StartBackup(file_path))
                 .ok();
         }
     }
 
-    /// Open a DFU file
+    /// Choose a file and save the memory range read last as raw binary
+    fn save_memory_range(&mut self) {
+        let Some((_, address, data)) = &self.read_memory_view.content else {
+            return;
+        };
+
+        let result = rfd::FileDialog::new()
+            .add_filter("Binary files", &["bin"])
+            .set_directory(
+                self.file_dialog_path
+                    .clone()
+                    .unwrap_or(dirs::home_dir().unwrap_or_default()),
+            )
+            .set_file_name(format!("memory-{address:08X}.bin"))
+            .save_file();
+
+        if let Some(file_path) = result {
+            match std::fs::write(&file_path, data) {
+                Ok(_) => log::info!("{} bytes saved to {:?}", data.len(), file_path),
+                Err(error) => self.show_error("Error saving memory", error),
+            }
+        }
+    }
+
+    /// Return the portable part of the settings
+    fn config(&self) -> config::Config {
+        config::Config {
+            progress_rate: self.progress_rate,
+            mass_erase: self.mass_erase,
+            skip_blank_blocks: self.skip_blank_blocks,
+            differential_update: self.differential_update,
+            padding: self.padding,
+            transfer_settings: self.transfer_settings,
+            after_update: self.after_update,
+            preset: self.preset.clone(),
+            phases: self.phases,
+            memory_maps: self.memory_maps.clone(),
+            production_mode: self.production_mode,
+            operators: self.operators.clone(),
+            inspector_mode: self.inspector_mode,
+            strict_mode: self.strict_mode,
+            integrity_checks: self.integrity_checks.clone(),
+            public_key: self.public_key.clone(),
+            profiles: self.profiles.clone(),
+            profile: self.profile.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Apply settings from a configuration
+    fn apply_config(&mut self, config: config::Config) {
+        self.progress_rate = config.progress_rate;
+        self.mass_erase = config.mass_erase;
+        self.skip_blank_blocks = config.skip_blank_blocks;
+        self.differential_update = config.differential_update;
+        self.padding = config.padding;
+        if self.transfer_settings != config.transfer_settings {
+            self.transfer_settings = config.transfer_settings;
+            dfudev::settings::set(self.transfer_settings);
+            self.rescan_pending = true;
+        }
+        self.after_update = config.after_update;
+        if self.preset != config.preset {
+            self.preset = config.preset;
+            self.rescan_pending = true;
+        }
+        self.phases = config.phases;
+        self.memory_maps = config.memory_maps;
+        self.message_channel.0.send(Message::MemoryMapsChanged).ok();
+        self.production_mode = config.production_mode;
+        self.operators = config.operators;
+        self.inspector_mode = config.inspector_mode || self.inspector_locked;
+        self.strict_mode = config.strict_mode;
+        if self.strict_mode {
+            self.targets.remapped.clear();
+        }
+        self.integrity_checks = config.integrity_checks;
+        self.public_key = config.public_key;
+        if self.profiles != config.profiles || self.profile != config.profile {
+            self.profiles = config.profiles;
+            self.profile = config.profile;
+            self.rescan_pending = true;
+        }
+        if let Some(quirks) = &config.quirks {
+            match dfudev::quirks::replace_file(quirks) {
+                Ok(count) => {
+                    log::info!("{count} quirks entries imported");
+                    self.rescan_pending = true;
+                }
+                Err(error) => self.show_error("Error importing quirks", error),
+            }
+        }
+        self.check_file_integrity();
+        self.match_file_against_device();
+    }
+
+    /// Choose a file and export the configuration
+    fn export_config(&mut self) {
+        let result = rfd::FileDialog::new()
+            .add_filter("Configuration files", &["json"])
+            .set_directory(dirs::home_dir().unwrap_or_default())
+            .set_file_name("dfu-buddy-config.json")
+            .save_file();
+
+        if let Some(file_path) = result {
+            let quirks = match dfudev::quirks::read_file() {
+                Ok(quirks) => quirks,
+                Err(error) => {
+                    self.show_error("Error reading quirks file", error);
+                    return;
+                }
+            };
+            let config = config::Config {
+                quirks,
+                ..self.config()
+            };
+            match config.export(&file_path) {
+                Ok(_) => log::info!("Configuration exported to {:?}", file_path),
+                Err(error) => self.show_error("Error exporting configuration", error),
+            }
+        }
+    }
+
+    /// Choose a file and export the records shown in the flash history window
+    fn export_flash_history(&mut self, format: journal::ExportFormat) {
+        let result = rfd::FileDialog::new()
+            .add_filter(format!("{format} files"), &[format.extension()])
+            .set_directory(dirs::home_dir().unwrap_or_default())
+            .set_file_name(format!("dfu-buddy-history.{}", format.extension()))
+            .save_file();
+
+        if let Some(file_path) = result {
+            let records: Vec<journal::Record> = self
+                .flash_history_view
+                .filtered()
+                .into_iter()
+                .cloned()
+                .collect();
+            match journal::export(&records, &file_path, format) {
+                Ok(_) => log::info!("{} records exported to {:?}", records.len(), file_path),
+                Err(error) => self.show_error("Error exporting flash history", error),
+            }
+        }
+    }
+
+    /// Choose a file and import the configuration
+    fn import_config(&mut self) {
+        let result = rfd::FileDialog::new()
+            .add_filter("Configuration files", &["json"])
+            .set_directory(dirs::home_dir().unwrap_or_default())
+            .pick_file();
+
+        if let Some(file_path) = result {
+            match config::Config::import(&file_path) {
+                Ok(config) => {
+                    log::info!("Configuration imported from {:?}", file_path);
+                    self.apply_config(config);
+                }
+                Err(error) => self.show_error("Error importing configuration", error),
+            }
+        }
+    }
+
+    /// Log an error and show it in the message dialog
+    fn show_error(&self, title: &str, error: impl std::fmt::Display) {
+        log::error!("{}", error);
+        self.message_channel
+            .0
+            .send(Message::OpenMessageDialog {
+                title: title.into(),
+                body: format!("{error}"),
+            })
+            .ok();
+    }
+
+    /// Open a DFU file, an archive containing one or a file to be converted
     fn open_file(&mut self, file_path: &std::path::Path) {
+        if archive::is_archive(file_path) {
+            match archive::dfu_entries(file_path) {
+                Ok(entries) if entries.is_empty() => {
+                    let name = file_path.file_name().unwrap_or_default().to_string_lossy();
+                    self.show_error(
+                        "Error opening archive",
+                        format!("{name} contains no DFU file."),
+                    );
+                }
+                Ok(entries) if entries.len() == 1 => {
+                    self.open_archive_entry(file_path, &entries[0]);
+                }
+                Ok(entries) => {
+                    self.archive_choice = Some(ui::archive::ArchiveChoice {
+                        path: file_path.to_path_buf(),
+                        entries,
+                    });
+                }
+                Err(error) => self.show_error("Error opening archive", error),
+            }
+            return;
+        }
+
+        self.archive_path = None;
+        if let Some(format) = convert::SourceFormat::detect(file_path) {
+            self.converted_file = Some(convert::ConvertedFile::new(
+                file_path,
+                format,
+                self.get_selected_device(),
+            ));
+            self.convert_file();
+        } else {
+            self.converted_file = None;
+            self.load_dfu_file(file_path);
+        }
+    }
+
+    /// Extract a DFU file of an archive and load it
+    fn open_archive_entry(&mut self, archive_path: &std::path::Path, entry: &str) {
+        match archive::extract(archive_path, entry) {
+            Ok(dfu_path) => {
+                log::info!("Extracted {entry} from {}", archive_path.display());
+                self.converted_file = None;
+                self.load_dfu_file(&dfu_path);
+                self.archive_path = self.dfu_file.is_some().then(|| archive_path.join(entry));
+            }
+            Err(error) => self.show_error("Error extracting file", error),
+        }
+    }
+
+    /// Convert the opened file into a temporary DFU file and load it
+    fn convert_file(&mut self) {
+        // Borrow only the fields required, the converted file is borrowed mutably
+        let device = self
+            .devices
+            .iter()
+            .flatten()
+            .find(|device| Some(device.id) == self.device_id);
+
+        let Some(converted_file) = &mut self.converted_file else {
+            return;
+        };
+
+        match converted_file.convert(device, self.padding) {
+            Ok(dfu_path) => {
+                self.load_dfu_file(&dfu_path);
+            }
+            Err(error) => {
+                log::error!("{}", error);
+                self.message_channel
+                    .0
+                    .send(Message::OpenMessageDialog {
+                        title: format!("Error converting {} file", converted_file.format),
+                        body: format!("{error}"),
+                    })
+                    .ok();
+                self.converted_file = None;
+                self.dfu_file = None;
+            }
+        }
+    }
+
+    /// Load a DFU file and check its CRC
+    fn load_dfu_file(&mut self, file_path: &std::path::Path) {
         let dfu_file = dfufile::DfuFile::open(file_path);
 
         match dfu_file {
             Ok(mut dfu_file) => {
                 self.dfu_file_checks = DfuFileChecks::default();
-                let crc = dfu_file.calc_crc();
-                match crc {
-                    Ok(crc) => {
-                        self.dfu_file_checks.crc_checked = true;
-                        self.dfu_file_checks.crc_valid = crc == dfu_file.suffix.dwCRC;
-                    }
-                    Err(error) => {
-                        log::error!("{}", error);
-                    }
-                }
+                self.targets.remapped.clear();
+                self.dfu_file_checks.check_crc(&mut dfu_file);
+                self.file_hash = history::file_hash(file_path)
+                    .map_err(|error| log::error!("{}", error))
+                    .ok();
                 self.dfu_file = Some(dfu_file);
+                self.hash_file();
+                self.check_file_integrity();
             }
             Err(error) => {
                 log::error!("{}", error);
@@ -624,61 +3417,340 @@ impl App {
                 self.dfu_file = None;
             }
         }
+
+        self.collect_file_warnings();
     }
 
-    /// Match the selected file against the current device
-    /// and set the file check flags accordingly
-    fn match_file_against_device(&mut self) {
+    /// Collect the warnings about the opened file
+    fn collect_file_warnings(&mut self) {
+        let mut list = Vec::new();
+
         if let Some(dfu_file) = &self.dfu_file {
-            if let Some(device) = self.get_selected_device() {
-                let device_dfu_version = device.info.dfu_version;
-                let device_vendor_id = device.info.vendor_id;
-                let device_product_id = device.info.product_id;
-                let device_alt_settings = device.info.alt_settings.clone();
-                let file_dfu_version = dfu_file.suffix.bcdDFU;
-                let file_vendor_id = dfu_file.suffix.idVendor;
-                let file_product_id = dfu_file.suffix.idProduct;
-
-                self.dfu_file_checks.dfu_version_valid = file_dfu_version == device_dfu_version;
-
-                self.dfu_file_checks.vendor_id_accepted =
-                    (file_vendor_id == 0xFFFF) || (file_vendor_id == device_vendor_id);
-                self.dfu_file_checks.product_id_accepted =
-                    (file_product_id == 0xFFFF) || (file_product_id == device_product_id);
-
-                match &dfu_file.content {
-                    dfufile::Content::Plain => {
-                        self.dfu_file_checks.targets_valid = true;
-                    }
-                    dfufile::Content::DfuSe(content) => {
-                        self.dfu_file_checks.targets_valid = true;
-                        for image in &content.images {
-                            let target = device_alt_settings
-                                .iter()
-                                .find(|&alt| alt.0 == image.target_prefix.bAlternateSetting);
-                            if target.is_none() {
-                                self.dfu_file_checks.targets_valid = false;
-                                break;
-                            }
-                        }
-                    }
+            if let Some(converted_file) = &self.converted_file {
+                list.push(Warning::new(
+                    Severity::Info,
+                    format!(
+                        "Converted from a {} file, the file is not checked against the device ids.",
+                        converted_file.format
+                    ),
+                ));
+            } else if dfu_file.suffix.idVendor == 0xFFFF && dfu_file.suffix.idProduct == 0xFFFF {
+                list.push(Warning::new(
+                    Severity::Info,
+                    "The file does not name the device it is intended for.",
+                ));
+            }
+
+            if let dfufile::Content::DfuSe(content) = &dfu_file.content {
+                if content
+                    .images
+                    .iter()
+                    .all(|image| image.image_elements.is_empty())
+                {
+                    list.push(Warning::new(
+                        Severity::Critical,
+                        "The file contains no data.",
+                    ));
                 }
             }
         }
+
+        self.warnings.set(warnings::Stage::File, list);
+    }
+
+    /// Collect the warnings about the selected device
+    fn collect_device_warnings(&mut self) {
+        let mut list = Vec::new();
+
+        if let Some(device) = self.get_selected_device() {
+            let info = &device.info;
+            if info.driver == dfudev::driver::DriverStatus::Missing {
+                list.push(
+                    Warning::new(
+                        Severity::Critical,
+                        "No usable driver is installed for the device.",
+                    )
+                    .with_details(ui::device::driver_instructions(
+                        info.vendor_id,
+                        info.product_id,
+                    )),
+                );
+            }
+            if !info.can_upload {
+                list.push(Warning::new(
+                    Severity::Info,
+                    "The device can't read its memory, the update is not verified.",
+                ));
+            }
+            if !info.quirks.memory_map.is_empty() {
+                list.push(Warning::new(
+                    Severity::Info,
+                    "The memory map of the device is replaced by an override.",
+                ));
+            }
+        }
+
+        self.warnings.set(warnings::Stage::Device, list);
+    }
+
+    /// Collect the warnings about the update as configured now
+    fn collect_preflight_warnings(&mut self) {
+        let mut list = Vec::new();
+        let checks = &self.dfu_file_checks;
+        let phases = self.phases;
+
+        if let (true, Some(report)) = (phases.modifies_device(), &checks.geometry_report) {
+            list.push(
+                Warning::new(
+                    Severity::Critical,
+                    "Invalid memory map reported by the device, erase and program are blocked.",
+                )
+                .with_details(report.clone()),
+            );
+        }
+        if phases.erase && !self.mass_erase && !checks.erase_problems.is_empty() {
+            list.push(
+                Warning::new(
+                    Severity::Critical,
+                    "Parts of the file can't be erased sector by sector, erase is blocked.",
+                )
+                .with_details(checks.erase_problems.join("\n")),
+            );
+        }
+        if let (true, Some(message)) = (phases.program, checks.downgrade_message()) {
+            list.push(
+                Warning::new(
+                    Severity::Critical,
+                    if self.strict_mode {
+                        format!("{message} Refused in strict mode.")
+                    } else {
+                        message
+                    },
+                )
+                .with_details(
+                    "The firmware version stored in the file suffix is lower than the \
+                    version reported by the device. Some bootloaders report their own \
+                    version instead of the one of the firmware, check the device \
+                    documentation if unsure.",
+                ),
+            );
+        }
+        if checks.preset.is_some() && checks.preset_accepted && !checks.preset_problems.is_empty() {
+            list.push(
+                Warning::new(
+                    Severity::Warning,
+                    "Differences to the board preset were approved.",
+                )
+                .with_details(checks.preset_problems.join("\n")),
+            );
+        }
+        if phases.modifies_device() {
+            list.push(Warning::new(
+                Severity::Warning,
+                if phases.erase && self.mass_erase {
+                    "All data on the device will be erased."
+                } else {
+                    "The data covered by the file will be overwritten."
+                },
+            ));
+            if phases.program && !phases.verify {
+                list.push(Warning::new(
+                    Severity::Warning,
+                    "The written data is not verified.",
+                ));
+            }
+        }
+        if let Some(range) = self.address_range {
+            list.push(Warning::new(
+                Severity::Info,
+                format!("Updates are restricted to {range}."),
+            ));
+        }
+
+        self.warnings.set(warnings::Stage::Preflight, list);
+    }
+
+    /// Check the loaded file with the configured integrity algorithms
+    ///
+    /// Sidecar files belong to the file opened by the user, so converted
+    /// files are checked by their source file.
+    fn check_file_integrity(&mut self) {
+        if let Some(dfu_file) = &mut self.dfu_file {
+            let source_path = self
+                .converted_file
+                .as_ref()
+                .map_or_else(|| dfu_file.path.clone(), |file| file.path.clone());
+            self.dfu_file_checks.integrity = integrity::check(
+                &integrity::required(&self.integrity_checks, self.strict_mode, &self.public_key),
+                &self.public_key,
+                &source_path,
+                dfu_file,
+            );
+        }
+    }
+
+    /// Calculate the hashes of the loaded file shown with its metadata,
+    /// converted files are hashed by their source file like in the
+    /// integrity checks
+    fn hash_file(&mut self) {
+        if let Some(dfu_file) = &mut self.dfu_file {
+            let source_path = self
+                .converted_file
+                .as_ref()
+                .map_or_else(|| dfu_file.path.clone(), |file| file.path.clone());
+            self.dfu_file_checks.hashes = integrity::hashes(&source_path, dfu_file)
+                .map_err(|error| log::error!("Error calculating file hashes: {error}"))
+                .ok();
+        }
+    }
+
+    /// Match the selected file against the current device
+    /// and set the file check flags accordingly
+    fn match_file_against_device(&mut self) {
+        let mut checks = std::mem::take(&mut self.dfu_file_checks);
+        if let (Some(dfu_file), Some(device)) = (&self.dfu_file, self.get_selected_device()) {
+            let targets = update::Targets {
+                skipped: self.skipped_targets(),
+                ..self.targets.clone()
+            };
+            checks.match_device(dfu_file, device, &targets);
+            checks.check_preset(self.selected_preset(), dfu_file, device, &targets);
+        }
+        self.dfu_file_checks = checks;
+    }
+
+    /// Return the preferences shown in the preferences window
+    fn preferences(&self) -> ui::settings::Preferences {
+        ui::settings::Preferences {
+            after_update: self.after_update,
+            verify: self.phases.verify,
+            theme: self.theme,
+            log_level: self.log_level,
+            remember_device: self.remember_device,
+            session_log: self.session_log,
+            session_log_directory: self.session_log_directory.clone(),
+        }
+    }
+
+    /// Apply the preferences edited in the preferences window
+    fn set_preferences(&mut self, ctx: &egui::Context, preferences: ui::settings::Preferences) {
+        self.after_update = preferences.after_update;
+        self.phases.verify = preferences.verify;
+        if self.theme != preferences.theme {
+            self.theme = preferences.theme;
+            self.theme.apply(ctx);
+        }
+        if self.log_level != preferences.log_level {
+            self.log_level = preferences.log_level;
+            self.log_level.apply();
+        }
+        self.remember_device = preferences.remember_device;
+        if !self.remember_device {
+            self.last_device = None;
+        }
+        self.session_log = preferences.session_log;
+        self.session_log_directory = preferences.session_log_directory.trim().to_string();
+    }
+
+    /// Return where the session logs of jobs are written, `None` if disabled
+    fn session_log_config(&self) -> Option<session::Config> {
+        if !self.session_log {
+            return None;
+        }
+
+        let directory = if self.session_log_directory.is_empty() {
+            session::Config::default_directory()?
+        } else {
+            std::path::PathBuf::from(&self.session_log_directory)
+        };
+
+        Some(session::Config {
+            directory,
+            keep: session::Config::DEFAULT_KEEP,
+        })
+    }
+
+    /// Return the selected board preset, `None` if there is none
+    /// or it's no longer available
+    fn selected_preset(&self) -> Option<&'static presets::Preset> {
+        self.preset.as_deref().and_then(presets::find)
+    }
+
+    /// Return the selected profile
+    fn selected_profile(&self) -> Option<&profile::Profile> {
+        self.profile
+            .as_ref()
+            .and_then(|name| self.profiles.iter().find(|profile| &profile.name == name))
     }
 
     /// Check if everything is ready to program the device
     fn preflight_checks(&self) -> bool {
         let device = self.get_selected_device();
 
-        let checks = &self.dfu_file_checks;
+        device.is_some() && self.dfu_file.is_some() && self.dfu_file_checks.passed()
+    }
+}
+
+/// Return the images of a DfuSe file that are not skipped,
+/// together with the alt setting they are written to
+fn selected_images<'a>(
+    content: &'a dfufile::dfuse::Content,
+    targets: &'a update::Targets,
+) -> impl Iterator<Item = (u8, &'a dfufile::dfuse::Image)> {
+    content
+        .images
+        .iter()
+        .enumerate()
+        .filter(|&(index, _)| targets.is_selected(index))
+        .map(|(index, image)| (targets.alt_setting(index, image), image))
+}
+
+/// Report an error of the update thread, with a hint if the device can't be accessed
+fn send_update_error(
+    reporter: &progress::Reporter,
+    message_sender: &std::sync::mpsc::Sender<Message>,
+    error: &anyhow::Error,
+    retry: Message,
+) {
+    let mut message = format!("{error}");
+    if matches!(
+        error.downcast_ref::<update::Error>(),
+        Some(update::Error::ReadProtected)
+    ) {
+        message = format!("{message} {UNPROTECT_HINT}");
+    }
+    reporter.send(progress::Event::Error(message)).ok();
+    send_access_hint(message_sender, error, retry);
+}
+
+/// Show a hint if access to the device was denied or another application
+/// holds it, `retry` is sent again if the user retries in the latter case
+fn send_access_hint(
+    message_sender: &std::sync::mpsc::Sender<Message>,
+    error: &anyhow::Error,
+    retry: Message,
+) {
+    if dfudev::is_access_denied(error) {
+        message_sender.send(Message::DeviceAccessDenied).ok();
+    } else if let Some(owner) = dfudev::in_use_by(error) {
+        message_sender
+            .send(Message::DeviceInUse {
+                owner,
+                retry: Box::new(retry),
+            })
+            .ok();
+    }
+}
+
+/// Return why a dropped file can't be opened or `None` if it is supported
+fn drop_error(path: &std::path::Path) -> Option<String> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
 
-        device.is_some()
-            && self.dfu_file.is_some()
-            && checks.crc_valid
-            && checks.dfu_version_valid
-            && checks.vendor_id_accepted
-            && checks.product_id_accepted
-            && checks.targets_valid
+    if path.is_dir() {
+        Some(format!("{name} is a folder, drop a firmware file instead."))
+    } else if !convert::is_supported(path) {
+        Some(format!("Unsupported file type: {name}"))
+    } else {
+        None
     }
 }
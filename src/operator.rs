@@ -0,0 +1,42 @@
+//! Operators allowed to flash devices in production mode
+
+/// Operator with name and PIN
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Operator {
+    /// Name shown in the login and recorded in the session history
+    pub name: String,
+
+    /// SHA-256 hash of name and PIN as hex string, the PIN itself is not stored
+    pin_hash: String,
+}
+
+impl Operator {
+    /// Return a new operator with the given name and PIN
+    pub fn new(name: &str, pin: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            pin_hash: pin_hash(name, pin),
+        }
+    }
+
+    /// Return if the PIN is correct
+    pub fn verify(&self, pin: &str) -> bool {
+        self.pin_hash == pin_hash(&self.name, pin)
+    }
+}
+
+/// Return the hash of a PIN, salted with the operator name
+fn pin_hash(name: &str, pin: &str) -> String {
+    use sha2::Digest;
+
+    let mut hasher = sha2::Sha256::new();
+    hasher.update(name.as_bytes());
+    hasher.update([0]);
+    hasher.update(pin.as_bytes());
+
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
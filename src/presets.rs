@@ -0,0 +1,146 @@
+//! Presets for popular boards
+//!
+//! A preset describes the bootloader of a board, so users who don't know the
+//! details of their chip still get a safe configuration: only devices of the
+//! board are listed, the recommended options are set, and devices with an
+//! unexpected memory layout or files writing into the bootloader are reported
+//! before flashing.
+
+use crate::dfudev::DeviceInfo;
+use crate::update::{AddressRange, AfterUpdate};
+
+/// Preset of a board
+#[derive(Debug)]
+pub struct Preset {
+    /// Name shown in the menu, also stored in the settings
+    pub name: &'static str,
+
+    /// USB vendor id of the bootloader
+    pub vendor_id: u16,
+
+    /// USB product id of the bootloader
+    pub product_id: u16,
+
+    /// Part of the alt setting name expected for the internal flash,
+    /// `None` if the bootloader reports no DfuSe memory layout
+    pub memory_layout: Option<&'static str>,
+
+    /// Address range occupied by the bootloader, which must not be written
+    pub reserved: Option<AddressRange>,
+
+    /// Recommended action after an update
+    pub after_update: AfterUpdate,
+
+    /// Hints shown when the preset is selected, including known quirks
+    pub notes: &'static str,
+}
+
+/// Presets in the order they are shown
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "STM32 Nucleo / Discovery",
+        vendor_id: 0x0483,
+        product_id: 0xDF11,
+        memory_layout: Some("/0x08000000/"),
+        reserved: None,
+        after_update: AfterUpdate::Leave,
+        notes: "The STM32 system bootloader is started by holding BOOT0 high during reset. \
+            The on-board ST-LINK is not a DFU device, connect the USB port of the target MCU. \
+            STM32H7 erase quirks are handled automatically.",
+    },
+    Preset {
+        name: "Electrosmith Daisy Seed",
+        vendor_id: 0x0483,
+        product_id: 0xDF11,
+        memory_layout: Some("/0x08000000/01*128K"),
+        reserved: None,
+        after_update: AfterUpdate::Leave,
+        notes: "Hold BOOT and press RESET to start the STM32H750 system bootloader. \
+            Only the 128 KiB internal flash is programmed, applications linked for the \
+            QSPI flash need the Daisy bootloader instead.",
+    },
+    Preset {
+        name: "Black Magic Probe",
+        vendor_id: 0x1D50,
+        product_id: 0x6017,
+        memory_layout: Some("/0x08000000/8*001Ka"),
+        reserved: Some(AddressRange {
+            start: 0x0800_0000,
+            end: 0x0800_2000,
+        }),
+        after_update: AfterUpdate::Leave,
+        notes: "The bootloader occupies the first 8 KiB of the flash, the firmware must start \
+            at 0x08002000. A full chip erase is not supported.",
+    },
+    Preset {
+        name: "Maple / STM32duino bootloader",
+        vendor_id: 0x1EAF,
+        product_id: 0x0003,
+        memory_layout: None,
+        reserved: None,
+        after_update: AfterUpdate::Stay,
+        notes: "Plain DFU bootloader, which only runs for a short time after reset. \
+            Alt setting 2 writes the flash after the bootloader, binaries must be linked \
+            for that address. The device restarts on its own after programming.",
+    },
+    Preset {
+        name: "GD32VF103 (Longan Nano)",
+        vendor_id: 0x28E9,
+        product_id: 0x0189,
+        memory_layout: Some("/0x08000000/"),
+        reserved: None,
+        after_update: AfterUpdate::Leave,
+        notes: "Hold BOOT0 and press RESET to start the system bootloader. \
+            Some bootloader versions report a wrong flash size, check the memory map.",
+    },
+];
+
+impl Preset {
+    /// Return if a device is a bootloader of the board
+    pub fn matches(&self, info: &DeviceInfo) -> bool {
+        info.vendor_id == self.vendor_id && info.product_id == self.product_id
+    }
+
+    /// Return the problems of a device and the start address and size of
+    /// the file elements written to it
+    pub fn problems(&self, info: &DeviceInfo, elements: &[(u32, u32)]) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if !self.matches(info) {
+            problems.push(format!(
+                "The device is not a {} bootloader ({:04x}:{:04x} expected).",
+                self.name, self.vendor_id, self.product_id
+            ));
+        }
+
+        if let Some(memory_layout) = self.memory_layout {
+            if !info
+                .alt_settings
+                .iter()
+                .any(|(_, name)| name.contains(memory_layout))
+            {
+                problems.push(format!(
+                    "The device reports no memory layout containing \"{memory_layout}\", \
+                    it may be another board or chip."
+                ));
+            }
+        }
+
+        if let Some(reserved) = self.reserved {
+            for &(address, size) in elements {
+                if reserved.clip(address, size).is_some() {
+                    problems.push(format!(
+                        "Element at 0x{address:08X} overwrites the bootloader at {reserved}."
+                    ));
+                }
+            }
+        }
+
+        problems
+    }
+}
+
+/// Return the preset with a name
+pub fn find(name: &str) -> Option<&'static Preset> {
+    PRESETS.iter().find(|preset| preset.name == name)
+}
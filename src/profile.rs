@@ -0,0 +1,116 @@
+//! Project profiles pairing devices with their firmware
+//!
+//! A profile holds what differs between the products someone maintains:
+//! which devices belong to it, where the firmware comes from and what happens
+//! after an update. Selecting a profile restricts the devices shown like a
+//! board preset, opens the firmware and sets the action after the update.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::Result;
+
+use crate::dfudev::DeviceInfo;
+use crate::update::AfterUpdate;
+
+/// Name of a downloaded file if the URL contains none
+const DEFAULT_FILE_NAME: &str = "firmware.dfu";
+
+/// Profile of a project
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Profile {
+    /// Name shown in the selection, unique
+    pub name: String,
+
+    /// USB vendor id of the devices, `None` for any
+    pub vendor_id: Option<u16>,
+
+    /// USB product id of the devices, `None` for any
+    pub product_id: Option<u16>,
+
+    /// Pattern of the serial number, `*` matches any number of characters
+    /// and `?` a single one, empty for any
+    pub serial_number: String,
+
+    /// Path or http(s) URL of the firmware, empty to keep the opened file
+    pub firmware: String,
+
+    /// Action after a successful update
+    pub after_update: AfterUpdate,
+}
+
+impl Profile {
+    /// Return if a device belongs to the profile
+    pub fn matches(&self, info: &DeviceInfo) -> bool {
+        self.vendor_id.map_or(true, |id| id == info.vendor_id)
+            && self.product_id.map_or(true, |id| id == info.product_id)
+            && (self.serial_number.is_empty()
+                || wildcard_match(&self.serial_number, &info.serial_number_string))
+    }
+
+    /// Return the URL of the firmware, `None` if it's a local file
+    pub fn firmware_url(&self) -> Option<&str> {
+        let firmware = self.firmware.trim();
+
+        (firmware.starts_with("http://") || firmware.starts_with("https://")).then_some(firmware)
+    }
+}
+
+/// Download a firmware file into the temporary directory and return its path
+///
+/// The name is taken from the URL, so the format is recognized by the
+/// extension like for local files. Each call uses a new path, so that a
+/// changed content is never mistaken for a previous download.
+pub fn download(url: &str) -> Result<std::path::PathBuf> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let data = crate::selfupdate::download(url)?;
+
+    let name: String = url
+        .split(['?', '#'])
+        .next()
+        .and_then(|path| path.rsplit('/').next())
+        .filter(|name| !name.is_empty())
+        .unwrap_or(DEFAULT_FILE_NAME)
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() || "._-".contains(c) {
+            true => c,
+            false => '_',
+        })
+        .collect();
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let path =
+        std::env::temp_dir().join(format!("dfu-buddy-{}-{count}-{name}", std::process::id()));
+    std::fs::write(&path, data)?;
+
+    Ok(path)
+}
+
+/// Return if a text matches a pattern with `*` and `?` wildcards
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and of the text it was matched against
+    let mut star = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // Let the last `*` cover one more character
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
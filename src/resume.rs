@@ -0,0 +1,140 @@
+//! Progress of interrupted updates, to resume them later
+//!
+//! While programming, each sector written completely is reported. If the
+//! update fails or is stopped, these sectors are stored in a small file in
+//! the local data directory, keyed by the serial number of the device and the
+//! hash of the file. Resuming the same file on the same device skips them,
+//! even after the application was restarted.
+
+use anyhow::{anyhow, Result};
+
+use crate::update::AddressRange;
+
+/// Name of the file holding the progress of all interrupted updates
+const FILE_NAME: &str = "resume.json";
+
+/// Sectors completed by an interrupted update
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Progress {
+    /// Serial number of the device
+    pub serial_number: String,
+
+    /// SHA-256 hash of the file as hex string
+    pub file_hash: String,
+
+    /// Sectors written completely, by alt setting
+    pub completed_sectors: Vec<(u8, AddressRange)>,
+}
+
+impl Progress {
+    /// Return the progress of an update of a device with a file
+    pub fn new(
+        serial_number: &str,
+        file_hash: &[u8; 32],
+        completed_sectors: Vec<(u8, AddressRange)>,
+    ) -> Self {
+        Self {
+            serial_number: serial_number.to_string(),
+            file_hash: hex(file_hash),
+            completed_sectors,
+        }
+    }
+
+    /// Return a short description of the completed part
+    pub fn summary(&self) -> String {
+        let size: u64 = self
+            .completed_sectors
+            .iter()
+            .map(|(_, sector)| (sector.end - sector.start) as u64)
+            .sum();
+
+        format!(
+            "{} sectors ({}) already written",
+            self.completed_sectors.len(),
+            crate::format::size(size)
+        )
+    }
+
+    /// Add sectors completed by another attempt, skipping the known ones
+    pub fn merge(&mut self, sectors: &[(u8, AddressRange)]) {
+        for sector in sectors {
+            if !self.completed_sectors.contains(sector) {
+                self.completed_sectors.push(*sector);
+            }
+        }
+    }
+
+    /// Return if the progress belongs to a device and file
+    fn matches(&self, serial_number: &str, file_hash: &str) -> bool {
+        self.serial_number == serial_number && self.file_hash == file_hash
+    }
+}
+
+/// Return the progress stored for a device and file, `None` if there is none
+/// or it can't be read
+pub fn load(serial_number: &str, file_hash: &[u8; 32]) -> Option<Progress> {
+    let file_hash = hex(file_hash);
+
+    match read_all() {
+        Ok(entries) => entries
+            .into_iter()
+            .find(|progress| progress.matches(serial_number, &file_hash)),
+        Err(error) => {
+            log::error!("Error reading resume progress: {}", error);
+            None
+        }
+    }
+}
+
+/// Store the progress, replacing an older one of the same device and file
+pub fn save(progress: &Progress) -> Result<()> {
+    let mut entries = read_all()?;
+    entries.retain(|entry| !entry.matches(&progress.serial_number, &progress.file_hash));
+    entries.push(progress.clone());
+
+    write_all(&entries)
+}
+
+/// Remove the progress of a device and file
+pub fn remove(serial_number: &str, file_hash: &[u8; 32]) -> Result<()> {
+    let file_hash = hex(file_hash);
+    let mut entries = read_all()?;
+    let count = entries.len();
+    entries.retain(|entry| !entry.matches(serial_number, &file_hash));
+
+    if entries.len() == count {
+        return Ok(());
+    }
+
+    write_all(&entries)
+}
+
+/// Read the progress of all interrupted updates
+fn read_all() -> Result<Vec<Progress>> {
+    match file_path().filter(|path| path.exists()) {
+        Some(path) => Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Write the progress of all interrupted updates
+fn write_all(entries: &[Progress]) -> Result<()> {
+    let path = file_path().ok_or_else(|| anyhow!("No data directory available."))?;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(entries)?)?;
+
+    Ok(())
+}
+
+/// Return the path of the progress file
+fn file_path() -> Option<std::path::PathBuf> {
+    dirs::data_local_dir().map(|dir| dir.join(env!("CARGO_PKG_NAME")).join(FILE_NAME))
+}
+
+/// Return a hash as hex string
+fn hex(hash: &[u8; 32]) -> String {
+    hash.iter().map(|byte| format!("{byte:02x}")).collect()
+}
@@ -0,0 +1,181 @@
+//! Update of the application itself from the latest GitHub release
+//!
+//! Releases provide the executable for each platform as separate asset
+//! together with a minisign signature. The signature is checked against the
+//! public key compiled into the application before the running executable is
+//! replaced. Builds without a key, set via the `DFU_BUDDY_UPDATE_KEY`
+//! environment variable at compile time, refuse to update.
+
+use std::io::Read;
+
+use anyhow::{anyhow, Result};
+
+/// URL of the API endpoint returning the latest release
+const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/sourcebox/dfu-buddy/releases/latest";
+
+/// Public minisign key releases are signed with
+const PUBLIC_KEY: Option<&str> = option_env!("DFU_BUDDY_UPDATE_KEY");
+
+/// Extension of signature assets
+const SIGNATURE_EXTENSION: &str = ".minisig";
+
+/// Max. size of a downloaded file
+const MAX_DOWNLOAD_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Release as returned by the GitHub API
+#[derive(Debug, serde::Deserialize)]
+pub struct Release {
+    /// Tag name, the version with a leading `v`
+    pub tag_name: String,
+
+    /// Downloadable files
+    assets: Vec<Asset>,
+}
+
+/// Downloadable file of a release
+#[derive(Debug, serde::Deserialize)]
+struct Asset {
+    /// File name
+    name: String,
+
+    /// Download URL
+    browser_download_url: String,
+}
+
+impl Release {
+    /// Return the version without the leading `v`
+    pub fn version(&self) -> &str {
+        self.tag_name.trim_start_matches('v')
+    }
+
+    /// Return if the release is newer than the running application
+    pub fn is_newer(&self) -> bool {
+        parse_version(self.version()) > parse_version(env!("CARGO_PKG_VERSION"))
+    }
+
+    /// Return the asset with a name
+    fn asset(&self, name: &str) -> Result<&Asset> {
+        self.assets
+            .iter()
+            .find(|asset| asset.name == name)
+            .ok_or_else(|| anyhow!("Release {} contains no file {name}.", self.tag_name))
+    }
+}
+
+/// Return the latest release
+pub fn latest_release() -> Result<Release> {
+    let release = ureq::get(LATEST_RELEASE_URL)
+        .set("User-Agent", env!("CARGO_PKG_NAME"))
+        .call()?
+        .into_json()?;
+
+    Ok(release)
+}
+
+/// Download the executable of a release, verify its signature and replace
+/// the running executable with it
+pub fn install(release: &Release) -> Result<()> {
+    let public_key = PUBLIC_KEY.ok_or_else(|| {
+        anyhow!("This build has no key to verify updates, install new versions manually.")
+    })?;
+    let public_key = minisign_verify::PublicKey::from_base64(public_key)?;
+
+    let asset_name = asset_name();
+    let data = download(&release.asset(&asset_name)?.browser_download_url)?;
+    let signature = download(
+        &release
+            .asset(&format!("{asset_name}{SIGNATURE_EXTENSION}"))?
+            .browser_download_url,
+    )?;
+    let signature = minisign_verify::Signature::decode(&String::from_utf8_lossy(&signature))?;
+    public_key
+        .verify(&data, &signature, false)
+        .map_err(|error| anyhow!("Signature verification failed: {error}"))?;
+    log::info!("Signature of {asset_name} verified");
+
+    replace_executable(&data)
+}
+
+/// Return the name of the executable asset for the current platform
+fn asset_name() -> String {
+    format!(
+        "{}-{}-{}{}",
+        env!("CARGO_PKG_NAME"),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        std::env::consts::EXE_SUFFIX
+    )
+}
+
+/// Return the content of a file downloaded from a URL
+pub fn download(url: &str) -> Result<Vec<u8>> {
+    log::info!("Downloading {url}");
+
+    let mut data = Vec::new();
+    ureq::get(url)
+        .set("User-Agent", env!("CARGO_PKG_NAME"))
+        .call()?
+        .into_reader()
+        .take(MAX_DOWNLOAD_SIZE)
+        .read_to_end(&mut data)?;
+
+    Ok(data)
+}
+
+/// Replace the running executable
+///
+/// The new file is written next to the executable and renamed afterwards,
+/// so an interrupted update never leaves a partial executable behind.
+fn replace_executable(data: &[u8]) -> Result<()> {
+    let exe_path = std::env::current_exe()?;
+    let new_path = exe_path.with_extension("new");
+    std::fs::write(&new_path, data)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&new_path, std::fs::Permissions::from_mode(0o755))?;
+    }
+
+    // Windows does not allow replacing a running executable, but renaming it
+    if cfg!(windows) {
+        let old_path = exe_path.with_extension("old");
+        std::fs::remove_file(&old_path).ok();
+        std::fs::rename(&exe_path, &old_path)?;
+    }
+
+    std::fs::rename(&new_path, &exe_path)?;
+    log::info!("Executable {} replaced", exe_path.display());
+
+    Ok(())
+}
+
+/// Return major, minor and patch number and the pre-release parts of a
+/// version, pre-releases are ordered before the release
+fn parse_version(version: &str) -> (u32, u32, u32, bool, String, u32) {
+    let (release, pre_release) = match version.split_once('-') {
+        Some((release, pre_release)) => (release, Some(pre_release)),
+        None => (version, None),
+    };
+
+    let mut numbers = release
+        .split('.')
+        .map(|number| number.parse::<u32>().unwrap_or_default());
+    let major = numbers.next().unwrap_or_default();
+    let minor = numbers.next().unwrap_or_default();
+    let patch = numbers.next().unwrap_or_default();
+
+    // Trailing numbers are compared numerically, e.g. alpha10 after alpha9
+    let pre_release = pre_release.unwrap_or_default();
+    let name = pre_release.trim_end_matches(|c: char| c.is_ascii_digit());
+    let number = pre_release[name.len()..].parse::<u32>().unwrap_or_default();
+
+    (
+        major,
+        minor,
+        patch,
+        pre_release.is_empty(),
+        name.to_string(),
+        number,
+    )
+}
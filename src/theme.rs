@@ -1,7 +1,8 @@
-//! Plasma theme.
+//! Plasma theme and the selectable color themes.
 //!
 //! Taken from <https://github.com/scruffykat/egui-Themes>.
 
+use eframe::egui;
 use eframe::egui::{
     epaint::Shadow,
     style::{Interaction, Selection, Spacing, TextCursorStyle, WidgetVisuals, Widgets},
@@ -9,6 +10,54 @@ use eframe::egui::{
 };
 use eframe::emath::vec2;
 
+/// Color theme of the application
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum Theme {
+    /// Default dark colors of egui
+    #[default]
+    Dark,
+
+    /// Dark colors with purple accents
+    Plasma,
+
+    /// Default light colors of egui
+    Light,
+}
+
+impl Theme {
+    /// All themes in the order they are shown
+    pub const ALL: [Self; 3] = [Self::Dark, Self::Plasma, Self::Light];
+
+    /// Apply the theme to the context, keeping the spacing of the plasma style
+    pub fn apply(self, ctx: &egui::Context) {
+        let mut style = style();
+        let theme = match self {
+            Self::Dark => {
+                style.visuals = Visuals::dark();
+                egui::Theme::Dark
+            }
+            Self::Plasma => egui::Theme::Dark,
+            Self::Light => {
+                style.visuals = Visuals::light();
+                egui::Theme::Light
+            }
+        };
+        ctx.set_theme(theme);
+        ctx.set_style_of(theme, style);
+    }
+}
+
+impl std::fmt::Display for Theme {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Dark => write!(f, "Dark"),
+            Self::Plasma => write!(f, "Plasma"),
+            Self::Light => write!(f, "Light"),
+        }
+    }
+}
+
+/// Return the plasma style
 pub fn style() -> Style {
     Style {
         text_styles: [
@@ -0,0 +1,82 @@
+//! Generation and installation of udev rules on Linux
+//!
+//! Non-root users need a udev rule to access USB devices. The rule generated
+//! here tags the devices with `uaccess`, granting access to the user logged in
+//! at the local seat. Installing it requires root privileges, which are
+//! requested via `pkexec`.
+
+use anyhow::{anyhow, Result};
+
+/// Path of the installed rules file
+///
+/// The number must be lower than 73, otherwise the `uaccess` tag is applied
+/// too late by systemd-logind.
+pub const RULES_PATH: &str = "/etc/udev/rules.d/70-dfu-buddy.rules";
+
+/// Exit code of `pkexec` if the authentication dialog was dismissed
+const PKEXEC_DISMISSED: i32 = 126;
+
+/// Exit code of `pkexec` if the user is not authorized
+const PKEXEC_NOT_AUTHORIZED: i32 = 127;
+
+/// Return the content of a rules file for a list of vendor and product ids
+pub fn rules(usb_ids: &[(u16, u16)]) -> String {
+    let mut content = format!(
+        "# Access to DFU devices for the local user, generated by {}\n",
+        env!("CARGO_PKG_NAME")
+    );
+
+    for (vendor_id, product_id) in usb_ids {
+        content.push_str(&format!(
+            "SUBSYSTEM==\"usb\", ATTRS{{idVendor}}==\"{vendor_id:04x}\", \
+            ATTRS{{idProduct}}==\"{product_id:04x}\", MODE=\"0660\", TAG+=\"uaccess\"\n"
+        ));
+    }
+
+    content
+}
+
+/// Install a rules file and reload the rules, asking for root privileges
+pub fn install(rules: &str) -> Result<()> {
+    let temp_path = std::env::temp_dir().join(format!("{}.rules", env!("CARGO_PKG_NAME")));
+    std::fs::write(&temp_path, rules)?;
+
+    let script = format!(
+        "install -m 644 \"$1\" {RULES_PATH} && udevadm control --reload-rules \
+        && udevadm trigger --subsystem-match=usb"
+    );
+    let status = std::process::Command::new("pkexec")
+        .args(["sh", "-c", &script, "sh"])
+        .arg(&temp_path)
+        .status();
+
+    std::fs::remove_file(&temp_path).ok();
+
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => match status.code() {
+            Some(PKEXEC_DISMISSED) => Err(anyhow!("Authentication was dismissed.")),
+            Some(PKEXEC_NOT_AUTHORIZED) => Err(anyhow!("Not authorized to install the rule.")),
+            code => Err(anyhow!(
+                "Installing the rule failed with exit code {code:?}."
+            )),
+        },
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Err(anyhow!(
+            "pkexec is not available. Save the rule to a file and install it manually."
+        )),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Return instructions to install a rules file saved to a path manually
+pub fn instructions(path: &std::path::Path) -> String {
+    format!(
+        "Rule written to {}.\n\nInstall it by running\n\n\
+        sudo cp \"{}\" {RULES_PATH}\n\
+        sudo udevadm control --reload-rules\n\
+        sudo udevadm trigger --subsystem-match=usb\n\n\
+        and reconnect the device.",
+        path.display(),
+        path.display()
+    )
+}
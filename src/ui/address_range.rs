@@ -0,0 +1,114 @@
+//! Window to restrict the update to an address window
+//!
+//! Only the data of the file inside the window is erased, programmed and
+//! verified, e.g. to leave a configuration region of the device untouched.
+
+use eframe::egui;
+
+use crate::update::AddressRange;
+use crate::Message;
+
+/// State of the address range window
+#[derive(Default)]
+pub struct AddressRangeForm {
+    /// Flag if the window is open
+    pub open: bool,
+
+    /// Start address as hex text
+    start: String,
+
+    /// End address as hex text
+    end: String,
+}
+
+impl AddressRangeForm {
+    /// Open the window with the current range
+    pub fn show(&mut self, range: Option<AddressRange>) {
+        if let Some(range) = range {
+            self.start = format!("{:08X}", range.start);
+            self.end = format!("{:08X}", range.end);
+        }
+        self.open = true;
+    }
+}
+
+/// Show the address range window
+pub fn window(
+    ctx: &egui::Context,
+    form: &mut AddressRangeForm,
+    range: Option<AddressRange>,
+    message_sender: &std::sync::mpsc::Sender<Message>,
+) {
+    let mut open = form.open;
+    let mut close = false;
+
+    egui::Window::new("Address Range")
+        .open(&mut open)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.set_max_width(350.0);
+
+            ui.label(
+                "Only the data of the file inside this range is erased, programmed \
+                and verified. Start and end must lie on sector boundaries.",
+            );
+            ui.add_space(5.0);
+
+            egui::Grid::new("address_range")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Start: 0x");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut form.start)
+                            .desired_width(70.0)
+                            .char_limit(8),
+                    );
+                    ui.end_row();
+
+                    ui.label("End: 0x")
+                        .on_hover_text("First address after the range");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut form.end)
+                            .desired_width(70.0)
+                            .char_limit(8),
+                    );
+                    ui.end_row();
+                });
+
+            let parsed = parse_range(form);
+
+            ui.add_space(5.0);
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(parsed.is_some(), egui::Button::new("Apply"))
+                    .on_disabled_hover_text("Enter hex addresses with the start below the end")
+                    .clicked()
+                {
+                    message_sender.send(Message::SetAddressRange(parsed)).ok();
+                    close = true;
+                }
+                if ui
+                    .add_enabled(range.is_some(), egui::Button::new("Whole file"))
+                    .on_hover_text("Remove the restriction")
+                    .clicked()
+                {
+                    message_sender.send(Message::SetAddressRange(None)).ok();
+                    close = true;
+                }
+            });
+        });
+
+    form.open = open && !close;
+}
+
+/// Return the range if both addresses are valid and in order
+fn parse_range(form: &AddressRangeForm) -> Option<AddressRange> {
+    let parse = |text: &str| u32::from_str_radix(text.trim().trim_start_matches("0x"), 16).ok();
+    let range = AddressRange {
+        start: parse(&form.start)?,
+        end: parse(&form.end)?,
+    };
+
+    (range.start < range.end).then_some(range)
+}
@@ -0,0 +1,60 @@
+//! Window to choose one of several DFU files in an archive
+
+use eframe::egui;
+
+use crate::Message;
+
+/// Archive with several DFU files to choose from
+pub struct ArchiveChoice {
+    /// Path of the archive
+    pub path: std::path::PathBuf,
+
+    /// Names of the DFU files in the archive
+    pub entries: Vec<String>,
+}
+
+/// Show the window, closed when a file is chosen
+pub fn window(
+    ctx: &egui::Context,
+    choice: &mut Option<ArchiveChoice>,
+    message_sender: &std::sync::mpsc::Sender<Message>,
+) {
+    let Some(archive) = choice else {
+        return;
+    };
+
+    let mut open = true;
+    let mut chosen = false;
+
+    egui::Window::new("Open Archive")
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label(format!(
+                "{} contains several DFU files, choose the one to open:",
+                archive
+                    .path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+            ));
+            ui.add_space(5.0);
+
+            for entry in &archive.entries {
+                if ui.button(entry).clicked() {
+                    message_sender
+                        .send(Message::OpenArchiveEntry(
+                            archive.path.clone(),
+                            entry.clone(),
+                        ))
+                        .ok();
+                    chosen = true;
+                }
+            }
+        });
+
+    if !open || chosen {
+        *choice = None;
+    }
+}
@@ -1,6 +1,13 @@
 //! UI elements showing device-related information
 
-use crate::{dfudev, DeviceUpdateState, DeviceUpdateStep, Message};
+use super::{preflight, warnings};
+use crate::dfudev::dfuse::{MemorySegment, MemorySegmentRegion};
+use crate::dfudev::driver::{self, DriverStatus};
+use crate::progress::CancelMode;
+use crate::update::{AfterUpdate, Phases};
+use crate::{
+    dfudev, format, DeviceUpdateState, DeviceUpdateStep, DfuFileChecks, Message, SectorState,
+};
 use eframe::egui;
 
 /// Show combobox with devices
@@ -8,19 +15,33 @@ pub fn selection(
     ui: &mut egui::Ui,
     devices: &Option<Vec<dfudev::DfuDevice>>,
     selected_device: &Option<&dfudev::DfuDevice>,
+    stale_devices: &std::collections::HashSet<u64>,
+    preset: Option<&str>,
     message_sender: &std::sync::mpsc::Sender<Message>,
 ) {
     let mut device_list = Vec::new();
+    let mut device_details = Vec::new();
     let mut device_index = 0;
 
-    if devices.is_some() {
-        for (index, device) in devices.as_ref().unwrap().iter().enumerate() {
-            device_list.push(format!(
-                "{} | {}",
-                &device.info.manufacturer_string, &device.info.product_string
-            ));
+    if let Some(devices) = devices {
+        for (index, device) in devices.iter().enumerate() {
+            let label = format!(
+                "{} | {} | S/N {} | Bus {}, address {}",
+                &device.info.manufacturer_string,
+                &device.info.product_string,
+                &device.info.serial_number_string,
+                device.info.bus_path(),
+                device.info.address
+            );
+            // Stale entries are kept until the next scan replaces them
+            device_list.push(if stale_devices.contains(&device.id) {
+                egui::RichText::new(format!("{label} (disconnected)")).color(egui::Color32::RED)
+            } else {
+                egui::RichText::new(label)
+            });
+            device_details.push(device.info.details());
 
-            if selected_device.is_some() && selected_device.unwrap().id == device.id {
+            if selected_device.is_some_and(|selected| selected.id == device.id) {
                 device_index = index;
             }
         }
@@ -36,37 +57,76 @@ pub fn selection(
 
         ui.scope(|ui| {
             if device_list.is_empty() {
-                ui.disable();
+                ui.vertical(|ui| {
+                    ui.add_space(2.0);
+                    ui.set_width(ui.available_width() - 280.0);
+                    let text = match preset {
+                        Some(preset) => format!("No DFU devices of the {preset} preset found."),
+                        None => "No DFU devices found.".into(),
+                    };
+                    ui.add(egui::Label::new(
+                        egui::RichText::new(text).color(egui::Color32::YELLOW),
+                    ));
+                });
+                return;
             }
 
+            let previous_index = device_index;
+
             let combo_box = egui::ComboBox::from_id_salt("device_list")
-                .width(ui.available_width() - 100.0)
-                .show_index(ui, &mut device_index, device_list.len(), |i| {
-                    if device_count > 0 {
-                        device_list[i].clone()
-                    } else {
-                        String::from("No devices found")
+                .width(ui.available_width() - 280.0)
+                .selected_text(if device_count > 0 {
+                    device_list[device_index].clone()
+                } else {
+                    egui::RichText::new("No devices found")
+                })
+                .show_ui(ui, |ui| {
+                    for (index, label) in device_list.iter().enumerate() {
+                        ui.selectable_value(&mut device_index, index, label.clone())
+                            .on_hover_text(&device_details[index]);
                     }
                 });
 
-            if combo_box.changed() && devices.is_some() {
-                for (index, device) in devices.as_ref().unwrap().iter().enumerate() {
-                    let d = if devices.is_some() {
-                        devices
-                            .as_ref()
-                            .unwrap()
-                            .iter()
-                            .find(|&x| x.id == device.id)
-                    } else {
-                        None
-                    };
-                    if d.is_some() && index == device_index {
-                        message_sender.send(Message::DeviceSelected(device.id)).ok();
-                    }
+            if device_count > 0 {
+                combo_box
+                    .response
+                    .on_hover_text(&device_details[device_index]);
+            }
+
+            // Devices are sorted, so the index maps to the same device as displayed
+            if device_index != previous_index {
+                if let Some(device) = devices.as_ref().and_then(|d| d.get(device_index)) {
+                    message_sender.send(Message::DeviceSelected(device.id)).ok();
                 }
-            };
+            }
         });
 
+        let can_upload = selected_device.is_some_and(|device| device.info.can_upload);
+        if ui
+            .add_enabled(can_upload, egui::Button::new("Backup..."))
+            .on_hover_text("Read the device memory into a file")
+            .on_disabled_hover_text("The selected device does not support reading its memory")
+            .clicked()
+        {
+            message_sender.send(Message::OpenBackupDialog).ok();
+        }
+
+        if ui
+            .add_enabled(device_count > 0, egui::Button::new("Journal..."))
+            .on_hover_text("Show all operations recorded for the device")
+            .clicked()
+        {
+            message_sender.send(Message::OpenJournal).ok();
+        }
+
+        if ui
+            .button("Events...")
+            .on_hover_text("Show device events of this session")
+            .clicked()
+        {
+            message_sender.send(Message::OpenEvents).ok();
+        }
+
         ui.centered_and_justified(|ui| {
             if ui.button("Rescan").clicked() {
                 message_sender.send(Message::RescanDevices).ok();
@@ -75,8 +135,75 @@ pub fn selection(
     });
 }
 
+/// Show box with troubleshooting hints when no devices are found
+pub fn troubleshooting(ui: &mut egui::Ui, message_sender: &std::sync::mpsc::Sender<Message>) {
+    ui.group(|ui| {
+        ui.set_width(ui.available_width());
+        ui.set_height(ui.available_height());
+
+        ui.vertical(|ui| {
+            ui.heading("No devices found");
+            ui.add_space(5.0);
+            ui.label("Likely causes:");
+
+            egui::containers::ScrollArea::vertical()
+                .max_height(ui.available_height() - 40.0)
+                .show(ui, |ui| {
+                    ui.set_width(ui.available_width());
+                    for hint in troubleshooting_hints() {
+                        ui.label(format!("• {hint}"));
+                    }
+                });
+
+            ui.add_space(5.0);
+
+            ui.horizontal(|ui| {
+                if ui.button("Rescan").clicked() {
+                    message_sender.send(Message::RescanDevices).ok();
+                }
+                if ui
+                    .button("Diagnostics")
+                    .on_hover_text("List all USB devices with DFU capability")
+                    .clicked()
+                {
+                    message_sender.send(Message::RunDiagnostics).ok();
+                }
+            });
+        });
+    });
+}
+
+/// Return a list of likely causes for devices not being found
+fn troubleshooting_hints() -> Vec<&'static str> {
+    let mut hints = vec![
+        "The device is not in DFU mode. Refer to its manual on how to enter \
+        the bootloader, e.g. by holding a button or setting the BOOT0 pin while powering up.",
+        "The cable is charge-only or the device is not powered.",
+    ];
+
+    if cfg!(target_os = "linux") {
+        hints.push(
+            "Missing permissions. A udev rule granting access to the device \
+            is required for non-root users.",
+        );
+    } else if cfg!(target_os = "windows") {
+        hints.push(
+            "No suitable USB driver installed. The device must be bound to the \
+            WinUSB driver, e.g. by using Zadig.",
+        );
+    } else if cfg!(target_os = "macos") {
+        hints.push("The device is claimed by another application.");
+    }
+
+    hints
+}
+
 /// Show box with common device information
-pub fn common_info(ui: &mut egui::Ui, device_info: Option<&dfudev::DeviceInfo>) {
+pub fn common_info(
+    ui: &mut egui::Ui,
+    device_info: Option<&dfudev::DeviceInfo>,
+    message_sender: &std::sync::mpsc::Sender<Message>,
+) {
     ui.group(|ui| {
         ui.set_width(ui.available_width() / 3.0);
         ui.set_height(ui.available_height());
@@ -103,6 +230,21 @@ pub fn common_info(ui: &mut egui::Ui, device_info: Option<&dfudev::DeviceInfo>)
                         ui.label(device_info.serial_number_string.to_owned());
                         ui.end_row();
 
+                        ui.label("USB Port:");
+                        ui.label(device_info.bus_path()).on_hover_text(format!(
+                            "Bus {}, address {}, port path {}",
+                            device_info.bus_number,
+                            device_info.address,
+                            device_info.bus_path()
+                        ));
+                        ui.end_row();
+
+                        if let Some(family) = device_info.family {
+                            ui.label("MCU Family:");
+                            ui.label(family.to_string());
+                            ui.end_row();
+                        }
+
                         ui.label("DFU Version:");
                         let version_info = if device_info.dfu_version == 0x011A {
                             "(DfuSe)"
@@ -114,6 +256,52 @@ pub fn common_info(ui: &mut egui::Ui, device_info: Option<&dfudev::DeviceInfo>)
                             device_info.dfu_version, version_info
                         ));
                         ui.end_row();
+
+                        ui.label("Attributes:");
+                        let attributes = device_info.attribute_names();
+                        ui.label(if attributes.is_empty() {
+                            "none".to_string()
+                        } else {
+                            attributes.join(", ")
+                        })
+                        .on_hover_text(format!(
+                            "bmAttributes: 0x{:02X}\n\
+                            Download: {}\nUpload: {}\n\
+                            Manifestation tolerant: {}\nWill detach: {}",
+                            device_info.dfu_attributes,
+                            yes_no(device_info.can_download),
+                            yes_no(device_info.can_upload),
+                            yes_no(device_info.manifestation_tolerant),
+                            yes_no(device_info.will_detach),
+                        ));
+                        ui.end_row();
+
+                        ui.label("Transfer Size:");
+                        ui.label(format::bytes(device_info.dfu_transfer_size as u64))
+                            .on_hover_text(format!(
+                                "Detach timeout: {} ms",
+                                device_info.dfu_detach_timeout
+                            ));
+                        ui.end_row();
+
+                        if device_info.driver == DriverStatus::Missing {
+                            ui.label("Driver:");
+                            ui.horizontal(|ui| {
+                                ui.colored_label(ui.visuals().error_fg_color, "Not installed");
+                                if ui.small_button("Help").clicked() {
+                                    message_sender
+                                        .send(Message::OpenMessageDialog {
+                                            title: "Driver installation".into(),
+                                            body: driver_instructions(
+                                                device_info.vendor_id,
+                                                device_info.product_id,
+                                            ),
+                                        })
+                                        .ok();
+                                }
+                            });
+                            ui.end_row();
+                        }
                     });
                 });
             }
@@ -126,8 +314,30 @@ pub fn common_info(ui: &mut egui::Ui, device_info: Option<&dfudev::DeviceInfo>)
     });
 }
 
+/// Return the instructions to install the driver for a device, with the
+/// step to find it in the application again
+pub fn driver_instructions(vendor_id: u16, product_id: u16) -> String {
+    format!(
+        "{}\n\nClick Rescan after reconnecting the device.",
+        driver::install_instructions(vendor_id, product_id)
+    )
+}
+
+/// Return a flag as text
+fn yes_no(flag: bool) -> &'static str {
+    if flag {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
 /// Show box with target information
-pub fn memory_info(ui: &mut egui::Ui, device_info: Option<&dfudev::DeviceInfo>) {
+pub fn memory_info(
+    ui: &mut egui::Ui,
+    device_info: Option<&dfudev::DeviceInfo>,
+    dfu_file: Option<&dfufile::DfuFile>,
+) {
     ui.group(|ui| {
         ui.set_width(ui.available_width());
         ui.set_height(ui.available_height());
@@ -141,17 +351,34 @@ pub fn memory_info(ui: &mut egui::Ui, device_info: Option<&dfudev::DeviceInfo>)
 
                     egui::containers::ScrollArea::vertical().show(ui, |ui| {
                         ui.set_width(ui.available_width());
-                        egui::Grid::new("segments_info").show(ui, |ui| {
-                            ui.label("ID");
-                            ui.label("Name");
-                            ui.end_row();
 
-                            for alt_setting in &device_info.alt_settings {
-                                ui.label(format!("{}", alt_setting.0));
-                                ui.label(alt_setting.1.to_owned());
-                                ui.end_row();
-                            }
-                        });
+                        for (alt, name) in &device_info.alt_settings {
+                            let segment = name
+                                .starts_with('@')
+                                .then(|| MemorySegment::from_string_desc(name));
+                            let title = match &segment {
+                                Some(Ok(segment)) => format!(
+                                    "{alt}: {} ({})",
+                                    segment.name.trim(),
+                                    format::size(segment.size())
+                                ),
+                                Some(Err(_)) => format!("{alt}: {name} (unparseable layout)"),
+                                None => format!("{alt}: {name}"),
+                            };
+
+                            egui::CollapsingHeader::new(title)
+                                .id_salt(("segment_info", *alt))
+                                .show(ui, |ui| {
+                                    segment_details(
+                                        ui,
+                                        device_info,
+                                        *alt,
+                                        name,
+                                        segment,
+                                        &file_elements(dfu_file, *alt),
+                                    );
+                                });
+                        }
                     });
                 });
             }
@@ -164,10 +391,209 @@ pub fn memory_info(ui: &mut egui::Ui, device_info: Option<&dfudev::DeviceInfo>)
     });
 }
 
+/// Return address and size of all elements of a file written to an alt setting
+fn file_elements(dfu_file: Option<&dfufile::DfuFile>, alt: u8) -> Vec<(u32, u32)> {
+    match dfu_file.map(|file| &file.content) {
+        Some(dfufile::Content::DfuSe(content)) => content
+            .images
+            .iter()
+            .filter(|image| image.target_prefix.bAlternateSetting == alt)
+            .flat_map(|image| &image.image_elements)
+            .map(|element| (element.dwElementAddress, element.dwElementSize))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Show the raw descriptor of an alt setting, the regions parsed from it
+/// and the parts occupied by the file
+fn segment_details(
+    ui: &mut egui::Ui,
+    device_info: &dfudev::DeviceInfo,
+    alt: u8,
+    name: &str,
+    segment: Option<anyhow::Result<MemorySegment>>,
+    elements: &[(u32, u32)],
+) {
+    // Lets users check if the parser understood their device
+    let overridden = device_info.quirks.memory_map.contains_key(&alt);
+    ui.horizontal(|ui| {
+        ui.label(if overridden {
+            "Descriptor (override):"
+        } else {
+            "Descriptor:"
+        });
+        ui.add(egui::Label::new(egui::RichText::new(name).monospace()).selectable(true));
+    });
+
+    let segment = match segment {
+        Some(Ok(segment)) => segment,
+        Some(Err(error)) => {
+            ui.colored_label(ui.visuals().error_fg_color, error.to_string());
+            return;
+        }
+        None => {
+            ui.label("No DfuSe memory layout.");
+            return;
+        }
+    };
+
+    egui::Grid::new(("segment_regions", alt))
+        .striped(true)
+        .show(ui, |ui| {
+            ui.strong("Address range");
+            ui.strong("Size");
+            ui.strong("Sectors");
+            ui.strong("Access");
+            ui.end_row();
+
+            for region in &segment.regions {
+                ui.monospace(format!(
+                    "0x{:08X}..0x{:08X}",
+                    region.start_address, region.end_address
+                ))
+                .on_hover_text("First and last address");
+                ui.label(format::size(
+                    region.end_address as u64 - region.start_address as u64 + 1,
+                ));
+                ui.label(format!(
+                    "{} × {}",
+                    region.sector_count,
+                    format::size(region.sector_size as u64)
+                ));
+                access_badges(ui, region);
+                ui.end_row();
+            }
+        });
+
+    for error in segment.geometry_errors() {
+        ui.colored_label(ui.visuals().error_fg_color, error);
+    }
+
+    if !elements.is_empty() {
+        occupancy_bar(ui, &segment, elements);
+    }
+}
+
+/// Show the permissions of a region as badges, missing ones dimmed
+fn access_badges(ui: &mut egui::Ui, region: &MemorySegmentRegion) {
+    ui.horizontal(|ui| {
+        ui.spacing_mut().item_spacing.x = 2.0;
+
+        for (granted, letter, permission) in [
+            (region.readable, "R", "readable"),
+            (region.writable, "W", "writable"),
+            (region.erasable, "E", "erasable"),
+        ] {
+            let visuals = ui.visuals();
+            let (fill, color) = if granted {
+                (visuals.selection.bg_fill, visuals.strong_text_color())
+            } else {
+                (visuals.faint_bg_color, visuals.weak_text_color())
+            };
+
+            egui::Frame::none()
+                .fill(fill)
+                .rounding(3.0)
+                .inner_margin(egui::Margin::symmetric(4.0, 0.0))
+                .show(ui, |ui| {
+                    ui.label(egui::RichText::new(letter).monospace().color(color));
+                })
+                .response
+                .on_hover_text(if granted {
+                    format!("Region is {permission}")
+                } else {
+                    format!("Region is not {permission}")
+                });
+        }
+    });
+}
+
+/// Show the address range of a segment as bar with the parts occupied
+/// by the file highlighted
+fn occupancy_bar(ui: &mut egui::Ui, segment: &MemorySegment, elements: &[(u32, u32)]) {
+    let (Some(first), Some(last)) = (segment.regions.first(), segment.regions.last()) else {
+        return;
+    };
+    let start = first.start_address as f64;
+    let span = (last.end_address as f64 - start + 1.0).max(1.0);
+
+    let size = egui::vec2(ui.available_width(), ui.spacing().interact_size.y);
+    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let visuals = ui.visuals();
+    let x = |address: f64| {
+        rect.left() + (((address - start) / span).clamp(0.0, 1.0) as f32) * rect.width()
+    };
+
+    ui.painter()
+        .rect_filled(rect, 0.0, visuals.extreme_bg_color);
+
+    // Region borders
+    for region in segment.regions.iter().skip(1) {
+        let left = x(region.start_address as f64);
+        ui.painter()
+            .vline(left, rect.y_range(), visuals.window_stroke);
+    }
+
+    let mut used = 0u64;
+    let mut outside = false;
+    for &(address, length) in elements {
+        let end = address as f64 + length as f64;
+        outside |= !segment.is_writable(address, length);
+        used += length as u64;
+
+        // Tiny elements are drawn with a minimum width to stay visible
+        let left = x(address as f64);
+        let right = x(end).max(left + 1.0);
+        let color = if segment.is_writable(address, length) {
+            visuals.selection.bg_fill
+        } else {
+            visuals.error_fg_color
+        };
+        ui.painter().rect_filled(
+            egui::Rect::from_x_y_ranges(left..=right, rect.y_range()),
+            0.0,
+            color,
+        );
+    }
+
+    ui.painter().text(
+        rect.center(),
+        egui::Align2::CENTER_CENTER,
+        format!(
+            "File: {} of {}",
+            format::size(used),
+            format::size(segment.size())
+        ),
+        egui::TextStyle::Body.resolve(ui.style()),
+        visuals.text_color(),
+    );
+
+    response.on_hover_ui(|ui| {
+        for &(address, length) in elements {
+            ui.monospace(format!(
+                "0x{address:08X}..0x{:08X} {}",
+                address as u64 + length as u64,
+                format::size(length as u64)
+            ));
+        }
+        if outside {
+            ui.colored_label(
+                ui.visuals().error_fg_color,
+                "Parts of the file are outside the writable regions.",
+            );
+        }
+    });
+}
+
 /// Show update button and additional messages
 pub fn update_controls(
     ui: &mut egui::Ui,
     update_state: &mut DeviceUpdateState,
+    dfu_file_checks: &mut DfuFileChecks,
+    mass_erase: &mut bool,
+    phases: &mut Phases,
+    after_update: &mut AfterUpdate,
     message_sender: &std::sync::mpsc::Sender<Message>,
 ) {
     ui.vertical(|ui| {
@@ -184,6 +610,144 @@ pub fn update_controls(
                     egui::RichText::new(update_state.error.as_ref().unwrap())
                         .color(egui::Color32::RED),
                 ));
+                if !update_state.differences.is_empty() {
+                    let bytes: u64 = update_state
+                        .differences
+                        .iter()
+                        .map(|diff| diff.bytes as u64)
+                        .sum();
+                    ui.label(format!(
+                        "{bytes} bytes in {} sectors differ.",
+                        update_state.differences.len()
+                    ));
+                    if ui.link("Show differences...").clicked() {
+                        message_sender.send(Message::OpenDifferences).ok();
+                    }
+                }
+                ui.add_space(10.0);
+
+                ui.horizontal(|ui| {
+                    let continue_button = ui.add(
+                        egui::widgets::Button::new("Continue")
+                            .fill(ui.style().visuals.selection.bg_fill),
+                    );
+
+                    if continue_button.clicked() {
+                        update_state.error = None;
+                    };
+
+                    if let (Some(resumable), false) =
+                        (&update_state.resumable, update_state.simulate)
+                    {
+                        if ui
+                            .button("Resume from last block")
+                            .on_hover_text(format!("Skip the {resumable}"))
+                            .clicked()
+                        {
+                            message_sender.send(Message::ResumeUpdate).ok();
+                            update_state.error = None;
+                        }
+                    }
+                });
+            });
+        } else if update_state.running {
+            ui.vertical_centered(|ui| {
+                ui.add_space(10.0);
+                match update_state.cancelling {
+                    Some(CancelMode::Abort) => ui.label("Aborting..."),
+                    Some(CancelMode::Stop) => ui.label("Stopping after the current sector..."),
+                    None if update_state.step == Some(DeviceUpdateStep::Backup) => {
+                        ui.label(format!(
+                            "Backup in progress... {:.0}%",
+                            update_state.step_progress(DeviceUpdateStep::Backup) * 100.0
+                        ))
+                    }
+                    None if update_state.simulate => ui.label("Simulation in progress..."),
+                    None => ui.label("Update in progress..."),
+                };
+                ui.add_space(10.0);
+
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(
+                            update_state.cancelling.is_none(),
+                            egui::Button::new("Stop"),
+                        )
+                        .on_hover_text(
+                            "Finish the sector being written and stop, \
+                            the update can be resumed from there",
+                        )
+                        .clicked()
+                    {
+                        message_sender
+                            .send(Message::CancelJob(CancelMode::Stop))
+                            .ok();
+                    }
+                    if ui
+                        .add_enabled(
+                            update_state.cancelling != Some(CancelMode::Abort),
+                            egui::Button::new("Abort"),
+                        )
+                        .on_hover_text(
+                            "Stop immediately, a partly written sector must be erased again",
+                        )
+                        .clicked()
+                    {
+                        message_sender
+                            .send(Message::CancelJob(CancelMode::Abort))
+                            .ok();
+                    }
+                });
+            });
+        } else if update_state.finished {
+            ui.vertical_centered(|ui| {
+                ui.add_space(10.0);
+                let phases = if update_state.read_only {
+                    Phases::VERIFY_ONLY
+                } else {
+                    *phases
+                };
+                let text = if update_state.unverified.is_some() {
+                    "Update finished, but the data written was not verified."
+                } else if update_state.backup_path.is_some() {
+                    "Backup finished successfully."
+                } else if update_state.simulate {
+                    "Simulation finished successfully, the device was not changed."
+                } else if phases.program {
+                    "Update finished successfully."
+                } else if phases.erase {
+                    "Erase finished successfully."
+                } else {
+                    "Device content matches the file."
+                };
+                let color = match update_state.unverified {
+                    Some(_) => egui::Color32::YELLOW,
+                    None => egui::Color32::GREEN,
+                };
+                let mut label =
+                    ui.add(egui::Label::new(egui::RichText::new(text).color(color)));
+                if let Some(reason) = &update_state.unverified {
+                    label = label.on_hover_text(format!("{reason} {}", crate::UNPROTECT_HINT));
+                }
+                if let Some(backup_path) = &update_state.backup_path {
+                    label.on_hover_text(format!("Written to {}", backup_path.display()));
+                }
+                if let Some(checksum) = update_state.checksum {
+                    ui.label(format!("CRC-32: 0x{checksum:08X}"));
+                }
+                if let Some(check) = &update_state.firmware_check {
+                    let color = match check.passed() {
+                        Some(true) => egui::Color32::GREEN,
+                        Some(false) => egui::Color32::YELLOW,
+                        None => ui.visuals().text_color(),
+                    };
+                    ui.colored_label(color, check.summary());
+                } else if update_state.awaiting_firmware {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Waiting for the application to start...");
+                    });
+                }
                 ui.add_space(10.0);
 
                 let continue_button = ui.add(
@@ -192,69 +756,200 @@ pub fn update_controls(
                 );
 
                 if continue_button.clicked() {
-                    update_state.error = None;
+                    *update_state = DeviceUpdateState::default();
                 };
             });
-        } else if update_state.running {
-            ui.centered_and_justified(|ui| {
-                ui.label("Update in progress...");
+        } else if let Some(warning) = update_state.downgrade_warning.clone() {
+            ui.vertical_centered(|ui| {
+                ui.add_space(5.0);
+                ui.add(egui::Label::new(
+                    egui::RichText::new(warning).color(egui::Color32::RED),
+                ));
+                ui.label("Installing older firmware might lose settings or features.");
+                ui.add_space(10.0);
+
+                ui.horizontal(|ui| {
+                    if ui.button("Cancel").clicked() {
+                        update_state.downgrade_warning = None;
+                    }
+
+                    let downgrade_button = ui.add(
+                        egui::widgets::Button::new("Downgrade anyway")
+                            .fill(ui.style().visuals.selection.bg_fill),
+                    );
+
+                    if downgrade_button.clicked() {
+                        log::warn!("Downgrading the firmware confirmed by user.");
+                        update_state.downgrade_warning = None;
+                        update_state.downgrade_confirmed = true;
+                        message_sender.send(Message::StartUpdate).ok();
+                    }
+                });
             });
-        } else if update_state.finished {
+        } else if let Some(warning) = update_state.duplicate_warning.clone() {
+            ui.vertical_centered(|ui| {
+                ui.add_space(5.0);
+                ui.add(egui::Label::new(
+                    egui::RichText::new(warning).color(egui::Color32::YELLOW),
+                ));
+                ui.add_space(10.0);
+
+                ui.horizontal(|ui| {
+                    if ui.button("Cancel").clicked() {
+                        update_state.duplicate_warning = None;
+                    }
+
+                    let flash_button = ui.add(
+                        egui::widgets::Button::new("Flash anyway")
+                            .fill(ui.style().visuals.selection.bg_fill),
+                    );
+
+                    if flash_button.clicked() {
+                        log::warn!("Flashing the same file again confirmed by user.");
+                        update_state.duplicate_warning = None;
+                        update_state.duplicate_confirmed = true;
+                        message_sender.send(Message::StartUpdate).ok();
+                    }
+                });
+            });
+        } else if update_state.device_ready && update_state.file_ready && update_state.read_only {
             ui.vertical_centered(|ui| {
                 ui.add_space(10.0);
                 ui.add(egui::Label::new(
-                    egui::RichText::new("Update finished successfully.")
-                        .color(egui::Color32::GREEN),
+                    egui::RichText::new("Inspector mode, updates are disabled.")
+                        .color(egui::Color32::YELLOW),
                 ));
                 ui.add_space(10.0);
+                ui.label("Backups can still be made via the File menu.");
+                ui.add_space(5.0);
 
-                let continue_button = ui.add(
-                    egui::widgets::Button::new("Continue")
+                let verify_button = ui.add(
+                    egui::widgets::Button::new("Verify against file")
                         .fill(ui.style().visuals.selection.bg_fill),
                 );
 
-                if continue_button.clicked() {
-                    *update_state = DeviceUpdateState::default();
-                };
+                if verify_button.clicked() {
+                    message_sender.send(Message::StartUpdate).ok();
+                }
+            });
+        } else if update_state.device_ready
+            && update_state.file_ready
+            && update_state.login_required
+        {
+            ui.vertical_centered(|ui| {
+                ui.add_space(10.0);
+                ui.add(egui::Label::new(
+                    egui::RichText::new("Operator login required.").color(egui::Color32::YELLOW),
+                ));
+                ui.add_space(10.0);
+
+                if ui.button("Log in...").clicked() {
+                    message_sender.send(Message::OpenLoginWindow).ok();
+                }
             });
         } else if update_state.device_ready && update_state.file_ready {
             if update_state.preflight_checks_passed {
                 ui.vertical_centered(|ui| {
                     ui.add_space(5.0);
-                    ui.add(egui::Label::new(
-                        egui::RichText::new("Warning! All data on device will be erased!")
-                            .color(egui::Color32::YELLOW),
-                    ));
-                    ui.add_space(10.0);
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut phases.erase, "Erase");
+                        ui.checkbox(&mut phases.program, "Program");
+                        ui.add_enabled(
+                            dfu_file_checks.upload_supported,
+                            egui::Checkbox::new(&mut phases.verify, "Verify"),
+                        )
+                        .on_disabled_hover_text("The device does not support reading its memory");
 
-                    ui.checkbox(&mut update_state.confirmed, "Confirm to proceed.");
+                        let mut reboot = *after_update != AfterUpdate::Stay;
+                        if ui
+                            .checkbox(&mut reboot, "Reboot after")
+                            .on_hover_text(
+                                "Leave DFU mode after programming.\n\
+                                The action can be changed via Options → After update.",
+                            )
+                            .changed()
+                        {
+                            *after_update = if reboot {
+                                AfterUpdate::Leave
+                            } else {
+                                AfterUpdate::Stay
+                            };
+                        }
+                    });
+                    ui.add_space(5.0);
 
-                    ui.add_space(10.0);
+                    // Blocking problems are explained by the warnings below
+                    if phases.modifies_device() && dfu_file_checks.geometry_report.is_some() {
+                        // Erasing with a nonsense geometry could hit wrong sectors
+                        update_state.confirmed = false;
+                    } else if phases.erase
+                        && !*mass_erase
+                        && !dfu_file_checks.erase_problems.is_empty()
+                    {
+                        ui.checkbox(mass_erase, "Full chip erase");
+                        update_state.confirmed = false;
+                    } else if update_state.simulate {
+                        ui.label("Simulation, the device is not changed.");
+                        update_state.confirmed = true;
+                    } else if phases.modifies_device() {
+                        ui.checkbox(mass_erase, "Full chip erase")
+                            .on_hover_text(
+                                "Erase the whole flash at once instead of sector by sector.\n\
+                                Much faster for large parts, but also erases data outside the file.",
+                            );
+                        ui.checkbox(&mut update_state.confirmed, "Confirm to proceed.");
+                    } else {
+                        ui.label("The device content is compared with the file.");
+                        update_state.confirmed = true;
+                    }
+
+                    if phases.is_empty() {
+                        update_state.confirmed = false;
+                    }
+
+                    if !update_state.warnings.is_empty() {
+                        ui.add_space(5.0);
+                        warnings::list(ui, &update_state.warnings, message_sender);
+                    }
+
+                    ui.add_space(5.0);
 
-                    ui.scope(|ui| {
+                    ui.horizontal(|ui| {
                         if !update_state.confirmed {
                             ui.disable();
                         }
                         let update_button = ui.add(
-                            egui::widgets::Button::new("Start update")
-                                .fill(ui.style().visuals.selection.bg_fill),
+                            egui::widgets::Button::new(if update_state.simulate {
+                                "Simulate"
+                            } else {
+                                "Start"
+                            })
+                            .fill(ui.style().visuals.selection.bg_fill),
                         );
 
                         if update_button.clicked() {
                             message_sender.send(Message::StartUpdate).ok();
                             update_state.confirmed = false;
                         };
+
+                        if let (Some(resumable), false) =
+                            (&update_state.resumable, update_state.simulate)
+                        {
+                            let resume_button = ui
+                                .button("Resume")
+                                .on_hover_text(format!(
+                                    "An earlier update was interrupted, skip the {resumable}"
+                                ));
+
+                            if resume_button.clicked() {
+                                message_sender.send(Message::ResumeUpdate).ok();
+                                update_state.confirmed = false;
+                            }
+                        }
                     });
                 });
             } else {
-                ui.centered_and_justified(|ui| {
-                    ui.add(egui::Label::new(
-                        egui::RichText::new(
-                            "Some requirements are not met.\nPlease check your settings.",
-                        )
-                        .color(egui::Color32::RED),
-                    ));
-                });
+                preflight::checklist(ui, dfu_file_checks, update_state.strict, message_sender);
             }
         } else {
             ui.centered_and_justified(|ui| {
@@ -281,45 +976,129 @@ pub fn update_progress(ui: &mut egui::Ui, update_state: &DeviceUpdateState) {
                 .num_columns(2)
                 .spacing((20.0, 10.0))
                 .show(ui, |ui| {
-                    ui.label("Erase");
-                    ui.add(
-                        egui::ProgressBar::new(update_state.erase_progress)
-                            .show_percentage()
-                            .animate(
-                                update_state
-                                    .step
-                                    .as_ref()
-                                    .map_or(false, |step| *step == DeviceUpdateStep::Erase),
-                            ),
-                    );
-                    ui.end_row();
+                    // Before the first start, the steps of a full update are shown
+                    let phases: Vec<(DeviceUpdateStep, f32)> = if update_state.phases.is_empty() {
+                        [
+                            DeviceUpdateStep::Erase,
+                            DeviceUpdateStep::Program,
+                            DeviceUpdateStep::Verify,
+                        ]
+                        .into_iter()
+                        .map(|step| (step, 0.0))
+                        .collect()
+                    } else {
+                        update_state
+                            .phases
+                            .iter()
+                            .map(|phase| (phase.step, phase.progress))
+                            .collect()
+                    };
 
-                    ui.label("Program");
-                    ui.add(
-                        egui::ProgressBar::new(update_state.program_progress)
-                            .show_percentage()
-                            .animate(
-                                update_state
-                                    .step
-                                    .as_ref()
-                                    .map_or(false, |step| *step == DeviceUpdateStep::Program),
-                            ),
-                    );
-                    ui.end_row();
+                    for (step, progress) in phases {
+                        ui.label(format!("{step:?}"));
+                        // Sectors are shown individually while they are erased
+                        if step == DeviceUpdateStep::Erase
+                            && update_state.step == Some(step)
+                            && !update_state.sectors.is_empty()
+                        {
+                            sector_strip(ui, update_state);
+                        } else {
+                            ui.add(
+                                egui::ProgressBar::new(progress)
+                                    .show_percentage()
+                                    .animate(update_state.step == Some(step)),
+                            );
+                        }
+                        ui.end_row();
+                    }
 
-                    ui.label("Verify");
+                    for (step, reason) in &update_state.skipped_steps {
+                        ui.label(format!("{step:?}"));
+                        ui.weak("Skipped").on_hover_text(reason);
+                        ui.end_row();
+                    }
+
+                    ui.label("Total");
                     ui.add(
-                        egui::ProgressBar::new(update_state.verify_progress)
-                            .show_percentage()
-                            .animate(
-                                update_state
-                                    .step
-                                    .as_ref()
-                                    .map_or(false, |step| *step == DeviceUpdateStep::Verify),
-                            ),
+                        egui::ProgressBar::new(update_state.overall_progress()).show_percentage(),
                     );
                     ui.end_row();
-                })
+                });
+
+            ui.add_space(5.0);
+            ui.horizontal(|ui| {
+                let elapsed = update_state.elapsed().map_or("-".into(), format::duration);
+                ui.label(format!("Elapsed: {elapsed}"));
+                ui.separator();
+                let remaining = update_state
+                    .remaining()
+                    .map_or("-".into(), format::duration);
+                ui.label(format!("Remaining: {remaining}"));
+                ui.separator();
+                let speed = update_state
+                    .transfer_speed()
+                    .map_or("-".into(), format::speed);
+                ui.label(format!("Speed: {speed}"));
+                if let (true, Some(address)) = (update_state.running, update_state.address) {
+                    ui.separator();
+                    ui.monospace(format!("0x{address:08X}"))
+                        .on_hover_text("Address of the last block transferred");
+                }
+            });
         });
     });
 }
+
+/// Show the sectors of the target being erased as strip of cells
+/// colored by their state
+fn sector_strip(ui: &mut egui::Ui, update_state: &DeviceUpdateState) {
+    let sectors = &update_state.sectors;
+    let size = egui::vec2(ui.available_width(), ui.spacing().interact_size.y);
+    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let visuals = ui.visuals();
+    let cell_width = rect.width() / sectors.len() as f32;
+
+    // Gaps are left out if there are too many sectors to tell them apart
+    let gap = if cell_width >= 4.0 { 1.0 } else { 0.0 };
+
+    for (index, sector) in sectors.iter().enumerate() {
+        let left = rect.left() + index as f32 * cell_width;
+        let cell = egui::Rect::from_x_y_ranges(left..=left + cell_width - gap, rect.y_range());
+        let color = match sector.state {
+            SectorState::Pending => visuals.extreme_bg_color,
+            SectorState::Erasing => visuals.warn_fg_color,
+            SectorState::Done => visuals.selection.bg_fill,
+            SectorState::Failed => visuals.error_fg_color,
+        };
+        ui.painter().rect_filled(cell, 0.0, color);
+    }
+
+    let done = sectors
+        .iter()
+        .filter(|sector| sector.state == SectorState::Done)
+        .count();
+    ui.painter().text(
+        rect.center(),
+        egui::Align2::CENTER_CENTER,
+        format!("{done}/{} sectors", sectors.len()),
+        egui::TextStyle::Body.resolve(ui.style()),
+        visuals.text_color(),
+    );
+
+    response.on_hover_ui_at_pointer(|ui| {
+        ui.label(format!("Target: {}", update_state.erase_target));
+        let hovered = ui
+            .ctx()
+            .pointer_hover_pos()
+            .map(|pos| ((pos.x - rect.left()) / cell_width) as usize)
+            .and_then(|index| sectors.get(index));
+        if let Some(sector) = hovered {
+            ui.label(format!(
+                "Sector at 0x{:08X}, {}: {:?}",
+                sector.address,
+                format::size(sector.size as u64),
+                sector.state
+            ));
+        }
+    });
+}
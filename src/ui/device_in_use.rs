@@ -0,0 +1,77 @@
+//! Window shown when another application holds the device
+//!
+//! The action that failed can be retried after the other application
+//! released the device, e.g. a serial terminal, an IDE or a vendor tool.
+
+use eframe::egui;
+
+use crate::Message;
+
+/// State of the device in use window
+#[derive(Default)]
+pub struct DeviceInUseForm {
+    /// Flag if the window is open
+    pub open: bool,
+
+    /// Name and process id of the application holding the device, if known
+    owner: Option<String>,
+
+    /// Message sent again when retrying
+    retry: Option<Message>,
+}
+
+impl DeviceInUseForm {
+    /// Open the window with the owner of the device and the action to retry
+    pub fn show(&mut self, owner: Option<String>, retry: Message) {
+        self.owner = owner;
+        self.retry = Some(retry);
+        self.open = true;
+    }
+}
+
+/// Show the device in use window
+pub fn window(
+    ctx: &egui::Context,
+    form: &mut DeviceInUseForm,
+    message_sender: &std::sync::mpsc::Sender<Message>,
+) {
+    let mut open = form.open;
+    let mut close = false;
+
+    egui::Window::new("Device in Use")
+        .open(&mut open)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.set_max_width(400.0);
+
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                "Device is in use by another application.",
+            );
+            ui.add_space(5.0);
+            match &form.owner {
+                Some(owner) => ui.label(format!("It is held by {owner}.")),
+                None => ui.label("The application holding it could not be determined."),
+            };
+            ui.label(
+                "Close the other application or disconnect it from the device, \
+                then retry.",
+            );
+            ui.add_space(10.0);
+
+            ui.horizontal(|ui| {
+                if ui.button("Retry").clicked() {
+                    if let Some(retry) = form.retry.take() {
+                        message_sender.send(retry).ok();
+                    }
+                    close = true;
+                }
+                if ui.button("Cancel").clicked() {
+                    close = true;
+                }
+            });
+        });
+
+    form.open = open && !close;
+}
@@ -0,0 +1,57 @@
+//! Window listing the sectors that differ between the file and the device
+//!
+//! Verification reads the whole file back instead of stopping at the first
+//! mismatch, so the extent of the damage is visible, e.g. a single sector that
+//! was not erased or a target that was not written at all.
+
+use eframe::egui;
+
+use crate::update::Difference;
+
+/// Show the differences window
+pub fn window(ctx: &egui::Context, open: &mut bool, differences: &[Difference]) {
+    egui::Window::new("Differences")
+        .open(open)
+        .collapsible(false)
+        .default_width(500.0)
+        .show(ctx, |ui| {
+            if differences.is_empty() {
+                ui.label("No differences found by the last verification.");
+                return;
+            }
+
+            let bytes: u64 = differences.iter().map(|diff| diff.bytes as u64).sum();
+            ui.label(format!(
+                "{bytes} bytes in {} sectors differ from the file.",
+                differences.len()
+            ));
+            ui.separator();
+
+            egui::ScrollArea::both().max_height(300.0).show(ui, |ui| {
+                egui::Grid::new("differences")
+                    .num_columns(5)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong("Alt");
+                        ui.strong("Sector");
+                        ui.strong("Bytes");
+                        ui.strong("First");
+                        ui.strong("Last");
+                        ui.end_row();
+
+                        for diff in differences {
+                            ui.label(diff.alt_setting.to_string());
+                            ui.monospace(diff.sector.to_string());
+                            ui.label(format!(
+                                "{} of {}",
+                                diff.bytes,
+                                diff.sector.end - diff.sector.start
+                            ));
+                            ui.monospace(format!("0x{:08X}", diff.first_address));
+                            ui.monospace(format!("0x{:08X}", diff.last_address));
+                            ui.end_row();
+                        }
+                    });
+            });
+        });
+}
@@ -0,0 +1,56 @@
+//! Window showing the device events of the current session
+
+use eframe::egui;
+
+use crate::events::{EventKind, EventLog};
+
+/// Show the event window
+pub fn window(ctx: &egui::Context, open: &mut bool, event_log: &mut EventLog) {
+    egui::Window::new("Device Events")
+        .open(open)
+        .collapsible(false)
+        .default_width(600.0)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!("{} events, times in UTC", event_log.len()));
+                if ui
+                    .add_enabled(!event_log.is_empty(), egui::Button::new("Clear"))
+                    .clicked()
+                {
+                    event_log.clear();
+                }
+            });
+            ui.separator();
+
+            if event_log.is_empty() {
+                ui.label("No device events recorded in this session.");
+                return;
+            }
+
+            egui::ScrollArea::both().max_height(300.0).show(ui, |ui| {
+                egui::Grid::new("device_events")
+                    .num_columns(4)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong("Time");
+                        ui.strong("Event");
+                        ui.strong("Device");
+                        ui.strong("Details");
+                        ui.end_row();
+
+                        for event in event_log.events().rev() {
+                            ui.monospace(event.time_of_day());
+                            let color = match event.kind {
+                                EventKind::Error => ui.visuals().error_fg_color,
+                                EventKind::Disappeared => ui.visuals().warn_fg_color,
+                                _ => ui.visuals().text_color(),
+                            };
+                            ui.colored_label(color, event.kind.to_string());
+                            ui.label(&event.device);
+                            ui.label(&event.details);
+                            ui.end_row();
+                        }
+                    });
+            });
+        });
+}
@@ -2,16 +2,15 @@
 
 use eframe::egui;
 
-use crate::{dfudev, DfuFileChecks, Message};
+use crate::convert::{ConvertedFile, SourceFormat};
+use crate::{dfudev, format, integrity, update, DfuFileChecks, Message};
 
 /// Show box with file selection
 pub fn selection(
     ui: &mut egui::Ui,
-    selected_file: &Option<dfufile::DfuFile>,
+    file_path: Option<&std::path::Path>,
     message_sender: &std::sync::mpsc::Sender<Message>,
 ) {
-    let file_path = selected_file.as_ref().map(|file| &file.path);
-
     ui.horizontal(|ui| {
         ui.vertical(|ui| {
             ui.add_space(6.0);
@@ -51,116 +50,130 @@ pub fn selection(
 pub fn common_info(
     ui: &mut egui::Ui,
     dfu_file: &Option<dfufile::DfuFile>,
-    dfu_file_checks: &mut DfuFileChecks,
+    dfu_file_checks: &DfuFileChecks,
     device_active: bool,
 ) {
     ui.group(|ui| {
         ui.set_width(ui.available_width() / 12.0 * 4.0);
         ui.set_height(ui.available_height());
 
-        let mut approve_vendor_id = false;
-        let mut approve_product_id = false;
-
         match dfu_file {
             Some(dfu_file) => {
                 ui.vertical(|ui| {
                     ui.heading("Metadata");
                     ui.add_space(5.0);
-                    egui::Grid::new("file_info").show(ui, |ui| {
-                        let vendor_id = dfu_file.suffix.idVendor;
-                        let product_id = dfu_file.suffix.idProduct;
+                    // Scroll if the hashes don't fit below the metadata
+                    egui::ScrollArea::vertical().show(ui, |ui| {
+                        egui::Grid::new("file_info").show(ui, |ui| {
+                            let vendor_id = dfu_file.suffix.idVendor;
+                            let product_id = dfu_file.suffix.idProduct;
 
-                        ui.label("Format:");
-                        let text_color = if device_active {
-                            if dfu_file_checks.dfu_version_valid {
-                                egui::Color32::GREEN
+                            ui.label("Format:");
+                            let text_color = if device_active {
+                                if dfu_file_checks.dfu_version_valid {
+                                    egui::Color32::GREEN
+                                } else {
+                                    egui::Color32::RED
+                                }
                             } else {
-                                egui::Color32::RED
+                                egui::Color32::LIGHT_GRAY
+                            };
+                            let format_label = ui.add(egui::Label::new(
+                                egui::RichText::new(format!("{}", dfu_file.content))
+                                    .color(text_color),
+                            ));
+                            if device_active && !dfu_file_checks.dfu_version_valid {
+                                format_label
+                                    .on_hover_text("File format is not appropriate for the device");
                             }
-                        } else {
-                            egui::Color32::LIGHT_GRAY
-                        };
-                        let format_label = ui.add(egui::Label::new(
-                            egui::RichText::new(format!("{}", dfu_file.content)).color(text_color),
-                        ));
-                        if device_active && !dfu_file_checks.dfu_version_valid {
-                            format_label
-                                .on_hover_text("File format is not appropriate for the device");
-                        }
-                        ui.end_row();
+                            ui.end_row();
 
-                        ui.label("Vendor ID:");
-                        let text_color = if device_active {
-                            if dfu_file_checks.vendor_id_accepted {
-                                egui::Color32::GREEN
+                            ui.label("Vendor ID:");
+                            let text_color = if device_active {
+                                if dfu_file_checks.vendor_id_accepted {
+                                    egui::Color32::GREEN
+                                } else {
+                                    egui::Color32::RED
+                                }
                             } else {
-                                egui::Color32::RED
+                                egui::Color32::LIGHT_GRAY
+                            };
+                            let vendor_id_label = ui.add(egui::Label::new(
+                                egui::RichText::new(format!("0x{vendor_id:04X}")).color(text_color),
+                            ));
+                            if device_active && !dfu_file_checks.vendor_id_accepted {
+                                vendor_id_label.on_hover_text(
+                                    "Vendor id does not match the one of the device",
+                                );
                             }
-                        } else {
-                            egui::Color32::LIGHT_GRAY
-                        };
-                        let vendor_id_label = ui.add(egui::Label::new(
-                            egui::RichText::new(format!("0x{vendor_id:04X}")).color(text_color),
-                        ));
-                        if device_active && !dfu_file_checks.vendor_id_accepted {
-                            vendor_id_label
-                                .on_hover_text("Vendor id does not match the one of the device");
-                            if ui
-                                .button("Approve")
-                                .on_hover_text("Accept vendor id for this device")
-                                .clicked()
-                            {
-                                approve_vendor_id = true;
+                            ui.end_row();
+
+                            ui.label("Product ID:");
+                            let text_color = if device_active {
+                                if dfu_file_checks.product_id_accepted {
+                                    egui::Color32::GREEN
+                                } else {
+                                    egui::Color32::RED
+                                }
+                            } else {
+                                egui::Color32::LIGHT_GRAY
+                            };
+                            let product_id_label = ui.add(egui::Label::new(
+                                egui::RichText::new(format!("0x{product_id:04X}"))
+                                    .color(text_color),
+                            ));
+                            if device_active && !dfu_file_checks.product_id_accepted {
+                                product_id_label.on_hover_text(
+                                    "Product id does not match the one of the device",
+                                );
                             }
-                        }
-                        ui.end_row();
+                            ui.end_row();
+
+                            ui.label("Version:");
+                            ui.label(format!("0x{:04X}", dfu_file.suffix.bcdDevice));
+                            ui.end_row();
 
-                        ui.label("Product ID:");
-                        let text_color = if device_active {
-                            if dfu_file_checks.product_id_accepted {
+                            ui.label("CRC:");
+                            let text_color = if dfu_file_checks.crc_valid {
                                 egui::Color32::GREEN
                             } else {
                                 egui::Color32::RED
+                            };
+                            let crc_label = ui.add(egui::Label::new(
+                                egui::RichText::new(format!("0x{:08X}", dfu_file.suffix.dwCRC))
+                                    .color(text_color),
+                            ));
+                            if !dfu_file_checks.crc_valid {
+                                crc_label.on_hover_text(
+                                    "Calculated CRC does not match the value stored in the file",
+                                );
                             }
-                        } else {
-                            egui::Color32::LIGHT_GRAY
-                        };
-                        let product_id_label = ui.add(egui::Label::new(
-                            egui::RichText::new(format!("0x{product_id:04X}")).color(text_color),
-                        ));
-                        if device_active && !dfu_file_checks.product_id_accepted {
-                            product_id_label
-                                .on_hover_text("Product id does not match the one of the device");
-                            if ui
-                                .button("Approve")
-                                .on_hover_text("Accept product id for this device")
-                                .clicked()
-                            {
-                                approve_product_id = true;
+                            ui.end_row();
+
+                            if let Some(hashes) = &dfu_file_checks.hashes {
+                                ui.label("SHA-256:");
+                                copyable(
+                                    ui,
+                                    &hashes.file.sha256,
+                                    format!("{}…", &hashes.file.sha256[..16]),
+                                );
+                                ui.end_row();
+
+                                ui.label("CRC-32:");
+                                let crc32 = format!("{:08x}", hashes.file.crc32);
+                                copyable(ui, &crc32, format!("0x{crc32}"));
+                                ui.end_row();
+
+                                ui.label("Payload:");
+                                ui.menu_button(
+                                    format!("{} element(s)", hashes.elements.len()),
+                                    |ui| {
+                                        element_hashes(ui, &hashes.elements);
+                                    },
+                                );
+                                ui.end_row();
                             }
-                        }
-                        ui.end_row();
-
-                        ui.label("Version:");
-                        ui.label(format!("0x{:04X}", dfu_file.suffix.bcdDevice));
-                        ui.end_row();
-
-                        ui.label("CRC:");
-                        let text_color = if dfu_file_checks.crc_valid {
-                            egui::Color32::GREEN
-                        } else {
-                            egui::Color32::RED
-                        };
-                        let crc_label = ui.add(egui::Label::new(
-                            egui::RichText::new(format!("0x{:08X}", dfu_file.suffix.dwCRC))
-                                .color(text_color),
-                        ));
-                        if !dfu_file_checks.crc_valid {
-                            crc_label.on_hover_text(
-                                "Calculated CRC does not match the value stored in the file",
-                            );
-                        }
-                        ui.end_row();
+                        });
                     });
                 });
             }
@@ -170,27 +183,108 @@ pub fn common_info(
                 });
             }
         }
+    });
+}
 
-        if approve_vendor_id {
-            dfu_file_checks.vendor_id_accepted = true;
-        }
+/// Show a grid with the hashes of the image elements
+fn element_hashes(ui: &mut egui::Ui, elements: &[(String, integrity::Digest)]) {
+    egui::Grid::new("element_hashes")
+        .striped(true)
+        .show(ui, |ui| {
+            ui.strong("Element");
+            ui.strong("SHA-256");
+            ui.strong("CRC-32");
+            ui.end_row();
 
-        if approve_product_id {
-            dfu_file_checks.product_id_accepted = true;
-        }
-    });
+            for (name, digest) in elements {
+                ui.label(name);
+                copyable(ui, &digest.sha256, digest.sha256.clone());
+                let crc32 = format!("{:08x}", digest.crc32);
+                copyable(ui, &crc32, format!("0x{crc32}"));
+                ui.end_row();
+            }
+        });
+}
+
+/// Show a monospace value that is copied to the clipboard when clicked
+fn copyable(ui: &mut egui::Ui, value: &str, text: String) {
+    let label = ui
+        .add(egui::Label::new(egui::RichText::new(text).monospace()).sense(egui::Sense::click()))
+        .on_hover_text(format!("{value}\nClick to copy"));
+
+    if label.clicked() {
+        ui.ctx().copy_text(value.to_string());
+    }
+}
+
+/// Show a selection of the device alt setting an image is written to,
+/// for images whose alt setting doesn't exist on the device
+fn target_remap(
+    ui: &mut egui::Ui,
+    image_index: usize,
+    image: &dfufile::dfuse::Image,
+    alt_setting: u8,
+    device_info: &dfudev::DeviceInfo,
+    message_sender: &std::sync::mpsc::Sender<Message>,
+) {
+    let file_alt_setting = image.target_prefix.bAlternateSetting;
+    let mut selected = alt_setting;
+
+    let (selected_text, color) = match device_info
+        .alt_settings
+        .iter()
+        .find(|alt| alt.0 == alt_setting)
+    {
+        Some(alt) => (format!("{}: {}", alt.0, alt.1), egui::Color32::YELLOW),
+        None => ("Not found".to_string(), egui::Color32::RED),
+    };
+
+    egui::ComboBox::from_id_salt(("target_remap", image_index))
+        .selected_text(egui::RichText::new(selected_text).color(color))
+        .width(150.0)
+        .truncate()
+        .show_ui(ui, |ui| {
+            for alt in &device_info.alt_settings {
+                ui.selectable_value(&mut selected, alt.0, format!("{}: {}", alt.0, alt.1));
+            }
+        })
+        .response
+        .on_hover_text(format!(
+            "Alt setting {file_alt_setting} of the file is written to the selected \
+            alt setting of the device"
+        ));
+
+    if selected != alt_setting {
+        message_sender
+            .send(Message::RemapTarget {
+                image: image_index,
+                alt_setting: (selected != file_alt_setting).then_some(selected),
+            })
+            .ok();
+    }
 }
 
 /// Show box with file content information
 pub fn content_info(
     ui: &mut egui::Ui,
     dfu_file: &Option<dfufile::DfuFile>,
+    converted_file: Option<&mut ConvertedFile>,
     device_info: Option<&dfudev::DeviceInfo>,
+    dfu_file_checks: &DfuFileChecks,
+    targets: &mut update::Targets,
+    message_sender: &std::sync::mpsc::Sender<Message>,
 ) {
     ui.group(|ui| {
         ui.set_width(ui.available_width());
         ui.set_height(ui.available_height());
 
+        if let Some(converted_file) = converted_file {
+            if converted_file.format == SourceFormat::Binary {
+                raw_binary_target(ui, converted_file, device_info, message_sender);
+                ui.separator();
+            }
+        }
+
         match dfu_file {
             Some(dfu_file) => match &dfu_file.content {
                 dfufile::Content::Plain => {
@@ -199,12 +293,20 @@ pub fn content_info(
                     });
                 }
                 dfufile::Content::DfuSe(content) => {
+                    let images = update::ordered_images(content, &targets.order);
+                    let reorderable = images.len() > 1;
+                    let mut moved = None;
+
                     ui.vertical(|ui| {
                         ui.heading("Images");
                         ui.add_space(5.0);
                         egui::Grid::new("file_content_info")
-                            .num_columns(5)
+                            .num_columns(7)
                             .show(ui, |ui| {
+                                if reorderable {
+                                    ui.label("Order");
+                                    ui.label("Write");
+                                }
                                 ui.label("ID");
                                 ui.label("Name");
                                 ui.label("Size");
@@ -214,19 +316,74 @@ pub fn content_info(
                                 }
                                 ui.end_row();
 
-                                for image in &content.images {
+                                for (index, &(image_index, image)) in images.iter().enumerate() {
+                                    if reorderable {
+                                        ui.horizontal(|ui| {
+                                            if ui
+                                                .add_enabled(
+                                                    index > 0,
+                                                    egui::Button::new("⏶").small(),
+                                                )
+                                                .on_hover_text("Process earlier")
+                                                .clicked()
+                                            {
+                                                moved = Some((index, index - 1));
+                                            }
+                                            if ui
+                                                .add_enabled(
+                                                    index + 1 < images.len(),
+                                                    egui::Button::new("⏷").small(),
+                                                )
+                                                .on_hover_text("Process later")
+                                                .clicked()
+                                            {
+                                                moved = Some((index, index + 1));
+                                            }
+                                        });
+
+                                        let mut write = targets.is_selected(image_index);
+                                        if ui
+                                            .checkbox(&mut write, "")
+                                            .on_hover_text(
+                                                "Uncheck to leave the memory of this image \
+                                                untouched when erasing, programming and verifying",
+                                            )
+                                            .changed()
+                                        {
+                                            if write {
+                                                targets.skipped.retain(|&i| i != image_index);
+                                            } else {
+                                                targets.skipped.push(image_index);
+                                            }
+                                        }
+                                    }
                                     ui.label(format!("{}", image.target_prefix.bAlternateSetting));
                                     ui.label(match image.target_prefix.bTargetNamed {
                                         0 => "(unnamed)".to_string(),
                                         _ => image.target_prefix.szTargetName.to_string(),
                                     });
-                                    ui.label(format!("{}", image.target_prefix.dwTargetSize));
+                                    let target_size = image.target_prefix.dwTargetSize as u64;
+                                    ui.label(format::size(target_size))
+                                        .on_hover_text(format::bytes(target_size));
                                     ui.label(format!("{}", image.target_prefix.dwNbElements));
                                     if let Some(device_info) = device_info {
-                                        let target = device_info.alt_settings.iter().find(|&alt| {
-                                            alt.0 == image.target_prefix.bAlternateSetting
-                                        });
-                                        if let Some(target) = target {
+                                        let alt_setting = targets.alt_setting(image_index, image);
+                                        let target = device_info
+                                            .alt_settings
+                                            .iter()
+                                            .find(|&alt| alt.0 == alt_setting);
+                                        if target.is_none()
+                                            || targets.remapped.contains_key(&image_index)
+                                        {
+                                            target_remap(
+                                                ui,
+                                                image_index,
+                                                image,
+                                                alt_setting,
+                                                device_info,
+                                                message_sender,
+                                            );
+                                        } else if let Some(target) = target {
                                             ui.add(
                                                 egui::Label::new(
                                                     egui::RichText::new(&target.1)
@@ -244,7 +401,49 @@ pub fn content_info(
                                     ui.end_row();
                                 }
                             });
+
+                        // Checks are only valid while a device is selected
+                        if device_info.is_some() {
+                            let error_color = ui.visuals().error_fg_color;
+                            let warn_color = ui.visuals().warn_fg_color;
+                            for problem in &dfu_file_checks.memory_problems {
+                                ui.colored_label(error_color, problem);
+                            }
+                            for problem in &dfu_file_checks.erase_problems {
+                                ui.colored_label(
+                                    warn_color,
+                                    format!("{problem}, use full chip erase"),
+                                );
+                            }
+                        }
+
+                        if reorderable {
+                            ui.checkbox(
+                                &mut targets.reboot_between,
+                                "Reset device between targets",
+                            )
+                            .on_hover_text(
+                                "Run all steps for one target at a time \
+                                    and reset the device before the next one",
+                            );
+                        }
                     });
+
+                    if let Some((from, to)) = moved {
+                        let mut alts: Vec<u8> = images
+                            .iter()
+                            .map(|(_, image)| image.target_prefix.bAlternateSetting)
+                            .collect();
+                        alts.swap(from, to);
+
+                        // Several images can share an alt setting, the first one counts
+                        targets.order.clear();
+                        for alt in alts {
+                            if !targets.order.contains(&alt) {
+                                targets.order.push(alt);
+                            }
+                        }
+                    }
                 }
             },
             None => {
@@ -255,3 +454,75 @@ pub fn content_info(
         }
     });
 }
+
+/// Show the target settings of a raw binary
+fn raw_binary_target(
+    ui: &mut egui::Ui,
+    raw_binary: &mut ConvertedFile,
+    device_info: Option<&dfudev::DeviceInfo>,
+    message_sender: &std::sync::mpsc::Sender<Message>,
+) {
+    let mut alt_setting = raw_binary.alt_setting;
+
+    ui.horizontal(|ui| {
+        ui.label("Target:");
+        match device_info {
+            Some(device_info) if !device_info.alt_settings.is_empty() => {
+                let selected_text = device_info
+                    .alt_settings
+                    .iter()
+                    .find(|alt| alt.0 == alt_setting)
+                    .map_or(format!("{alt_setting}"), |alt| {
+                        format!("{}: {}", alt.0, alt.1)
+                    });
+                egui::ComboBox::from_id_salt("raw_binary_alt_setting")
+                    .selected_text(selected_text)
+                    .width(150.0)
+                    .truncate()
+                    .show_ui(ui, |ui| {
+                        for alt in &device_info.alt_settings {
+                            ui.selectable_value(
+                                &mut alt_setting,
+                                alt.0,
+                                format!("{}: {}", alt.0, alt.1),
+                            );
+                        }
+                    });
+            }
+            _ => {
+                ui.add(egui::DragValue::new(&mut alt_setting));
+            }
+        }
+
+        ui.label("Address: 0x");
+        let address_edit = ui.add(
+            egui::TextEdit::singleline(&mut raw_binary.address_text)
+                .desired_width(70.0)
+                .char_limit(8),
+        );
+
+        if address_edit.lost_focus() {
+            let address_text = raw_binary.address_text.trim().trim_start_matches("0x");
+            match u32::from_str_radix(address_text, 16) {
+                Ok(address) if address != raw_binary.address => {
+                    message_sender
+                        .send(Message::SetRawBinaryTarget {
+                            alt_setting,
+                            address,
+                        })
+                        .ok();
+                }
+                _ => raw_binary.address_text = format!("{:08X}", raw_binary.address),
+            }
+        }
+    });
+
+    if alt_setting != raw_binary.alt_setting {
+        message_sender
+            .send(Message::SetRawBinaryTarget {
+                alt_setting,
+                address: raw_binary.address,
+            })
+            .ok();
+    }
+}
@@ -0,0 +1,150 @@
+//! Window showing the journal records of all devices, with export
+
+use eframe::egui;
+
+use crate::journal::{ExportFormat, Record};
+use crate::Message;
+
+/// State of the flash history window
+#[derive(Default)]
+pub struct FlashHistoryView {
+    /// Flag if the window is open
+    pub open: bool,
+
+    /// Records of all devices, oldest first
+    pub records: Vec<Record>,
+
+    /// Text the shown records must contain
+    pub filter: String,
+}
+
+impl FlashHistoryView {
+    /// Return the records matching the filter, oldest first
+    pub fn filtered(&self) -> Vec<&Record> {
+        let filter = self.filter.trim().to_lowercase();
+
+        self.records
+            .iter()
+            .filter(|record| {
+                filter.is_empty()
+                    || [
+                        record.serial_number.to_lowercase(),
+                        record.file_path.display().to_string().to_lowercase(),
+                        format!("{:04x}:{:04x}", record.vendor_id, record.product_id),
+                        record.operation.to_string().to_lowercase(),
+                        record.result().to_lowercase(),
+                    ]
+                    .iter()
+                    .any(|field| field.contains(&filter))
+            })
+            .collect()
+    }
+}
+
+/// Show the flash history window
+pub fn window(
+    ctx: &egui::Context,
+    view: &mut FlashHistoryView,
+    message_sender: &std::sync::mpsc::Sender<Message>,
+) {
+    let mut open = view.open;
+
+    egui::Window::new("Flash History")
+        .open(&mut open)
+        .collapsible(false)
+        .default_width(700.0)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Filter:");
+                ui.add(
+                    egui::TextEdit::singleline(&mut view.filter)
+                        .hint_text("Serial number, file, VID:PID or result")
+                        .desired_width(250.0),
+                );
+                if ui.button("Reload").clicked() {
+                    message_sender.send(Message::OpenFlashHistory).ok();
+                }
+                for format in [ExportFormat::Csv, ExportFormat::Json] {
+                    if ui
+                        .add_enabled(
+                            !view.records.is_empty(),
+                            egui::Button::new(format!("Export {format}...")),
+                        )
+                        .on_hover_text("Export the records shown")
+                        .clicked()
+                    {
+                        message_sender
+                            .send(Message::ExportFlashHistory(format))
+                            .ok();
+                    }
+                }
+            });
+            ui.separator();
+
+            let records = view.filtered();
+            ui.label(format!(
+                "{} of {} records, times in UTC",
+                records.len(),
+                view.records.len()
+            ));
+
+            if records.is_empty() {
+                ui.label("No operations recorded.");
+                return;
+            }
+
+            egui::ScrollArea::both().max_height(300.0).show(ui, |ui| {
+                egui::Grid::new("flash_history_records")
+                    .num_columns(8)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong("Time");
+                        ui.strong("Operation");
+                        ui.strong("VID:PID");
+                        ui.strong("Serial No");
+                        ui.strong("File");
+                        ui.strong("SHA-256");
+                        ui.strong("Duration");
+                        ui.strong("Result");
+                        ui.end_row();
+
+                        for record in records.iter().rev() {
+                            ui.label(record.time());
+                            ui.label(record.operation.to_string());
+                            ui.monospace(format!(
+                                "{:04x}:{:04x}",
+                                record.vendor_id, record.product_id
+                            ));
+                            ui.label(&record.serial_number);
+                            ui.label(
+                                record
+                                    .file_path
+                                    .file_name()
+                                    .unwrap_or_default()
+                                    .to_string_lossy(),
+                            )
+                            .on_hover_text(record.file_path.display().to_string());
+                            match &record.file_hash {
+                                Some(hash) => ui
+                                    .monospace(&hash[..hash.len().min(12)])
+                                    .on_hover_text(hash),
+                                None => ui.label("-"),
+                            };
+                            ui.label(format!("{:.1} s", record.duration));
+                            let color = match (&record.error, &record.cancellation) {
+                                (None, _) => egui::Color32::GREEN,
+                                (Some(_), Some(_)) => egui::Color32::YELLOW,
+                                (Some(_), None) => egui::Color32::RED,
+                            };
+                            let label = ui.colored_label(color, record.result());
+                            if let Some(error) = &record.error {
+                                label.on_hover_text(error);
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+        });
+
+    view.open = open;
+}
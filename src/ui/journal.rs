@@ -0,0 +1,96 @@
+//! Window showing the journal of the selected device
+
+use eframe::egui;
+
+use crate::journal::Record;
+use crate::progress::CancelMode;
+
+/// State of the journal window
+#[derive(Default)]
+pub struct JournalView {
+    /// Flag if the window is open
+    pub open: bool,
+
+    /// Serial number of the device shown
+    pub serial_number: String,
+
+    /// Records of the device, oldest first
+    pub records: Vec<Record>,
+}
+
+/// Show the journal window
+pub fn window(ctx: &egui::Context, view: &mut JournalView) {
+    egui::Window::new(format!("Journal of Device {}", view.serial_number))
+        .id(egui::Id::new("journal"))
+        .open(&mut view.open)
+        .collapsible(false)
+        .default_width(600.0)
+        .show(ctx, |ui| {
+            if view.records.is_empty() {
+                ui.label("No operations recorded for this device.");
+                return;
+            }
+
+            egui::ScrollArea::both().max_height(300.0).show(ui, |ui| {
+                egui::Grid::new("journal_records")
+                    .num_columns(6)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong("Time (UTC)");
+                        ui.strong("Operation");
+                        ui.strong("Steps");
+                        ui.strong("Result");
+                        ui.strong("File hash");
+                        ui.strong("Operator");
+                        ui.end_row();
+
+                        for record in view.records.iter().rev() {
+                            ui.label(record.time());
+                            ui.label(record.operation.to_string());
+                            ui.label(
+                                record
+                                    .steps
+                                    .iter()
+                                    .map(|step| {
+                                        format!(
+                                            "{} {}",
+                                            step.step,
+                                            if step.passed { "✔" } else { "✖" }
+                                        )
+                                    })
+                                    .collect::<Vec<_>>()
+                                    .join(", "),
+                            );
+                            match (&record.error, &record.cancellation) {
+                                (None, _) => ui.colored_label(egui::Color32::GREEN, "OK"),
+                                (Some(error), Some(cancellation)) => ui
+                                    .colored_label(
+                                        egui::Color32::YELLOW,
+                                        match cancellation.mode {
+                                            CancelMode::Abort => "Aborted",
+                                            CancelMode::Stop => "Stopped",
+                                        },
+                                    )
+                                    .on_hover_text(match &cancellation.resume_point {
+                                        Some(resume_point) => format!("Resume at {resume_point}"),
+                                        None => error.clone(),
+                                    }),
+                                (Some(error), None) => ui
+                                    .colored_label(egui::Color32::RED, "Failed")
+                                    .on_hover_text(error),
+                            };
+                            match &record.file_hash {
+                                Some(hash) => {
+                                    ui.monospace(&hash[..hash.len().min(12)]).on_hover_text(
+                                        format!("{}\n{}", record.file_path.display(), hash),
+                                    )
+                                }
+                                None => ui.label("-"),
+                            };
+                            ui.label(record.operator.as_deref().unwrap_or("-"));
+                            ui.end_row();
+                        }
+                    });
+            });
+        });
+}
@@ -0,0 +1,105 @@
+//! Window of the kiosk mode with the results of the session
+
+use eframe::egui;
+
+use crate::kiosk::Session;
+use crate::Message;
+
+/// Show the kiosk window
+///
+/// The session can only be started with a file opened.
+pub fn window(
+    ctx: &egui::Context,
+    open: &mut bool,
+    session: &mut Session,
+    file_ready: bool,
+    message_sender: &std::sync::mpsc::Sender<Message>,
+) {
+    egui::Window::new("Kiosk Mode")
+        .open(open)
+        .collapsible(false)
+        .default_width(600.0)
+        .show(ctx, |ui| {
+            ui.label(
+                "Every device appearing while the kiosk mode is running is flashed \
+                with the opened file automatically.",
+            );
+            ui.add_space(5.0);
+
+            ui.horizontal(|ui| {
+                if session.is_active() {
+                    if ui.button("Stop").clicked() {
+                        message_sender.send(Message::StopKiosk).ok();
+                    }
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        format!("Running, {} devices waiting", session.waiting()),
+                    );
+                } else {
+                    if ui
+                        .add_enabled(file_ready, egui::Button::new("Start"))
+                        .on_disabled_hover_text("Open a file first")
+                        .clicked()
+                    {
+                        message_sender.send(Message::StartKiosk).ok();
+                    }
+                    ui.label("Stopped");
+                }
+            });
+            ui.separator();
+
+            let outcomes = session.outcomes();
+            let failed = outcomes
+                .iter()
+                .filter(|outcome| outcome.error.is_some())
+                .count();
+            let mut clear = false;
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "{} passed, {} failed, times in UTC",
+                    outcomes.len() - failed,
+                    failed
+                ));
+                clear = ui
+                    .add_enabled(!outcomes.is_empty(), egui::Button::new("Clear"))
+                    .clicked();
+            });
+
+            if outcomes.is_empty() {
+                ui.label("No devices flashed in this session.");
+            } else {
+                egui::ScrollArea::both().max_height(300.0).show(ui, |ui| {
+                    egui::Grid::new("kiosk_results")
+                        .num_columns(4)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.strong("Time");
+                            ui.strong("Serial No");
+                            ui.strong("Device");
+                            ui.strong("Result");
+                            ui.end_row();
+
+                            for outcome in outcomes.iter().rev() {
+                                ui.monospace(outcome.time_of_day());
+                                ui.label(&outcome.serial_number);
+                                ui.label(&outcome.device);
+                                match &outcome.error {
+                                    Some(error) => {
+                                        ui.colored_label(ui.visuals().error_fg_color, "Failed")
+                                            .on_hover_text(error);
+                                    }
+                                    None => {
+                                        ui.colored_label(egui::Color32::GREEN, "Passed");
+                                    }
+                                }
+                                ui.end_row();
+                            }
+                        });
+                });
+            }
+
+            if clear {
+                session.clear();
+            }
+        });
+}
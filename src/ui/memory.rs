@@ -0,0 +1,151 @@
+//! Window for read-only browsing of system memory and OTP areas
+
+use eframe::egui;
+
+use crate::dfudev::{self, dfuse::MemorySegment};
+use crate::Message;
+
+/// Number of bytes per line of the hex dump
+const BYTES_PER_LINE: usize = 16;
+
+/// State of the memory window
+#[derive(Default)]
+pub struct MemoryView {
+    /// Flag if the window is open
+    pub open: bool,
+
+    /// Selected alt setting
+    pub alt_setting: Option<u8>,
+
+    /// Flag if a read is in progress
+    pub reading: bool,
+
+    /// Start address and data of the last read
+    pub content: Option<(u32, Vec<u8>)>,
+}
+
+/// Show the memory window
+pub fn window(
+    ctx: &egui::Context,
+    view: &mut MemoryView,
+    device_info: Option<&dfudev::DeviceInfo>,
+    message_sender: &std::sync::mpsc::Sender<Message>,
+) {
+    let mut open = view.open;
+
+    egui::Window::new("System Memory")
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            let segments: Vec<(u8, MemorySegment)> = device_info
+                .map(|info| {
+                    info.alt_settings
+                        .iter()
+                        .filter(|(_, name)| name.starts_with('@'))
+                        .filter_map(|(alt, name)| {
+                            Some((*alt, MemorySegment::from_string_desc(name).ok()?))
+                        })
+                        .filter(|(_, segment)| segment.is_protected())
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            if segments.is_empty() {
+                ui.label("The selected device exposes no system memory or OTP area.");
+                return;
+            }
+
+            if !segments
+                .iter()
+                .any(|(alt, _)| Some(*alt) == view.alt_setting)
+            {
+                view.alt_setting = Some(segments[0].0);
+                view.content = None;
+            }
+
+            ui.horizontal(|ui| {
+                ui.label("Area:");
+                let selected = segments
+                    .iter()
+                    .find(|(alt, _)| Some(*alt) == view.alt_setting)
+                    .map(|(_, segment)| segment.name.clone())
+                    .unwrap_or_default();
+                egui::ComboBox::from_id_salt("memory_area")
+                    .selected_text(selected)
+                    .show_ui(ui, |ui| {
+                        for (alt, segment) in &segments {
+                            if ui
+                                .selectable_value(&mut view.alt_setting, Some(*alt), &segment.name)
+                                .clicked()
+                            {
+                                view.content = None;
+                            }
+                        }
+                    });
+
+                if ui
+                    .add_enabled(
+                        !view.reading && device_info.is_some_and(|info| info.can_upload),
+                        egui::Button::new("Read"),
+                    )
+                    .clicked()
+                {
+                    if let Some(alt_setting) = view.alt_setting {
+                        view.reading = true;
+                        message_sender.send(Message::ReadMemory(alt_setting)).ok();
+                    }
+                }
+
+                if view.reading {
+                    ui.spinner();
+                }
+            });
+
+            ui.label("Read-only, writing to these areas is blocked.");
+            ui.separator();
+
+            match &view.content {
+                Some((address, data)) => hex_dump(ui, *address, data),
+                None => {
+                    ui.label("Press Read to load the content from the device.");
+                }
+            }
+        });
+
+    view.open = open;
+}
+
+/// Show data as hex dump with addresses and ASCII column
+pub fn hex_dump(ui: &mut egui::Ui, address: u32, data: &[u8]) {
+    let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+    let num_rows = data.len().div_ceil(BYTES_PER_LINE);
+
+    egui::ScrollArea::vertical().max_height(300.0).show_rows(
+        ui,
+        row_height,
+        num_rows,
+        |ui, rows| {
+            for row in rows {
+                let offset = row * BYTES_PER_LINE;
+                let bytes = &data[offset..(offset + BYTES_PER_LINE).min(data.len())];
+                let hex: String = bytes.iter().map(|byte| format!("{byte:02X} ")).collect();
+                let ascii: String = bytes
+                    .iter()
+                    .map(|&byte| {
+                        if byte.is_ascii_graphic() || byte == b' ' {
+                            byte as char
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect();
+                ui.monospace(format!(
+                    "{:08X}  {hex:<width$} {ascii}",
+                    address as usize + offset,
+                    width = BYTES_PER_LINE * 3
+                ));
+            }
+        },
+    );
+}
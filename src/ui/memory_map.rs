@@ -0,0 +1,165 @@
+//! Window to enter the memory map of a device manually
+//!
+//! Some bootloaders report malformed memory layouts in their alt setting
+//! names. The names entered here replace the reported ones for all
+//! operations on matching devices.
+
+use eframe::egui;
+
+use crate::dfudev::{self, dfuse::MemorySegment, quirks::MemoryMapOverride};
+use crate::Message;
+
+/// State of the memory map window
+#[derive(Default)]
+pub struct MemoryMapForm {
+    /// Flag if the window is open
+    pub open: bool,
+
+    /// USB vendor id of the device
+    vendor_id: u16,
+
+    /// USB product id of the device
+    product_id: u16,
+
+    /// Serial number of the device
+    serial_number: String,
+
+    /// Flag if the override applies to all devices with the same USB ids
+    all_serial_numbers: bool,
+
+    /// Alt settings and their names as edited
+    alt_settings: Vec<(u8, String)>,
+}
+
+impl MemoryMapForm {
+    /// Open the window for a device, prefilled with an existing override
+    /// or the alt setting names currently in effect
+    pub fn show(&mut self, info: &dfudev::DeviceInfo, overrides: &[MemoryMapOverride]) {
+        let existing = overrides
+            .iter()
+            .find(|memory_map_override| memory_map_override.matches(info));
+
+        self.vendor_id = info.vendor_id;
+        self.product_id = info.product_id;
+        self.serial_number = info.serial_number_string.clone();
+        self.all_serial_numbers = existing.is_some_and(|existing| existing.serial_number.is_none());
+        self.alt_settings = info
+            .alt_settings
+            .iter()
+            .map(|(alt, name)| {
+                let name = existing
+                    .and_then(|existing| existing.memory_map.get(alt))
+                    .unwrap_or(name);
+                (*alt, name.clone())
+            })
+            .collect();
+        self.open = true;
+    }
+
+    /// Return the serial number the override is restricted to
+    fn serial_number(&self) -> Option<String> {
+        (!self.all_serial_numbers).then(|| self.serial_number.clone())
+    }
+
+    /// Return if an override is for the device of the form
+    fn is_for_device(&self, memory_map_override: &MemoryMapOverride) -> bool {
+        memory_map_override.vendor_id == self.vendor_id
+            && memory_map_override.product_id == self.product_id
+            && memory_map_override.serial_number == self.serial_number()
+    }
+}
+
+/// Show the memory map window
+pub fn window(
+    ctx: &egui::Context,
+    form: &mut MemoryMapForm,
+    overrides: &mut Vec<MemoryMapOverride>,
+    message_sender: &std::sync::mpsc::Sender<Message>,
+) {
+    let mut open = form.open;
+
+    egui::Window::new("Memory Map Override")
+        .open(&mut open)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.label(format!(
+                "Memory layout of device {:04X}:{:04X}, replaces the one reported by the device.",
+                form.vendor_id, form.product_id
+            ));
+            ui.label("Format: @Name/0xStartAddress/Count*SizeUnitType,...");
+            ui.add_space(5.0);
+
+            let mut valid = true;
+
+            egui::Grid::new("memory_map").num_columns(3).show(ui, |ui| {
+                for (alt, name) in &mut form.alt_settings {
+                    ui.label(format!("Alt {alt}:"));
+                    ui.add(
+                        egui::TextEdit::singleline(name)
+                            .code_editor()
+                            .desired_width(400.0),
+                    );
+                    if MemorySegment::from_string_desc(name.as_str())
+                        .is_ok_and(|segment| segment.geometry_errors().is_empty())
+                    {
+                        ui.label("");
+                    } else {
+                        valid = false;
+                        ui.colored_label(ui.visuals().error_fg_color, "Invalid");
+                    }
+                    ui.end_row();
+                }
+            });
+
+            ui.add_space(5.0);
+            ui.checkbox(
+                &mut form.all_serial_numbers,
+                "Apply to all devices with this vendor and product id",
+            )
+            .on_hover_text(format!(
+                "Otherwise only to serial number {}",
+                form.serial_number
+            ));
+            ui.add_space(5.0);
+
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(valid, egui::Button::new("Save"))
+                    .on_disabled_hover_text("Fix the invalid entries first")
+                    .clicked()
+                {
+                    overrides
+                        .retain(|memory_map_override| !form.is_for_device(memory_map_override));
+                    overrides.push(MemoryMapOverride {
+                        vendor_id: form.vendor_id,
+                        product_id: form.product_id,
+                        serial_number: form.serial_number(),
+                        memory_map: form.alt_settings.iter().cloned().collect(),
+                    });
+                    log::info!(
+                        "Memory map override saved for device {:04X}:{:04X}",
+                        form.vendor_id,
+                        form.product_id
+                    );
+                    message_sender.send(Message::MemoryMapsChanged).ok();
+                    form.open = false;
+                }
+
+                let existing = overrides
+                    .iter()
+                    .any(|memory_map_override| form.is_for_device(memory_map_override));
+                if ui
+                    .add_enabled(existing, egui::Button::new("Remove override"))
+                    .clicked()
+                {
+                    overrides
+                        .retain(|memory_map_override| !form.is_for_device(memory_map_override));
+                    message_sender.send(Message::MemoryMapsChanged).ok();
+                    form.open = false;
+                }
+            });
+        });
+
+    form.open &= open;
+}
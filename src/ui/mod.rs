@@ -1,3 +1,24 @@
+pub mod address_range;
+pub mod archive;
 pub mod device;
+pub mod device_in_use;
+pub mod differences;
+pub mod events;
 pub mod file;
+pub mod flash_history;
+pub mod journal;
+pub mod kiosk;
+pub mod memory;
+pub mod memory_map;
 pub mod modal;
+pub mod operator;
+pub mod permissions;
+pub mod preflight;
+pub mod profiles;
+pub mod read_memory;
+pub mod settings;
+pub mod statistics;
+pub mod suffix_tool;
+pub mod transfer_settings;
+pub mod unprotect;
+pub mod warnings;
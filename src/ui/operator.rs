@@ -0,0 +1,178 @@
+//! Windows for operator login and management
+
+use eframe::egui;
+
+use crate::operator::Operator;
+use crate::Message;
+
+/// Min. number of characters of a PIN
+const MIN_PIN_LENGTH: usize = 4;
+
+/// State of the login window
+#[derive(Default)]
+pub struct LoginForm {
+    /// Flag if the window is open
+    pub open: bool,
+
+    /// Selected operator name
+    name: String,
+
+    /// Entered PIN
+    pin: String,
+
+    /// Flag if the last login attempt failed
+    failed: bool,
+}
+
+/// State of the operator management window
+#[derive(Default)]
+pub struct OperatorsForm {
+    /// Flag if the window is open
+    pub open: bool,
+
+    /// Name of the operator to add
+    name: String,
+
+    /// PIN of the operator to add
+    pin: String,
+}
+
+/// Show the login window
+pub fn login_window(
+    ctx: &egui::Context,
+    form: &mut LoginForm,
+    operators: &[Operator],
+    message_sender: &std::sync::mpsc::Sender<Message>,
+) {
+    let mut open = form.open;
+    let mut logged_in = false;
+
+    egui::Window::new("Operator Login")
+        .open(&mut open)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            if operators.is_empty() {
+                ui.label("No operators configured.");
+                return;
+            }
+
+            egui::Grid::new("login").num_columns(2).show(ui, |ui| {
+                ui.label("Operator:");
+                egui::ComboBox::from_id_salt("login_operator")
+                    .selected_text(form.name.as_str())
+                    .show_ui(ui, |ui| {
+                        for operator in operators {
+                            ui.selectable_value(
+                                &mut form.name,
+                                operator.name.clone(),
+                                &operator.name,
+                            );
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("PIN:");
+                let pin_edit = ui.add(
+                    egui::TextEdit::singleline(&mut form.pin)
+                        .password(true)
+                        .desired_width(100.0),
+                );
+                ui.end_row();
+
+                let enter_pressed =
+                    pin_edit.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+
+                ui.label("");
+                if ui.button("Log in").clicked() || enter_pressed {
+                    let operator = operators.iter().find(|op| op.name == form.name);
+                    if operator.is_some_and(|operator| operator.verify(&form.pin)) {
+                        message_sender
+                            .send(Message::OperatorLoggedIn(form.name.clone()))
+                            .ok();
+                        logged_in = true;
+                    } else {
+                        log::warn!("Login of operator \"{}\" failed.", form.name);
+                        form.failed = true;
+                    }
+                    form.pin.clear();
+                }
+                ui.end_row();
+            });
+
+            if form.failed {
+                ui.colored_label(egui::Color32::RED, "Wrong operator or PIN.");
+            }
+        });
+
+    form.open = open && !logged_in;
+    if !form.open {
+        form.failed = false;
+    }
+}
+
+/// Show the window to add and remove operators
+pub fn operators_window(
+    ctx: &egui::Context,
+    form: &mut OperatorsForm,
+    operators: &mut Vec<Operator>,
+) {
+    egui::Window::new("Operators")
+        .open(&mut form.open)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            let mut remove = None;
+
+            egui::Grid::new("operators").num_columns(2).show(ui, |ui| {
+                for (index, operator) in operators.iter().enumerate() {
+                    ui.label(&operator.name);
+                    if ui.small_button("Remove").clicked() {
+                        remove = Some(index);
+                    }
+                    ui.end_row();
+                }
+            });
+
+            if let Some(index) = remove {
+                let operator = operators.remove(index);
+                log::info!("Operator \"{}\" removed.", operator.name);
+            }
+
+            ui.separator();
+
+            egui::Grid::new("new_operator")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Name:");
+                    ui.add(egui::TextEdit::singleline(&mut form.name).desired_width(150.0));
+                    ui.end_row();
+
+                    ui.label("PIN:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut form.pin)
+                            .password(true)
+                            .desired_width(100.0),
+                    );
+                    ui.end_row();
+                });
+
+            let name = form.name.trim();
+            let valid = !name.is_empty()
+                && form.pin.len() >= MIN_PIN_LENGTH
+                && !operators.iter().any(|operator| operator.name == name);
+
+            if ui
+                .add_enabled(valid, egui::Button::new("Add"))
+                .on_disabled_hover_text(format!(
+                    "Enter a new name and a PIN with at least {MIN_PIN_LENGTH} characters"
+                ))
+                .clicked()
+            {
+                log::info!("Operator \"{name}\" added.");
+                operators.push(Operator::new(name, &form.pin));
+                form.name.clear();
+                form.pin.clear();
+            }
+        });
+}
@@ -0,0 +1,124 @@
+//! Window to set up the udev rule granting access to DFU devices on Linux
+
+use eframe::egui;
+
+use crate::{udev, Message};
+
+/// Vendor and product id of the STM32 bootloader, used if no device is found
+const DEFAULT_USB_ID: (u16, u16) = (0x0483, 0xDF11);
+
+/// State of the permissions window
+#[derive(Default)]
+pub struct PermissionsForm {
+    /// Flag if the window is open
+    pub open: bool,
+
+    /// Content of the rules file, editable to add ids of other devices
+    rules: String,
+}
+
+impl PermissionsForm {
+    /// Open the window with a rule for the devices with the given ids
+    pub fn show(&mut self, usb_ids: &[(u16, u16)]) {
+        self.rules = if usb_ids.is_empty() {
+            udev::rules(&[DEFAULT_USB_ID])
+        } else {
+            udev::rules(usb_ids)
+        };
+        self.open = true;
+    }
+}
+
+/// Show the permissions window
+pub fn window(
+    ctx: &egui::Context,
+    form: &mut PermissionsForm,
+    message_sender: &std::sync::mpsc::Sender<Message>,
+) {
+    let mut open = form.open;
+
+    egui::Window::new("Setup Device Permissions")
+        .open(&mut open)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.label(
+                "Non-root users need a udev rule to access USB devices. \
+                The rule below covers the DFU devices currently connected.",
+            );
+            ui.add_space(5.0);
+
+            ui.add(
+                egui::TextEdit::multiline(&mut form.rules)
+                    .code_editor()
+                    .desired_rows(4)
+                    .desired_width(500.0),
+            );
+
+            ui.add_space(5.0);
+            ui.horizontal(|ui| {
+                if ui
+                    .button("Install...")
+                    .on_hover_text(format!(
+                        "Write the rule to {} and reload the rules, \
+                        requires the administrator password",
+                        udev::RULES_PATH
+                    ))
+                    .clicked()
+                {
+                    let rules = form.rules.clone();
+                    let message_sender = message_sender.clone();
+                    std::thread::spawn(move || {
+                        let body = match udev::install(&rules) {
+                            Ok(_) => {
+                                log::info!("udev rule installed to {}", udev::RULES_PATH);
+                                message_sender.send(Message::RescanDevices).ok();
+                                "Rule installed. Reconnect the device if it is still not found."
+                                    .to_string()
+                            }
+                            Err(error) => {
+                                log::error!("{}", error);
+                                format!("Error: {error}")
+                            }
+                        };
+                        message_sender
+                            .send(Message::OpenMessageDialog {
+                                title: "Setup device permissions".into(),
+                                body,
+                            })
+                            .ok();
+                    });
+                }
+
+                if ui.button("Save as...").clicked() {
+                    let result = rfd::FileDialog::new()
+                        .add_filter("udev rules", &["rules"])
+                        .set_file_name(
+                            std::path::Path::new(udev::RULES_PATH)
+                                .file_name()
+                                .unwrap_or_default()
+                                .to_string_lossy(),
+                        )
+                        .save_file();
+
+                    if let Some(path) = result {
+                        let body = match std::fs::write(&path, &form.rules) {
+                            Ok(_) => udev::instructions(&path),
+                            Err(error) => {
+                                log::error!("{}", error);
+                                format!("Error: {error}")
+                            }
+                        };
+                        message_sender
+                            .send(Message::OpenMessageDialog {
+                                title: "Setup device permissions".into(),
+                                body,
+                            })
+                            .ok();
+                    }
+                }
+            });
+        });
+
+    form.open = open;
+}
@@ -0,0 +1,212 @@
+//! Checklist summarizing the preflight checks
+
+use eframe::egui;
+
+use crate::{DfuFileChecks, Message};
+
+/// Single entry of the checklist
+struct Check {
+    /// Short name shown in the list
+    name: &'static str,
+
+    /// Flag if the check passed
+    passed: bool,
+
+    /// Explanation shown when the entry is clicked
+    explanation: String,
+
+    /// Flag to set when the user overrides the failed check
+    override_flag: Option<fn(&mut DfuFileChecks) -> &mut bool>,
+}
+
+/// Return the entries of the checklist, followed by the integrity checks
+fn checks(dfu_file_checks: &DfuFileChecks) -> Vec<Check> {
+    let mut checks = vec![
+        Check {
+            name: "CRC",
+            passed: dfu_file_checks.crc_valid,
+            explanation: if dfu_file_checks.crc_valid {
+                "The CRC calculated over the file matches the value stored in its suffix."
+            } else if dfu_file_checks.crc_checked {
+                "The CRC calculated over the file does not match the value stored in its \
+                suffix. The file is probably corrupted."
+            } else {
+                "The CRC of the file could not be calculated because reading it failed."
+            }
+            .into(),
+            override_flag: None,
+        },
+        Check {
+            name: "DFU version",
+            passed: dfu_file_checks.dfu_version_valid,
+            explanation: if dfu_file_checks.dfu_version_valid {
+                "The file format matches the DFU version of the device."
+            } else {
+                "The file format does not match the DFU version of the device, \
+                e.g. a DfuSe file for a plain DFU device or vice versa."
+            }
+            .into(),
+            override_flag: None,
+        },
+        Check {
+            name: "Vendor ID",
+            passed: dfu_file_checks.vendor_id_accepted,
+            explanation: if dfu_file_checks.vendor_id_accepted {
+                "The vendor id of the file matches the device or is a wildcard."
+            } else {
+                "The vendor id stored in the file differs from the one of the device. \
+                The firmware is probably intended for another device."
+            }
+            .into(),
+            override_flag: Some(|checks| &mut checks.vendor_id_accepted),
+        },
+        Check {
+            name: "Product ID",
+            passed: dfu_file_checks.product_id_accepted,
+            explanation: if dfu_file_checks.product_id_accepted {
+                "The product id of the file matches the device or is a wildcard."
+            } else {
+                "The product id stored in the file differs from the one of the device. \
+                The firmware is probably intended for another device."
+            }
+            .into(),
+            override_flag: Some(|checks| &mut checks.product_id_accepted),
+        },
+        Check {
+            name: "Targets",
+            passed: dfu_file_checks.targets_valid,
+            explanation: if dfu_file_checks.targets_valid {
+                "All images in the file have a matching target on the device."
+            } else {
+                "At least one image in the file refers to an alternate setting that \
+                the device does not provide."
+            }
+            .into(),
+            override_flag: None,
+        },
+        Check {
+            name: "Fits in memory",
+            passed: dfu_file_checks.fits_in_memory,
+            explanation: if dfu_file_checks.fits_in_memory {
+                "All image elements are located in writable memory of their targets."
+            } else {
+                "At least one image element is located outside of the writable memory \
+                regions reported by the device."
+            }
+            .into(),
+            override_flag: None,
+        },
+    ];
+
+    if let Some(preset) = dfu_file_checks.preset {
+        checks.push(Check {
+            name: "Board preset",
+            passed: dfu_file_checks.preset_accepted,
+            explanation: if dfu_file_checks.preset_problems.is_empty() {
+                format!("The device and file match the {preset} preset.")
+            } else {
+                format!(
+                    "The device or file differ from the {preset} preset:\n\n- {}",
+                    dfu_file_checks.preset_problems.join("\n- ")
+                )
+            },
+            override_flag: Some(|checks| &mut checks.preset_accepted),
+        });
+    }
+
+    checks.extend(dfu_file_checks.integrity.iter().map(|outcome| Check {
+        name: outcome.algorithm.name(),
+        passed: outcome.passed,
+        explanation: format!(
+            "{}.\n\n{}",
+            outcome.algorithm.description(),
+            outcome.details
+        ),
+        override_flag: None,
+    }));
+
+    checks
+}
+
+/// Show the checklist, each entry opens its explanation when clicked
+///
+/// Failed checks that can be overridden offer an approve button,
+/// unless `strict` is set.
+pub fn checklist(
+    ui: &mut egui::Ui,
+    dfu_file_checks: &mut DfuFileChecks,
+    strict: bool,
+    message_sender: &std::sync::mpsc::Sender<Message>,
+) {
+    ui.vertical(|ui| {
+        ui.add(egui::Label::new(
+            egui::RichText::new("Some requirements are not met:").color(egui::Color32::RED),
+        ));
+        ui.add_space(5.0);
+
+        let mut approved = None;
+
+        egui::Grid::new("preflight_checklist")
+            .num_columns(2)
+            .spacing((10.0, 2.0))
+            .show(ui, |ui| {
+                for (index, check) in checks(dfu_file_checks).into_iter().enumerate() {
+                    let (icon, color) = if check.passed {
+                        ("✔", egui::Color32::GREEN)
+                    } else {
+                        ("✖", egui::Color32::RED)
+                    };
+
+                    ui.horizontal(|ui| {
+                        let label = ui
+                            .add(
+                                egui::Label::new(
+                                    egui::RichText::new(format!("{icon} {}", check.name))
+                                        .color(color),
+                                )
+                                .sense(egui::Sense::click()),
+                            )
+                            .on_hover_text("Click for details");
+
+                        if label.clicked() {
+                            message_sender
+                                .send(Message::OpenMessageDialog {
+                                    title: check.name.into(),
+                                    body: check.explanation,
+                                })
+                                .ok();
+                        }
+
+                        if let (false, false, Some(override_flag)) =
+                            (strict, check.passed, check.override_flag)
+                        {
+                            if ui
+                                .small_button("Approve")
+                                .on_hover_text(format!(
+                                    "Accept the {} for this device",
+                                    check.name.to_lowercase()
+                                ))
+                                .clicked()
+                            {
+                                approved = Some((check.name, override_flag));
+                            }
+                        }
+                    });
+
+                    if index % 2 == 1 {
+                        ui.end_row();
+                    }
+                }
+            });
+
+        if strict {
+            ui.add_space(5.0);
+            ui.label("Strict mode is enabled, failed checks can't be approved.");
+        }
+
+        if let Some((name, override_flag)) = approved {
+            log::warn!("Check \"{name}\" overridden by user.");
+            *override_flag(dfu_file_checks) = true;
+        }
+    });
+}
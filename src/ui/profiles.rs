@@ -0,0 +1,230 @@
+//! Selection and management of the project profiles
+
+use eframe::egui;
+
+use crate::dfudev::DeviceInfo;
+use crate::profile::Profile;
+use crate::update::AfterUpdate;
+use crate::Message;
+
+/// State of the profile management window
+#[derive(Default)]
+pub struct ProfilesForm {
+    /// Flag if the window is open
+    pub open: bool,
+
+    /// Profile being edited
+    profile: Profile,
+
+    /// Vendor id as hex text
+    vendor_id: String,
+
+    /// Product id as hex text
+    product_id: String,
+}
+
+impl ProfilesForm {
+    /// Load a profile into the form
+    fn edit(&mut self, profile: &Profile) {
+        self.profile = profile.clone();
+        self.vendor_id = profile
+            .vendor_id
+            .map(|id| format!("{id:04X}"))
+            .unwrap_or_default();
+        self.product_id = profile
+            .product_id
+            .map(|id| format!("{id:04X}"))
+            .unwrap_or_default();
+    }
+}
+
+/// Show the row with the profile selection
+pub fn selection(
+    ui: &mut egui::Ui,
+    profiles: &[Profile],
+    selected: Option<&str>,
+    downloading: bool,
+    message_sender: &std::sync::mpsc::Sender<Message>,
+) {
+    let selected = selected.filter(|name| profiles.iter().any(|profile| profile.name == *name));
+
+    ui.horizontal(|ui| {
+        ui.label("Profile:");
+
+        let mut choice = selected;
+        egui::ComboBox::from_id_salt("profile")
+            .selected_text(selected.unwrap_or("None"))
+            .width(250.0)
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut choice, None, "None");
+                for profile in profiles {
+                    ui.selectable_value(&mut choice, Some(&profile.name), &profile.name);
+                }
+            });
+        if choice != selected {
+            message_sender
+                .send(Message::SelectProfile(choice.map(String::from)))
+                .ok();
+        }
+
+        if downloading {
+            ui.spinner();
+            ui.label("Downloading firmware...");
+        }
+    });
+}
+
+/// Show the window to add, edit and remove profiles
+pub fn window(
+    ctx: &egui::Context,
+    form: &mut ProfilesForm,
+    profiles: &mut Vec<Profile>,
+    device_info: Option<&DeviceInfo>,
+    file_path: Option<&std::path::Path>,
+) {
+    let mut open = form.open;
+
+    egui::Window::new("Profiles")
+        .open(&mut open)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            let mut remove = None;
+
+            egui::Grid::new("profiles").num_columns(3).show(ui, |ui| {
+                for (index, profile) in profiles.iter().enumerate() {
+                    ui.label(&profile.name);
+                    if ui.small_button("Edit").clicked() {
+                        form.edit(profile);
+                    }
+                    if ui.small_button("Remove").clicked() {
+                        remove = Some(index);
+                    }
+                    ui.end_row();
+                }
+            });
+
+            if let Some(index) = remove {
+                let profile = profiles.remove(index);
+                log::info!("Profile \"{}\" removed.", profile.name);
+            }
+
+            if !profiles.is_empty() {
+                ui.separator();
+            }
+
+            egui::Grid::new("profile").num_columns(2).show(ui, |ui| {
+                ui.label("Name:");
+                ui.add(egui::TextEdit::singleline(&mut form.profile.name).desired_width(250.0));
+                ui.end_row();
+
+                ui.label("Vendor ID: 0x");
+                ui.add(
+                    egui::TextEdit::singleline(&mut form.vendor_id)
+                        .desired_width(50.0)
+                        .char_limit(4)
+                        .hint_text("any"),
+                );
+                ui.end_row();
+
+                ui.label("Product ID: 0x");
+                ui.add(
+                    egui::TextEdit::singleline(&mut form.product_id)
+                        .desired_width(50.0)
+                        .char_limit(4)
+                        .hint_text("any"),
+                );
+                ui.end_row();
+
+                ui.label("Serial number:");
+                ui.add(
+                    egui::TextEdit::singleline(&mut form.profile.serial_number)
+                        .desired_width(250.0)
+                        .hint_text("any, * and ? as wildcards"),
+                )
+                .on_hover_text("E.g. ABC* for all serial numbers starting with ABC");
+                ui.end_row();
+
+                ui.label("Firmware:");
+                ui.add(
+                    egui::TextEdit::singleline(&mut form.profile.firmware)
+                        .desired_width(250.0)
+                        .hint_text("path or http(s) URL"),
+                );
+                ui.end_row();
+
+                ui.label("After update:");
+                egui::ComboBox::from_id_salt("profile_after_update")
+                    .selected_text(form.profile.after_update.to_string())
+                    .show_ui(ui, |ui| {
+                        for action in AfterUpdate::ALL {
+                            ui.selectable_value(
+                                &mut form.profile.after_update,
+                                action,
+                                action.to_string(),
+                            );
+                        }
+                    });
+                ui.end_row();
+            });
+
+            let parse_id = |text: &str| match text.trim() {
+                "" => Some(None),
+                text => u16::from_str_radix(text, 16).ok().map(Some),
+            };
+            let vendor_id = parse_id(&form.vendor_id);
+            let product_id = parse_id(&form.product_id);
+
+            ui.add_space(5.0);
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(
+                        device_info.is_some() || file_path.is_some(),
+                        egui::Button::new("Use selected device and file"),
+                    )
+                    .clicked()
+                {
+                    if let Some(info) = device_info {
+                        form.vendor_id = format!("{:04X}", info.vendor_id);
+                        form.product_id = format!("{:04X}", info.product_id);
+                        form.profile.serial_number = info.serial_number_string.clone();
+                    }
+                    // Entries of an archive are opened by the archive
+                    if let Some(path) = file_path {
+                        let path = path.ancestors().find(|path| path.is_file()).unwrap_or(path);
+                        form.profile.firmware = path.to_string_lossy().into_owned();
+                    }
+                }
+
+                let name = form.profile.name.trim().to_string();
+                if ui
+                    .add_enabled(
+                        !name.is_empty() && vendor_id.is_some() && product_id.is_some(),
+                        egui::Button::new("Save"),
+                    )
+                    .on_disabled_hover_text("Enter a name and the ids as hex numbers or nothing")
+                    .clicked()
+                {
+                    let profile = Profile {
+                        name,
+                        vendor_id: vendor_id.flatten(),
+                        product_id: product_id.flatten(),
+                        serial_number: form.profile.serial_number.trim().to_string(),
+                        firmware: form.profile.firmware.trim().to_string(),
+                        after_update: form.profile.after_update,
+                    };
+                    log::info!("Profile \"{}\" saved.", profile.name);
+                    match profiles.iter_mut().find(|other| other.name == profile.name) {
+                        Some(other) => *other = profile,
+                        None => profiles.push(profile),
+                    }
+                    *form = ProfilesForm {
+                        open: true,
+                        ..Default::default()
+                    };
+                }
+            });
+        });
+
+    form.open = open;
+}
@@ -0,0 +1,212 @@
+//! Window to read an address range of the device memory
+//!
+//! Any readable part of a target can be uploaded and shown as hex dump, e.g.
+//! to inspect option areas or check single bytes after an update. The data
+//! read can be saved as raw binary file.
+
+use eframe::egui;
+
+use crate::dfudev::{self, dfuse::MemorySegment};
+use crate::update::MAX_MEMORY_READ_SIZE;
+use crate::Message;
+
+/// Number of bytes read by default
+const DEFAULT_LENGTH: u32 = 0x100;
+
+/// State of the read memory window
+#[derive(Default)]
+pub struct ReadMemoryView {
+    /// Flag if the window is open
+    pub open: bool,
+
+    /// Selected alt setting
+    alt_setting: Option<u8>,
+
+    /// Start address as hex text
+    address: String,
+
+    /// Number of bytes as hex text
+    length: String,
+
+    /// Flag if a read is in progress
+    pub reading: bool,
+
+    /// Alt setting, start address and data of the last read
+    pub content: Option<(u8, u32, Vec<u8>)>,
+}
+
+/// Show the read memory window
+pub fn window(
+    ctx: &egui::Context,
+    view: &mut ReadMemoryView,
+    device_info: Option<&dfudev::DeviceInfo>,
+    message_sender: &std::sync::mpsc::Sender<Message>,
+) {
+    let mut open = view.open;
+
+    egui::Window::new("Read Memory")
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            let segments: Vec<(u8, MemorySegment)> = device_info
+                .filter(|info| info.dfu_version == crate::builder::DFU_VERSION_DFUSE)
+                .map(|info| {
+                    info.alt_settings
+                        .iter()
+                        .filter_map(|(alt, name)| {
+                            Some((*alt, MemorySegment::from_string_desc(name).ok()?))
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            if segments.is_empty() {
+                ui.label("Reading memory ranges requires a DfuSe device.");
+                return;
+            }
+
+            if !segments
+                .iter()
+                .any(|(alt, _)| Some(*alt) == view.alt_setting)
+            {
+                select_target(view, &segments[0]);
+            }
+
+            let Some((_, segment)) = segments
+                .iter()
+                .find(|(alt, _)| Some(*alt) == view.alt_setting)
+            else {
+                return;
+            };
+
+            egui::Grid::new("read_memory")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Target:");
+                    egui::ComboBox::from_id_salt("read_memory_target")
+                        .selected_text(&segment.name)
+                        .show_ui(ui, |ui| {
+                            for target in &segments {
+                                if ui
+                                    .selectable_label(
+                                        view.alt_setting == Some(target.0),
+                                        &target.1.name,
+                                    )
+                                    .clicked()
+                                {
+                                    select_target(view, target);
+                                }
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Address: 0x");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut view.address)
+                            .desired_width(70.0)
+                            .char_limit(8),
+                    );
+                    ui.end_row();
+
+                    ui.label("Length: 0x");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut view.length)
+                            .desired_width(70.0)
+                            .char_limit(8),
+                    );
+                    ui.end_row();
+                });
+
+            let regions: Vec<String> = segment
+                .regions
+                .iter()
+                .filter(|region| region.readable)
+                .map(|region| {
+                    format!(
+                        "0x{:08X}..0x{:08X}",
+                        region.start_address, region.end_address
+                    )
+                })
+                .collect();
+            ui.label(match regions.is_empty() {
+                true => "The target has no readable region.".to_string(),
+                false => format!("Readable: {}", regions.join(", ")),
+            });
+
+            let parsed = parse_range(view);
+
+            ui.add_space(5.0);
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(
+                        !view.reading
+                            && parsed.is_some()
+                            && device_info.is_some_and(|info| info.can_upload),
+                        egui::Button::new("Read"),
+                    )
+                    .on_disabled_hover_text(format!(
+                        "Enter a hex address and a length of 1 to 0x{MAX_MEMORY_READ_SIZE:X} \
+                        bytes, the device must support reading its memory"
+                    ))
+                    .clicked()
+                {
+                    if let (Some(alt_setting), Some((address, length))) = (view.alt_setting, parsed)
+                    {
+                        view.reading = true;
+                        message_sender
+                            .send(Message::ReadMemoryRange(alt_setting, address, length))
+                            .ok();
+                    }
+                }
+
+                if ui
+                    .add_enabled(view.content.is_some(), egui::Button::new("Save as .bin..."))
+                    .clicked()
+                {
+                    message_sender.send(Message::SaveMemoryRange).ok();
+                }
+
+                if view.reading {
+                    ui.spinner();
+                }
+            });
+
+            ui.separator();
+
+            match &view.content {
+                Some((alt_setting, address, data)) => {
+                    ui.label(format!(
+                        "{} bytes at 0x{address:08X} of alt setting {alt_setting}",
+                        data.len()
+                    ));
+                    super::memory::hex_dump(ui, *address, data);
+                }
+                None => {
+                    ui.label("Press Read to load the range from the device.");
+                }
+            }
+        });
+
+    view.open = open;
+}
+
+/// Select a target and preset the range to the start of its first readable region
+fn select_target(view: &mut ReadMemoryView, (alt_setting, segment): &(u8, MemorySegment)) {
+    view.alt_setting = Some(*alt_setting);
+    if let Some(region) = segment.regions.iter().find(|region| region.readable) {
+        view.address = format!("{:08X}", region.start_address);
+        view.length = format!("{DEFAULT_LENGTH:X}");
+    }
+}
+
+/// Return start address and length if both are valid
+fn parse_range(view: &ReadMemoryView) -> Option<(u32, u32)> {
+    let parse = |text: &str| u32::from_str_radix(text.trim().trim_start_matches("0x"), 16).ok();
+    let address = parse(&view.address)?;
+    let length = parse(&view.length)?;
+
+    (1..=MAX_MEMORY_READ_SIZE)
+        .contains(&length)
+        .then_some((address, length))
+}
@@ -0,0 +1,211 @@
+//! Preferences window
+//!
+//! Collects the personal preferences, which are stored with the application
+//! state but not part of exported configurations.
+
+use eframe::egui;
+
+use crate::logging::LogLevel;
+use crate::session;
+use crate::theme::Theme;
+use crate::update::AfterUpdate;
+use crate::Message;
+
+/// Preferences edited in the window
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Preferences {
+    /// Action after a successful update
+    pub after_update: AfterUpdate,
+
+    /// Flag to compare the device memory with the file after programming
+    pub verify: bool,
+
+    /// Color theme
+    pub theme: Theme,
+
+    /// Most verbose level of the log messages shown
+    pub log_level: LogLevel,
+
+    /// Flag to remember the device selected last
+    pub remember_device: bool,
+
+    /// Flag to write a session log for each job
+    pub session_log: bool,
+
+    /// Directory of the session logs, empty for the default one
+    pub session_log_directory: String,
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Self {
+            after_update: Default::default(),
+            verify: true,
+            theme: Default::default(),
+            log_level: Default::default(),
+            remember_device: true,
+            session_log: false,
+            session_log_directory: String::new(),
+        }
+    }
+}
+
+/// State of the preferences window
+#[derive(Default)]
+pub struct SettingsForm {
+    /// Flag if the window is open
+    pub open: bool,
+
+    /// Preferences being edited
+    preferences: Preferences,
+}
+
+impl SettingsForm {
+    /// Open the window with the current preferences
+    pub fn show(&mut self, preferences: Preferences) {
+        self.preferences = preferences;
+        self.open = true;
+    }
+}
+
+/// Show the preferences window
+///
+/// `last_device` is the serial number of the device remembered.
+pub fn window(
+    ctx: &egui::Context,
+    form: &mut SettingsForm,
+    last_device: Option<&str>,
+    message_sender: &std::sync::mpsc::Sender<Message>,
+) {
+    let mut open = form.open;
+    let mut close = false;
+
+    egui::Window::new("Preferences")
+        .open(&mut open)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.set_max_width(380.0);
+            let preferences = &mut form.preferences;
+
+            egui::Grid::new("preferences")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("After update:");
+                    egui::ComboBox::from_id_salt("preferences_after_update")
+                        .selected_text(preferences.after_update.to_string())
+                        .show_ui(ui, |ui| {
+                            for action in AfterUpdate::ALL {
+                                ui.selectable_value(
+                                    &mut preferences.after_update,
+                                    action,
+                                    action.to_string(),
+                                );
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Verify:");
+                    ui.checkbox(
+                        &mut preferences.verify,
+                        "Compare with the file after programming",
+                    );
+                    ui.end_row();
+
+                    ui.label("Transfers:");
+                    if ui
+                        .button("Transfer settings...")
+                        .on_hover_text("Override transfer size, timeouts and polling")
+                        .clicked()
+                    {
+                        message_sender.send(Message::OpenTransferSettings).ok();
+                    }
+                    ui.end_row();
+
+                    ui.label("Theme:");
+                    egui::ComboBox::from_id_salt("preferences_theme")
+                        .selected_text(preferences.theme.to_string())
+                        .show_ui(ui, |ui| {
+                            for theme in Theme::ALL {
+                                ui.selectable_value(
+                                    &mut preferences.theme,
+                                    theme,
+                                    theme.to_string(),
+                                );
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Log level:")
+                        .on_hover_text("Most verbose messages shown on the console");
+                    egui::ComboBox::from_id_salt("preferences_log_level")
+                        .selected_text(preferences.log_level.to_string())
+                        .show_ui(ui, |ui| {
+                            for level in LogLevel::ALL {
+                                ui.selectable_value(
+                                    &mut preferences.log_level,
+                                    level,
+                                    level.to_string(),
+                                );
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Session log:");
+                    ui.vertical(|ui| {
+                        ui.checkbox(&mut preferences.session_log, "Write a file for each job")
+                            .on_hover_text(
+                                "Record all requests, statuses and timings of updates and \
+                                backups to diagnose failures. The newest files are kept.",
+                            );
+                        ui.add_enabled(
+                            preferences.session_log,
+                            egui::TextEdit::singleline(&mut preferences.session_log_directory)
+                                .hint_text(
+                                    session::Config::default_directory()
+                                        .map(|dir| dir.display().to_string())
+                                        .unwrap_or_default(),
+                                ),
+                        )
+                        .on_hover_text("Directory of the files, empty for the default one");
+                    });
+                    ui.end_row();
+
+                    ui.label("Device:");
+                    ui.vertical(|ui| {
+                        ui.checkbox(
+                            &mut preferences.remember_device,
+                            "Remember last used device",
+                        );
+                        ui.horizontal(|ui| {
+                            ui.label(match last_device {
+                                Some(serial_number) => format!("S/N {serial_number}"),
+                                None => "No device remembered".into(),
+                            });
+                            if ui
+                                .add_enabled(last_device.is_some(), egui::Button::new("Forget"))
+                                .clicked()
+                            {
+                                message_sender.send(Message::ForgetLastDevice).ok();
+                            }
+                        });
+                    });
+                    ui.end_row();
+                });
+
+            ui.add_space(5.0);
+            ui.horizontal(|ui| {
+                if ui.button("Apply").clicked() {
+                    message_sender
+                        .send(Message::SetPreferences(form.preferences.clone()))
+                        .ok();
+                    close = true;
+                }
+                if ui.button("Defaults").clicked() {
+                    form.preferences = Preferences::default();
+                }
+            });
+        });
+
+    form.open = open && !close;
+}
@@ -0,0 +1,85 @@
+//! Window showing statistics of the last update
+
+use eframe::egui;
+
+use crate::dfudev::timing::Timings;
+use crate::format;
+use crate::update::Statistics;
+
+/// Show the statistics window
+pub fn window(ctx: &egui::Context, open: &mut bool, statistics: &Statistics, timings: &Timings) {
+    egui::Window::new("Statistics")
+        .open(open)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            egui::Grid::new("statistics").num_columns(2).show(ui, |ui| {
+                ui.label("File data:");
+                ui.label(if statistics.streamed {
+                    "Streamed"
+                } else {
+                    "Cached"
+                });
+                ui.end_row();
+
+                ui.label("Cache memory:");
+                ui.label(format::size(statistics.cache_memory as u64));
+                ui.end_row();
+
+                ui.label("Buffer memory:");
+                ui.label(format::size(statistics.buffer_memory as u64));
+                ui.end_row();
+            });
+
+            if timings.requests.is_empty() {
+                return;
+            }
+
+            ui.separator();
+            ui.heading("Timing");
+            ui.add_space(5.0);
+
+            egui::Grid::new("statistics_time")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Total:");
+                    ui.label(format::millis(timings.elapsed));
+                    ui.end_row();
+
+                    ui.label("USB requests:");
+                    ui.label(format::millis(timings.request_time()));
+                    ui.end_row();
+
+                    ui.label("Poll timeouts:")
+                        .on_hover_text("Time waited as requested by the device in bwPollTimeout");
+                    ui.label(format::millis(timings.poll_wait));
+                    ui.end_row();
+
+                    ui.label("Host:")
+                        .on_hover_text("Remaining time, e.g. for reading the file");
+                    ui.label(format::millis(timings.host_time()));
+                    ui.end_row();
+                });
+
+            ui.add_space(5.0);
+
+            egui::Grid::new("statistics_requests")
+                .num_columns(4)
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("Request");
+                    ui.label("Count");
+                    ui.label("Average");
+                    ui.label("Max.");
+                    ui.end_row();
+
+                    for (request, time) in &timings.requests {
+                        ui.label(request.to_string());
+                        ui.label(format::number(time.count as u64));
+                        ui.label(format::millis(time.average()));
+                        ui.label(format::millis(time.max));
+                        ui.end_row();
+                    }
+                });
+        });
+}
@@ -0,0 +1,192 @@
+//! Tool window to create DFU files from raw binaries and fix suffix CRCs
+
+use eframe::egui;
+
+use crate::{builder, Message};
+
+/// State of the tool window
+pub struct SuffixToolForm {
+    /// Flag if the window is open
+    pub open: bool,
+
+    /// Path of the raw binary
+    binary_path: Option<std::path::PathBuf>,
+
+    /// Vendor id as hex text
+    vendor_id: String,
+
+    /// Product id as hex text
+    product_id: String,
+
+    /// Device version as hex text
+    device_version: String,
+}
+
+impl Default for SuffixToolForm {
+    fn default() -> Self {
+        Self {
+            open: false,
+            binary_path: None,
+            vendor_id: "FFFF".into(),
+            product_id: "FFFF".into(),
+            device_version: "FFFF".into(),
+        }
+    }
+}
+
+/// Show the tool window
+pub fn window(
+    ctx: &egui::Context,
+    form: &mut SuffixToolForm,
+    message_sender: &std::sync::mpsc::Sender<Message>,
+) {
+    let mut open = form.open;
+
+    egui::Window::new("Create DFU File")
+        .open(&mut open)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.label("Append a DFU 1.1 suffix to a raw binary.");
+            ui.add_space(5.0);
+
+            egui::Grid::new("suffix_tool")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Binary:");
+                    ui.horizontal(|ui| {
+                        let name = form
+                            .binary_path
+                            .as_ref()
+                            .and_then(|path| path.file_name())
+                            .map_or("(none)".into(), |name| name.to_string_lossy());
+                        ui.label(name);
+                        if ui.button("Choose...").clicked() {
+                            let result = rfd::FileDialog::new()
+                                .add_filter("Binary files", &["bin"])
+                                .pick_file();
+                            if result.is_some() {
+                                form.binary_path = result;
+                            }
+                        }
+                    });
+                    ui.end_row();
+
+                    for (label, value) in [
+                        ("Vendor ID: 0x", &mut form.vendor_id),
+                        ("Product ID: 0x", &mut form.product_id),
+                        ("Device version: 0x", &mut form.device_version),
+                    ] {
+                        ui.label(label);
+                        ui.add(
+                            egui::TextEdit::singleline(value)
+                                .desired_width(50.0)
+                                .char_limit(4),
+                        );
+                        ui.end_row();
+                    }
+                });
+
+            let suffix = parse_suffix(form);
+
+            ui.add_space(5.0);
+            let create_button = ui
+                .add_enabled(
+                    form.binary_path.is_some() && suffix.is_some(),
+                    egui::Button::new("Create..."),
+                )
+                .on_disabled_hover_text("Choose a binary and enter hex values for all ids");
+
+            if let (true, Some(binary_path), Some(suffix)) =
+                (create_button.clicked(), &form.binary_path, suffix)
+            {
+                let result = rfd::FileDialog::new()
+                    .add_filter("DFU files", &["dfu"])
+                    .set_file_name(format!(
+                        "{}.dfu",
+                        binary_path
+                            .file_stem()
+                            .unwrap_or_default()
+                            .to_string_lossy()
+                    ))
+                    .save_file();
+
+                if let Some(dfu_path) = result {
+                    let result = std::fs::read(binary_path).and_then(|data| {
+                        std::fs::write(&dfu_path, builder::plain_file(&data, &suffix))
+                    });
+                    report(
+                        message_sender,
+                        "Create DFU file",
+                        result.map(|_| format!("DFU file written to {}.", dfu_path.display())),
+                    );
+                }
+            }
+
+            ui.separator();
+
+            ui.label("Recalculate the CRC of a DFU file with a wrong suffix CRC.");
+            ui.add_space(5.0);
+
+            if ui.button("Fix CRC...").clicked() {
+                let result = rfd::FileDialog::new()
+                    .add_filter("DFU files", &["dfu"])
+                    .pick_file();
+
+                if let Some(dfu_path) = result {
+                    report(message_sender, "Fix CRC", fix_crc(&dfu_path));
+                }
+            }
+        });
+
+    form.open = open;
+}
+
+/// Return the suffix if all values are valid hex numbers
+fn parse_suffix(form: &SuffixToolForm) -> Option<builder::Suffix> {
+    let parse = |text: &str| u16::from_str_radix(text.trim(), 16).ok();
+
+    Some(builder::Suffix {
+        device_version: parse(&form.device_version)?,
+        product_id: parse(&form.product_id)?,
+        vendor_id: parse(&form.vendor_id)?,
+        dfu_version: builder::DFU_VERSION_PLAIN,
+    })
+}
+
+/// Fix the CRC of a file and return a description of the result
+fn fix_crc(path: &std::path::Path) -> anyhow::Result<String> {
+    let mut data = std::fs::read(path)?;
+
+    if builder::fix_crc(&mut data)? {
+        std::fs::write(path, &data)?;
+        Ok("CRC was wrong and has been corrected.".into())
+    } else {
+        Ok("CRC is already correct, file left unchanged.".into())
+    }
+}
+
+/// Log the result of an action and show it in the message dialog
+fn report<E: std::fmt::Display>(
+    message_sender: &std::sync::mpsc::Sender<Message>,
+    title: &str,
+    result: Result<String, E>,
+) {
+    let body = match result {
+        Ok(text) => {
+            log::info!("{}", text);
+            text
+        }
+        Err(error) => {
+            log::error!("{}", error);
+            format!("Error: {error}")
+        }
+    };
+
+    message_sender
+        .send(Message::OpenMessageDialog {
+            title: title.into(),
+            body,
+        })
+        .ok();
+}
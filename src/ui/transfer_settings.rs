@@ -0,0 +1,125 @@
+//! Window to override transfer size, timeouts and polling behavior
+//!
+//! Some bootloaders report bogus values, which can be worked around here
+//! without a quirks entry for each device.
+
+use eframe::egui;
+
+use crate::dfudev::settings::TransferSettings;
+use crate::Message;
+
+/// State of the transfer settings window
+#[derive(Default)]
+pub struct TransferSettingsForm {
+    /// Flag if the window is open
+    pub open: bool,
+
+    /// Settings being edited
+    settings: TransferSettings,
+
+    /// Transfer size shown while the override is disabled
+    transfer_size: u16,
+
+    /// Poll timeout shown while the override is disabled
+    poll_timeout: u32,
+}
+
+impl TransferSettingsForm {
+    /// Open the window with the current settings
+    pub fn show(&mut self, settings: TransferSettings) {
+        self.settings = settings;
+        self.transfer_size = settings.transfer_size.unwrap_or(2048);
+        self.poll_timeout = settings.poll_timeout.unwrap_or(10);
+        self.open = true;
+    }
+}
+
+/// Show the transfer settings window
+pub fn window(
+    ctx: &egui::Context,
+    form: &mut TransferSettingsForm,
+    message_sender: &std::sync::mpsc::Sender<Message>,
+) {
+    let mut open = form.open;
+    let mut close = false;
+
+    egui::Window::new("Transfer Settings")
+        .open(&mut open)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.set_max_width(380.0);
+
+            ui.label(
+                "These settings apply to all devices. Change them only if a \
+                bootloader reports wrong values or needs more time.",
+            );
+            ui.add_space(5.0);
+
+            egui::Grid::new("transfer_settings")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    let mut override_transfer_size = form.settings.transfer_size.is_some();
+                    ui.checkbox(&mut override_transfer_size, "Transfer size:")
+                        .on_hover_text("Use instead of wTransferSize of the functional descriptor");
+                    ui.add_enabled(
+                        override_transfer_size,
+                        egui::DragValue::new(&mut form.transfer_size)
+                            .range(1..=u16::MAX)
+                            .suffix(" bytes"),
+                    );
+                    form.settings.transfer_size =
+                        override_transfer_size.then_some(form.transfer_size);
+                    ui.end_row();
+
+                    ui.label("Timeout:")
+                        .on_hover_text("Max. duration of a single control transfer");
+                    ui.add(
+                        egui::DragValue::new(&mut form.settings.timeout)
+                            .range(100..=60000)
+                            .speed(100)
+                            .suffix(" ms"),
+                    );
+                    ui.end_row();
+
+                    ui.label("Polling retries:").on_hover_text(
+                        "Status requests repeated after a download before giving up, \
+                        for devices reporting a too short poll timeout",
+                    );
+                    ui.add(egui::DragValue::new(&mut form.settings.polling_retries).range(0..=100));
+                    ui.end_row();
+
+                    let mut override_poll_timeout = form.settings.poll_timeout.is_some();
+                    ui.checkbox(&mut override_poll_timeout, "Poll timeout:")
+                        .on_hover_text("Use instead of bwPollTimeout of the status responses");
+                    ui.add_enabled(
+                        override_poll_timeout,
+                        egui::DragValue::new(&mut form.poll_timeout)
+                            .range(0..=60000)
+                            .suffix(" ms"),
+                    );
+                    form.settings.poll_timeout = override_poll_timeout.then_some(form.poll_timeout);
+                    ui.end_row();
+                });
+
+            ui.add_space(5.0);
+            ui.horizontal(|ui| {
+                let problem = form.settings.problem();
+                if ui
+                    .add_enabled(problem.is_none(), egui::Button::new("Apply"))
+                    .on_disabled_hover_text(problem.unwrap_or_default())
+                    .clicked()
+                {
+                    message_sender
+                        .send(Message::SetTransferSettings(form.settings))
+                        .ok();
+                    close = true;
+                }
+                if ui.button("Defaults").clicked() {
+                    form.show(TransferSettings::DEFAULT);
+                }
+            });
+        });
+
+    form.open = open && !close;
+}
@@ -0,0 +1,104 @@
+//! Window to remove the readout protection of a device
+//!
+//! Removing the protection erases the whole flash memory, so the user must
+//! type a confirmation word before the command is sent.
+
+use eframe::egui;
+
+use crate::dfudev;
+use crate::Message;
+
+/// Word to type to confirm the mass erase
+const CONFIRMATION_WORD: &str = "ERASE";
+
+/// State of the unprotect window
+#[derive(Default)]
+pub struct UnprotectForm {
+    /// Flag if the window is open
+    pub open: bool,
+
+    /// Flag if the command is running
+    pub running: bool,
+
+    /// Description of the device
+    device: String,
+
+    /// Confirmation as typed by the user
+    confirmation: String,
+}
+
+impl UnprotectForm {
+    /// Open the window for a device
+    pub fn show(&mut self, info: &dfudev::DeviceInfo) {
+        self.device = format!(
+            "{} | {} | S/N {}",
+            info.manufacturer_string, info.product_string, info.serial_number_string
+        );
+        self.confirmation.clear();
+        self.open = true;
+    }
+}
+
+/// Show the unprotect window
+pub fn window(
+    ctx: &egui::Context,
+    form: &mut UnprotectForm,
+    message_sender: &std::sync::mpsc::Sender<Message>,
+) {
+    let mut open = form.open;
+    let mut started = false;
+
+    egui::Window::new("Remove Read Protection")
+        .open(&mut open)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.set_max_width(400.0);
+
+            if form.running {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Removing read protection, do not disconnect the device...");
+                });
+                return;
+            }
+
+            ui.label(format!("Device: {}", form.device));
+            ui.add_space(5.0);
+            ui.colored_label(
+                ui.visuals().error_fg_color,
+                "Removing the read protection performs a mass erase. The whole flash \
+                memory is erased, including the application and all data stored in \
+                flash. This can't be undone.",
+            );
+            ui.add_space(5.0);
+            ui.label(
+                "The device resets itself afterwards and is reconnected automatically, \
+                which can take up to a minute.",
+            );
+            ui.add_space(10.0);
+
+            ui.horizontal(|ui| {
+                ui.label(format!("Type {CONFIRMATION_WORD} to confirm:"));
+                ui.add(egui::TextEdit::singleline(&mut form.confirmation).desired_width(80.0));
+            });
+            ui.add_space(5.0);
+
+            let confirmed = form.confirmation.trim() == CONFIRMATION_WORD;
+            if ui
+                .add_enabled(confirmed, egui::Button::new("Erase and unprotect"))
+                .clicked()
+            {
+                started = true;
+            }
+        });
+
+    if started {
+        form.running = true;
+        form.confirmation.clear();
+        message_sender.send(Message::UnprotectDevice).ok();
+    }
+
+    // The window can't be closed while the command is running
+    form.open = open || form.running;
+}
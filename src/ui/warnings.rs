@@ -0,0 +1,47 @@
+//! List of the warnings collected for the file, device and update
+
+use eframe::egui;
+
+use crate::warnings::{Severity, Warning};
+use crate::Message;
+
+/// Show the warnings with an icon each, entries with details open
+/// them when clicked
+pub fn list(
+    ui: &mut egui::Ui,
+    warnings: &[Warning],
+    message_sender: &std::sync::mpsc::Sender<Message>,
+) {
+    for warning in warnings {
+        let color = match warning.severity {
+            Severity::Info => ui.visuals().weak_text_color(),
+            Severity::Warning => egui::Color32::YELLOW,
+            Severity::Critical => egui::Color32::RED,
+        };
+
+        ui.horizontal(|ui| {
+            ui.label(egui::RichText::new(warning.severity.icon()).color(color));
+            let label = egui::Label::new(egui::RichText::new(&warning.message).color(color));
+
+            match &warning.details {
+                Some(details) => {
+                    if ui
+                        .add(label.sense(egui::Sense::click()))
+                        .on_hover_text("Click for details")
+                        .clicked()
+                    {
+                        message_sender
+                            .send(Message::OpenMessageDialog {
+                                title: warning.message.clone(),
+                                body: details.clone(),
+                            })
+                            .ok();
+                    }
+                }
+                None => {
+                    ui.add(label);
+                }
+            }
+        });
+    }
+}
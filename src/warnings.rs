@@ -0,0 +1,114 @@
+//! Warnings about the file, the device and the planned update
+//!
+//! Warnings are collected at the stage where their cause becomes known: when
+//! a file is opened, when a device is selected and before an update is
+//! started. Each stage replaces only its own warnings, so all of them can be
+//! shown together in a single list above the start button.
+
+/// Severity of a warning
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Worth knowing, but nothing to worry about
+    Info,
+
+    /// Might lead to an unexpected result
+    Warning,
+
+    /// Prevents the update or is likely to damage the device content
+    Critical,
+}
+
+impl Severity {
+    /// Return the icon shown in front of the message
+    pub fn icon(&self) -> &'static str {
+        match self {
+            Self::Info => "ℹ",
+            Self::Warning => "⚠",
+            Self::Critical => "✖",
+        }
+    }
+}
+
+/// Stage collecting warnings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Opening or converting a file
+    File,
+
+    /// Selecting a device
+    Device,
+
+    /// Checking the planned update before starting it
+    Preflight,
+}
+
+/// Single warning
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    /// Severity of the warning
+    pub severity: Severity,
+
+    /// Message shown in the list
+    pub message: String,
+
+    /// Explanation shown on request, `None` if the message says it all
+    pub details: Option<String>,
+}
+
+impl Warning {
+    /// Return a warning without details
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    /// Return the warning with details
+    pub fn with_details(self, details: impl Into<String>) -> Self {
+        Self {
+            details: Some(details.into()),
+            ..self
+        }
+    }
+}
+
+/// Warnings of all stages
+#[derive(Debug, Default)]
+pub struct Warnings {
+    /// Warnings with the stage that collected them
+    entries: Vec<(Stage, Warning)>,
+}
+
+impl Warnings {
+    /// Replace the warnings of a stage, logging the ones not known before
+    pub fn set(&mut self, stage: Stage, warnings: Vec<Warning>) {
+        for warning in &warnings {
+            if !self.entries.contains(&(stage, warning.clone())) {
+                match warning.severity {
+                    Severity::Info => log::info!("{}", warning.message),
+                    Severity::Warning => log::warn!("{}", warning.message),
+                    Severity::Critical => log::error!("{}", warning.message),
+                }
+            }
+        }
+
+        self.entries
+            .retain(|(entry_stage, _)| *entry_stage != stage);
+        self.entries
+            .extend(warnings.into_iter().map(|warning| (stage, warning)));
+    }
+
+    /// Return all warnings, the most severe first
+    pub fn list(&self) -> Vec<Warning> {
+        let mut warnings: Vec<Warning> = self
+            .entries
+            .iter()
+            .map(|(_, warning)| warning.clone())
+            .collect();
+        warnings.sort_by_key(|warning| std::cmp::Reverse(warning.severity));
+
+        warnings
+    }
+}